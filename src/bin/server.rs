@@ -1,18 +1,66 @@
 use axum::{
     body::Bytes,
-    extract::State,
-    http::{header, StatusCode},
+    extract::{RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post},
     Router,
 };
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use sha1::{Digest, Sha1};
+use std::{net::SocketAddr, path::{Path, PathBuf}, sync::Arc};
 use tokio::net::TcpListener;
+use tokio::sync::Mutex;
 use rust_git::repository::{bundle, Repository};
 
 #[derive(Clone)]
 struct AppState {
     repo_path: Arc<PathBuf>,
+    // Bearer token required for pushes, from RUST_GIT_PUSH_TOKEN. `None` leaves pushes open.
+    push_token: Option<Arc<String>>,
+    // Bearer token required for fetches, from RUST_GIT_READ_TOKEN. `None` leaves fetches open.
+    read_token: Option<Arc<String>>,
+    // Held while a push writes objects and refs, so pushes apply one at a time and each
+    // checks for fast-forwards against refs no other push is changing. Fetches only read
+    // and do not take it.
+    push_lock: Arc<Mutex<()>>,
+}
+
+// Check the request's `Authorization: Bearer <token>` header against the expected token.
+// Always authorized when no token is configured.
+fn is_authorized(headers: &HeaderMap, expected: Option<&Arc<String>>) -> bool {
+    let Some(expected) = expected else {
+        return true;
+    };
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    provided.is_some_and(|provided| tokens_match(provided, expected))
+}
+
+// Compare the tokens' SHA-1 digests without stopping at the first difference, so the time a
+// check takes says nothing about how much of the expected token a guess gets right
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    let provided = Sha1::digest(provided.as_bytes());
+    let expected = Sha1::digest(expected.as_bytes());
+    provided.iter().zip(expected.iter()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "Missing or invalid bearer token".to_string(),
+    )
+        .into_response()
+}
+
+// A servable repository is either a working tree holding `.git` or a bare repository,
+// a directory that is itself a git dir
+fn is_repository(path: &Path) -> bool {
+    path.join(".git").is_dir() || Repository::open_bare(path).is_ok()
 }
 
 #[tokio::main]
@@ -24,34 +72,97 @@ async fn main() {
         std::process::exit(1);
     }
     let repo_path = PathBuf::from(&args[1]);
-    if !repo_path.join(".git").is_dir() {
+    if !is_repository(&repo_path) {
         eprintln!("Error: Provided path is not a valid git repository.");
         std::process::exit(1);
     }
 
+    let token_from_env = |name: &str| {
+        std::env::var(name).ok().filter(|token| !token.is_empty()).map(Arc::new)
+    };
     let state = AppState {
         repo_path: Arc::new(repo_path),
+        push_token: token_from_env("RUST_GIT_PUSH_TOKEN"),
+        read_token: token_from_env("RUST_GIT_READ_TOKEN"),
+        push_lock: Arc::default(),
     };
 
-    let app = Router::new()
-        .route("/repo.bundle", get(handle_fetch))
-        .route("/repo.bundle", post(handle_push))
-        .with_state(state.clone());
+    let app = app(state.clone());
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     println!("Listening on {}", addr);
     println!("Serving repository at: {}", state.repo_path.display());
+    if state.push_token.is_some() {
+        println!("Pushes require a bearer token");
+    }
 
     let listener = TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
-// Handler for fetch (client GETs a bundle)
-async fn handle_fetch(State(state): State<AppState>) -> Response {
+fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/info/refs", get(handle_info_refs))
+        .route("/repo.bundle", get(handle_fetch))
+        .route("/repo.bundle", post(handle_push))
+        .with_state(state)
+}
+
+// Handler for ref advertisement (client GETs the branch HEAD points at and the branch heads)
+async fn handle_info_refs(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !is_authorized(&headers, state.read_token.as_ref()) {
+        return unauthorized();
+    }
+    match Repository::open(state.repo_path.as_ref()) {
+        Ok(repo) => match bundle::format_advertisement(&repo) {
+            Ok(body) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain")], body).into_response(),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to list refs: {}", e),
+            )
+                .into_response(),
+        },
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to open repository: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+// Parse the commits a client already has from a `have=<sha>&have=<sha>` query string
+fn parse_haves(query: Option<&str>) -> Vec<String> {
+    query
+        .unwrap_or("")
+        .split('&')
+        .filter_map(|pair| pair.strip_prefix("have="))
+        .filter(|sha| !sha.is_empty())
+        .map(|sha| sha.to_string())
+        .collect()
+}
+
+// Parse the history depth a shallow fetch asks for from a `depth=<n>` query parameter
+fn parse_depth(query: Option<&str>) -> Option<usize> {
+    query
+        .unwrap_or("")
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("depth="))
+        .and_then(|depth| depth.parse().ok())
+        .filter(|&depth| depth > 0)
+}
+
+// Handler for fetch (client GETs a bundle, optionally listing the commits it already has
+// and how many commits of history it wants)
+async fn handle_fetch(State(state): State<AppState>, headers: HeaderMap, RawQuery(query): RawQuery) -> Response {
+    if !is_authorized(&headers, state.read_token.as_ref()) {
+        return unauthorized();
+    }
+    let haves = parse_haves(query.as_deref());
+    let depth = parse_depth(query.as_deref());
     match Repository::open(state.repo_path.as_ref()) {
         Ok(repo) => {
             let mut buffer = Vec::new();
-            match bundle::create_bundle(&repo, &mut buffer) {
+            match bundle::create_shallow_bundle(&repo, &mut buffer, &haves, depth) {
                 Ok(_) => (
                     StatusCode::OK,
                     [(header::CONTENT_TYPE, "application/octet-stream")],
@@ -73,13 +184,26 @@ async fn handle_fetch(State(state): State<AppState>) -> Response {
     }
 }
 
-// Handler for push (client POSTs a bundle)
-async fn handle_push(State(state): State<AppState>, body: Bytes) -> Response {
+// Whether a push asks to overwrite branches that would not fast-forward, with `force=1`
+fn parse_force(query: Option<&str>) -> bool {
+    query.unwrap_or("").split('&').any(|pair| pair == "force=1")
+}
+
+// Handler for push (client POSTs a bundle, with `force=1` to allow non-fast-forward updates)
+async fn handle_push(State(state): State<AppState>, headers: HeaderMap, RawQuery(query): RawQuery, body: Bytes) -> Response {
+    if !is_authorized(&headers, state.push_token.as_ref()) {
+        return unauthorized();
+    }
+    let _push_guard = state.push_lock.lock().await;
     match Repository::open(state.repo_path.as_ref()) {
         Ok(repo) => {
             let reader = std::io::Cursor::new(body);
-            match bundle::unbundle(&repo, reader, None) {
+            let mapping = if parse_force(query.as_deref()) { bundle::RefMapping::LocalForced } else { bundle::RefMapping::Local };
+            match bundle::unbundle(&repo, reader, mapping) {
                 Ok(_) => (StatusCode::OK, "Push successful".to_string()).into_response(),
+                Err(e) if e.is::<bundle::NonFastForwardError>() => {
+                    (StatusCode::CONFLICT, e.to_string()).into_response()
+                }
                 Err(e) => (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     format!("Failed to unbundle: {}", e),
@@ -93,4 +217,353 @@ async fn handle_push(State(state): State<AppState>, body: Bytes) -> Response {
         )
             .into_response(),
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_git::commands::{clone, push};
+    use rust_git::repository::{objects, refs};
+    use rust_git::repository::objects::ObjectType;
+
+    // Add a commit on top of master in the given repository
+    fn commit_on_master(repo: &Repository, message: &str) -> String {
+        let store = repo.objects.as_ref();
+        let parent = refs::read_ref(&repo.git_dir, "refs/heads/master").unwrap();
+        let blob = objects::write_blob(store, message.as_bytes()).unwrap();
+        let mut tree = b"100644 file.txt\0".to_vec();
+        tree.extend_from_slice(&hex::decode(&blob).unwrap());
+        let tree_id = store.write(ObjectType::Tree, &tree).unwrap();
+        let signature = objects::Signature::now("Test <test@example.com>");
+        let commit = objects::write_commit(store, &tree_id, &[&parent], message, &signature, &signature).unwrap();
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &commit).unwrap();
+        commit
+    }
+
+    fn bundle_bytes(repo: &Repository) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        bundle::create_bundle(repo, &mut buffer).unwrap();
+        buffer
+    }
+
+    async fn spawn_server(repo_path: PathBuf) -> SocketAddr {
+        spawn_server_with_tokens(repo_path, None, None).await
+    }
+
+    async fn spawn_server_with_tokens(repo_path: PathBuf, push_token: Option<&str>, read_token: Option<&str>) -> SocketAddr {
+        let state = AppState {
+            repo_path: Arc::new(repo_path),
+            push_token: push_token.map(|token| Arc::new(token.to_string())),
+            read_token: read_token.map(|token| Arc::new(token.to_string())),
+            push_lock: Arc::default(),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app(state)).await.unwrap();
+        });
+        addr
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cres", "s3cret"));
+        assert!(!tokens_match("s3cret ", "s3cret"));
+        assert!(!tokens_match("", "s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_info_refs_lists_branch_heads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let master_sha = refs::read_ref(&repo.git_dir, "refs/heads/master").unwrap();
+
+        let addr = spawn_server(temp_dir.path().to_path_buf()).await;
+        let response = reqwest::get(format!("http://{}/info/refs", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let body = response.text().await.unwrap();
+        assert!(body.lines().any(|line| line == format!("{}\trefs/heads/master", master_sha)));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_bare_repository() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init_bare(server_dir.path()).unwrap();
+        let tip = commit_on_master(&server, "served from a bare repository");
+        assert!(is_repository(server_dir.path()));
+
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+        let response = reqwest::get(format!("http://{}/repo.bundle", addr)).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body = response.bytes().await.unwrap();
+
+        let client_dir = tempfile::tempdir().unwrap();
+        let client = Repository::init(client_dir.path()).unwrap();
+        bundle::unbundle(&client, std::io::Cursor::new(body), bundle::RefMapping::Remote("origin")).unwrap();
+        assert_eq!(refs::read_ref(&client.git_dir, "refs/remotes/origin/master").unwrap(), tip);
+
+        // Neither a plain directory nor a repository's subdirectory is servable
+        let plain_dir = tempfile::tempdir().unwrap();
+        assert!(!is_repository(plain_dir.path()));
+        assert!(!is_repository(&server_dir.path().join("refs")));
+    }
+
+    #[tokio::test]
+    async fn test_clone_checks_out_remote_default_branch() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init_bare(server_dir.path()).unwrap();
+        let master_tip = commit_on_master(&server, "on master");
+
+        // Move the work to main and make it the default branch, leaving master behind
+        let main_tip = commit_on_master(&server, "on main");
+        refs::update_ref(&server.git_dir, "refs/heads/main", &main_tip).unwrap();
+        refs::update_ref(&server.git_dir, "refs/heads/master", &master_tip).unwrap();
+        std::fs::write(server.git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+        let body = reqwest::get(format!("http://{}/info/refs", addr)).await.unwrap().text().await.unwrap();
+        assert_eq!(bundle::advertised_head(&body), Some("refs/heads/main"));
+
+        let client_dir = tempfile::tempdir().unwrap();
+        let target = client_dir.path().join("repo");
+        let url = format!("http://{}/repo.bundle", addr);
+        let client = tokio::task::spawn_blocking(move || clone::run(&url, &target)).await.unwrap().unwrap();
+
+        assert_eq!(client.current_branch().unwrap(), "main");
+        assert_eq!(refs::get_head_commit(&client.git_dir).unwrap(), main_tip);
+        assert!(!client.git_dir.join("refs/heads/master").exists());
+        assert_eq!(std::fs::read_to_string(client.path.join("file.txt")).unwrap(), "on main");
+        assert_eq!(
+            std::fs::read_to_string(client.git_dir.join("refs/remotes/origin/HEAD")).unwrap(),
+            "ref: refs/remotes/origin/main"
+        );
+        assert_eq!(client.config.branch_upstream("main").as_deref(), Some("refs/remotes/origin/main"));
+    }
+
+    #[tokio::test]
+    async fn test_bare_clone_keeps_branches_as_local_refs() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init_bare(server_dir.path()).unwrap();
+        let master_tip = commit_on_master(&server, "on master");
+        let main_tip = commit_on_master(&server, "on main");
+        refs::update_ref(&server.git_dir, "refs/heads/main", &main_tip).unwrap();
+        refs::update_ref(&server.git_dir, "refs/heads/master", &master_tip).unwrap();
+        std::fs::write(server.git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+
+        let client_dir = tempfile::tempdir().unwrap();
+        let target = client_dir.path().join("repo.git");
+        let url = format!("http://{}/repo.bundle", addr);
+        let clone_target = target.clone();
+        let bare = tokio::task::spawn_blocking(move || clone::run_bare(&url, &clone_target, true)).await.unwrap().unwrap();
+
+        // Branches land under refs/heads as they are on the server, HEAD included
+        assert!(bare.is_bare());
+        assert_eq!(refs::read_ref(&bare.git_dir, "refs/heads/main").unwrap(), main_tip);
+        assert_eq!(refs::read_ref(&bare.git_dir, "refs/heads/master").unwrap(), master_tip);
+        assert_eq!(bare.current_branch().unwrap(), "main");
+        assert!(!bare.git_dir.join("refs/remotes").exists());
+        assert_eq!(bare.config.data["remote \"origin\""]["mirror"], "true");
+
+        // Nothing is checked out
+        assert!(!target.join("file.txt").exists());
+        assert!(!target.join(".git").exists());
+        assert!(bare.index.get_entries().is_empty());
+
+        // The bare clone can be served and cloned in turn
+        let mirror_addr = spawn_server(target).await;
+        let second_dir = tempfile::tempdir().unwrap();
+        let second_target = second_dir.path().join("repo");
+        let url = format!("http://{}/repo.bundle", mirror_addr);
+        let second = tokio::task::spawn_blocking(move || clone::run(&url, &second_target)).await.unwrap().unwrap();
+        assert_eq!(second.current_branch().unwrap(), "main");
+        assert_eq!(std::fs::read_to_string(second.path.join("file.txt")).unwrap(), "on main");
+    }
+
+    #[tokio::test]
+    async fn test_push_requires_bearer_token() {
+        let server_dir = tempfile::tempdir().unwrap();
+        Repository::init(server_dir.path()).unwrap();
+        let client_dir = tempfile::tempdir().unwrap();
+        let client = Repository::init(client_dir.path()).unwrap();
+        let mut bundle_bytes = Vec::new();
+        bundle::create_bundle(&client, &mut bundle_bytes).unwrap();
+
+        let addr = spawn_server_with_tokens(server_dir.path().to_path_buf(), Some("s3cret"), None).await;
+        let url = format!("http://{}/repo.bundle", addr);
+        let http = reqwest::Client::new();
+
+        let response = http.post(&url).body(bundle_bytes.clone()).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = http.post(&url).bearer_auth("wrong").body(bundle_bytes.clone()).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = http.post(&url).bearer_auth("s3cret").body(bundle_bytes).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        // Fetch stays open when no read token is configured
+        let response = http.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_non_fast_forward_push_is_rejected_with_conflict() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init(server_dir.path()).unwrap();
+        let base = refs::read_ref(&server.git_dir, "refs/heads/master").unwrap();
+
+        // Two clients start from the same base and then diverge
+        let client1_dir = tempfile::tempdir().unwrap();
+        let client1 = Repository::init(client1_dir.path()).unwrap();
+        let client2_dir = tempfile::tempdir().unwrap();
+        let client2 = Repository::init(client2_dir.path()).unwrap();
+        for client in [&client1, &client2] {
+            // Point master at the base first: each init makes its own root commit, which differs
+            // from the server's when the clock ticks over, and would make this a non-fast-forward
+            refs::update_ref(&client.git_dir, "refs/heads/master", &base).unwrap();
+            bundle::unbundle(client, std::io::Cursor::new(bundle_bytes(&server)), bundle::RefMapping::Local).unwrap();
+        }
+        let pushed = commit_on_master(&client1, "from client 1");
+        commit_on_master(&client2, "from client 2");
+
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+        let url = format!("http://{}/repo.bundle", addr);
+        let http = reqwest::Client::new();
+
+        let response = http.post(&url).body(bundle_bytes(&client1)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = http.post(&url).body(bundle_bytes(&client2)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        // The client turns the 409 into a pull-first hint
+        let client2_bundle = bundle_bytes(&client2);
+        let err = tokio::task::spawn_blocking(move || push::send_bundle(&client2.config, &url, client2_bundle, false))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains(push::NON_FAST_FORWARD_HINT));
+
+        assert_eq!(refs::read_ref(&server.git_dir, "refs/heads/master").unwrap(), pushed);
+    }
+
+    #[tokio::test]
+    async fn test_forced_push_overwrites_diverged_branch() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init(server_dir.path()).unwrap();
+        let base = refs::read_ref(&server.git_dir, "refs/heads/master").unwrap();
+        commit_on_master(&server, "on the server");
+
+        // The client's history does not contain the server's tip, as after a rebase
+        let client_dir = tempfile::tempdir().unwrap();
+        let client = Repository::init(client_dir.path()).unwrap();
+        refs::update_ref(&client.git_dir, "refs/heads/master", &base).unwrap();
+        bundle::unbundle(&client, std::io::Cursor::new(bundle_bytes(&server)), bundle::RefMapping::Remote("origin")).unwrap();
+        let rewritten = commit_on_master(&client, "rewritten");
+
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+        let url = format!("http://{}/repo.bundle", addr);
+        let client_bundle = bundle_bytes(&client);
+        let (rejected, forced) = tokio::task::spawn_blocking(move || {
+            let config = Repository::open(client_dir.path()).unwrap().config;
+            let rejected = push::send_bundle(&config, &url, client_bundle.clone(), false).is_err();
+            (rejected, push::send_bundle(&config, &url, client_bundle, true))
+        })
+        .await
+        .unwrap();
+        assert!(rejected, "an unforced push must be rejected");
+        forced.unwrap();
+
+        assert_eq!(refs::read_ref(&server.git_dir, "refs/heads/master").unwrap(), rewritten);
+    }
+
+    #[tokio::test]
+    async fn test_push_of_named_branches_only() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init(server_dir.path()).unwrap();
+        let master = refs::read_ref(&server.git_dir, "refs/heads/master").unwrap();
+
+        let client_dir = tempfile::tempdir().unwrap();
+        let client = Repository::init(client_dir.path()).unwrap();
+        refs::update_ref(&client.git_dir, "refs/heads/master", &master).unwrap();
+        bundle::unbundle(&client, std::io::Cursor::new(bundle_bytes(&server)), bundle::RefMapping::Local).unwrap();
+        let one = commit_on_master(&client, "one");
+        refs::create_branch(&client.git_dir, "one", &one).unwrap();
+        let two = commit_on_master(&client, "two");
+        refs::create_branch(&client.git_dir, "two", &two).unwrap();
+        refs::update_ref(&client.git_dir, "refs/heads/master", &master).unwrap();
+
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+        let url = format!("http://{}/repo.bundle", addr);
+        tokio::task::spawn_blocking(move || {
+            let client = Repository::open(client_dir.path()).unwrap();
+            for refspecs in [vec!["one".to_string()], vec!["two:renamed".to_string()]] {
+                let buffer = push::create_push_bundle(&client, &refspecs).unwrap();
+                push::send_bundle(&client.config, &url, buffer, false).unwrap();
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(refs::read_ref(&server.git_dir, "refs/heads/one").unwrap(), one);
+        assert_eq!(refs::read_ref(&server.git_dir, "refs/heads/renamed").unwrap(), two);
+        assert!(refs::read_ref(&server.git_dir, "refs/heads/two").is_err());
+        assert_eq!(refs::list_branches(&server.git_dir).unwrap(), ["master", "one", "renamed"]);
+        assert_eq!(refs::read_ref(&server.git_dir, "refs/heads/master").unwrap(), master);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_pushes_are_serialized() {
+        let server_dir = tempfile::tempdir().unwrap();
+        let server = Repository::init(server_dir.path()).unwrap();
+        let base = refs::read_ref(&server.git_dir, "refs/heads/master").unwrap();
+
+        // Two clients diverge from the same base
+        let client_dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        let mut tips = Vec::new();
+        let mut bundles = Vec::new();
+        for (i, client_dir) in client_dirs.iter().enumerate() {
+            let client = Repository::init(client_dir.path()).unwrap();
+            refs::update_ref(&client.git_dir, "refs/heads/master", &base).unwrap();
+            bundle::unbundle(&client, std::io::Cursor::new(bundle_bytes(&server)), bundle::RefMapping::Local).unwrap();
+            tips.push(commit_on_master(&client, &format!("from client {}", i)));
+            bundles.push(bundle_bytes(&client));
+        }
+
+        let addr = spawn_server(server_dir.path().to_path_buf()).await;
+        let url = format!("http://{}/repo.bundle", addr);
+        let http = reqwest::Client::new();
+        let [first, second] = bundles.try_into().unwrap();
+        let (first, second) = tokio::join!(
+            http.post(&url).body(first).send(),
+            http.post(&url).body(second).send(),
+        );
+        let statuses = [first.unwrap().status(), second.unwrap().status()];
+
+        // Exactly one push lands; the other is no longer a fast-forward once it does
+        let winner = match statuses {
+            [reqwest::StatusCode::OK, reqwest::StatusCode::CONFLICT] => &tips[0],
+            [reqwest::StatusCode::CONFLICT, reqwest::StatusCode::OK] => &tips[1],
+            other => panic!("unexpected push results {:?}", other),
+        };
+        let server = Repository::open(server_dir.path()).unwrap();
+        let master = refs::read_ref(&server.git_dir, "refs/heads/master").unwrap();
+        assert_eq!(&master, winner);
+        let commit = objects::read_commit(server.objects.as_ref(), &master).unwrap();
+        assert_eq!(commit.parents, vec![base]);
+        assert!(objects::flatten_tree(server.objects.as_ref(), &commit.tree).is_ok());
+    }
+
+    #[test]
+    fn test_parse_haves() {
+        let haves = parse_haves(Some("have=abc&other=1&have=def"));
+        assert_eq!(haves, vec!["abc".to_string(), "def".to_string()]);
+        assert!(parse_haves(None).is_empty());
+        assert_eq!(parse_depth(Some("have=abc&depth=2")), Some(2));
+        assert_eq!(parse_depth(Some("depth=0")), None);
+        assert_eq!(parse_depth(None), None);
+    }
+}
@@ -1,58 +1,273 @@
 use anyhow::Result;
-use std::env;
-use std::fs;
-use std::path::Path;
-use crate::repository::Repository;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use crate::commands::pathspec;
+use crate::repository::{Repository, diff, objects};
+use crate::repository::index::{self, IndexEntry, MODE_FILE};
+use crate::repository::location::Location;
+use crate::{info, verbose};
 
-pub fn execute(paths: &[String]) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    
+/// Lines of unchanged context shown around each hunk by `add -p`
+const PATCH_CONTEXT: usize = 3;
+
+/// Stage `paths`, or with `patch` choose hunk by hunk which changes to tracked files among
+/// them to stage, all tracked files when no paths are given
+pub fn execute(location: &Location, paths: &[String], patch: bool) -> Result<()> {
     // Open the repository
-    let mut repo = Repository::open(&current_dir)?;
+    let mut repo = location.open()?;
+    let current_dir = location.pathspec_dir(&repo);
     
-    let mut added_files = Vec::new();
+    for spec in paths {
+        if repo.is_inside_git_dir(&current_dir.join(spec)) {
+            anyhow::bail!("'{}' is inside the git directory", spec);
+        }
+    }
     
-    // Add each path
-    for path_str in paths {
-        let path = Path::new(path_str);
-        
-        if !path.exists() {
-            #[cfg(not(feature = "online_judge"))]
-            println!("pathspec '{}' did not match any files", path_str);
-            continue;
+    if patch {
+        let mut tracked = pathspec::tracked_files(&repo);
+        if !paths.is_empty() {
+            tracked = pathspec::expand(&repo, &current_dir, paths, &tracked)?.paths;
         }
-        
-        if path.is_dir() {
-            let files = repo.index.add_directory(
-                &repo.path,
-                path,
-                &repo.git_dir.join("objects"),
-            )?;
-            added_files.extend(files);
-        } else {
-            let content = fs::read(path)?;
-            let object_id = crate::repository::objects::write_blob(
-                &repo.git_dir.join("objects"),
-                &content,
-            )?;
-            
-            repo.index.add_file(&repo.path, path, &object_id)?;
-            
-            let relative_path = path.strip_prefix(&repo.path)
-                .unwrap_or(path)
-                .to_string_lossy()
-                .to_string();
-            added_files.push(relative_path);
+        tracked.sort();
+        let staged = run_patch(&mut repo, &tracked, io::stdin().lock(), io::stdout().lock())?;
+        repo.index.save(repo.git_dir.join("index"))?;
+        if !staged.is_empty() {
+            info!("Staged changes to {} file(s)", staged.len());
         }
+        return Ok(());
     }
     
+    // Tracked files are candidates too, so that deleted files can be staged
+    let mut candidates = pathspec::working_files(&repo);
+    candidates.extend(pathspec::tracked_files(&repo));
+    let expansion = pathspec::expand(&repo, &current_dir, paths, &candidates)?;
+    
+    for spec in &expansion.unmatched {
+        info!("pathspec '{}' did not match any files", spec);
+    }
+    
+    let added_files = run(&mut repo, &expansion.paths)?;
+    
     // Save the index
     repo.index.save(repo.git_dir.join("index"))?;
     
+    for file in &added_files {
+        verbose!("add '{}'", file);
+    }
     if !added_files.is_empty() {
-        #[cfg(not(feature = "online_judge"))]
-        println!("Added {} file(s) to the index", added_files.len());
+        info!("Added {} file(s) to the index", added_files.len());
     }
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Stage `paths` (relative to the repository root): files in the working tree are hashed and
+/// recorded, and tracked files that no longer exist are dropped from the index. Paths in the
+/// git dir are refused. Returns the staged paths.
+pub fn run(repo: &mut Repository, paths: &[PathBuf]) -> Result<Vec<String>> {
+    if let Some(path) = paths.iter().find(|path| repo.is_inside_git_dir(path)) {
+        anyhow::bail!("'{}' is inside the git directory", path.display());
+    }
+    
+    // A symlink is added as a link even when its target is missing
+    let (present, missing): (Vec<PathBuf>, Vec<PathBuf>) = paths
+        .iter()
+        .map(|path| repo.path.join(path))
+        .partition(|path| path.symlink_metadata().is_ok());
+    
+    let mut added_files = repo.index.add_files(&repo.path, &present, repo.objects.as_ref(), &repo.config)?;
+    for path in missing {
+        added_files.extend(repo.index.remove_path(&repo.path, path)?);
+    }
+    
+    Ok(added_files)
+}
+
+/// Stage some of the changes to the tracked files among `paths` (relative to the repository
+/// root), hunk by hunk, as `add -p` does.
+///
+/// Each hunk of the diff from the indexed version of a file to the working tree is written to
+/// `output` with a prompt, answered by a line of `input`: `y` stages it, `n` leaves it out and
+/// `q`, or the end of `input`, stops without staging it or any later hunk. The blob staged is
+/// the indexed version with the accepted hunks applied. Only regular text files are offered.
+/// Returns the paths whose staged version changed.
+pub fn run_patch(repo: &mut Repository, paths: &[PathBuf], input: impl BufRead, mut output: impl Write) -> Result<Vec<String>> {
+    let mut answers = input.lines();
+    let mut staged = Vec::new();
+    for path in paths {
+        let Some(entry) = repo.index.get_entries().get(path) else {
+            continue;
+        };
+        let full_path = repo.path.join(path);
+        if entry.mode != MODE_FILE || !full_path.symlink_metadata().is_ok_and(|metadata| metadata.is_file()) {
+            continue;
+        }
+        let old_data = repo.read_object(&entry.object_id)?.data;
+        let new_data = index::read_worktree_content(&full_path, &repo.config)?;
+        if old_data.contains(&0) || new_data.contains(&0) {
+            continue;
+        }
+        let (Ok(old), Ok(new)) = (String::from_utf8(old_data), String::from_utf8(new_data)) else {
+            continue;
+        };
+
+        let old_lines = diff::split_lines(&old);
+        let new_lines = diff::split_lines(&new);
+        let hunks = diff::diff_hunks(&old_lines, &new_lines);
+        if hunks.is_empty() {
+            continue;
+        }
+        write!(output, "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", path.display())?;
+
+        let mut accepted = Vec::new();
+        let mut quit = false;
+        for group in diff::group_hunks(&hunks, PATCH_CONTEXT) {
+            write!(output, "{}", diff::format_hunk(&old_lines, &new_lines, group, PATCH_CONTEXT))?;
+            loop {
+                write!(output, "Stage this hunk [y,n,q,?]? ")?;
+                output.flush()?;
+                let answer = answers.next().transpose()?.unwrap_or_else(|| "q".to_string());
+                match answer.trim() {
+                    "y" => accepted.extend_from_slice(group),
+                    "n" => {}
+                    "q" => quit = true,
+                    _ => {
+                        writeln!(output, "y - stage this hunk")?;
+                        writeln!(output, "n - do not stage this hunk")?;
+                        writeln!(output, "q - quit; do not stage this hunk or any of the remaining ones")?;
+                        continue;
+                    }
+                }
+                break;
+            }
+            if quit {
+                break;
+            }
+        }
+
+        if !accepted.is_empty() {
+            let content = diff::apply_hunks(&old, &new, &accepted);
+            let object_id = objects::write_blob(repo.objects.as_ref(), content.as_bytes())?;
+            repo.index.insert_entry(path.clone(), IndexEntry { mtime: 0, object_id, mode: MODE_FILE });
+            staged.push(path.to_string_lossy().to_string());
+        }
+        if quit {
+            break;
+        }
+    }
+    Ok(staged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{self, Verbosity};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_add_glob() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::create_dir_all(repo.path.join("src"))?;
+        fs::write(repo.path.join("main.rs"), "fn main() {}\n")?;
+        fs::write(repo.path.join("lib.rs"), "\n")?;
+        fs::write(repo.path.join("notes.txt"), "notes\n")?;
+        fs::write(repo.path.join("src/util.rs"), "\n")?;
+        
+        let expansion = pathspec::expand(&repo, &repo.path, &["*.rs".to_string()], &pathspec::working_files(&repo))?;
+        let added = run(&mut repo, &expansion.paths)?;
+        
+        assert_eq!(added, vec!["lib.rs", "main.rs"]);
+        let mut staged: Vec<&PathBuf> = repo.index.get_entries().keys().collect();
+        staged.sort();
+        assert_eq!(staged, vec![&PathBuf::from("lib.rs"), &PathBuf::from("main.rs")]);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_add_refuses_git_dir() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::create_dir(repo.path.join("sub"))?;
+        let location = Location::at(&repo.path);
+        
+        for spec in [".git/config", ".git", "./.git/HEAD", "sub/../.git/config", ".git/missing"] {
+            assert!(execute(&location, &[spec.to_string()], false).is_err(), "{}", spec);
+            assert!(run(&mut repo, &[PathBuf::from(spec)]).is_err(), "{}", spec);
+        }
+        assert!(execute(&Location::at(repo.path.join("sub")), &["../.git/config".to_string()], false).is_err());
+        
+        assert!(repo.index.add_files(&repo.path, &[repo.path.join(".git/config")], repo.objects.as_ref(), &repo.config).is_err());
+        assert!(Repository::open(&repo.path)?.index.get_entries().is_empty());
+        
+        // Whatever the git dir is called
+        let other_dir = tempdir()?;
+        fs::create_dir(other_dir.path().join("meta"))?;
+        Repository::init_bare(other_dir.path().join("meta"))?;
+        let location = Location { git_dir: Some("meta".into()), work_tree: Some(".".into()), ..Location::at(other_dir.path()) };
+        assert!(execute(&location, &["meta/config".to_string()], false).is_err());
+        fs::write(other_dir.path().join("file.txt"), "content\n")?;
+        execute(&location, &[".".to_string()], false)?;
+        let staged: Vec<PathBuf> = location.open()?.index.get_entries().keys().cloned().collect();
+        assert_eq!(staged, vec![PathBuf::from("file.txt")]);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_quiet_add_prints_nothing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        fs::write(repo.path.join("file.txt"), "content\n")?;
+        fs::write(repo.path.join("other.txt"), "content\n")?;
+        let location = Location::at(&repo.path);
+        
+        output::set_verbosity(Verbosity::Quiet);
+        let (result, printed) = output::capture(|| execute(&location, &["file.txt".to_string()], false));
+        result?;
+        assert_eq!(printed, "");
+        assert_eq!(Repository::open(&repo.path)?.index.get_entries().len(), 1);
+        
+        output::set_verbosity(Verbosity::Verbose);
+        let (result, printed) = output::capture(|| execute(&location, &["other.txt".to_string()], false));
+        result?;
+        assert_eq!(printed, "add 'other.txt'\nAdded 1 file(s) to the index\n");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_patch_stages_only_accepted_hunks() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let lines: Vec<String> = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        fs::write(repo.path.join("file.txt"), lines.concat())?;
+        run(&mut repo, &[PathBuf::from("file.txt")])?;
+        
+        // Two changes far enough apart to be separate hunks
+        let mut changed = lines.clone();
+        changed[1] = "line 2 changed\n".to_string();
+        changed[17] = "line 18 changed\n".to_string();
+        fs::write(repo.path.join("file.txt"), changed.concat())?;
+        
+        let mut output = Vec::new();
+        let staged = run_patch(&mut repo, &[PathBuf::from("file.txt")], "y\nn\n".as_bytes(), &mut output)?;
+        assert_eq!(staged, vec!["file.txt"]);
+        let output = String::from_utf8(output)?;
+        assert_eq!(output.matches("Stage this hunk").count(), 2);
+        assert!(output.contains("+line 2 changed\n"));
+        
+        let mut expected = lines.clone();
+        expected[1] = "line 2 changed\n".to_string();
+        let entry = &repo.index.get_entries()[&PathBuf::from("file.txt")];
+        assert_eq!(repo.read_object(&entry.object_id)?.data, expected.concat().into_bytes());
+        
+        // Declining everything leaves the index alone
+        let staged = run_patch(&mut repo, &[PathBuf::from("file.txt")], "q\n".as_bytes(), io::sink())?;
+        assert!(staged.is_empty());
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use std::path::Path;
+use crate::repository::{Repository, normalize_path, objects, refs};
+use crate::repository::diff::{self, DiffOp};
+use crate::repository::objects::Signature;
+use crate::repository::store::ObjectStore;
+use crate::repository::location::Location;
+
+/// Length of abbreviated commit names in `blame` output
+const ABBREV_LEN: usize = 8;
+
+/// The commit that last changed one line of a file
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    pub commit_id: String,
+    pub author: Signature,
+    /// 1-based line number in the blamed version of the file
+    pub line_number: usize,
+    pub content: String,
+}
+
+pub fn execute(location: &Location, file: &str) -> Result<()> {
+    let repo = location.open()?;
+    let current_dir = location.pathspec_dir(&repo);
+
+    let full_path = current_dir.join(file);
+    let Ok(relative_path) = full_path.strip_prefix(&repo.path) else {
+        anyhow::bail!("'{}' is outside repository at '{}'", file, repo.path.display());
+    };
+
+    for line in run(&repo, &normalize_path(relative_path))? {
+        let name = line.author.identity.split(" <").next().unwrap_or_default();
+        let date = DateTime::from_timestamp(line.author.seconds(), 0)
+            .map_or(line.author.timestamp.clone(), |date| date.format("%Y-%m-%d %H:%M:%S").to_string());
+        println!(
+            "{} ({} {} {}) {}",
+            &line.commit_id[..ABBREV_LEN.min(line.commit_id.len())],
+            name,
+            date,
+            line.line_number,
+            line.content.trim_end_matches('\n')
+        );
+    }
+
+    Ok(())
+}
+
+// The content of `path` as of `commit_id`, or None when the commit does not have the file
+fn file_at(store: &dyn ObjectStore, commit_id: &str, path: &Path) -> Result<Option<String>> {
+    let Ok(tree_id) = objects::read_commit_tree(store, commit_id) else {
+        return Ok(None); // Missing from a shallow clone
+    };
+    let Some(entry) = objects::find_tree_entry(store, &tree_id, path)? else {
+        return Ok(None);
+    };
+    let data = store.read(&entry.oid)?.data;
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+/// Attribute each line of `path` (relative to the repository root) at HEAD to the commit
+/// that last changed it.
+///
+/// History is followed through first parents only: each commit's version of the file is
+/// diffed against its first parent's, and lines the diff does not carry over belong to it.
+pub fn run(repo: &Repository, path: &Path) -> Result<Vec<BlameLine>> {
+    let store = repo.objects.as_ref();
+    let head = refs::get_head_commit(&repo.git_dir)?;
+    let content = file_at(store, &head, path)?
+        .with_context(|| format!("no such path '{}' in HEAD", path.display()))?;
+    let lines = diff::split_lines(&content);
+
+    // For each line of the blamed file: its index in the version currently examined, until blamed
+    let mut positions: Vec<Option<usize>> = (0..lines.len()).map(Some).collect();
+    let mut blamed: Vec<Option<String>> = vec![None; lines.len()];
+
+    let shallow = objects::shallow_commits(&repo.git_dir);
+    let mut commit_id = head;
+    let mut current = content.clone();
+    while positions.iter().any(Option::is_some) {
+        let parent = objects::commit_parents(repo, &commit_id, &shallow).into_iter().next();
+        let parent_content = match &parent {
+            Some(parent) => file_at(store, parent, path)?,
+            None => None,
+        };
+
+        // Map each line of this version to the parent's line it came from, if any
+        let mut origin: Vec<Option<usize>> = vec![None; diff::split_lines(&current).len()];
+        if let Some(parent_content) = &parent_content {
+            let ops = diff::diff(&diff::split_lines(parent_content), &diff::split_lines(&current));
+            for op in ops {
+                if let DiffOp::Equal { old_index, new_index } = op {
+                    origin[new_index] = Some(old_index);
+                }
+            }
+        }
+
+        for (line, position) in positions.iter_mut().enumerate() {
+            if let Some(index) = *position {
+                *position = origin[index];
+                if position.is_none() {
+                    blamed[line] = Some(commit_id.clone());
+                }
+            }
+        }
+
+        match (parent, parent_content) {
+            (Some(parent), Some(parent_content)) => {
+                commit_id = parent;
+                current = parent_content;
+            }
+            _ => break,
+        }
+    }
+
+    let mut result = Vec::new();
+    for (index, (line, commit_id)) in lines.iter().zip(blamed).enumerate() {
+        let commit_id = commit_id.context("line was never attributed")?;
+        result.push(BlameLine {
+            author: objects::read_commit(store, &commit_id)?.author,
+            commit_id,
+            line_number: index + 1,
+            content: line.to_string(),
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, content: &str, message: &str) -> Result<String> {
+        fs::write(repo.path.join("file.txt"), content)?;
+        repo.index.add_directory(&repo.path, repo.path.join("file.txt"), repo.objects.as_ref(), &repo.config)?;
+        Ok(commit::run(repo, &CommitOptions { message: Some(message), ..Default::default() })?.unwrap())
+    }
+
+    #[test]
+    fn test_blame_attributes_lines() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+
+        let first = commit_file(&mut repo, "one\ntwo\nthree\nfour\n", "First")?;
+        let second = commit_file(&mut repo, "one\nTWO\nthree\nfour\n", "Second")?;
+        let third = commit_file(&mut repo, "one\nTWO\nthree\nFOUR\nfive\n", "Third")?;
+
+        let blame = run(&repo, Path::new("file.txt"))?;
+        let owners: Vec<&str> = blame.iter().map(|line| line.commit_id.as_str()).collect();
+        assert_eq!(owners, vec![&first, &second, &first, &third, &third]);
+        assert_eq!(blame[1].content, "TWO\n");
+        assert_eq!(blame[4].line_number, 5);
+        assert!(run(&repo, Path::new("missing.txt")).is_err());
+
+        Ok(())
+    }
+}
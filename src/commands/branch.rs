@@ -1,45 +1,200 @@
 use anyhow::Result;
-use std::env;
-use crate::repository::{Repository, refs};
+use crate::repository::{Repository, objects, refs};
+use crate::repository::location::Location;
+use crate::info;
 
-pub fn execute(name: Option<&str>, delete: bool) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    
+pub fn execute(location: &Location, name: Option<&str>, delete: bool, force: bool, show_current: bool, set_upstream_to: Option<&str>) -> Result<()> {
     // Open the repository
-    let repo = Repository::open(&current_dir)?;
+    let mut repo = location.open()?;
+    
+    if show_current {
+        if let Some(branch) = current(&repo) {
+            println!("{}", branch);
+        }
+        return Ok(());
+    }
+    
+    if let Some(upstream) = set_upstream_to {
+        let branch = match name {
+            Some(name) => name.to_string(),
+            None => repo.current_branch()?,
+        };
+        set_upstream(&mut repo, &branch, upstream)?;
+        info!("branch '{}' set up to track '{}'.", branch, upstream);
+        return Ok(());
+    }
     
     if let Some(name) = name {
-        if delete {
+        if delete || force {
             // Delete branch
-            refs::delete_branch(&repo.git_dir, name)?;
-            #[cfg(not(feature = "online_judge"))]
-            println!("Deleted branch {}", name);
+            delete_branch(&repo, name, force)?;
+            info!("Deleted branch {}", name);
         } else {
             // Create branch
             let head_commit = refs::get_head_commit(&repo.git_dir)?;
             refs::create_branch(&repo.git_dir, name, &head_commit)?;
-            #[cfg(not(feature = "online_judge"))]
-            println!("Created branch {}", name);
+            info!("Created branch {}", name);
         }
     } else {
         // List branches
-        let _branches = refs::list_branches(&repo.git_dir)?;
-        let _current_branch = repo.current_branch()?;
+        let branches = refs::list_branches(&repo.git_dir)?;
+        let current_branch = repo.current_branch()?;
         
-        #[cfg(not(feature = "online_judge"))] {
-            if _branches.is_empty() {
-                println!("No branches");
-            } else {
-                for branch in _branches {
-                    if branch == _current_branch {
-                        println!("* {}", branch);
-                    } else {
-                        println!("  {}", branch);
-                    }
+        if branches.is_empty() {
+            info!("No branches");
+        } else {
+            for branch in branches {
+                if branch == current_branch {
+                    println!("* {}", branch);
+                } else {
+                    println!("  {}", branch);
                 }
             }
         }
     }
     
     Ok(())
-} 
\ No newline at end of file
+} 
+
+/// Delete the branch `name`.
+///
+/// Unless `force` is set, a branch whose tip is not reachable from HEAD is kept, so that
+/// deleting it cannot lose commits.
+pub fn delete_branch(repo: &Repository, name: &str, force: bool) -> Result<()> {
+    if !force {
+        let tip = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", name))
+            .map_err(|_| anyhow::anyhow!("Branch {} not found", name))?;
+        if let Some(head) = refs::head_commit(&repo.git_dir)? {
+            if !objects::is_ancestor(repo, &tip, &head)? {
+                anyhow::bail!(
+                    "The branch '{}' is not fully merged.\nIf you are sure you want to delete it, run 'rust-git branch -D {}'.",
+                    name,
+                    name
+                );
+            }
+        }
+    }
+    refs::delete_branch(&repo.git_dir, name)
+}
+
+/// The branch HEAD points at, or `None` when HEAD is detached
+pub fn current(repo: &Repository) -> Option<String> {
+    repo.current_branch().ok()
+}
+
+/// Make `branch` track `upstream`, written as `<remote>/<branch>` for a remote-tracking branch
+/// or as a local branch name, by setting `branch.<name>.remote` and `branch.<name>.merge`.
+pub fn set_upstream(repo: &mut Repository, branch: &str, upstream: &str) -> Result<()> {
+    let remote_tracking = upstream.split_once('/').filter(|(remote, _)| {
+        refs::read_ref(&repo.git_dir, &format!("refs/remotes/{}", upstream)).is_ok()
+            || repo.config.get_remote_url(remote).is_some()
+    });
+    let (remote, merge) = match remote_tracking {
+        Some((remote, remote_branch)) => (remote.to_string(), format!("refs/heads/{}", remote_branch)),
+        None if refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", upstream)).is_ok() => {
+            (".".to_string(), format!("refs/heads/{}", upstream))
+        }
+        None => anyhow::bail!("the requested upstream branch '{}' does not exist", upstream),
+    };
+
+    let config_path = repo.common_dir.join("config");
+    let section = format!("branch \"{}\"", branch);
+    repo.config.set(&config_path, &section, "remote", &remote)?;
+    repo.config.set(&config_path, &section, "merge", &merge)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::config::Config;
+    use crate::repository::objects::Signature;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_show_current() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        assert_eq!(current(&repo), Some("master".to_string()));
+
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        std::fs::write(repo.git_dir.join("HEAD"), format!("{}\n", head))?;
+        assert_eq!(current(&repo), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_requires_merged_unless_forced() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+
+        // A branch at HEAD is merged
+        refs::create_branch(&repo.git_dir, "merged", &head)?;
+        delete_branch(&repo, "merged", false)?;
+        assert!(!repo.git_dir.join("refs/heads/merged").exists());
+
+        // A branch with a commit HEAD does not have is not
+        let signature = Signature::now("Test User <test@example.com>");
+        let tree = objects::read_commit_tree(repo.objects.as_ref(), &head)?;
+        let commit = objects::write_commit(repo.objects.as_ref(), &tree, &[&head], "Unmerged", &signature, &signature)?;
+        refs::create_branch(&repo.git_dir, "topic", &commit)?;
+        let err = delete_branch(&repo, "topic", false).unwrap_err();
+        assert!(err.to_string().contains("not fully merged"));
+        assert!(repo.git_dir.join("refs/heads/topic").exists());
+
+        delete_branch(&repo, "topic", true)?;
+        assert!(!repo.git_dir.join("refs/heads/topic").exists());
+        assert!(delete_branch(&repo, "topic", false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_upstream_to_writes_config() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        refs::update_ref(&repo.git_dir, "refs/remotes/origin/main", &head)?;
+
+        set_upstream(&mut repo, "master", "origin/main")?;
+        let config = Config::open(&repo.git_dir.join("config"))?;
+        let section = &config.data["branch \"master\""];
+        assert_eq!(section["remote"], "origin");
+        assert_eq!(section["merge"], "refs/heads/main");
+        assert_eq!(config.branch_upstream("master"), Some("refs/remotes/origin/main".to_string()));
+
+        // Setting it again replaces the values instead of adding duplicates
+        refs::create_branch(&repo.git_dir, "topic", &head)?;
+        set_upstream(&mut repo, "master", "topic")?;
+        let content = std::fs::read_to_string(repo.git_dir.join("config"))?;
+        assert_eq!(content.matches("[branch \"master\"]").count(), 1);
+        assert_eq!(content.matches("merge = ").count(), 1);
+        assert_eq!(repo.config.branch_upstream("master"), Some("refs/heads/topic".to_string()));
+
+        assert!(set_upstream(&mut repo, "master", "nowhere/main").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_upstream_to_packed_branch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        std::fs::write(
+            repo.git_dir.join("packed-refs"),
+            format!("{} refs/heads/packed\n{} refs/remotes/mirror/main\n", head, head),
+        )?;
+
+        set_upstream(&mut repo, "master", "packed")?;
+        assert_eq!(repo.config.branch_upstream("master"), Some("refs/heads/packed".to_string()));
+
+        // `mirror` is not a configured remote; its packed remote-tracking branch is enough
+        set_upstream(&mut repo, "master", "mirror/main")?;
+        assert_eq!(repo.config.branch_upstream("master"), Some("refs/remotes/mirror/main".to_string()));
+
+        Ok(())
+    }
+}
@@ -1,69 +1,212 @@
 use anyhow::Result;
-use std::env;
-use std::str;
-use crate::repository::Repository;
-use hex;
-
-pub fn execute(object_hash: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
-
-    let (object_type, data) = crate::repository::objects::read_object(&repo.git_dir.join("objects"), object_hash)?;
-
-    match object_type.as_str() {
-        "blob" => {
-            // For blobs, just print the content as a string.
-            // git typically tries to print it as UTF-8, and might warn or error if it's not valid.
-            // For simplicity, we'll use from_utf8_lossy which will replace invalid UTF-8 sequences.
-            print!("{}", String::from_utf8_lossy(&data));
+use std::io::{self, BufRead, Write};
+use crate::repository::{Repository, objects};
+use crate::repository::objects::{Object, ObjectType, RawObject};
+use crate::repository::location::Location;
+
+/// What `cat-file` shows of an object
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CatFileMode {
+    /// The content, printed according to its type
+    #[default]
+    Pretty,
+    /// The type name
+    Type,
+    /// The size in bytes
+    Size,
+}
+
+/// Answer `--batch-check` queries: for each object id or prefix read from `input`, write
+/// `<oid> <type> <size>`, or `<input> missing` when it names no object.
+pub fn batch_check(repo: &Repository, input: impl BufRead, output: impl Write) -> Result<()> {
+    answer_batch(repo, input, output, false)
+}
+
+/// Answer `--batch` queries: like `batch_check`, but each header line is followed by the
+/// object's raw content and a newline, so a reader can take exactly `<size>` bytes after it.
+pub fn batch(repo: &Repository, input: impl BufRead, output: impl Write) -> Result<()> {
+    answer_batch(repo, input, output, true)
+}
+
+fn answer_batch(repo: &Repository, input: impl BufRead, mut output: impl Write, with_contents: bool) -> Result<()> {
+    let objects_dir = repo.common_dir.join("objects");
+    for line in input.lines() {
+        let line = line?;
+        let query = line.trim();
+        if query.is_empty() {
+            continue;
         }
-        "tree" => {
-            let mut g_cursor = 0;
-            while g_cursor < data.len() {
-                // Find the space separating mode and name
-                let g_space_idx = match data[g_cursor..].iter().position(|&b| b == b' ') {
-                    Some(idx) => idx + g_cursor,
-                    None => anyhow::bail!("Invalid tree object: missing space after mode"),
-                };
-                let g_mode_str = str::from_utf8(&data[g_cursor..g_space_idx])?;
-
-                // Find the null byte terminating the name
-                let g_nul_idx = match data[g_space_idx + 1..].iter().position(|&b| b == 0) {
-                    Some(idx) => idx + g_space_idx + 1,
-                    None => anyhow::bail!("Invalid tree object: missing null terminator after name"),
-                };
-                let g_name_str = str::from_utf8(&data[g_space_idx + 1..g_nul_idx])?;
-
-                // The SHA-1 hash is the next 20 bytes
-                let g_sha1_start = g_nul_idx + 1;
-                let g_sha1_end = g_sha1_start + 20;
-                if g_sha1_end > data.len() {
-                    anyhow::bail!("Invalid tree object: insufficient data for SHA-1 hash");
+
+        let object = objects::expand_object_id(&objects_dir, query)
+            .and_then(|object_id| Ok((repo.read_object(&object_id)?, object_id)));
+        match object {
+            Ok((object, object_id)) => {
+                writeln!(output, "{} {} {}", object_id, object.object_type, object.data.len())?;
+                if with_contents {
+                    output.write_all(&object.data)?;
+                    writeln!(output)?;
                 }
-                let g_sha1_bytes = &data[g_sha1_start..g_sha1_end];
-                let g_sha1_hex = hex::encode(g_sha1_bytes);
+            }
+            Err(_) => writeln!(output, "{} missing", query)?,
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
 
-                // Determine object type from mode (simplified)
-                let g_entry_type = if g_mode_str == "040000" {
-                    "tree"
-                } else {
-                    "blob"
-                };
+pub fn execute(location: &Location, object_hash: Option<&str>, mode: CatFileMode, allow_unknown_type: bool, batch_check_mode: bool, batch_mode: bool) -> Result<()> {
+    let repo = location.open()?;
 
-                println!("{:06} {} {}\t{}", g_mode_str, g_entry_type, g_sha1_hex, g_name_str);
+    if batch_check_mode {
+        return batch_check(&repo, io::stdin().lock(), io::stdout().lock());
+    }
+    if batch_mode {
+        return batch(&repo, io::stdin().lock(), io::stdout().lock());
+    }
+    let Some(object_hash) = object_hash else {
+        anyhow::bail!("an object is required unless --batch or --batch-check is given");
+    };
 
-                g_cursor = g_sha1_end;
+    match mode {
+        CatFileMode::Type | CatFileMode::Size => {
+            let (type_name, size) = type_and_size(&repo, object_hash, allow_unknown_type)?;
+            if mode == CatFileMode::Type {
+                println!("{}", type_name);
+            } else {
+                println!("{}", size);
             }
+            return Ok(());
+        }
+        CatFileMode::Pretty if allow_unknown_type => {
+            anyhow::bail!("--allow-unknown-type can only be used with -t or -s");
         }
-        "commit" => {
-            // For commits, print the commit message and other information.
-            // Commit objects are plain text.
+        CatFileMode::Pretty => {}
+    }
+
+    let (object_id, raw) = run(&repo, object_hash)?;
+    if raw.object_type == ObjectType::Tag {
+        // Refuse to show a tag whose structure or target is broken
+        objects::verify_tag(repo.objects.as_ref(), &object_id)?;
+    }
+    match raw.clone().parse()? {
+        Object::Blob(data) => {
+            // git prints blobs verbatim; we replace invalid UTF-8 sequences for simplicity.
             print!("{}", String::from_utf8_lossy(&data));
         }
-        _ => {
-            anyhow::bail!("Unknown object type: {}", object_type);
+        Object::Tree(entries) => {
+            for entry in entries {
+                let entry_type = if entry.mode == objects::MODE_TREE { ObjectType::Tree } else { ObjectType::Blob };
+                println!("{:06o} {} {}\t{}", entry.mode, entry_type, entry.oid, entry.name.display());
+            }
+        }
+        Object::Commit(_) | Object::Tag(_) => {
+            // Commits and tags are plain text; print them as stored rather than re-serialized.
+            print!("{}", String::from_utf8_lossy(&raw.data));
         }
     }
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Look up the object a full or abbreviated id names, returning its full id and content
+pub fn run(repo: &Repository, object_hash: &str) -> Result<(String, RawObject)> {
+    let object_id = objects::expand_object_id(repo.common_dir.join("objects"), object_hash)?;
+    let object = repo.read_object(&object_id)?;
+    Ok((object_id, object))
+}
+
+/// The type name and size of the object `object_hash` names, as `-t` and `-s` show them.
+///
+/// With `allow_unknown_type` they are taken from the object's header as stored, so an object
+/// of a type this tool does not know is reported instead of refused, which helps to diagnose
+/// corruption.
+pub fn type_and_size(repo: &Repository, object_hash: &str, allow_unknown_type: bool) -> Result<(String, usize)> {
+    if allow_unknown_type {
+        let objects_dir = repo.common_dir.join("objects");
+        let object_id = objects::expand_object_id(&objects_dir, object_hash)?;
+        return objects::read_object_header(&objects_dir, &object_id);
+    }
+    let (_, object) = run(repo, object_hash)?;
+    Ok((object.object_type.to_string(), object.data.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use sha1::{Digest, Sha1};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_allow_unknown_type_reports_header() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+
+        // A loose object whose header names a type git does not have
+        let content = b"garbage 5\0hello";
+        let object_id = hex::encode(Sha1::digest(content));
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content)?;
+        let object_dir = repo.common_dir.join("objects").join(&object_id[..2]);
+        fs::create_dir_all(&object_dir)?;
+        fs::write(object_dir.join(&object_id[2..]), encoder.finish()?)?;
+
+        assert_eq!(type_and_size(&repo, &object_id, true)?, ("garbage".to_string(), 5));
+        assert_eq!(type_and_size(&repo, &object_id[..8], true)?, ("garbage".to_string(), 5));
+        assert!(type_and_size(&repo, &object_id, false).is_err());
+
+        // Known types read the same either way
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"hello\n")?;
+        assert_eq!(type_and_size(&repo, &blob_id, false)?, ("blob".to_string(), 6));
+        assert_eq!(type_and_size(&repo, &blob_id, true)?, ("blob".to_string(), 6));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_check() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"hello\n")?;
+        let head = crate::repository::refs::get_head_commit(&repo.git_dir)?;
+        let commit_data = repo.read_object(&head)?.data;
+
+        let input = format!("{}\n{}\ndeadbeef\n\n{}\n", blob_id, &head[..8], "not-an-oid");
+        let mut output = Vec::new();
+        batch_check(&repo, input.as_bytes(), &mut output)?;
+
+        assert_eq!(
+            String::from_utf8(output)?,
+            format!(
+                "{} blob 6\n{} commit {}\ndeadbeef missing\nnot-an-oid missing\n",
+                blob_id,
+                head,
+                commit_data.len()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_frames_object_contents() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"hello\n")?;
+        let head = crate::repository::refs::get_head_commit(&repo.git_dir)?;
+        let commit_data = repo.read_object(&head)?.data;
+
+        let input = format!("{}\n{}\n{}\n", blob_id, head, "0".repeat(40));
+        let mut output = Vec::new();
+        batch(&repo, input.as_bytes(), &mut output)?;
+
+        let mut expected = format!("{} blob 6\nhello\n\n{} commit {}\n", blob_id, head, commit_data.len()).into_bytes();
+        expected.extend_from_slice(&commit_data);
+        expected.extend_from_slice(format!("\n{} missing\n", "0".repeat(40)).as_bytes());
+        assert_eq!(output, expected);
+
+        Ok(())
+    }
+}
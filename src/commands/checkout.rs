@@ -1,38 +1,113 @@
 use anyhow::Result;
-use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
-use hex;
-use crate::repository::{Repository, refs, objects};
+use std::collections::{BTreeSet, HashMap};
+use crate::repository::{Head, Repository, native_path, refs, objects, index};
+use crate::repository::config::Config;
+use crate::repository::objects::ObjectType;
+use crate::repository::store::ObjectStore;
+use crate::repository::location::Location;
+use crate::info;
 
-pub fn execute(branch_name: &str, create_branch_flag: bool) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let mut repo = Repository::open(&current_dir)?;
+// A file recorded in a tree
+struct TreeFile {
+    mode: u32,
+    object_id: String,
+}
 
-    if create_branch_flag {
+/// Write a checked-out blob to the working tree, recreating symlinks as links
+pub fn write_working_file(path: &Path, data: &[u8], mode: u32, config: &Config) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    
+    #[cfg(unix)]
+    if mode == index::MODE_SYMLINK {
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(path)?;
+        }
+        let target = String::from_utf8_lossy(data).into_owned();
+        std::os::unix::fs::symlink(target, path)?;
+        return Ok(());
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    
+    // Replace a link left from a previous checkout instead of writing through it
+    if path.symlink_metadata().is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        fs::remove_file(path)?;
+    }
+    fs::write(path, config.autocrlf().to_worktree(data))?;
+    Ok(())
+}
+
+pub fn execute(location: &Location, branch_name: &str, create_branch_flag: bool, start_point: Option<&str>, force: bool) -> Result<()> {
+    let mut repo = location.open()?;
+    run(&mut repo, branch_name, create_branch_flag, start_point, force)
+}
+
+/// Switch to `branch_name`, updating the working tree and index.
+///
+/// With `create_branch_flag`, the branch is first created at `start_point` (any revision
+/// `refs::resolve_revision` understands), or at HEAD when no start point is given.
+///
+/// Nothing is changed when the switch would overwrite uncommitted changes to tracked files,
+/// staged or not, unless `force` is given.
+pub fn run(repo: &mut Repository, branch_name: &str, create_branch_flag: bool, start_point: Option<&str>, force: bool) -> Result<()> {
+    // Get current HEAD commit BEFORE updating HEAD
+    let current_head_commit = match repo.head()? {
+        Head::Branch(_) => Some(refs::get_head_commit(&repo.git_dir)?),
+        Head::Detached(commit_id) => Some(commit_id),
+        Head::Unborn(_) => None,
+    };
+
+    let branch_commit = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", branch_name)).ok();
+    let target_commit = if create_branch_flag {
         // Check if branch already exists
-        let branch_path = repo.git_dir.join("refs/heads").join(branch_name);
-        if branch_path.exists() {
+        if branch_commit.is_some() {
             anyhow::bail!("Branch '{}' already exists", branch_name);
         }
 
-        let head_commit = refs::get_head_commit(&repo.git_dir)?;
-        refs::create_branch(&repo.git_dir, branch_name, &head_commit)?;
-        #[cfg(not(feature = "online_judge"))]
-        println!("Switched to a new branch '{}'", branch_name);
+        match start_point {
+            Some(start_point) => {
+                let commit_id = refs::resolve_revision(&repo.git_dir, start_point)?;
+                if repo.read_object(&commit_id)?.object_type != ObjectType::Commit {
+                    anyhow::bail!("'{}' is not a commit and a branch '{}' cannot be created from it", start_point, branch_name);
+                }
+                commit_id
+            }
+            None => match &current_head_commit {
+                Some(commit_id) => commit_id.clone(),
+                None => anyhow::bail!("Cannot create branch '{}': HEAD has no commits yet", branch_name),
+            },
+        }
     } else {
+        if start_point.is_some() {
+            anyhow::bail!("A start point can only be given when creating a branch with -b");
+        }
         // Check if the branch exists
-        let branch_path = repo.git_dir.join("refs/heads").join(branch_name);
-        if !branch_path.exists() {
+        let Some(branch_commit) = branch_commit else {
             anyhow::bail!("Branch '{}' not found. If you want to create it, use -b option.", branch_name);
+        };
+        branch_commit
+    };
+
+    if !force {
+        let overwritten = local_changes_overwritten(repo, current_head_commit.as_deref(), &target_commit)?;
+        if !overwritten.is_empty() {
+            anyhow::bail!(
+                "Your local changes would be overwritten by checkout:\n\t{}\nPlease commit your changes or stash them before you switch branches, or use --force to discard them.",
+                overwritten.join("\n\t")
+            );
         }
-        #[cfg(not(feature = "online_judge"))]
-        println!("Switched to branch '{}'", branch_name);
     }
 
-    // Get current HEAD commit BEFORE updating HEAD
-    let current_head_commit = refs::get_head_commit(&repo.git_dir).ok();
+    if create_branch_flag {
+        refs::create_branch(&repo.git_dir, branch_name, &target_commit)?;
+        info!("Switched to a new branch '{}'", branch_name);
+    } else {
+        info!("Switched to branch '{}'", branch_name);
+    }
     
     // Update HEAD to point to the new branch
     fs::write(
@@ -41,54 +116,75 @@ pub fn execute(branch_name: &str, create_branch_flag: bool) -> Result<()> {
     )?;
     
     // Update working directory and index to match the target branch
-    update_working_directory_and_index(&mut repo, branch_name, current_head_commit)?;
+    update_working_directory_and_index(repo, branch_name, current_head_commit, force)?;
     
     Ok(())
 }
 
-fn update_working_directory_and_index(repo: &mut Repository, branch_name: &str, current_head_commit: Option<String>) -> Result<()> {
+/// The tracked files, sorted, that differ between the current and target commits and have
+/// uncommitted changes: a staged change, or working tree content that differs from the index
+pub fn local_changes_overwritten(repo: &Repository, current_head_commit: Option<&str>, target_commit: &str) -> Result<Vec<String>> {
+    let store = repo.objects.as_ref();
+    let current_tree_files = match current_head_commit {
+        Some(commit_id) => get_tree_files(store, &objects::read_commit(store, commit_id)?.tree)?,
+        None => HashMap::new(),
+    };
+    let target_tree_files = get_tree_files(store, &objects::read_commit(store, target_commit)?.tree)?;
+
+    let paths: BTreeSet<&PathBuf> = current_tree_files.keys().chain(target_tree_files.keys()).collect();
+    let mut overwritten = Vec::new();
+    for path in paths {
+        let committed = current_tree_files.get(path).map(|file| (file.mode, &file.object_id));
+        let target = target_tree_files.get(path).map(|file| (file.mode, &file.object_id));
+        if committed == target {
+            continue;
+        }
+
+        let staged = repo.index.get_entries().get(path);
+        let staged_change = staged.map(|entry| (entry.mode, &entry.object_id)) != committed;
+        let full_path = repo.path.join(native_path(path));
+        let working_change = match staged {
+            Some(entry) if full_path.symlink_metadata().is_ok() => {
+                let content = index::read_worktree_content(&full_path, &repo.config)?;
+                objects::hash_object(&content, ObjectType::Blob) != entry.object_id
+            }
+            _ => false,
+        };
+        if staged_change || working_change {
+            overwritten.push(path.to_string_lossy().to_string());
+        }
+    }
+    Ok(overwritten)
+}
+
+/// Fill the working tree and index from `branch_name` in a repository with nothing checked
+/// out yet, such as a fresh clone. HEAD is left as it is.
+pub fn check_out_branch(repo: &mut Repository, branch_name: &str) -> Result<()> {
+    update_working_directory_and_index(repo, branch_name, None, false)
+}
+
+// Make the working tree and index match `branch_name`, coming from `current_head_commit`. With
+// `force`, files the two commits have in common are rewritten too, discarding local changes.
+fn update_working_directory_and_index(repo: &mut Repository, branch_name: &str, current_head_commit: Option<String>, force: bool) -> Result<()> {
     // Get the commit ID for the target branch
     let target_commit_id = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", branch_name))?;
     
     // Get current HEAD tree files (if exists)
-    let current_tree_files = if let Some(current_head_commit_id) = current_head_commit {
-        let (commit_type, commit_data) = objects::read_object(&repo.git_dir.join("objects"), &current_head_commit_id)?;
-        if commit_type == "commit" {
-            let commit_content = String::from_utf8_lossy(&commit_data);
-            let lines: Vec<&str> = commit_content.lines().collect();
-            if !lines.is_empty() && lines[0].starts_with("tree ") {
-                let current_tree_id = lines[0].strip_prefix("tree ").unwrap().trim();
-                get_tree_files(&repo.git_dir.join("objects"), current_tree_id)?
-            } else {
-                HashMap::new()
-            }
-        } else {
-            HashMap::new()
-        }
-    } else {
-        HashMap::new()
+    let store = repo.objects.as_ref();
+    let current_tree_files = match current_head_commit.map(|commit_id| objects::read_commit(store, &commit_id)) {
+        Some(Ok(commit)) => get_tree_files(store, &commit.tree)?,
+        _ => HashMap::new(),
     };
     
     // Get target branch tree files
-    let (commit_type, commit_data) = objects::read_object(&repo.git_dir.join("objects"), &target_commit_id)?;
-    if commit_type != "commit" {
-        anyhow::bail!("Expected commit object, got {}", commit_type);
-    }
-    
-    let commit_content = String::from_utf8_lossy(&commit_data);
-    let lines: Vec<&str> = commit_content.lines().collect();
-    if lines.is_empty() || !lines[0].starts_with("tree ") {
-        anyhow::bail!("Invalid commit object format");
-    }
-    
-    let target_tree_id = lines[0].strip_prefix("tree ").unwrap().trim();
-    let target_tree_files = get_tree_files(&repo.git_dir.join("objects"), target_tree_id)?;
+    let target_tree_id = objects::read_commit(store, &target_commit_id)?.tree;
+    let target_tree_files = get_tree_files(store, &target_tree_id)?;
     
     // Step 1: Remove files that exist in current tree but not in target tree
-    for (file_path, _) in &current_tree_files {
+    for file_path in current_tree_files.keys() {
         if !target_tree_files.contains_key(file_path.as_path()) {
-            let full_path = repo.path.join(file_path);
-            if full_path.is_file() {
+            let full_path = repo.path.join(native_path(file_path));
+            if full_path.symlink_metadata().is_ok() {
                 match fs::remove_file(&full_path) {
                     Ok(_) => {},
                     Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}, // Already gone
@@ -99,44 +195,42 @@ fn update_working_directory_and_index(repo: &mut Repository, branch_name: &str,
     }
     
     // Step 2: Add/update files from target tree
-    for (file_path, object_id) in &target_tree_files {
-        let (obj_type, blob_data) = objects::read_object(&repo.git_dir.join("objects"), object_id)?;
-        if obj_type != "blob" {
-            continue; // Skip non-blob objects
+    for (file_path, tree_file) in &target_tree_files {
+        // A file that is the same in both trees is left alone, so uncommitted changes to it,
+        // staged or not, carry over to the target branch
+        let unchanged = current_tree_files
+            .get(file_path.as_path())
+            .is_some_and(|current| current.object_id == tree_file.object_id && current.mode == tree_file.mode);
+        if unchanged && !force {
+            continue;
         }
         
-        // Write file to working directory
-        let full_path = repo.path.join(file_path);
-        if let Some(parent) = full_path.parent() {
-            fs::create_dir_all(parent)?;
+        let object_id = &tree_file.object_id;
+        let object = repo.read_object(object_id)?;
+        if object.object_type != ObjectType::Blob {
+            continue; // Skip non-blob objects
         }
-        fs::write(&full_path, &blob_data)?;
         
-        // Step 3: Update index only if the file is different from current tree
-        // or if it's not in the current tree at all
-        let should_update_index = match current_tree_files.get(file_path.as_path()) {
-            Some(current_object_id) => current_object_id != object_id, // Different content
-            None => true, // New file in target branch
-        };
+        // Write file to working directory
+        let full_path = repo.path.join(native_path(file_path));
+        write_working_file(&full_path, &object.data, tree_file.mode, &repo.config)?;
         
-        if should_update_index {
-            repo.index.add_file(&repo.path, &full_path, object_id)?;
-        }
-        // If the file is the same in both trees, preserve whatever is in the index
+        // Step 3: Update the index entry of the file just written
+        repo.index.add_file(&repo.path, &full_path, object_id)?;
     }
     
     // Step 4: Remove index entries for files that no longer exist in target tree
     // but preserve staged changes for files that still exist
     let mut paths_to_remove_from_index = Vec::new();
-    for (indexed_path, _) in repo.index.get_entries() {
+    for indexed_path in repo.index.get_entries().keys() {
         // If this path was in the current tree but not in target tree,
         // and it's not a staged change (i.e., it matches the current tree),
         // then remove it from index
-        if let Some(current_object_id) = current_tree_files.get(indexed_path.as_path()) {
+        if let Some(current) = current_tree_files.get(indexed_path.as_path()) {
             if !target_tree_files.contains_key(indexed_path.as_path()) {
                 // File was removed in target branch
                 let index_entry = repo.index.get_entries().get(indexed_path).unwrap();
-                if &index_entry.object_id == current_object_id {
+                if index_entry.object_id == current.object_id {
                     // Index matches current tree, so this is not a staged change
                     paths_to_remove_from_index.push(indexed_path.clone());
                 }
@@ -156,47 +250,209 @@ fn update_working_directory_and_index(repo: &mut Repository, branch_name: &str,
     Ok(())
 }
 
-// Modified to return HashMap<PathBuf, String>
-fn get_tree_files(objects_dir: &Path, tree_id: &str) -> Result<HashMap<PathBuf, String>> {
-    let mut files = HashMap::new();
-    
-    let (tree_type, tree_data) = objects::read_object(objects_dir, tree_id)?;
-    if tree_type != "tree" {
-        anyhow::bail!("Expected tree object, got {}", tree_type);
+fn get_tree_files(store: &dyn ObjectStore, tree_id: &str) -> Result<HashMap<PathBuf, TreeFile>> {
+    Ok(objects::flatten_tree(store, tree_id)?
+        .into_iter()
+        .map(|entry| (entry.name, TreeFile { mode: entry.mode, object_id: entry.oid }))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_checkout_restores_symlink() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let link_path = repo.path.join("link");
+        fs::write(repo.path.join("target.txt"), "target\n")?;
+        std::os::unix::fs::symlink("target.txt", &link_path)?;
+
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let options = CommitOptions { message: Some("Add link"), ..Default::default() };
+        let head = commit::run(&mut repo, &options)?.unwrap();
+
+        fs::remove_file(&link_path)?;
+        update_working_directory_and_index(&mut repo, "master", Some(head), true)?;
+
+        assert!(link_path.symlink_metadata()?.file_type().is_symlink());
+        assert_eq!(fs::read_link(&link_path)?, PathBuf::from("target.txt"));
+        assert_eq!(fs::read_to_string(&link_path)?, "target\n");
+
+        Ok(())
     }
-    
-    let mut cursor = 0;
-    while cursor < tree_data.len() {
-        if let Some(space_idx) = tree_data[cursor..].iter().position(|&b| b == b' ') {
-            let space_idx_abs = space_idx + cursor;
-            
-            if let Some(null_idx) = tree_data[space_idx_abs + 1..].iter().position(|&b| b == 0) {
-                let null_idx_abs = null_idx + space_idx_abs + 1;
-                let filename_bytes = &tree_data[space_idx_abs + 1..null_idx_abs];
-                let filename_str = std::str::from_utf8(filename_bytes)?;
-                let filename_path = PathBuf::from(filename_str); // Store as PathBuf
-                
-                let sha1_start = null_idx_abs + 1;
-                let sha1_end = sha1_start + 20;
-                if sha1_end <= tree_data.len() {
-                    let sha1_bytes = &tree_data[sha1_start..sha1_end];
-                    let sha1_hex = hex::encode(sha1_bytes);
-                    
-                    files.insert(filename_path, sha1_hex);
-                    cursor = sha1_end;
-                } else {
-                    // Malformed tree entry or end of data
-                    anyhow::bail!("Malformed tree object: not enough data for SHA1 hash");
-                }
-            } else {
-                // Malformed tree entry: no null terminator for filename
-                anyhow::bail!("Malformed tree object: no null terminator for filename");
-            }
-        } else {
-            // End of tree data or malformed entry
-            break;
-        }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_checkout_restores_non_utf8_file_name() -> Result<()> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let name = Path::new("dir").join(OsStr::from_bytes(b"caf\xe9.txt"));
+        let file_path = repo.path.join(&name);
+        fs::create_dir_all(file_path.parent().unwrap())?;
+        fs::write(&file_path, "latin-1\n")?;
+
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let options = CommitOptions { message: Some("Add a Latin-1 name"), ..Default::default() };
+        let head = commit::run(&mut repo, &options)?.unwrap();
+
+        // The name round-trips through the tree and the index on disk
+        let tree_id = objects::read_commit(repo.objects.as_ref(), &head)?.tree;
+        let tree = objects::flatten_tree(repo.objects.as_ref(), &tree_id)?;
+        assert!(tree.iter().any(|entry| entry.name == name));
+        assert!(Repository::open(&repo.path)?.index.get_entries().contains_key(&name));
+
+        fs::remove_dir_all(repo.path.join("dir"))?;
+        update_working_directory_and_index(&mut repo, "master", Some(head), true)?;
+        assert_eq!(fs::read_to_string(&file_path)?, "latin-1\n");
+
+        Ok(())
     }
-    
-    Ok(files)
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_checkout_new_branch_at_start_point() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let file_path = repo.path.join("file.txt");
+
+        fs::write(&file_path, "v1\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let first = commit::run(&mut repo, &CommitOptions { message: Some("v1"), ..Default::default() })?.unwrap();
+        fs::write(&file_path, "v2\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let second = commit::run(&mut repo, &CommitOptions { message: Some("v2"), ..Default::default() })?.unwrap();
+
+        run(&mut repo, "feature", true, Some(&first), false)?;
+
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/feature")?, first);
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/master")?, second);
+        assert_eq!(repo.current_branch()?, "feature");
+        assert_eq!(fs::read_to_string(&file_path)?, "v1\n");
+
+        assert!(run(&mut repo, "other", true, Some("no-such-branch"), false).is_err());
+        assert!(run(&mut repo, "master", false, Some(&first), false).is_err());
+
+        Ok(())
+    }
+
+    // Commit a CRLF file under the given autocrlf setting, check it out again and return
+    // the stored blob id and the checked-out content
+    fn round_trip_with_autocrlf(autocrlf: &str, content: &str) -> Result<(String, String)> {
+        let temp_dir = tempdir()?;
+        let path = Repository::init(temp_dir.path())?.path;
+        let config_path = path.join(".git/config");
+        let config = fs::read_to_string(&config_path)?;
+        fs::write(&config_path, format!("{}\tautocrlf = {}\n", config, autocrlf))?;
+        let mut repo = Repository::open(&path)?;
+
+        let file_path = repo.path.join("file.txt");
+        fs::write(&file_path, content)?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let blob_id = repo.index.get_entries().get(Path::new("file.txt")).unwrap().object_id.clone();
+        let options = CommitOptions { message: Some("Add file"), ..Default::default() };
+        let head = commit::run(&mut repo, &options)?.unwrap();
+
+        fs::remove_file(&file_path)?;
+        update_working_directory_and_index(&mut repo, "master", Some(head), true)?;
+        Ok((blob_id, fs::read_to_string(&file_path)?))
+    }
+
+    #[test]
+    fn test_autocrlf_input_normalizes_on_add_only() -> Result<()> {
+        let lf_blob = objects::hash_object(b"one\ntwo\n", ObjectType::Blob);
+
+        let (blob_id, checked_out) = round_trip_with_autocrlf("input", "one\r\ntwo\r\n")?;
+        assert_eq!(blob_id, lf_blob);
+        assert_eq!(checked_out, "one\ntwo\n");
+
+        let (blob_id, _) = round_trip_with_autocrlf("input", "one\ntwo\n")?;
+        assert_eq!(blob_id, lf_blob);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_autocrlf_true_converts_both_ways() -> Result<()> {
+        let lf_blob = objects::hash_object(b"one\ntwo\n", ObjectType::Blob);
+
+        let (blob_id, checked_out) = round_trip_with_autocrlf("true", "one\r\ntwo\r\n")?;
+        assert_eq!(blob_id, lf_blob);
+        assert_eq!(checked_out, "one\r\ntwo\r\n");
+
+        let (blob_id, checked_out) = round_trip_with_autocrlf("true", "one\ntwo\n")?;
+        assert_eq!(blob_id, lf_blob);
+        assert_eq!(checked_out, "one\r\ntwo\r\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkout_refuses_to_overwrite_local_changes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let file_path = repo.path.join("file.txt");
+
+        fs::write(&file_path, "v1\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("v1"), ..Default::default() })?;
+        run(&mut repo, "feature", true, None, false)?;
+        fs::write(&file_path, "v2\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("v2"), ..Default::default() })?;
+
+        fs::write(&file_path, "local edit\n")?;
+        let error = run(&mut repo, "master", false, None, false).unwrap_err().to_string();
+        assert!(error.starts_with("Your local changes would be overwritten by checkout:\n\tfile.txt\n"), "{}", error);
+        assert_eq!(repo.current_branch()?, "feature");
+        assert_eq!(fs::read_to_string(&file_path)?, "local edit\n");
+
+        // Staged changes are protected too
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        assert!(run(&mut repo, "master", false, None, false).is_err());
+
+        run(&mut repo, "master", false, None, true)?;
+        assert_eq!(repo.current_branch()?, "master");
+        assert_eq!(fs::read_to_string(&file_path)?, "v1\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkout_carries_over_changes_to_unchanged_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let file_path = repo.path.join("file.txt");
+        let other_path = repo.path.join("other.txt");
+
+        fs::write(&file_path, "v1\n")?;
+        fs::write(&other_path, "same\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("v1"), ..Default::default() })?;
+
+        // other.txt is the same on both sides, so the switch neither refuses nor reverts it
+        fs::write(&other_path, "local edit\n")?;
+        run(&mut repo, "feature", true, None, false)?;
+        assert_eq!(repo.current_branch()?, "feature");
+        assert_eq!(fs::read_to_string(&other_path)?, "local edit\n");
+
+        fs::write(&file_path, "v2\n")?;
+        repo.index.add_files(&repo.path, std::slice::from_ref(&file_path), repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("v2"), ..Default::default() })?;
+        run(&mut repo, "master", false, None, false)?;
+        assert_eq!(fs::read_to_string(&file_path)?, "v1\n");
+        assert_eq!(fs::read_to_string(&other_path)?, "local edit\n");
+
+        // --force discards it
+        run(&mut repo, "feature", false, None, true)?;
+        assert_eq!(fs::read_to_string(&other_path)?, "same\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::commands::status;
+use crate::repository::Repository;
+use crate::repository::ignore::IgnoreRules;
+use crate::repository::location::Location;
+use crate::info;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CleanOptions {
+    /// Actually delete; without it `run` only reports what would be removed
+    pub force: bool,
+    /// Also remove untracked directories
+    pub directories: bool,
+    /// Remove ignored files too
+    pub ignored: bool,
+}
+
+pub fn execute(location: &Location, options: &CleanOptions) -> Result<()> {
+    let repo = location.open()?;
+
+    let removed = run(&repo, options)?;
+
+    for path in &removed {
+        if options.force {
+            info!("Removing {}", path);
+        } else {
+            info!("Would remove {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+// The outermost directory above `path` that holds no tracked file, if any
+fn untracked_dir(path: &Path, tracked: &[&PathBuf]) -> Option<PathBuf> {
+    let mut parents: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect();
+    parents.reverse();
+    parents
+        .into_iter()
+        .find(|dir| !tracked.iter().any(|tracked_path| tracked_path.starts_with(dir)))
+        .map(Path::to_path_buf)
+}
+
+/// Remove the files `status` reports as untracked, or with `force` unset only list them.
+///
+/// Files inside untracked directories are left alone unless `directories` is set, in which
+/// case the whole directory goes at once. Paths matched by `.gitignore` are kept unless
+/// `ignored` is set; a directory holding such a path is cleaned file by file instead.
+/// Returns the removed paths relative to the repository root, directories with a trailing `/`.
+pub fn run(repo: &Repository, options: &CleanOptions) -> Result<Vec<String>> {
+    let rules = if options.ignored { IgnoreRules::default() } else { IgnoreRules::load(&repo.path)? };
+    let tracked: Vec<&PathBuf> = repo.index.get_entries().keys().collect();
+
+    let mut files = BTreeSet::new();
+    let mut dir_files: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    let mut dirs_with_ignored = HashSet::new();
+    for path in status::run(repo)?.untracked {
+        let path = PathBuf::from(path);
+        let dir = untracked_dir(&path, &tracked);
+        if rules.is_ignored(&path, false) {
+            dirs_with_ignored.extend(dir);
+            continue;
+        }
+        match dir {
+            Some(dir) if options.directories => dir_files.entry(dir).or_default().push(path),
+            Some(_) => {}
+            None => {
+                files.insert(path);
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+    let mut dirs = Vec::new();
+    for (dir, contents) in dir_files {
+        if dirs_with_ignored.contains(&dir) {
+            files.extend(contents);
+        } else {
+            dirs.push(dir);
+        }
+    }
+    for dir in dirs {
+        if options.force {
+            fs::remove_dir_all(repo.path.join(&dir))?;
+        }
+        removed.push(format!("{}/", dir.to_string_lossy()));
+    }
+    for file in files {
+        if options.force {
+            fs::remove_file(repo.path.join(&file))?;
+        }
+        removed.push(file.to_string_lossy().to_string());
+    }
+    removed.sort();
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use tempfile::tempdir;
+
+    fn repo_with_untracked_files() -> Result<(tempfile::TempDir, Repository)> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::create_dir_all(repo.path.join("src"))?;
+        fs::write(repo.path.join("src/lib.rs"), "tracked\n")?;
+        fs::write(repo.path.join(".gitignore"), "*.log\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("Initial"), ..Default::default() })?;
+
+        fs::write(repo.path.join("notes.txt"), "scratch\n")?;
+        fs::write(repo.path.join("src/tmp.rs"), "scratch\n")?;
+        fs::write(repo.path.join("debug.log"), "ignored\n")?;
+        fs::create_dir_all(repo.path.join("scratch/deep"))?;
+        fs::write(repo.path.join("scratch/deep/a.txt"), "scratch\n")?;
+        Ok((temp_dir, repo))
+    }
+
+    #[test]
+    fn test_dry_run_lists_without_deleting() -> Result<()> {
+        let (_temp_dir, repo) = repo_with_untracked_files()?;
+
+        let listed = run(&repo, &CleanOptions::default())?;
+        assert_eq!(listed, vec!["notes.txt", "src/tmp.rs"]);
+        assert!(repo.path.join("notes.txt").exists());
+        assert!(repo.path.join("src/tmp.rs").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_force_deletes_files_and_directories() -> Result<()> {
+        let (_temp_dir, repo) = repo_with_untracked_files()?;
+
+        let removed = run(&repo, &CleanOptions { force: true, directories: true, ..Default::default() })?;
+        assert_eq!(removed, vec!["notes.txt", "scratch/", "src/tmp.rs"]);
+        assert!(!repo.path.join("notes.txt").exists());
+        assert!(!repo.path.join("src/tmp.rs").exists());
+        assert!(!repo.path.join("scratch").exists());
+        assert!(repo.path.join("src/lib.rs").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignored_files_are_kept_without_x() -> Result<()> {
+        let (_temp_dir, repo) = repo_with_untracked_files()?;
+
+        run(&repo, &CleanOptions { force: true, ..Default::default() })?;
+        assert!(repo.path.join("debug.log").exists());
+        assert!(repo.path.join("scratch/deep/a.txt").exists());
+
+        let removed = run(&repo, &CleanOptions { force: true, ignored: true, ..Default::default() })?;
+        assert_eq!(removed, vec!["debug.log"]);
+        assert!(!repo.path.join("debug.log").exists());
+
+        Ok(())
+    }
+}
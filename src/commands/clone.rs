@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use crate::repository::{Repository, refs};
+use super::{checkout, fetch};
+use crate::repository::location::Location;
+use crate::info;
+
+/// Name of the remote a clone fetches from
+const ORIGIN: &str = "origin";
+
+/// Options for `clone`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CloneOptions {
+    /// Make a bare repository holding the remote's branches as its own
+    pub bare: bool,
+    /// As `bare`, and mark `origin` as a mirror
+    pub mirror: bool,
+}
+
+pub fn execute(location: &Location, url: &str, directory: Option<&str>, options: &CloneOptions) -> Result<()> {
+    let bare = options.bare || options.mirror;
+    let directory = match directory {
+        Some(directory) => directory.to_string(),
+        None if bare => format!("{}.git", directory_from_url(url)?),
+        None => directory_from_url(url)?,
+    };
+    let target_dir = location.current_dir.join(&directory);
+
+    if bare {
+        info!("Cloning into bare repository '{}'...", directory);
+        run_bare(url, &target_dir, options.mirror)?;
+        return Ok(());
+    }
+
+    info!("Cloning into '{}'...", directory);
+    let repo = run(url, &target_dir)?;
+
+    info!("Checked out branch '{}'", repo.current_branch()?);
+
+    Ok(())
+}
+
+// The directory `clone` creates when none is given: the last part of the URL without a
+// `.bundle` or `.git` suffix, e.g. `repo` for http://host/repo.bundle
+fn directory_from_url(url: &str) -> Result<String> {
+    let last = url.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    let name = last.strip_suffix(".bundle").or_else(|| last.strip_suffix(".git")).unwrap_or(last);
+    if name.is_empty() || name.contains(':') {
+        anyhow::bail!("Cannot guess a directory name from '{}'; please name one", url);
+    }
+    Ok(name.to_string())
+}
+
+/// Clone the repository served at `url` into `target_dir`, which must be missing or empty.
+///
+/// The remote is added as `origin` and fetched, then the branch the remote's HEAD points at
+/// is created, checked out and set to track its remote-tracking branch. Without an advertised
+/// HEAD, `master` is assumed.
+pub fn run(url: &str, target_dir: &Path) -> Result<Repository> {
+    create_target_dir(target_dir)?;
+
+    let mut repo = Repository::init(target_dir)?;
+    let initial_branch = repo.current_branch()?;
+    let config_path = repo.git_dir.join("config");
+    repo.config.set(&config_path, &format!("remote \"{}\"", ORIGIN), "url", url)?;
+    fetch::run(&repo, ORIGIN, None)?;
+
+    let remote_head = fs::read_to_string(repo.git_dir.join(format!("refs/remotes/{}/HEAD", ORIGIN))).unwrap_or_default();
+    let branch = remote_head
+        .trim()
+        .strip_prefix(&format!("ref: refs/remotes/{}/", ORIGIN))
+        .unwrap_or("master")
+        .to_string();
+    let tip = refs::read_ref(&repo.git_dir, &format!("refs/remotes/{}/{}", ORIGIN, branch))
+        .with_context(|| format!("remote HEAD refers to '{}', which was not fetched", branch))?;
+
+    // `init` made a root commit on its initial branch; the clone's history is the remote's alone
+    refs::update_ref(&repo.git_dir, &format!("refs/heads/{}", branch), &tip)?;
+    fs::write(repo.git_dir.join("HEAD"), format!("ref: refs/heads/{}\n", branch))?;
+    if branch != initial_branch {
+        refs::delete_branch(&repo.git_dir, &initial_branch)?;
+    }
+    checkout::check_out_branch(&mut repo, &branch)?;
+
+    let branch_section = format!("branch \"{}\"", branch);
+    repo.config.set(&config_path, &branch_section, "remote", ORIGIN)?;
+    repo.config.set(&config_path, &branch_section, "merge", &format!("refs/heads/{}", branch))?;
+
+    Ok(repo)
+}
+
+/// Clone the repository served at `url` into `target_dir` as a bare repository, with no
+/// working tree. `target_dir` must be missing or empty.
+///
+/// The remote's refs are stored under their own names, so its branches stay under
+/// `refs/heads` rather than becoming remote-tracking branches, and HEAD points at the
+/// remote's HEAD branch: the clone can be served as it is. With `mirror`, `origin` is
+/// also marked as a mirror.
+pub fn run_bare(url: &str, target_dir: &Path, mirror: bool) -> Result<Repository> {
+    create_target_dir(target_dir)?;
+
+    let mut repo = Repository::init_bare(target_dir)?;
+    let initial_branch = repo.current_branch()?;
+    let initial_commit = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", initial_branch))?;
+    let config_path = repo.git_dir.join("config");
+    let remote_section = format!("remote \"{}\"", ORIGIN);
+    repo.config.set(&config_path, &remote_section, "url", url)?;
+    if mirror {
+        repo.config.set(&config_path, &remote_section, "mirror", "true")?;
+    }
+    fetch::mirror(&repo, url)?;
+
+    // `init` made a root commit on its initial branch; the clone's refs are the remote's alone
+    if refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", initial_branch))? == initial_commit {
+        refs::delete_branch(&repo.git_dir, &initial_branch)?;
+    }
+
+    Ok(repo)
+}
+
+// Create the directory a clone goes into, refusing one that already has something in it
+fn create_target_dir(target_dir: &Path) -> Result<()> {
+    if target_dir.read_dir().is_ok_and(|mut entries| entries.next().is_some()) {
+        anyhow::bail!("destination path '{}' already exists and is not an empty directory", target_dir.display());
+    }
+    fs::create_dir_all(target_dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_from_url() -> Result<()> {
+        assert_eq!(directory_from_url("http://127.0.0.1:3000/repo.bundle")?, "repo");
+        assert_eq!(directory_from_url("https://example.com/team/project.git/")?, "project");
+        assert!(directory_from_url("http://127.0.0.1:3000/").is_err());
+        Ok(())
+    }
+}
@@ -1,75 +1,636 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::env;
-use crate::repository::{Repository, objects, refs};
+use std::fs;
+use std::io::ErrorKind;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::commands::{merge, status};
+use crate::repository::{Head, Repository, hooks, normalize_path, objects, refs};
+use crate::repository::objects::Signature;
+use crate::repository::location::Location;
+use crate::info;
 
-pub fn execute(message: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    
+/// Editors tried in turn when `EDITOR` is not set
+const FALLBACK_EDITORS: [&str; 2] = ["vi", "nano"];
+
+/// Options for `commit`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CommitOptions<'a> {
+    /// Commit message; when amending, the previous message is reused if absent
+    pub message: Option<&'a str>,
+    /// Replace the HEAD commit instead of adding a new one on top of it
+    pub amend: bool,
+    /// When amending, take authorship with the current identity instead of keeping the original author
+    pub reset_author: bool,
+    /// First stage every change to tracked files, including deletions
+    pub all: bool,
+    /// Append a `Signed-off-by` trailer for the configured identity
+    pub signoff: bool,
+    /// Skip the `pre-commit` and `commit-msg` hooks
+    pub no_verify: bool,
+    /// Without a message, take the prepared merge message as it is instead of opening the editor
+    pub no_edit: bool,
+    /// Commit with the message `fixup! <subject>` of this commit, for a later rebase to fold
+    /// the new commit into it
+    pub fixup: Option<&'a str>,
+}
+
+pub fn execute(location: &Location, options: &CommitOptions) -> Result<()> {
     // Open the repository
-    let repo = Repository::open(&current_dir)?;
-    
+    let mut repo = location.open()?;
+
+    // Without -m, ask for the message in an editor, unless a merge prepared one to take as is
+    let edited;
+    let options = if options.message.is_none() && options.fixup.is_none() && !options.amend && !options.no_edit {
+        edited = edit_message(&repo, env::var("EDITOR").ok().as_deref())?;
+        CommitOptions { message: Some(&edited), ..*options }
+    } else {
+        *options
+    };
+
+    let Some(commit_id) = run(&mut repo, &options)? else {
+        println!("Nothing to commit, working tree clean");
+        return Ok(());
+    };
+
+    // The online judge reads the new commit's id, whatever the verbosity
+    #[cfg(feature = "online_judge")]
+    println!("{}", commit_id);
+    let message = objects::read_commit(repo.objects.as_ref(), &commit_id)?.message;
+    match repo.head()? {
+        Head::Branch(branch) | Head::Unborn(branch) => info!("[{}] {}", branch, message),
+        Head::Detached(_) => info!("[detached HEAD {}] {}", &commit_id[..7], message),
+    }
+
+    Ok(())
+}
+
+/// Record the index as a new commit on the current branch.
+///
+/// Unless `no_verify` is set, the `pre-commit` hook runs first and the `commit-msg` hook is
+/// given the message in `.git/COMMIT_EDITMSG`, which it may rewrite; either failing aborts
+/// the commit.
+///
+/// While a merge stopped on conflicts is in progress, the commit concludes it: it gets the
+/// merged commit as a second parent and, without a message, the one in `.git/MERGE_MSG`.
+///
+/// Returns the new commit id, or `None` when the index matches HEAD and there is nothing to commit.
+pub fn run(repo: &mut Repository, options: &CommitOptions) -> Result<Option<String>> {
+    if options.all {
+        repo.index.update_tracked(&repo.path, repo.objects.as_ref(), &repo.config)?;
+    }
+    // The hook sees the index that is about to be committed, and the refreshed index is kept
+    // even when it turns out there is nothing to commit
+    if options.all || !options.no_verify {
+        repo.index.save(repo.git_dir.join("index"))?;
+    }
+    if !options.no_verify {
+        hooks::run(repo, "pre-commit", &[])?;
+    }
+
     // Write the current tree from index
-    let current_tree_id = objects::write_tree(&repo)?;
-    
-    // Get the current branch and parent commit
-    let branch = repo.current_branch()?;
-    let parent_commits = match refs::get_head_commit(&repo.git_dir) {
-        Ok(commit) => vec![commit],
-        Err(_) => Vec::new(), // No previous commits (initial commit)
+    let current_tree_id = objects::write_tree(repo)?;
+
+    // Get the current branch and parent commit; a detached HEAD is moved itself
+    let ref_name = match repo.head()? {
+        Head::Branch(branch) | Head::Unborn(branch) => format!("refs/heads/{}", branch),
+        Head::Detached(_) => "HEAD".to_string(),
     };
-    
-    // Check if there are changes to commit
-    if !parent_commits.is_empty() {
-        // Get the tree ID from the previous commit
-        let parent_commit_id = &parent_commits[0];
-        let (commit_type, commit_data) = objects::read_object(&repo.git_dir.join("objects"), parent_commit_id)?;
-        
-        if commit_type != "commit" {
-            anyhow::bail!("Expected commit object, got {}", commit_type);
-        }
-        
-        // Parse the commit to get the tree ID
-        let commit_content = String::from_utf8_lossy(&commit_data);
-        let lines: Vec<&str> = commit_content.lines().collect();
-        if lines.is_empty() || !lines[0].starts_with("tree ") {
-            anyhow::bail!("Invalid commit object format");
+    let head = refs::head_commit(&repo.git_dir)?;
+    let head_info = match &head {
+        Some(commit) => Some(objects::read_commit(repo.objects.as_ref(), commit)?),
+        None => None, // No previous commits (initial commit)
+    };
+
+    let merge_head = merge::merge_head(repo)?;
+
+    let identity = repo.config.user_identity();
+    let committer = Signature::now(&identity);
+    let (parent_commits, author, message) = if options.amend {
+        let Some(head_info) = head_info else {
+            anyhow::bail!("You have nothing to amend");
+        };
+        if merge_head.is_some() {
+            anyhow::bail!("You are in the middle of a merge -- cannot amend.");
         }
-        
-        let previous_tree_id = lines[0].strip_prefix("tree ").unwrap().trim();
-        
-        // Compare current tree with previous tree
-        if current_tree_id == previous_tree_id {
-            println!("Nothing to commit, working tree clean");
-            return Ok(());
+        let author = if options.reset_author { committer.clone() } else { head_info.author };
+        let message = options.message.map_or(head_info.message, str::to_string);
+        (head_info.parents, author, message)
+    } else {
+        let message = match (options.fixup, options.message, &merge_head) {
+            (Some(target), _, _) => fixup_message(repo, target)?,
+            (None, Some(message), _) => message.to_string(),
+            (None, None, Some(_)) => {
+                let message = cleanup_message(&merge::merge_message(repo)?.unwrap_or_default());
+                if message.is_empty() {
+                    anyhow::bail!("Aborting commit due to empty commit message");
+                }
+                message
+            }
+            (None, None, None) => anyhow::bail!("A commit message is required"),
+        };
+
+        // Check if there are changes to commit; a merge commit is made even without any
+        if let (Some(info), None) = (&head_info, &merge_head) {
+            if index_matches_tree(repo, &info.tree)? {
+                return Ok(None);
+            }
         }
-    }
-    
+
+        (head.into_iter().chain(merge_head.clone()).collect(), committer.clone(), message)
+    };
+    let message = if options.signoff { add_signoff(&message, &identity) } else { message };
+    let message = if options.no_verify { message } else { verify_message(repo, message)? };
+
     // Create the commit
-    let author = "Rust-git <user@example.com>";
     let parent_refs: Vec<&str> = parent_commits.iter().map(|s| s.as_str()).collect();
-    
+
     let commit_id = objects::write_commit(
-        &repo.git_dir.join("objects"),
+        repo.objects.as_ref(),
         &current_tree_id,
         &parent_refs,
-        message,
-        author,
+        &message,
+        &author,
+        &committer,
     )?;
-    
+
     // Update the branch reference
-    refs::update_ref(
-        &repo.git_dir,
-        &format!("refs/heads/{}", branch),
-        &commit_id,
-    )?;
-    
+    refs::update_ref(&repo.git_dir, &ref_name, &commit_id)?;
+
     // Save the index to preserve the current state
     repo.index.save(repo.git_dir.join("index"))?;
-    
-    #[cfg(feature = "online_judge")]
-    println!("{}", commit_id);
-    #[cfg(not(feature = "online_judge"))]
-    println!("[{}] {}", branch, message);
-    
+    if merge_head.is_some() {
+        merge::clear_merge_state(repo)?;
+    }
+
+    Ok(Some(commit_id))
+}
+
+// The message of a commit fixing up `target`: `fixup! ` and the subject of the commit it names
+fn fixup_message(repo: &Repository, target: &str) -> Result<String> {
+    let commit_id = refs::resolve_revision(&repo.git_dir, target)
+        .or_else(|_| objects::expand_object_id(repo.common_dir.join("objects"), target))
+        .map_err(|_| anyhow::anyhow!("could not lookup commit '{}'", target))?;
+    let commit = objects::read_commit(repo.objects.as_ref(), &commit_id)?;
+    let subject = commit.message.lines().next().unwrap_or_default();
+    Ok(format!("fixup! {}", subject))
+}
+
+// Pass `message` to the `commit-msg` hook through `.git/COMMIT_EDITMSG`, returning the
+// message as the hook leaves it
+fn verify_message(repo: &Repository, message: String) -> Result<String> {
+    let path = repo.git_dir.join("COMMIT_EDITMSG");
+    fs::write(&path, format!("{}\n", message))?;
+    hooks::run(repo, "commit-msg", &[&path])?;
+    Ok(fs::read_to_string(&path)?.trim_end().to_string())
+}
+
+// Whether the index records exactly the files of `tree_id`, whatever the tree's layout.
+// Comparing files rather than tree ids counts staged deletions as changes, and does not
+// mistake a nested tree (say, from a fetched bundle) for a change from the index's flat one.
+fn index_matches_tree(repo: &Repository, tree_id: &str) -> Result<bool> {
+    let tree_files: HashMap<PathBuf, (u32, String)> = objects::flatten_tree(repo.objects.as_ref(), tree_id)?
+        .into_iter()
+        .map(|entry| (normalize_path(&entry.name), (entry.mode, entry.oid)))
+        .collect();
+    let index = repo.index.get_entries();
+    Ok(index.len() == tree_files.len()
+        && index.iter().all(|(path, entry)| {
+            tree_files.get(path).is_some_and(|(mode, oid)| *mode == entry.mode && *oid == entry.object_id)
+        }))
+}
+
+/// Ask for a commit message by opening `.git/COMMIT_EDITMSG`, prefilled with a commented
+/// status summary, in `editor` (the `EDITOR` command) or else vi or nano. A message prepared
+/// in `.git/MERGE_MSG` by a merge comes first.
+///
+/// Lines starting with `#` are dropped from what the editor leaves behind; an empty message
+/// aborts the commit.
+pub fn edit_message(repo: &Repository, editor: Option<&str>) -> Result<String> {
+    let path = repo.git_dir.join("COMMIT_EDITMSG");
+    fs::write(&path, message_template(repo)?)?;
+    launch_editor(&path, editor)?;
+
+    let message = cleanup_message(&fs::read_to_string(&path)?);
+    if message.is_empty() {
+        anyhow::bail!("Aborting commit due to empty commit message");
+    }
+    Ok(message)
+}
+
+// A message as written in a file: without `#` comment lines and surrounding blank lines
+fn cleanup_message(text: &str) -> String {
+    let message = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    message.trim().to_string()
+}
+
+// The commented text the editor opens with
+fn message_template(repo: &Repository) -> Result<String> {
+    let mut lines = vec![
+        // A merge's prepared message, then a blank line, or just the blank line
+        merge::merge_message(repo)?.map(|message| format!("{}\n", message.trim_end())).unwrap_or_default(),
+        "# Please enter the commit message for your changes. Lines starting".to_string(),
+        "# with '#' will be ignored, and an empty message aborts the commit.".to_string(),
+        "#".to_string(),
+        match repo.head()? {
+            Head::Branch(branch) | Head::Unborn(branch) => format!("# On branch {}", branch),
+            Head::Detached(commit_id) => format!("# HEAD detached at {}", &commit_id[..7.min(commit_id.len())]),
+        },
+    ];
+    let report = status::run(repo)?;
+    if !report.staged.is_empty() {
+        lines.push("# Changes to be committed:".to_string());
+        lines.extend(report.staged.iter().map(|(file, status)| format!("#\t{}: {}", status, file)));
+    }
+    if !report.unstaged.is_empty() {
+        lines.push("# Changes not staged for commit:".to_string());
+        lines.extend(report.unstaged.iter().map(|(file, status)| format!("#\t{}: {}", status, file)));
+    }
+    if !report.untracked.is_empty() {
+        lines.push("# Untracked files:".to_string());
+        lines.extend(report.untracked.iter().map(|file| format!("#\t{}", file)));
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+// Run `editor` on `path` and wait for it to exit, falling back to vi and then nano
+fn launch_editor(path: &Path, editor: Option<&str>) -> Result<()> {
+    let status = match editor {
+        // Through the shell, so EDITOR may carry arguments such as `code --wait`
+        Some(editor) if !editor.trim().is_empty() => Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(editor)
+            .arg(path)
+            .status()
+            .with_context(|| format!("Failed to launch editor '{}'", editor))?,
+        _ => {
+            let mut launched = None;
+            for editor in FALLBACK_EDITORS {
+                match Command::new(editor).arg(path).status() {
+                    Ok(status) => {
+                        launched = Some(status);
+                        break;
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e).with_context(|| format!("Failed to launch editor '{}'", editor)),
+                }
+            }
+            launched.context("No editor found; set EDITOR or pass the message with -m")?
+        }
+    };
+    if !status.success() {
+        anyhow::bail!("There was a problem with the editor ({}); aborting commit", status);
+    }
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Append a `Signed-off-by: <identity>` trailer to `message`.
+///
+/// The trailer joins a trailer block that already ends the message, and otherwise starts a new
+/// paragraph after a blank line. A message already signed off by `identity` is left as it is.
+pub fn add_signoff(message: &str, identity: &str) -> String {
+    let message = message.trim_end_matches('\n');
+    let trailer = format!("Signed-off-by: {}", identity);
+
+    let paragraphs: Vec<&str> = message.split("\n\n").collect();
+    let last = paragraphs.last().copied().unwrap_or("");
+    let is_trailer = |line: &str| {
+        line.split_once(": ").is_some_and(|(token, _)| {
+            !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    };
+    // The subject alone is never a trailer block, whatever it looks like
+    let ends_with_trailers = paragraphs.len() > 1 && last.lines().all(is_trailer);
+
+    if ends_with_trailers && last.lines().any(|line| line == trailer) {
+        message.to_string()
+    } else if ends_with_trailers {
+        format!("{}\n{}", message, trailer)
+    } else {
+        format!("{}\n\n{}", message, trailer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::index::{Index, IndexEntry};
+    use crate::repository::objects::ObjectType;
+    use tempfile::tempdir;
+
+    fn read_commit_info(repo: &Repository, commit_id: &str) -> Result<objects::Commit> {
+        objects::read_commit(repo.objects.as_ref(), commit_id)
+    }
+
+    fn author_of(repo: &Repository, commit_id: &str) -> Result<String> {
+        Ok(read_commit_info(repo, commit_id)?.author.identity)
+    }
+
+    #[test]
+    fn test_amend_preserves_or_resets_author() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+
+        // A commit authored by someone else, e.g. applied from a patch
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let tree = read_commit_info(&repo, &head)?.tree;
+        let original = objects::write_commit(
+            repo.objects.as_ref(),
+            &tree,
+            &[&head],
+            "Their change",
+            &Signature::parse("Someone Else <else@example.com> 1600000000 +0000").unwrap(),
+            &Signature::now("Someone Else <else@example.com>"),
+        )?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &original)?;
+
+        let amended = run(&mut repo, &CommitOptions { amend: true, ..Default::default() })?.unwrap();
+        let info = read_commit_info(&repo, &amended)?;
+        assert_ne!(amended, original);
+        assert_eq!(info.parents, vec![head.clone()]);
+        assert_eq!(info.message, "Their change");
+        assert_eq!(info.author.to_string(), "Someone Else <else@example.com> 1600000000 +0000");
+
+        let reset = run(&mut repo, &CommitOptions {
+            message: Some("Reworded"),
+            amend: true,
+            reset_author: true,
+            ..Default::default()
+        })?.unwrap();
+        assert_eq!(author_of(&repo, &reset)?, repo.config.user_identity());
+        assert_eq!(read_commit_info(&repo, &reset)?.parents, vec![head]);
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, reset);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_all_stages_tracked_changes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.path.join("tracked.txt"), "v1\n")?;
+        std::fs::write(repo.path.join("removed.txt"), "gone soon\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        run(&mut repo, &CommitOptions { message: Some("Initial files"), ..Default::default() })?;
+
+        std::fs::write(repo.path.join("tracked.txt"), "v2\n")?;
+        std::fs::remove_file(repo.path.join("removed.txt"))?;
+        std::fs::write(repo.path.join("untracked.txt"), "new\n")?;
+
+        let head = run(&mut repo, &CommitOptions { message: Some("Update"), all: true, ..Default::default() })?.unwrap();
+        let tree_id = read_commit_info(&repo, &head)?.tree;
+        let tree: Vec<(String, String)> = objects::flatten_tree(repo.objects.as_ref(), &tree_id)?
+            .into_iter()
+            .map(|entry| (entry.name.to_string_lossy().to_string(), entry.oid))
+            .collect();
+        assert_eq!(tree, vec![("tracked.txt".to_string(), objects::hash_object(b"v2\n", ObjectType::Blob))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_all_saves_index_with_nothing_to_commit() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.path.join("file.txt"), "v1\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        run(&mut repo, &CommitOptions { message: Some("Initial"), ..Default::default() })?;
+
+        // Stage a change, then undo it in the working tree only
+        std::fs::write(repo.path.join("file.txt"), "v2\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        repo.index.save(repo.git_dir.join("index"))?;
+        std::fs::write(repo.path.join("file.txt"), "v1\n")?;
+
+        let options = CommitOptions { message: Some("Nothing"), all: true, no_verify: true, ..Default::default() };
+        assert_eq!(run(&mut repo, &options)?, None);
+        let saved = Index::load(repo.git_dir.join("index"))?;
+        assert_eq!(saved.get_entries()[Path::new("file.txt")].object_id, objects::hash_object(b"v1\n", ObjectType::Blob));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_commit_on_unborn_branch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.git_dir.join("HEAD"), "ref: refs/heads/orphan\n")?;
+
+        assert!(refs::head_commit(&repo.git_dir)?.is_none());
+        assert!(refs::get_head_commit(&repo.git_dir).unwrap_err().is::<refs::UnbornBranchError>());
+        assert!(crate::commands::log::run(&repo, &[], &Default::default())?.is_empty());
+        assert_eq!(crate::commands::status::branch_header(&repo)?, "## No commits yet on orphan");
+
+        std::fs::write(repo.path.join("file.txt"), "content\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let commit_id = run(&mut repo, &CommitOptions { message: Some("First"), ..Default::default() })?.unwrap();
+
+        assert!(read_commit_info(&repo, &commit_id)?.parents.is_empty());
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/orphan")?, commit_id);
+        assert_eq!(crate::commands::log::run(&repo, &[], &Default::default())?, vec![commit_id]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixup_message_names_target_subject() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.path.join("file.txt"), "v1\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let target = run(&mut repo, &CommitOptions { message: Some("Add file\n\nWith a body"), ..Default::default() })?.unwrap();
+
+        std::fs::write(repo.path.join("file.txt"), "v2\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let fixup = run(&mut repo, &CommitOptions { fixup: Some(&target[..7]), ..Default::default() })?.unwrap();
+
+        let commit = read_commit_info(&repo, &fixup)?;
+        assert_eq!(commit.message, "fixup! Add file");
+        assert_eq!(commit.parents, vec![target]);
+        assert!(run(&mut repo, &CommitOptions { fixup: Some("no-such-commit"), ..Default::default() }).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_on_detached_head() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let initial = refs::get_head_commit(&repo.git_dir)?;
+        std::fs::write(repo.git_dir.join("HEAD"), format!("{}\n", initial))?;
+        assert_eq!(crate::commands::status::branch_header(&repo)?, "## HEAD (no branch)");
+
+        std::fs::write(repo.path.join("file.txt"), "content\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let commit_id = run(&mut repo, &CommitOptions { message: Some("Detached"), ..Default::default() })?.unwrap();
+
+        // HEAD moves on its own, leaving the branch behind
+        assert_eq!(repo.head()?, Head::Detached(commit_id.clone()));
+        assert_eq!(read_commit_info(&repo, &commit_id)?.parents, vec![initial.clone()]);
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/master")?, initial);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_signoff_adds_one_trailer() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.path.join("file.txt"), "content\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let trailer = format!("Signed-off-by: {}", repo.config.user_identity());
+
+        let options = CommitOptions { message: Some("Add file\n\nWith a body"), signoff: true, ..Default::default() };
+        let commit_id = run(&mut repo, &options)?.unwrap();
+        let message = read_commit_info(&repo, &commit_id)?.message;
+        assert_eq!(message, format!("Add file\n\nWith a body\n\n{}", trailer));
+
+        // Signing off again while amending keeps a single trailer
+        let commit_id = run(&mut repo, &CommitOptions { amend: true, signoff: true, ..Default::default() })?.unwrap();
+        let message = read_commit_info(&repo, &commit_id)?.message;
+        assert!(message.ends_with(&format!("With a body\n\n{}", trailer)));
+        assert_eq!(message.matches(&trailer).count(), 1);
+
+        // An existing trailer block is extended rather than followed by another paragraph
+        assert_eq!(
+            add_signoff("Fix: crash\n\nReviewed-by: A <a@example.com>\n", "B <b@example.com>"),
+            "Fix: crash\n\nReviewed-by: A <a@example.com>\nSigned-off-by: B <b@example.com>"
+        );
+        assert_eq!(add_signoff("Fix: crash", "B <b@example.com>"), "Fix: crash\n\nSigned-off-by: B <b@example.com>");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_staged_deletion() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.path.join("kept.txt"), "kept\n")?;
+        std::fs::write(repo.path.join("gone.txt"), "gone\n")?;
+        std::fs::write(repo.path.join("cached.txt"), "cached\n")?;
+        repo.index.add_directory(&repo.path, repo.path.join("kept.txt"), repo.objects.as_ref(), &repo.config)?;
+        repo.index.add_directory(&repo.path, repo.path.join("gone.txt"), repo.objects.as_ref(), &repo.config)?;
+        repo.index.add_directory(&repo.path, repo.path.join("cached.txt"), repo.objects.as_ref(), &repo.config)?;
+        run(&mut repo, &CommitOptions { message: Some("Add files"), ..Default::default() })?.unwrap();
+
+        // Staged deletions: one gone from disk too, one still in the working tree
+        crate::commands::rm::run(&mut repo, &[PathBuf::from("gone.txt")])?;
+        repo.index.remove_path(&repo.path, "cached.txt")?;
+        assert!(repo.path.join("cached.txt").is_file());
+
+        let commit_id = run(&mut repo, &CommitOptions { message: Some("Remove files"), ..Default::default() })?.unwrap();
+        let tree = read_commit_info(&repo, &commit_id)?.tree;
+        let store = repo.objects.as_ref();
+        assert!(objects::find_tree_entry(store, &tree, Path::new("kept.txt"))?.is_some());
+        assert!(objects::find_tree_entry(store, &tree, Path::new("gone.txt"))?.is_none());
+        assert!(objects::find_tree_entry(store, &tree, Path::new("cached.txt"))?.is_none());
+
+        // With the deletions recorded there is nothing left to commit
+        assert_eq!(run(&mut repo, &CommitOptions { message: Some("Again"), ..Default::default() })?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nothing_to_commit_against_nested_tree() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+
+        // HEAD records dir/file.txt in a subtree of its own, as trees from other tools do
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"nested\n")?;
+        let mut subtree = b"100644 file.txt\0".to_vec();
+        subtree.extend(hex::decode(&blob_id)?);
+        let subtree_id = repo.objects.write(ObjectType::Tree, &subtree)?;
+        let mut root = b"40000 dir\0".to_vec();
+        root.extend(hex::decode(&subtree_id)?);
+        let root_id = repo.objects.write(ObjectType::Tree, &root)?;
+        let signature = Signature::now("Test User <test@example.com>");
+        let commit_id = objects::write_commit(repo.objects.as_ref(), &root_id, &[&head], "Nested", &signature, &signature)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &commit_id)?;
+
+        // An index holding the same file has nothing to commit, whatever tree it would write
+        repo.index.clear();
+        repo.index.insert_entry(PathBuf::from("dir/file.txt"), IndexEntry { mtime: 0, object_id: blob_id, mode: 0o100644 });
+        assert_eq!(run(&mut repo, &CommitOptions { message: Some("Empty"), ..Default::default() })?, None);
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, commit_id);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_message_from_editor() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::write(repo.path.join("file.txt"), "content\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+
+        // An editor that checks it was given the status summary, then replaces it with a message
+        let script = temp_dir.path().join("editor.sh");
+        std::fs::write(&script, "#!/bin/sh\ngrep -q '^#\tnew file: file.txt$' \"$1\" || exit 1\nprintf 'Edited message\\n# dropped\\n\\nBody\\n' > \"$1\"\n")?;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        let editor = script.to_str().unwrap();
+
+        let message = edit_message(&repo, Some(editor))?;
+        assert_eq!(message, "Edited message\n\nBody");
+        let commit_id = run(&mut repo, &CommitOptions { message: Some(&message), ..Default::default() })?.unwrap();
+        assert_eq!(read_commit_info(&repo, &commit_id)?.message, "Edited message\n\nBody");
+
+        // Leaving only comments aborts
+        std::fs::write(&script, "#!/bin/sh\necho '# nothing' > \"$1\"\n")?;
+        assert!(edit_message(&repo, Some(editor)).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hooks_can_block_and_be_skipped() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        std::fs::write(repo.path.join("file.txt"), "content\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+
+        let hooks_dir = repo.git_dir.join("hooks");
+        std::fs::create_dir_all(&hooks_dir)?;
+        let install = |name: &str, script: &str| -> Result<()> {
+            let path = hooks_dir.join(name);
+            std::fs::write(&path, script)?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+            Ok(())
+        };
+        install("pre-commit", "#!/bin/sh\necho 'checks failed' >&2\nexit 1\n")?;
+
+        let options = CommitOptions { message: Some("Blocked"), ..Default::default() };
+        let error = run(&mut repo, &options).unwrap_err();
+        assert!(error.to_string().contains("pre-commit hook failed"), "{}", error);
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, head);
+
+        let options = CommitOptions { message: Some("Unverified"), no_verify: true, ..Default::default() };
+        let commit_id = run(&mut repo, &options)?.unwrap();
+        assert_eq!(read_commit_info(&repo, &commit_id)?.message, "Unverified");
+
+        // commit-msg gets the message file and may rewrite it
+        install("pre-commit", "#!/bin/sh\nexit 0\n")?;
+        install("commit-msg", "#!/bin/sh\nprintf '\\nReviewed-by: Hook\\n' >> \"$1\"\n")?;
+        std::fs::write(repo.path.join("file.txt"), "changed\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let commit_id = run(&mut repo, &CommitOptions { message: Some("Verified"), ..Default::default() })?.unwrap();
+        assert_eq!(read_commit_info(&repo, &commit_id)?.message, "Verified\n\nReviewed-by: Hook");
+
+        Ok(())
+    }
+}
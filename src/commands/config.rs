@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use crate::repository::Repository;
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location, list: bool, get_regexp: Option<&str>) -> Result<()> {
+    let repo = location.open()?;
+
+    let lines = match get_regexp {
+        Some(pattern) => get_regexp_lines(&repo, pattern)?,
+        None if list => list_lines(&repo),
+        None => anyhow::bail!("Nothing to do: use --list or --get-regexp <pattern>"),
+    };
+    for line in lines {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Every setting as a `name=value` line, with names flattened to `section.key` or
+/// `section.subsection.key`
+pub fn list_lines(repo: &Repository) -> Vec<String> {
+    repo.config
+        .entries()
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect()
+}
+
+/// The settings whose flattened name matches the regular expression `pattern` anywhere,
+/// as `name value` lines
+pub fn get_regexp_lines(repo: &Repository, pattern: &str) -> Result<Vec<String>> {
+    let regex = Regex::new(pattern).with_context(|| format!("invalid pattern '{}'", pattern))?;
+    Ok(repo
+        .config
+        .entries()
+        .into_iter()
+        .filter(|(name, _)| regex.is_match(name))
+        .map(|(name, value)| format!("{} {}", name, value))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::config::Config;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_list_and_get_regexp() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let config_path = repo.git_dir.join("config");
+        fs::write(
+            &config_path,
+            "[user]\n\tname = A U Thor\n\temail = author@example.com\n[remote \"origin\"]\n\turl = http://127.0.0.1:3000/repo.bundle\n",
+        )?;
+        repo.config = Config::open(&config_path)?;
+
+        assert_eq!(list_lines(&repo), vec![
+            "remote.origin.url=http://127.0.0.1:3000/repo.bundle",
+            "user.email=author@example.com",
+            "user.name=A U Thor",
+        ]);
+        assert_eq!(get_regexp_lines(&repo, r"^user\.")?, vec![
+            "user.email author@example.com",
+            "user.name A U Thor",
+        ]);
+        assert_eq!(get_regexp_lines(&repo, "origin")?, vec!["remote.origin.url http://127.0.0.1:3000/repo.bundle"]);
+        assert!(get_regexp_lines(&repo, "(").is_err());
+
+        Ok(())
+    }
+}
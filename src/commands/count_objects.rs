@@ -0,0 +1,145 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use crate::output::{self, Verbosity};
+use crate::repository::pack;
+use crate::repository::Repository;
+use crate::repository::location::Location;
+
+/// What the object store holds, as `count-objects -v` reports it. Sizes are the disk space
+/// used, in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectCounts {
+    /// Loose objects
+    pub count: usize,
+    pub size: u64,
+    /// Objects in packs, as their indexes list them
+    pub in_pack: usize,
+    /// Packs with an index
+    pub packs: usize,
+    pub size_pack: u64,
+    /// Loose objects that a pack holds as well, which `prune-packed` would remove
+    pub prune_packable: usize,
+    /// Files in the object store that are neither objects nor complete packs
+    pub garbage: usize,
+    pub size_garbage: u64,
+}
+
+pub fn execute(location: &Location) -> Result<()> {
+    let repo = location.open()?;
+    let counts = run(&repo)?;
+    let kib = |bytes: u64| bytes.div_ceil(1024);
+
+    // The global --verbose asks for the full report, as `git count-objects -v` does
+    if output::verbosity() == Verbosity::Verbose {
+        println!("count: {}", counts.count);
+        println!("size: {}", kib(counts.size));
+        println!("in-pack: {}", counts.in_pack);
+        println!("packs: {}", counts.packs);
+        println!("size-pack: {}", kib(counts.size_pack));
+        println!("prune-packable: {}", counts.prune_packable);
+        println!("garbage: {}", counts.garbage);
+        println!("size-garbage: {}", kib(counts.size_garbage));
+    } else {
+        println!("{} objects, {} kilobytes", counts.count, kib(counts.size));
+    }
+
+    Ok(())
+}
+
+/// Count the loose objects, the packs and their objects, and the garbage in `repo`'s object store
+pub fn run(repo: &Repository) -> Result<ObjectCounts> {
+    let objects_dir = repo.common_dir.join("objects");
+    let mut counts = ObjectCounts::default();
+
+    let loose = pack::scan_loose_objects(&objects_dir)?;
+    for path in loose.others {
+        counts.garbage += 1;
+        counts.size_garbage += disk_usage(&fs::metadata(path)?);
+    }
+
+    // A pack counts with its index; either one alone, or anything else in pack/, is garbage
+    let mut packed = HashSet::new();
+    if let Ok(pack_dir) = fs::read_dir(objects_dir.join("pack")) {
+        for entry in pack_dir {
+            let path = entry?.path();
+            let size = disk_usage(&fs::metadata(&path)?);
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("pack") if path.with_extension("idx").is_file() => {
+                    let idx_path = path.with_extension("idx");
+                    counts.packs += 1;
+                    counts.size_pack += size;
+                    counts.in_pack += pack::pack_index_count(&idx_path)?;
+                    packed.extend(pack::read_pack_index(&idx_path)?.into_iter().map(|(oid, _)| oid));
+                }
+                Some("idx") if path.with_extension("pack").is_file() => counts.size_pack += size,
+                _ => {
+                    counts.garbage += 1;
+                    counts.size_garbage += size;
+                }
+            }
+        }
+    }
+
+    for (oid, path) in loose.objects {
+        counts.count += 1;
+        counts.size += disk_usage(&fs::metadata(path)?);
+        if packed.contains(&oid) {
+            counts.prune_packable += 1;
+        }
+    }
+
+    Ok(counts)
+}
+
+// The space a file takes on disk, which is what git reports, rather than its length
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.blocks() * 512
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_counts_loose_then_packed_objects() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::write(repo.path.join("a.txt"), "a\n")?;
+        fs::write(repo.path.join("b.txt"), "b\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("Add files"), ..Default::default() })?;
+
+        let objects_dir = repo.common_dir.join("objects");
+        let reachable = pack::reachable_objects(&objects_dir, &repo.ref_tips()?)?;
+        let counts = run(&repo)?;
+        assert_eq!(counts.count, reachable.len());
+        assert!(counts.size > 0);
+        assert_eq!((counts.in_pack, counts.packs, counts.garbage), (0, 0, 0));
+
+        // A stray file in a fan-out dir is garbage
+        let stray = objects_dir.join("ab").join("tmp_obj_123");
+        fs::create_dir_all(stray.parent().unwrap())?;
+        fs::write(&stray, "junk")?;
+
+        repo.repack()?;
+        let counts = run(&repo)?;
+        assert_eq!((counts.count, counts.size), (0, 0));
+        assert_eq!((counts.in_pack, counts.packs), (reachable.len(), 1));
+        assert!(counts.size_pack > 0);
+        assert_eq!(counts.garbage, 1);
+        assert!(counts.size_garbage > 0);
+
+        Ok(())
+    }
+}
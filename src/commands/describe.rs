@@ -0,0 +1,147 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::repository::{Repository, objects, refs};
+use crate::repository::objects::ObjectType;
+use crate::repository::location::Location;
+
+/// Length of abbreviated object names in `describe` output
+const ABBREV_LEN: usize = 7;
+
+pub fn execute(location: &Location, always: bool) -> Result<()> {
+    let repo = location.open()?;
+
+    println!("{}", describe(&repo, always)?);
+
+    Ok(())
+}
+
+/// A tag candidate for `describe`: annotated tags win over lightweight ones on the same commit.
+struct TagName {
+    name: String,
+    annotated: bool,
+}
+
+// Follow annotated tag objects until reaching the commit they tag
+fn peel_to_commit(repo: &Repository, object_id: &str) -> Option<(String, bool)> {
+    let mut object_id = object_id.to_string();
+    let mut annotated = false;
+
+    loop {
+        let object = repo.read_object(&object_id).ok()?;
+        match object.object_type {
+            ObjectType::Commit => return Some((object_id, annotated)),
+            ObjectType::Tag => {
+                annotated = true;
+                object_id = String::from_utf8_lossy(&object.data)
+                    .lines()
+                    .find_map(|line| line.strip_prefix("object "))?
+                    .trim()
+                    .to_string();
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Describe HEAD relative to the nearest reachable tag as `<tag>-<n>-g<shortsha>`,
+/// or just `<tag>` when HEAD is tagged.
+///
+/// With `always`, an untagged history is described by the abbreviated HEAD commit instead of an error.
+pub fn describe(repo: &Repository, always: bool) -> Result<String> {
+    let head = refs::get_head_commit(&repo.git_dir)?;
+
+    // Map tagged commits to the tag that names them best
+    let mut tagged: HashMap<String, TagName> = HashMap::new();
+    for (name, object_id) in refs::list_tags(&repo.git_dir)? {
+        let Some((commit_id, annotated)) = peel_to_commit(repo, &object_id) else {
+            continue; // Tags of trees or blobs cannot describe a commit
+        };
+        let better = tagged.get(&commit_id).is_none_or(|current| annotated && !current.annotated);
+        if better {
+            tagged.insert(commit_id, TagName { name, annotated });
+        }
+    }
+
+    // Breadth-first walk from HEAD finds the tag with the shortest parent path
+    let shallow = objects::shallow_commits(&repo.git_dir);
+    let mut queue = VecDeque::from([head.clone()]);
+    let mut visited = HashSet::new();
+    let mut nearest = None;
+    while let Some(commit_id) = queue.pop_front() {
+        if !visited.insert(commit_id.clone()) {
+            continue;
+        }
+        if let Some(tag) = tagged.get(&commit_id) {
+            nearest = Some((commit_id, tag));
+            break;
+        }
+        queue.extend(objects::commit_parents(repo, &commit_id, &shallow));
+    }
+
+    let abbrev = &head[..ABBREV_LEN.min(head.len())];
+    let Some((tag_commit, tag)) = nearest else {
+        if always {
+            return Ok(abbrev.to_string());
+        }
+        anyhow::bail!("No names found, cannot describe anything.");
+    };
+
+    if tag_commit == head {
+        return Ok(tag.name.clone());
+    }
+
+    // Count the commits in HEAD's history that are not in the tag's history
+    let tag_history = objects::ancestors(repo, &tag_commit);
+    let distance = objects::ancestors(repo, &head).difference(&tag_history).count();
+
+    Ok(format!("{}-{}-g{}", tag.name, distance, abbrev))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::objects::Signature;
+    use tempfile::tempdir;
+
+    fn commit(repo: &Repository, parent: &str, message: &str) -> Result<String> {
+        let signature = Signature::now("Test User <test@example.com>");
+        let commit_id = objects::write_commit(
+            repo.objects.as_ref(),
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+            &[parent],
+            message,
+            &signature,
+            &signature,
+        )?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &commit_id)?;
+        Ok(commit_id)
+    }
+
+    #[test]
+    fn test_describe_descendant_of_tag() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let root = refs::get_head_commit(&repo.git_dir)?;
+
+        assert!(describe(&repo, false).is_err());
+        assert_eq!(describe(&repo, true)?, root[..7]);
+
+        let tagged = commit(&repo, &root, "Release")?;
+        refs::update_ref(&repo.git_dir, "refs/tags/v1.0", &tagged)?;
+        assert_eq!(describe(&repo, false)?, "v1.0");
+
+        let first = commit(&repo, &tagged, "After release")?;
+        let head = commit(&repo, &first, "More work")?;
+        assert_eq!(describe(&repo, false)?, format!("v1.0-2-g{}", &head[..7]));
+
+        // An annotated tag on the newer commit is nearer
+        let tag_object = repo.objects.write(
+            ObjectType::Tag,
+            format!("object {}\ntype commit\ntag v1.1\ntagger Test User <test@example.com> 0 +0000\n\nv1.1\n", first).as_bytes(),
+        )?;
+        refs::update_ref(&repo.git_dir, "refs/tags/v1.1", &tag_object)?;
+        assert_eq!(describe(&repo, false)?, format!("v1.1-1-g{}", &head[..7]));
+
+        Ok(())
+    }
+}
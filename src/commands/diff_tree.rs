@@ -0,0 +1,247 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use crate::commands::ls_tree;
+use crate::repository::{Repository, diff, normalize_path};
+use crate::repository::objects::{Object, TreeEntry, MODE_TREE};
+use crate::repository::store::ObjectStore;
+use crate::repository::location::Location;
+
+/// Lines of unchanged context around each change in a patch
+const PATCH_CONTEXT: usize = 3;
+
+/// How a path differs between two trees
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeChange {
+    Added(TreeEntry),
+    Modified { old: TreeEntry, new: TreeEntry },
+    Deleted(TreeEntry),
+    /// Moved without changing content
+    Renamed { old: TreeEntry, new: TreeEntry },
+}
+
+impl TreeChange {
+    /// The path the change is listed under: the new one for a rename
+    pub fn path(&self) -> &Path {
+        match self {
+            TreeChange::Added(entry) | TreeChange::Deleted(entry) => &entry.name,
+            TreeChange::Modified { new, .. } | TreeChange::Renamed { new, .. } => &new.name,
+        }
+    }
+
+    /// `<status>\t<path>` as `git diff-tree --name-status` prints it, with
+    /// `R100\t<old>\t<new>` for a rename
+    pub fn status_line(&self) -> String {
+        match self {
+            TreeChange::Added(entry) => format!("A\t{}", entry.name.display()),
+            TreeChange::Modified { new, .. } => format!("M\t{}", new.name.display()),
+            TreeChange::Deleted(entry) => format!("D\t{}", entry.name.display()),
+            TreeChange::Renamed { old, new } => format!("R100\t{}\t{}", old.name.display(), new.name.display()),
+        }
+    }
+}
+
+pub fn execute(location: &Location, commit_a: &str, commit_b: &str, patch: bool) -> Result<()> {
+    let repo = location.open()?;
+
+    for change in run(&repo, commit_a, commit_b)? {
+        println!("{}", change.status_line());
+        if patch {
+            print!("{}", format_patch(repo.objects.as_ref(), &change)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the trees of `tree_ish_a` and `tree_ish_b` (commits, tags or trees), listing the
+/// files that differ sorted by path.
+///
+/// Subtrees with the same id on both sides are skipped without being read. A deleted file
+/// whose exact content was added under another path is reported as a rename.
+pub fn run(repo: &Repository, tree_ish_a: &str, tree_ish_b: &str) -> Result<Vec<TreeChange>> {
+    let old_tree = ls_tree::resolve_tree(repo, tree_ish_a)?;
+    let new_tree = ls_tree::resolve_tree(repo, tree_ish_b)?;
+    let mut changes = Vec::new();
+    compare_trees(repo.objects.as_ref(), Some(&old_tree), Some(&new_tree), Path::new(""), &mut changes)?;
+    detect_renames(&mut changes);
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(changes)
+}
+
+// The entries of `tree_id` by name, or none for a missing side
+fn tree_entries(store: &dyn ObjectStore, tree_id: Option<&str>) -> Result<BTreeMap<PathBuf, TreeEntry>> {
+    let Some(tree_id) = tree_id else {
+        return Ok(BTreeMap::new());
+    };
+    let Object::Tree(entries) = store.read(tree_id)?.parse()? else {
+        anyhow::bail!("not a tree object: {}", tree_id);
+    };
+    Ok(entries.into_iter().map(|entry| (entry.name.clone(), entry)).collect())
+}
+
+// Add the differences between `old_tree` and `new_tree`, found at `prefix`, to `changes`.
+// A missing side counts as an empty tree.
+fn compare_trees(
+    store: &dyn ObjectStore,
+    old_tree: Option<&str>,
+    new_tree: Option<&str>,
+    prefix: &Path,
+    changes: &mut Vec<TreeChange>,
+) -> Result<()> {
+    let old_entries = tree_entries(store, old_tree)?;
+    let new_entries = tree_entries(store, new_tree)?;
+    let names: BTreeSet<&PathBuf> = old_entries.keys().chain(new_entries.keys()).collect();
+
+    for name in names {
+        let path = normalize_path(&prefix.join(name));
+        let with_path = |entry: &TreeEntry| TreeEntry { name: path.clone(), ..entry.clone() };
+        let old = old_entries.get(name).filter(|entry| entry.mode != MODE_TREE);
+        let new = new_entries.get(name).filter(|entry| entry.mode != MODE_TREE);
+        let old_subtree = old_entries.get(name).filter(|entry| entry.mode == MODE_TREE).map(|entry| entry.oid.as_str());
+        let new_subtree = new_entries.get(name).filter(|entry| entry.mode == MODE_TREE).map(|entry| entry.oid.as_str());
+
+        if old_subtree != new_subtree {
+            compare_trees(store, old_subtree, new_subtree, &path, changes)?;
+        }
+        match (old, new) {
+            (Some(old), Some(new)) if old.oid != new.oid || old.mode != new.mode => {
+                changes.push(TreeChange::Modified { old: with_path(old), new: with_path(new) });
+            }
+            (Some(old), None) => changes.push(TreeChange::Deleted(with_path(old))),
+            (None, Some(new)) => changes.push(TreeChange::Added(with_path(new))),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Pair each deleted file with an added file of the same content and mode as a rename
+fn detect_renames(changes: &mut Vec<TreeChange>) {
+    let mut i = 0;
+    while i < changes.len() {
+        let TreeChange::Deleted(old) = &changes[i] else {
+            i += 1;
+            continue;
+        };
+        let added = changes.iter().position(|change| {
+            matches!(change, TreeChange::Added(new) if new.oid == old.oid && new.mode == old.mode)
+        });
+        let Some(added) = added else {
+            i += 1;
+            continue;
+        };
+        let TreeChange::Added(new) = changes.remove(added) else {
+            unreachable!("position matched an addition");
+        };
+        let index = if added < i { i - 1 } else { i };
+        let TreeChange::Deleted(old) = changes.remove(index) else {
+            unreachable!("the deletion is still at its index");
+        };
+        changes.insert(index, TreeChange::Renamed { old, new });
+        i = index + 1;
+    }
+}
+
+/// The patch for one change in git's format: a `diff --git` header, the file mode lines that
+/// apply, and unified hunks of the content
+pub fn format_patch(store: &dyn ObjectStore, change: &TreeChange) -> Result<String> {
+    let (old, new) = match change {
+        TreeChange::Added(entry) => (None, Some(entry)),
+        TreeChange::Deleted(entry) => (Some(entry), None),
+        TreeChange::Modified { old, new } | TreeChange::Renamed { old, new } => (Some(old), Some(new)),
+    };
+    let old_path = old.or(new).map(|entry| entry.name.display().to_string()).unwrap_or_default();
+    let new_path = new.or(old).map(|entry| entry.name.display().to_string()).unwrap_or_default();
+
+    let mut patch = format!("diff --git a/{} b/{}\n", old_path, new_path);
+    match (old, new) {
+        (None, Some(new)) => patch.push_str(&format!("new file mode {:o}\n", new.mode)),
+        (Some(old), None) => patch.push_str(&format!("deleted file mode {:o}\n", old.mode)),
+        (Some(old), Some(new)) => {
+            if old.mode != new.mode {
+                patch.push_str(&format!("old mode {:o}\nnew mode {:o}\n", old.mode, new.mode));
+            }
+            if old.name != new.name {
+                patch.push_str(&format!("rename from {}\nrename to {}\n", old_path, new_path));
+            }
+        }
+        (None, None) => {}
+    }
+    if old.map(|entry| &entry.oid) == new.map(|entry| &entry.oid) {
+        return Ok(patch);
+    }
+
+    let read = |entry: Option<&TreeEntry>| -> Result<Vec<u8>> {
+        entry.map_or(Ok(Vec::new()), |entry| Ok(store.read(&entry.oid)?.data))
+    };
+    let (old_data, new_data) = (read(old)?, read(new)?);
+    let old_label = old.map_or("/dev/null".to_string(), |_| format!("a/{}", old_path));
+    let new_label = new.map_or("/dev/null".to_string(), |_| format!("b/{}", new_path));
+    if old_data.contains(&0) || new_data.contains(&0) {
+        patch.push_str(&format!("Binary files {} and {} differ\n", old_label, new_label));
+        return Ok(patch);
+    }
+    patch.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+    patch.push_str(&diff::unified_diff(
+        &String::from_utf8_lossy(&old_data),
+        &String::from_utf8_lossy(&new_data),
+        PATCH_CONTEXT,
+    ));
+    Ok(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::commands::rm;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn commit(repo: &mut Repository, message: &str) -> Result<String> {
+        Ok(commit::run(repo, &CommitOptions { message: Some(message), ..Default::default() })?.unwrap())
+    }
+
+    #[test]
+    fn test_status_lines_and_patch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::create_dir(repo.path.join("src"))?;
+        fs::write(repo.path.join("src/modified.txt"), "one\ntwo\nthree\n")?;
+        fs::write(repo.path.join("deleted.txt"), "deleted\n")?;
+        fs::write(repo.path.join("old_name.txt"), "moved\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let before = commit(&mut repo, "Before")?;
+
+        fs::write(repo.path.join("src/modified.txt"), "one\n2\nthree\n")?;
+        fs::write(repo.path.join("added.txt"), "added\n")?;
+        fs::rename(repo.path.join("old_name.txt"), repo.path.join("new_name.txt"))?;
+        rm::run(&mut repo, &[PathBuf::from("deleted.txt"), PathBuf::from("old_name.txt")])?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let after = commit(&mut repo, "After")?;
+
+        let changes = run(&repo, &before, &after)?;
+        let lines: Vec<String> = changes.iter().map(TreeChange::status_line).collect();
+        assert_eq!(lines, vec![
+            "A\tadded.txt",
+            "D\tdeleted.txt",
+            "R100\told_name.txt\tnew_name.txt",
+            "M\tsrc/modified.txt",
+        ]);
+        assert!(run(&repo, &after, &after)?.is_empty());
+
+        let store = repo.objects.as_ref();
+        let modified = changes.iter().find(|change| change.path() == Path::new("src/modified.txt")).unwrap();
+        assert_eq!(
+            format_patch(store, modified)?,
+            "diff --git a/src/modified.txt b/src/modified.txt\n--- a/src/modified.txt\n+++ b/src/modified.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"
+        );
+        assert_eq!(
+            format_patch(store, &changes[0])?,
+            "diff --git a/added.txt b/added.txt\nnew file mode 100644\n--- /dev/null\n+++ b/added.txt\n@@ -0,0 +1 @@\n+added\n"
+        );
+
+        Ok(())
+    }
+}
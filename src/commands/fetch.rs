@@ -1,6 +1,11 @@
 use anyhow::{anyhow, Result};
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::Url;
 use std::env;
-use crate::repository::{bundle, Repository};
+use std::fs;
+use crate::info;
+use crate::repository::{bundle, http, Repository};
+use crate::repository::location::Location;
 
 // A helper function to resolve a remote name or a raw URL into a URL.
 // Returns a tuple of (resolved_url, remote_name_or_url).
@@ -19,18 +24,65 @@ fn resolve_url(repo: &Repository, remote_or_url: &str) -> Result<(String, String
     }
 }
 
-pub fn execute(remote_or_url: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
+/// Fetch branches from a remote. With `depth`, only the last `depth` commits of each branch
+/// are fetched and the clone becomes shallow.
+pub fn execute(location: &Location, remote_or_url: &str, depth: Option<usize>) -> Result<()> {
+    let repo = location.open()?;
+    run(&repo, remote_or_url, depth)
+}
+
+// A GET of `url`, carrying the read token when one is set.
+// Servers started with RUST_GIT_READ_TOKEN expect the same token as a bearer credential.
+fn get(repo: &Repository, url: &str) -> Result<RequestBuilder> {
+    let mut request = http::client(&repo.config)?.get(url);
+    if let Ok(token) = env::var("RUST_GIT_READ_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    Ok(request)
+}
 
+/// Fetch from `remote_or_url` into `repo`: store the objects, update the remote-tracking
+/// branches and record the branch the remote's HEAD points at.
+pub fn run(repo: &Repository, remote_or_url: &str, depth: Option<usize>) -> Result<()> {
     // 1. Resolve the remote name or URL.
-    let (remote_url, remote_name) = resolve_url(&repo, remote_or_url)?;
+    let (remote_url, remote_name) = resolve_url(repo, remote_or_url)?;
+
+    info!("Fetching from remote '{}' at '{}'", remote_name, remote_url);
 
-    println!("Fetching from remote '{}' at '{}'", remote_name, remote_url);
+    // 2. Download the bundle and unbundle it.
+    let response = download(repo, &remote_url, depth)?;
+    bundle::unbundle(repo, response, bundle::RefMapping::Remote(&remote_name))?;
 
-    // 2. Make an HTTP GET request to the remote URL.
-    let response = reqwest::blocking::get(&remote_url)
-        .map_err(|e| anyhow!("Failed to connect to remote url '{}': {}", remote_url, e))?;
+    // 3. The bundle's HEAD line only names a branch when no other branch shares its commit,
+    //    so take the remote's HEAD from its ref advertisement when the server offers one.
+    if let Some(branch_name) = remote_head(repo, &remote_url) {
+        bundle::write_remote_head(&repo.git_dir, &remote_name, &branch_name)?;
+    }
+    
+    info!("Successfully fetched from remote '{}'.", remote_name);
+    
+    Ok(())
+}
+
+/// Fetch every ref of the repository at `url` into `repo` under its own name, pointing HEAD
+/// at the remote's HEAD branch, as a bare or mirror clone does
+pub fn mirror(repo: &Repository, url: &str) -> Result<()> {
+    let response = download(repo, url, None)?;
+    bundle::unbundle(repo, response, bundle::RefMapping::Mirror)?;
+    if let Some(branch_name) = remote_head(repo, url) {
+        fs::write(repo.git_dir.join("HEAD"), format!("ref: refs/heads/{}\n", branch_name))?;
+    }
+    Ok(())
+}
+
+// GET the bundle served at `remote_url`, failing for anything but a success status.
+// The GET is idempotent, so transient failures are retried; see `http::send_with_retry`.
+fn download(repo: &Repository, remote_url: &str, depth: Option<usize>) -> Result<Response> {
+    let mut request = get(repo, remote_url)?;
+    if let Some(depth) = depth {
+        request = request.query(&[("depth", depth)]);
+    }
+    let response = http::send_with_retry(request, remote_url, repo.config.http_timeout())?;
 
     if !response.status().is_success() {
         anyhow::bail!(
@@ -39,11 +91,19 @@ pub fn execute(remote_or_url: &str) -> Result<()> {
             response.text().unwrap_or_else(|_| "No body".into())
         );
     }
+    Ok(response)
+}
 
-    // 3. The response body is the bundle. Call the unbundle function to process it.
-    bundle::unbundle(&repo, response, Some(&remote_name))?;
-    
-    println!("Successfully fetched from remote '{}'.", remote_name);
-    
-    Ok(())
-} 
\ No newline at end of file
+// The branch the remote's `info/refs` advertisement says its HEAD points at, if it says
+fn remote_head(repo: &Repository, remote_url: &str) -> Option<String> {
+    // The advertisement sits next to the bundle, e.g. http://host/info/refs for http://host/repo.bundle
+    let info_refs_url = Url::parse(remote_url).ok()?.join("info/refs").ok()?;
+    let request = get(repo, info_refs_url.as_str()).ok()?;
+    let response = http::send(request, info_refs_url.as_str(), repo.config.http_timeout()).ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let advertisement = response.text().ok()?;
+    let head = bundle::advertised_head(&advertisement)?;
+    head.strip_prefix("refs/heads/").map(str::to_string)
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use crate::repository::{Repository, objects};
+use crate::repository::objects::{ObjectType, MODE_GITLINK};
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location) -> Result<()> {
+    let repo = location.open()?;
+
+    let problems = run(&repo)?;
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("fsck found {} problem(s)", problems.len());
+    }
+
+    Ok(())
+}
+
+/// Walk every object reachable from the refs and HEAD, checking that it exists, parses, and has
+/// the type whatever refers to it declares; annotated tags are checked with
+/// `objects::verify_tag`. Returns one message per problem, in the order they were found.
+pub fn run(repo: &Repository) -> Result<Vec<String>> {
+    let shallow = objects::shallow_commits(&repo.git_dir);
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(String, Option<ObjectType>)> = repo.ref_tips()?.into_iter().map(|tip| (tip, None)).collect();
+
+    while let Some((object_id, expected)) = stack.pop() {
+        if !seen.insert(object_id.clone()) {
+            continue;
+        }
+        let Ok(object) = repo.read_object(&object_id) else {
+            let kind = expected.map_or("object".to_string(), |object_type| object_type.to_string());
+            problems.push(format!("missing {} {}", kind, object_id));
+            continue;
+        };
+        if let Some(expected) = expected.filter(|expected| *expected != object.object_type) {
+            problems.push(format!("error in {} {}: expected a {}", object.object_type, object_id, expected));
+        }
+
+        match object.object_type {
+            ObjectType::Commit => match objects::parse_commit(&object.data) {
+                Ok(commit) => {
+                    stack.push((commit.tree, Some(ObjectType::Tree)));
+                    // A shallow commit's parents were never fetched
+                    if !shallow.contains(&object_id) {
+                        stack.extend(commit.parents.into_iter().map(|parent| (parent, Some(ObjectType::Commit))));
+                    }
+                }
+                Err(err) => problems.push(format!("error in commit {}: {:#}", object_id, err)),
+            },
+            ObjectType::Tree => match objects::parse_tree(&object.data) {
+                Ok(entries) => {
+                    for entry in entries.into_iter().filter(|entry| entry.mode != MODE_GITLINK) {
+                        let entry_type = if entry.mode == objects::MODE_TREE { ObjectType::Tree } else { ObjectType::Blob };
+                        stack.push((entry.oid, Some(entry_type)));
+                    }
+                }
+                Err(err) => problems.push(format!("error in tree {}: {:#}", object_id, err)),
+            },
+            ObjectType::Tag => match objects::verify_tag(repo.objects.as_ref(), &object_id) {
+                Ok(tag) => stack.push((tag.object, Some(tag.object_type))),
+                Err(err) => {
+                    problems.push(format!("error in tag {}: {:#}", object_id, err));
+                    // Still look at whatever the tag names, so what hangs off it gets checked
+                    if let Ok(tag) = objects::parse_tag(&object.data) {
+                        stack.push((tag.object, None));
+                    }
+                }
+            },
+            ObjectType::Blob => {}
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{cat_file, show_ref, tag};
+    use crate::repository::refs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_fsck_accepts_annotated_tag() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let tag_id = tag::create(&repo, "v1.0", None, Some("Release 1.0"))?;
+
+        let tag = objects::verify_tag(repo.objects.as_ref(), &tag_id)?;
+        assert_eq!((tag.object.as_str(), tag.object_type), (head.as_str(), ObjectType::Commit));
+        assert_eq!(cat_file::run(&repo, &tag_id)?.1.object_type, ObjectType::Tag);
+        let options = show_ref::ShowRefOptions { tags: true, dereference: true, ..Default::default() };
+        assert_eq!(show_ref::run(&repo, &options)?.last(), Some(&(head, "refs/tags/v1.0^{}".to_string())));
+
+        assert_eq!(run(&repo)?, Vec::<String>::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fsck_flags_malformed_tags() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let tagger = "Test User <test@example.com> 1700000000 +0000";
+
+        // Declares the commit to be a tree
+        let wrong_type = format!("object {}\ntype tree\ntag wrong\ntagger {}\n\nWrong\n", head, tagger);
+        let wrong_type = repo.objects.write(ObjectType::Tag, wrong_type.as_bytes())?;
+        refs::update_ref(&repo.git_dir, "refs/tags/wrong", &wrong_type)?;
+        // No tagger line
+        let untagged = format!("object {}\ntype commit\ntag untagged\n\nUntagged\n", head);
+        let untagged = repo.objects.write(ObjectType::Tag, untagged.as_bytes())?;
+        refs::update_ref(&repo.git_dir, "refs/tags/untagged", &untagged)?;
+
+        let problems = run(&repo)?;
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems.iter().any(|problem| problem.starts_with(&format!("error in tag {}", wrong_type)) && problem.contains("not a tree")));
+        assert!(problems.iter().any(|problem| problem.starts_with(&format!("error in tag {}", untagged))));
+        assert!(objects::verify_tag(repo.objects.as_ref(), &untagged).is_err());
+
+        Ok(())
+    }
+}
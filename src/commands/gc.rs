@@ -1,33 +1,95 @@
 use anyhow::Result;
 use clap::Args;
-use std::env;
-use crate::repository::Repository;
+use std::fs;
+use std::time::SystemTime;
+use crate::commands::prune;
+use crate::info;
+use crate::repository::{pack, reflog, Repository};
+use crate::repository::pack::PackOptions;
+use crate::repository::location::Location;
 
 /// Garbage collect unnecessary files and optimize the local repository
-#[derive(Args)]
+#[derive(Args, Default)]
 #[command(name = "gc")]
-pub struct Command;
+pub struct Command {
+    /// Compress harder and search more delta bases, trading time for a smaller pack
+    #[arg(long)]
+    pub aggressive: bool,
+
+    /// Only delete unreachable objects older than this (e.g. "2.weeks.ago", "now")
+    #[arg(long)]
+    pub prune: Option<String>,
+
+    /// Do nothing unless there are more loose objects than `gc.auto` or more packs than
+    /// `gc.autoPackLimit`
+    #[arg(long)]
+    pub auto: bool,
+}
 
 impl Command {
-    pub fn run(&self, repo: &Repository) -> Result<()> {
-        repo.gc()
+    pub fn run(&self, repo: &Repository) -> Result<Vec<String>> {
+        if self.auto {
+            if !needs_auto_gc(repo)? {
+                return Ok(Vec::new());
+            }
+            info!("Auto packing the repository for optimum performance.");
+        }
+        // Reflog entries past gc.reflogExpire no longer keep their commits alive
+        let reflog_expire = repo.config.gc_reflog_expire().unwrap_or(crate::commands::reflog::DEFAULT_EXPIRE);
+        reflog::expire_all(&repo.git_dir, prune::parse_expire(reflog_expire, SystemTime::now())?)?;
+
+        let expire_before = prune::parse_expire(self.prune.as_deref().unwrap_or(prune::DEFAULT_EXPIRE), SystemTime::now())?;
+        if self.aggressive {
+            repo.gc_with_options(&PackOptions::aggressive(), expire_before)
+        } else {
+            repo.gc(expire_before)
+        }
     }
 }
  
-pub fn execute() -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
-    Command{}.run(&repo)
+pub fn execute(location: &Location, aggressive: bool, prune: Option<&str>, auto: bool) -> Result<()> {
+    let repo = location.open()?;
+    Command { aggressive, prune: prune.map(String::from), auto }.run(&repo)?;
+    Ok(())
 }
 
+/// Whether `gc --auto` would collect: there are more loose objects than `gc.auto`, or more
+/// packs than `gc.autoPackLimit`. Only directory entries are counted, so this is cheap enough
+/// to check after every command that writes objects.
+pub fn needs_auto_gc(repo: &Repository) -> Result<bool> {
+    let auto = repo.config.gc_auto();
+    if auto == 0 {
+        return Ok(false);
+    }
+    let objects_dir = repo.common_dir.join("objects");
+    if pack::scan_loose_objects(&objects_dir)?.objects.len() > auto {
+        return Ok(true);
+    }
+
+    let pack_limit = repo.config.gc_auto_pack_limit();
+    if pack_limit == 0 {
+        return Ok(false);
+    }
+    let packs = match fs::read_dir(objects_dir.join("pack")) {
+        Ok(pack_dir) => pack_dir
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "idx"))
+            .count(),
+        Err(_) => 0,
+    };
+    Ok(packs > pack_limit)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
     use std::fs;
-    use crate::repository::{Repository, objects};
+    use crate::repository::{Repository, objects, refs};
     use anyhow::Result;
+    use std::time::Duration;
+
+    const MONTH: Duration = Duration::from_secs(30 * 24 * 60 * 60);
 
     #[test]
     fn test_gc_removes_unreachable_loose_objects_and_packs_reachable() -> Result<()> {
@@ -35,19 +97,21 @@ mod tests {
         let repo = Repository::init(&temp_dir)?;
         let objects_dir = repo.git_dir.join("objects");
 
-        // Create a reachable object (added to repository)
-        let reachable_id = objects::write_blob(&objects_dir, b"reachable")?;
-        // Create an unreachable object (not referenced)
-        let unreachable_id = objects::write_blob(&objects_dir, b"unreachable")?;
+        // Create a reachable object (referenced by a tag)
+        let reachable_id = objects::write_blob(repo.objects.as_ref(), b"reachable")?;
+        refs::update_ref(&repo.git_dir, "refs/tags/keep", &reachable_id)?;
+        // Create an unreachable object (not referenced), old enough to be pruned
+        let unreachable_id = objects::write_blob(repo.objects.as_ref(), b"unreachable")?;
 
         // Ensure both exist as loose objects
         let reachable_path = objects_dir.join(&reachable_id[0..2]).join(&reachable_id[2..]);
         let unreachable_path = objects_dir.join(&unreachable_id[0..2]).join(&unreachable_id[2..]);
         assert!(reachable_path.exists());
         assert!(unreachable_path.exists());
+        fs::File::options().write(true).open(&unreachable_path)?.set_modified(SystemTime::now() - MONTH)?;
 
         // Run gc
-        let cmd = Command;
+        let cmd = Command::default();
         cmd.run(&repo)?;
 
         // Check that a pack file exists
@@ -64,4 +128,71 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gc_keeps_recent_unreachable_objects() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        let objects_dir = repo.git_dir.join("objects");
+        let loose_path = |object_id: &str| objects_dir.join(&object_id[0..2]).join(&object_id[2..]);
+
+        let old_id = objects::write_blob(repo.objects.as_ref(), b"old and unreachable")?;
+        let recent_id = objects::write_blob(repo.objects.as_ref(), b"recent and unreachable")?;
+        fs::File::options().write(true).open(loose_path(&old_id))?.set_modified(SystemTime::now() - MONTH)?;
+
+        let pruned = Command::default().run(&repo)?;
+
+        assert_eq!(pruned, vec![old_id.clone()]);
+        assert!(!objects::object_exists(&objects_dir, &old_id));
+        // Still loose, so a later gc can expire it
+        assert!(loose_path(&recent_id).exists());
+
+        // Nothing is recent enough with --prune=now
+        let pruned = Command { prune: Some("now".to_string()), ..Default::default() }.run(&repo)?;
+        assert_eq!(pruned, vec![recent_id.clone()]);
+        assert!(!objects::object_exists(&objects_dir, &recent_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_gc_waits_for_threshold() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(&temp_dir)?;
+        let config_path = repo.common_dir.join("config");
+        repo.config.set(&config_path, "gc", "auto", "5")?;
+        let objects_dir = repo.common_dir.join("objects");
+        let loose_count = || -> Result<usize> { Ok(pack::scan_loose_objects(&objects_dir)?.objects.len()) };
+        let auto = Command { auto: true, ..Default::default() };
+
+        // Reachable blobs, up to the threshold: nothing happens
+        for i in 0.. {
+            if loose_count()? >= 5 {
+                break;
+            }
+            let blob_id = objects::write_blob(repo.objects.as_ref(), format!("blob {}", i).as_bytes())?;
+            refs::update_ref(&repo.git_dir, &format!("refs/tags/t{}", i), &blob_id)?;
+        }
+        assert!(!needs_auto_gc(&repo)?);
+        auto.run(&repo)?;
+        assert_eq!(loose_count()?, 5);
+        assert!(!objects_dir.join("pack").exists());
+
+        // One more makes it pack
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"one too many")?;
+        refs::update_ref(&repo.git_dir, "refs/tags/last", &blob_id)?;
+        assert!(needs_auto_gc(&repo)?);
+        auto.run(&repo)?;
+        assert_eq!(loose_count()?, 0);
+        assert!(objects::object_exists(&objects_dir, &blob_id));
+
+        // gc.auto=0 turns it off
+        repo.config.set(&config_path, "gc", "auto", "0")?;
+        for i in 0..10 {
+            objects::write_blob(repo.objects.as_ref(), format!("more {}", i).as_bytes())?;
+        }
+        assert!(!needs_auto_gc(&repo)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::fs;
+use std::io::{self, Read};
+use crate::repository::objects;
+use crate::repository::objects::ObjectType;
+use crate::repository::store::{MemObjectStore, ObjectStore};
+use crate::repository::location::Location;
+
+/// Options for `hash-object`
+#[derive(Debug, Clone, Copy)]
+pub struct HashObjectOptions<'a> {
+    /// Name of the object type to hash the content as, as given on the command line
+    pub object_type: &'a str,
+    /// Store the object instead of only computing its id
+    pub write: bool,
+}
+
+/// Compute the id of `data` as an object of the requested type, writing it to `store` when asked.
+pub fn run(data: &[u8], options: &HashObjectOptions, store: &dyn ObjectStore) -> Result<String> {
+    let object_type: ObjectType = options.object_type.parse()?;
+
+    if options.write {
+        store.write(object_type, data)
+    } else {
+        Ok(objects::hash_object(data, object_type))
+    }
+}
+
+pub fn execute(location: &Location, path: Option<&str>, stdin: bool, options: &HashObjectOptions) -> Result<()> {
+    let data = match (path, stdin) {
+        (_, true) => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data)?;
+            data
+        }
+        (Some(path), false) => fs::read(path)?,
+        (None, false) => anyhow::bail!("a file or --stdin is required"),
+    };
+
+    // Only writing needs a repository; hashing works anywhere
+    let object_id = if options.write {
+        run(&data, options, location.open()?.objects.as_ref())?
+    } else {
+        run(&data, options, &MemObjectStore::new())?
+    };
+
+    println!("{}", object_id);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::Repository;
+    use tempfile::tempdir;
+
+    const HELLO_BLOB: &str = "ce013625030ba8dba906f756967f9e9ca394464a";
+
+    #[test]
+    fn test_hash_object_without_write() -> Result<()> {
+        let store = MemObjectStore::new();
+        let options = HashObjectOptions { object_type: "blob", write: false };
+
+        assert_eq!(run(b"hello\n", &options, &store)?, HELLO_BLOB);
+        assert!(store.is_empty());
+
+        let options = HashObjectOptions { object_type: "bogus", write: false };
+        assert!(run(b"hello\n", &options, &store).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_object_write_creates_loose_object() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let objects_dir = repo.git_dir.join("objects");
+        let options = HashObjectOptions { object_type: "blob", write: true };
+
+        assert_eq!(run(b"hello\n", &options, repo.objects.as_ref())?, HELLO_BLOB);
+        assert!(objects_dir.join(&HELLO_BLOB[..2]).join(&HELLO_BLOB[2..]).is_file());
+        assert_eq!(objects::read_object(&objects_dir, HELLO_BLOB)?, (ObjectType::Blob, b"hello\n".to_vec()));
+
+        Ok(())
+    }
+}
@@ -1,15 +1,27 @@
 use anyhow::Result;
-use std::env;
+use std::fs;
 use crate::repository::Repository;
+use crate::repository::location::Location;
+use crate::info;
 
-pub fn execute() -> Result<()> {
-    let current_dir = env::current_dir()?;
+pub fn execute(location: &Location, directory: Option<&str>, bare: bool) -> Result<()> {
+    let target_dir = match directory {
+        Some(dir) => {
+            let dir = location.current_dir.join(dir);
+            fs::create_dir_all(&dir)?;
+            dir
+        }
+        None => location.current_dir.clone(),
+    };
     
     // Open or initialize the repository
-    let _repo = Repository::init(&current_dir)?;
+    let repo = if bare {
+        Repository::init_bare(&target_dir)?
+    } else {
+        Repository::init(&target_dir)?
+    };
     
-    #[cfg(not(feature = "online_judge"))]
-    println!("Initialized empty Git repository in {}", _repo.git_dir.display());
+    info!("Initialized empty Git repository in {}", repo.git_dir.display());
     
     Ok(())
 } 
\ No newline at end of file
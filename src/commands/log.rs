@@ -0,0 +1,440 @@
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::{BinaryHeap, HashMap};
+use std::path::PathBuf;
+use crate::repository::{Head, Repository, normalize_path, objects, refs};
+use crate::repository::objects::Signature;
+use crate::repository::store::ObjectStore;
+use crate::repository::location::Location;
+
+/// Limits on the commits `log` lists
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogOptions {
+    /// Only commits authored at or after this time, in seconds since the epoch
+    pub since: Option<i64>,
+    /// Only commits authored at or before this time, in seconds since the epoch
+    pub until: Option<i64>,
+    /// List at most this many commits
+    pub max_count: Option<usize>,
+}
+
+pub fn execute(location: &Location, paths: &[String], format: Option<&str>, since: Option<&str>, until: Option<&str>, max_count: Option<usize>, relative_date: bool) -> Result<()> {
+    let repo = location.open()?;
+    let options = LogOptions {
+        since: since.map(parse_date).transpose()?,
+        until: until.map(parse_date).transpose()?,
+        max_count,
+    };
+    let current_dir = location.pathspec_dir(&repo);
+
+    let mut relative_paths = Vec::new();
+    for path in paths {
+        let full_path = current_dir.join(path);
+        let Ok(relative_path) = full_path.strip_prefix(&repo.path) else {
+            anyhow::bail!("'{}' is outside repository at '{}'", path, repo.path.display());
+        };
+        relative_paths.push(normalize_path(relative_path));
+    }
+
+    if let Head::Unborn(_) = repo.head()? {
+        println!("No commits yet");
+        return Ok(());
+    }
+
+    for commit_id in run(&repo, &relative_paths, &options)? {
+        if let Some(format) = format {
+            let commit = objects::parse_commit(&repo.read_object(&commit_id)?.data)?;
+            println!("{}", format_commit(&commit, format));
+            continue;
+        }
+        let commit = objects::read_commit(repo.objects.as_ref(), &commit_id)?;
+
+        println!("commit {}", commit.oid);
+        println!("Author: {}", commit.author.identity);
+        if relative_date {
+            println!("Date:   {}", relative_date_of(commit.author.seconds(), Utc::now().timestamp()));
+        } else {
+            println!("Date:   {}", format_date(&commit.author));
+        }
+        println!();
+        for line in commit.message.lines() {
+            println!("    {}", line);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Parse a `--since`/`--until` date into seconds since the epoch.
+///
+/// Accepted are `@<epoch>`, the `<epoch> <offset>` form commits store, RFC 3339 and RFC 2822
+/// dates, the dates `log` prints, `YYYY-MM-DD[ HH:MM[:SS]]` in local time (midnight when no
+/// time is given), and relative dates such as `2 weeks ago`.
+pub fn parse_date(value: &str) -> Result<i64> {
+    let value = value.trim();
+    if let Some(epoch) = value.strip_prefix('@') {
+        if let Ok(seconds) = epoch.parse() {
+            return Ok(seconds);
+        }
+    }
+
+    let with_offset = DateTime::parse_from_str(value, "%s %z")
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .or_else(|_| DateTime::parse_from_str(value, "%a %b %e %H:%M:%S %Y %z"));
+    if let Ok(date) = with_offset {
+        return Ok(date.timestamp());
+    }
+
+    let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)));
+    if let Some(date) = local.and_then(|date| Local.from_local_datetime(&date).earliest()) {
+        return Ok(date.timestamp());
+    }
+
+    if let Some(seconds_ago) = relative_seconds(value) {
+        return Ok(Utc::now().timestamp() - seconds_ago);
+    }
+
+    anyhow::bail!("invalid date '{}'", value)
+}
+
+// The length of a relative date such as `3 days ago`, in seconds. Months count as 30 days
+// and years as 365, as git counts them.
+fn relative_seconds(value: &str) -> Option<i64> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    let [count, unit, "ago"] = words.as_slice() else {
+        return None;
+    };
+    let count: i64 = count.parse().ok()?;
+    let unit_seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        "month" => 30 * 24 * 60 * 60,
+        "year" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    count.checked_mul(unit_seconds)
+}
+
+// Render a signature's timestamp the way `git log` does, in the signer's own timezone
+fn format_date(signature: &Signature) -> String {
+    let offset = signature
+        .timestamp
+        .split(' ')
+        .nth(1)
+        .and_then(|offset| DateTime::parse_from_str(&format!("0 {}", offset), "%s %z").ok())
+        .map_or(FixedOffset::east_opt(0).unwrap(), |date| *date.offset());
+    match DateTime::from_timestamp(signature.seconds(), 0) {
+        Some(date) => date.with_timezone(&offset).format("%a %b %-d %H:%M:%S %Y %z").to_string(),
+        None => signature.timestamp.clone(),
+    }
+}
+
+/// Describe how long before `now` the time `seconds` was, as `git log --relative-date` does:
+/// `5 minutes ago`, `3 days ago`, `1 year, 2 months ago`. Both are seconds since the epoch;
+/// a time after `now` is `in the future`.
+pub fn relative_date_of(seconds: i64, now: i64) -> String {
+    let ago = |count: i64, unit: &str| format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" });
+    let diff = now - seconds;
+    if diff < 0 {
+        return "in the future".to_string();
+    }
+    if diff == 0 {
+        return "just now".to_string();
+    }
+    // Each unit is used until it reaches 1.5 or so of the next, rounding to the nearest
+    if diff < 90 {
+        return ago(diff, "second");
+    }
+    let minutes = (diff + 30) / 60;
+    if minutes < 90 {
+        return ago(minutes, "minute");
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return ago(hours, "hour");
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return ago(days, "day");
+    }
+    if days < 70 {
+        return ago((days + 3) / 7, "week");
+    }
+    if days < 365 {
+        return ago((days + 15) / 30, "month");
+    }
+    // Under five years, the months left over are worth mentioning
+    if days < 1825 {
+        let total_months = (days * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        if months == 0 {
+            return ago(years, "year");
+        }
+        let years = format!("{} year{}", years, if years == 1 { "" } else { "s" });
+        return format!("{}, {}", years, ago(months, "month"));
+    }
+    ago((days + 183) / 365, "year")
+}
+
+/// Expand the placeholders of a `--format` template for `commit`:
+///
+/// `%H`/`%h` full and abbreviated commit id, `%P` parent ids, `%an`/`%ae`/`%ad`/`%ar` author
+/// name, email, date and relative date, `%s` subject, `%b` body, `%n` newline and `%%` a literal `%`. Anything else
+/// is copied as it is.
+pub fn format_commit(commit: &objects::Commit, format: &str) -> String {
+    let (subject, body) = commit.message.split_once("\n\n").unwrap_or((&commit.message, ""));
+    let mut output = String::new();
+    let mut rest = format;
+    while let Some(position) = rest.find('%') {
+        output.push_str(&rest[..position]);
+        rest = &rest[position..];
+        let (expansion, length) = match rest.as_bytes().get(1..3) {
+            Some(b"an") => (commit.author.name().to_string(), 3),
+            Some(b"ae") => (commit.author.email().to_string(), 3),
+            Some(b"ad") => (format_date(&commit.author), 3),
+            Some(b"ar") => (relative_date_of(commit.author.seconds(), Utc::now().timestamp()), 3),
+            _ => match rest.as_bytes().get(1) {
+                Some(b'H') => (commit.oid.clone(), 2),
+                Some(b'h') => (commit.oid[..7].to_string(), 2),
+                Some(b'P') => (commit.parents.join(" "), 2),
+                // The subject is the first paragraph, joined onto one line
+                Some(b's') => (subject.lines().collect::<Vec<_>>().join(" "), 2),
+                Some(b'b') => (body.to_string(), 2),
+                Some(b'n') => ("\n".to_string(), 2),
+                Some(b'%') => ("%".to_string(), 2),
+                _ => ("%".to_string(), 1),
+            },
+        };
+        output.push_str(&expansion);
+        rest = &rest[length..];
+    }
+    output.push_str(rest);
+    output
+}
+
+// The entries of `tree_id` that fall under any of `paths`, sorted by path
+fn entries_under(store: &dyn ObjectStore, tree_id: &str, paths: &[PathBuf]) -> Result<Vec<(PathBuf, String)>> {
+    let mut entries: Vec<(PathBuf, String)> = objects::flatten_tree(store, tree_id)?
+        .into_iter()
+        .map(|entry| (normalize_path(&entry.name), entry.oid))
+        .filter(|(path, _)| paths.iter().any(|pathspec| path.starts_with(pathspec)))
+        .collect();
+    entries.sort();
+    Ok(entries)
+}
+
+/// List the commits reachable from HEAD, newest first.
+///
+/// Children always come before their parents; among commits that are ready, the most recently
+/// committed goes first. With `paths` (relative to the repository root), only commits whose
+/// content under those paths differs from every parent are listed. `options` further limits
+/// the commits by author date and number. An unborn branch has no log.
+pub fn run(repo: &Repository, paths: &[PathBuf], options: &LogOptions) -> Result<Vec<String>> {
+    let store = repo.objects.as_ref();
+    let Some(head) = refs::head_commit(&repo.git_dir)? else {
+        return Ok(Vec::new());
+    };
+
+    // Count the children of each commit within the history
+    let history = objects::ancestors(repo, &head);
+    let shallow = objects::shallow_commits(&repo.git_dir);
+    let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+    let mut pending_children: HashMap<String, usize> = HashMap::new();
+    for commit_id in &history {
+        let commit_parents = objects::commit_parents(repo, commit_id, &shallow);
+        for parent in &commit_parents {
+            *pending_children.entry(parent.clone()).or_default() += 1;
+        }
+        parents.insert(commit_id.clone(), commit_parents);
+    }
+
+    let mut ready = BinaryHeap::from([(objects::commit_timestamp(repo, &head), head)]);
+    let mut log = Vec::new();
+    while let Some((_, commit_id)) = ready.pop() {
+        if options.max_count.is_some_and(|max_count| log.len() >= max_count) {
+            break;
+        }
+        let commit_parents = &parents[&commit_id];
+        for parent in commit_parents {
+            let remaining = pending_children.get_mut(parent).expect("parent was counted");
+            *remaining -= 1;
+            if *remaining == 0 {
+                ready.push((objects::commit_timestamp(repo, parent), parent.clone()));
+            }
+        }
+
+        if !paths.is_empty() {
+            let tree_id = objects::read_commit_tree(store, &commit_id)?;
+            let entries = entries_under(store, &tree_id, paths)?;
+            let mut touched = true;
+            for parent in commit_parents {
+                // Parents missing from a shallow clone count as having none of the content
+                let parent_entries = match objects::read_commit_tree(store, parent) {
+                    Ok(parent_tree) => entries_under(store, &parent_tree, paths)?,
+                    Err(_) => Vec::new(),
+                };
+                if parent_entries == entries {
+                    touched = false;
+                    break;
+                }
+            }
+            if commit_parents.is_empty() && entries.is_empty() {
+                touched = false;
+            }
+            if !touched {
+                continue;
+            }
+        }
+
+        if options.since.is_some() || options.until.is_some() {
+            let authored = objects::read_commit(store, &commit_id)?.author.seconds();
+            if options.since.is_some_and(|since| authored < since) || options.until.is_some_and(|until| authored > until) {
+                continue;
+            }
+        }
+
+        log.push(commit_id);
+    }
+
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::repository::objects::ObjectType;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, name: &str, content: &str) -> Result<String> {
+        fs::write(repo.path.join(name), content)?;
+        repo.index.add_directory(&repo.path, repo.path.join(name), repo.objects.as_ref(), &repo.config)?;
+        let message = format!("Update {}", name);
+        Ok(commit::run(repo, &CommitOptions { message: Some(&message), ..Default::default() })?.unwrap())
+    }
+
+    #[test]
+    fn test_log_filters_by_path() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let initial = refs::get_head_commit(&repo.git_dir)?;
+
+        let a1 = commit_file(&mut repo, "a.txt", "a1\n")?;
+        let b1 = commit_file(&mut repo, "b.txt", "b1\n")?;
+        let a2 = commit_file(&mut repo, "a.txt", "a2\n")?;
+        let b2 = commit_file(&mut repo, "b.txt", "b2\n")?;
+
+        assert_eq!(run(&repo, &[], &LogOptions::default())?, vec![b2.clone(), a2.clone(), b1.clone(), a1.clone(), initial]);
+        assert_eq!(run(&repo, &[PathBuf::from("a.txt")], &LogOptions::default())?, vec![a2, a1]);
+        assert_eq!(run(&repo, &[PathBuf::from("b.txt")], &LogOptions::default())?, vec![b2, b1]);
+        assert!(run(&repo, &[PathBuf::from("missing.txt")], &LogOptions::default())?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_placeholders() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let store = repo.objects.as_ref();
+
+        // Fixed signatures make the commit id known in advance
+        let author = Signature::parse("A U Thor <author@example.com> 1700000000 +0100").unwrap();
+        let tree = repo.objects.write(ObjectType::Tree, b"")?;
+        let root = objects::write_commit(store, &tree, &[], "Root", &author, &author)?;
+        let commit_id = objects::write_commit(store, &tree, &[&root], "Add a\n\nWith a body", &author, &author)?;
+        let commit = objects::parse_commit(&repo.read_object(&commit_id)?.data)?;
+
+        assert_eq!(format_commit(&commit, "%h %s"), "cca552b Add a");
+        assert_eq!(format_commit(&commit, "%H %P"), format!("{} {}", commit_id, root));
+        assert_eq!(format_commit(&commit, "%an <%ae>%n%ad"), "A U Thor <author@example.com>\nTue Nov 14 23:13:20 2023 +0100");
+        assert_eq!(format_commit(&commit, "[%b] 100%% %x"), "[With a body] 100% %x");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_filters_by_author_date_and_count() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let store = repo.objects.as_ref();
+
+        // A day between commits, starting at Tue Nov 14 22:13:20 2023 UTC
+        let tree = repo.objects.write(ObjectType::Tree, b"")?;
+        let mut commits: Vec<String> = Vec::new();
+        for day in 0..4 {
+            let signature = Signature::parse(&format!("A U Thor <author@example.com> {} +0100", 1700000000 + day * 86400)).unwrap();
+            let parents: Vec<&str> = commits.last().map(String::as_str).into_iter().collect();
+            commits.push(objects::write_commit(store, &tree, &parents, &format!("Day {}", day), &signature, &signature)?);
+        }
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &commits[3])?;
+
+        let log = |options: LogOptions| run(&repo, &[], &options);
+        assert_eq!(log(LogOptions::default())?.len(), 4);
+        assert_eq!(log(LogOptions { since: Some(parse_date("2023-11-15T22:13:20Z")?), ..Default::default() })?, vec![commits[3].clone(), commits[2].clone(), commits[1].clone()]);
+        assert_eq!(log(LogOptions { until: Some(parse_date("1700086400 +0000")?), ..Default::default() })?, vec![commits[1].clone(), commits[0].clone()]);
+        assert_eq!(
+            log(LogOptions { since: Some(parse_date("@1700000001")?), until: Some(parse_date("Fri, 17 Nov 2023 00:00:00 +0100")?), max_count: None })?,
+            vec![commits[2].clone(), commits[1].clone()],
+        );
+        assert_eq!(log(LogOptions { max_count: Some(2), ..Default::default() })?, vec![commits[3].clone(), commits[2].clone()]);
+        assert!(log(LogOptions { since: Some(1800000000), ..Default::default() })?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_date() -> Result<()> {
+        assert_eq!(parse_date("@1700000000")?, 1700000000);
+        assert_eq!(parse_date("1700000000 +0100")?, 1700000000);
+        assert_eq!(parse_date("2023-11-14T23:13:20+01:00")?, 1700000000);
+        assert_eq!(parse_date("Tue, 14 Nov 2023 23:13:20 +0100")?, 1700000000);
+        assert_eq!(parse_date("Tue Nov 14 23:13:20 2023 +0100")?, 1700000000);
+        assert_eq!(parse_date("2023-11-14")?, parse_date("2023-11-14 00:00:00")?);
+
+        let week_ago = Utc::now().timestamp() - 7 * 86400;
+        assert!((parse_date("1 week ago")? - week_ago).abs() <= 1);
+        assert!((parse_date("7 days ago")? - week_ago).abs() <= 1);
+
+        assert!(parse_date("yesterday-ish").is_err());
+        assert!(parse_date("3 fortnights ago").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_date() -> Result<()> {
+        let now = Utc::now().timestamp();
+        let signature = Signature::parse(&format!("A U Thor <author@example.com> {} +0100", now - 2 * 3600)).unwrap();
+        // Rounding to the nearest hour absorbs the clock ticking between the two readings
+        assert_eq!(relative_date_of(signature.seconds(), Utc::now().timestamp()), "2 hours ago");
+
+        let now = 1700000000;
+        assert_eq!(relative_date_of(now, now), "just now");
+        assert_eq!(relative_date_of(now + 60, now), "in the future");
+        assert_eq!(relative_date_of(now - 1, now), "1 second ago");
+        assert_eq!(relative_date_of(now - 45 * 60, now), "45 minutes ago");
+        assert_eq!(relative_date_of(now - 3 * 86400, now), "3 days ago");
+        assert_eq!(relative_date_of(now - 21 * 86400, now), "3 weeks ago");
+        assert_eq!(relative_date_of(now - 100 * 86400, now), "3 months ago");
+        assert_eq!(relative_date_of(now - 430 * 86400, now), "1 year, 2 months ago");
+        assert_eq!(relative_date_of(now - 3650 * 86400, now), "10 years ago");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_date_uses_signer_timezone() {
+        let signature = Signature::parse("A U Thor <author@example.com> 1700000000 +0100").unwrap();
+        assert_eq!(format_date(&signature), "Tue Nov 14 23:13:20 2023 +0100");
+    }
+}
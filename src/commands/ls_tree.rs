@@ -0,0 +1,145 @@
+use anyhow::Result;
+use std::path::Path;
+use crate::repository::{Repository, normalize_path, objects, refs};
+use crate::repository::objects::{Object, ObjectType, TreeEntry, MODE_GITLINK, MODE_TREE};
+use crate::repository::location::Location;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LsTreeOptions {
+    /// Recurse into subtrees, listing their entries with the directory prefixed
+    pub recursive: bool,
+    /// Only list subtrees
+    pub trees_only: bool,
+}
+
+pub fn execute(location: &Location, tree_ish: &str, options: &LsTreeOptions) -> Result<()> {
+    let repo = location.open()?;
+
+    for entry in run(&repo, tree_ish, options)? {
+        println!("{}", format_entry(&entry));
+    }
+
+    Ok(())
+}
+
+/// The tree a revision or object id names, following commits to their tree and tags to
+/// what they point at
+pub fn resolve_tree(repo: &Repository, tree_ish: &str) -> Result<String> {
+    let mut oid = refs::resolve_revision(&repo.git_dir, tree_ish)
+        .or_else(|_| objects::expand_object_id(repo.common_dir.join("objects"), tree_ish))?;
+    loop {
+        match repo.read_object(&oid)?.parse()? {
+            Object::Tree(_) => return Ok(oid),
+            Object::Commit(commit) => oid = commit.tree,
+            Object::Tag(tag) => oid = tag.object,
+            Object::Blob(_) => anyhow::bail!("not a tree object: {}", tree_ish),
+        }
+    }
+}
+
+/// List the entries of the tree `tree_ish` names, with names relative to that tree.
+///
+/// With `recursive`, subtrees are descended into and, unless `trees_only` is set, not listed
+/// themselves; with `trees_only`, entries other than subtrees are left out.
+pub fn run(repo: &Repository, tree_ish: &str, options: &LsTreeOptions) -> Result<Vec<TreeEntry>> {
+    let tree_id = resolve_tree(repo, tree_ish)?;
+    let mut entries = Vec::new();
+    list(repo, &tree_id, Path::new(""), options, &mut entries)?;
+    Ok(entries)
+}
+
+// Add the entries of `tree_id`, found at `prefix` in the listed tree, to `entries`
+fn list(repo: &Repository, tree_id: &str, prefix: &Path, options: &LsTreeOptions, entries: &mut Vec<TreeEntry>) -> Result<()> {
+    let Object::Tree(children) = repo.read_object(tree_id)?.parse()? else {
+        anyhow::bail!("not a tree object: {}", tree_id);
+    };
+    for mut entry in children {
+        entry.name = normalize_path(&prefix.join(&entry.name));
+        if entry.mode == MODE_TREE {
+            if !options.recursive || options.trees_only {
+                entries.push(entry.clone());
+            }
+            if options.recursive {
+                list(repo, &entry.oid, &entry.name, options, entries)?;
+            }
+        } else if !options.trees_only {
+            entries.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// `<mode> <type> <oid>\t<name>`, as git prints a tree entry
+pub fn format_entry(entry: &TreeEntry) -> String {
+    let object_type = match entry.mode {
+        MODE_TREE => ObjectType::Tree,
+        MODE_GITLINK => ObjectType::Commit,
+        _ => ObjectType::Blob,
+    };
+    format!("{:06o} {} {}\t{}", entry.mode, object_type, entry.oid, entry.name.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::objects::Signature;
+    use tempfile::tempdir;
+
+    fn listing(repo: &Repository, tree_ish: &str, options: LsTreeOptions) -> Result<Vec<String>> {
+        Ok(run(repo, tree_ish, &options)?.iter().map(format_entry).collect())
+    }
+
+    fn write_tree(repo: &Repository, entries: &[(&str, &str, &str)]) -> Result<String> {
+        let mut data = Vec::new();
+        for (mode, name, oid) in entries {
+            data.extend(format!("{} {}\0", mode, name).into_bytes());
+            data.extend(hex::decode(oid)?);
+        }
+        repo.objects.write(ObjectType::Tree, &data)
+    }
+
+    #[test]
+    fn test_flat_and_recursive_listing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let store = repo.objects.as_ref();
+
+        // a/b/f, a/g and top
+        let f = objects::write_blob(store, b"1\n")?;
+        let g = objects::write_blob(store, b"2\n")?;
+        let top = objects::write_blob(store, b"3\n")?;
+        let tree_b = write_tree(&repo, &[("100644", "f", &f)])?;
+        let tree_a = write_tree(&repo, &[("40000", "b", &tree_b), ("100644", "g", &g)])?;
+        let root = write_tree(&repo, &[("40000", "a", &tree_a), ("100644", "top", &top)])?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let signature = Signature::now("Test User <test@example.com>");
+        let commit = objects::write_commit(store, &root, &[&head], "Nested", &signature, &signature)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &commit)?;
+
+        assert_eq!(listing(&repo, "HEAD", LsTreeOptions::default())?, vec![
+            format!("040000 tree {}\ta", tree_a),
+            format!("100644 blob {}\ttop", top),
+        ]);
+        assert_eq!(listing(&repo, "master", LsTreeOptions { recursive: true, ..Default::default() })?, vec![
+            format!("100644 blob {}\ta/b/f", f),
+            format!("100644 blob {}\ta/g", g),
+            format!("100644 blob {}\ttop", top),
+        ]);
+        assert_eq!(listing(&repo, &commit, LsTreeOptions { trees_only: true, ..Default::default() })?, vec![
+            format!("040000 tree {}\ta", tree_a),
+        ]);
+        assert_eq!(listing(&repo, "HEAD", LsTreeOptions { recursive: true, trees_only: true })?, vec![
+            format!("040000 tree {}\ta", tree_a),
+            format!("040000 tree {}\ta/b", tree_b),
+        ]);
+
+        // A tree id lists relative to that tree; a blob is not a tree
+        assert_eq!(listing(&repo, &tree_a[..8], LsTreeOptions::default())?, vec![
+            format!("040000 tree {}\tb", tree_b),
+            format!("100644 blob {}\tg", g),
+        ]);
+        assert!(run(&repo, &top, &LsTreeOptions::default()).is_err());
+
+        Ok(())
+    }
+}
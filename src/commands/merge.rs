@@ -1,181 +1,85 @@
 use anyhow::Result;
-use std::env;
-use crate::repository::{Repository, refs, objects};
+use crate::commands::checkout;
+use crate::repository::{Repository, refs, objects, diff, index};
+use crate::repository::diff::MergeResult;
+use crate::repository::objects::ObjectType;
 use std::collections::HashMap;
-use std::path::Path;
-use hex;
-
-// Helper function to get tree files (filename -> object_id map) from a commit_id
-fn get_files_from_commit(repo: &Repository, commit_id: &str) -> Result<HashMap<String, String>> {
-    let objects_dir = repo.git_dir.join("objects");
-    let (commit_type, commit_data) = objects::read_object(&objects_dir, commit_id)?;
-    if commit_type != "commit" {
-        anyhow::bail!("Expected commit object, got {}", commit_type);
-    }
-    let commit_content = String::from_utf8_lossy(&commit_data);
-    let lines: Vec<&str> = commit_content.lines().collect();
-    if lines.is_empty() || !lines[0].starts_with("tree ") {
-        anyhow::bail!("Invalid commit object format for commit {}", commit_id);
-    }
-    let tree_id = lines[0].strip_prefix("tree ").unwrap().trim();
-    
-    // This is a simplified version of get_tree_files from checkout.rs
-    // It assumes files are at the root of the tree for simplicity, as per typical Git usage for simple cases.
-    // A full implementation would handle nested trees (directories).
-    get_tree_content(&objects_dir, tree_id)
-}
+use std::fs;
+use std::path::PathBuf;
+use std::io::ErrorKind;
+use crate::repository::location::Location;
+use crate::info;
 
-// Helper function to parse tree object content (similar to get_tree_files in checkout.rs)
-// For simplicity, this version assumes all entries are blobs (files) and not trees (directories)
-// and that filenames do not contain null bytes or other problematic characters.
-fn get_tree_content(objects_dir: &Path, tree_id: &str) -> Result<HashMap<String, String>> {
-    let mut files = HashMap::new();
-    let (tree_type, tree_data) = objects::read_object(objects_dir, tree_id)?;
-    if tree_type != "tree" {
-        anyhow::bail!("Expected tree object for ID {}, got {}", tree_id, tree_type);
-    }
+// Helper function to get tree files (path -> object_id map, subtrees included) from a commit_id
+fn get_files_from_commit(repo: &Repository, commit_id: &str) -> Result<HashMap<PathBuf, String>> {
+    let store = repo.objects.as_ref();
+    let tree_id = objects::read_commit(store, commit_id)?.tree;
+    Ok(objects::flatten_tree(store, &tree_id)?
+        .into_iter()
+        .map(|entry| (entry.name, entry.oid))
+        .collect())
+}
 
-    let mut cursor = 0;
-    while cursor < tree_data.len() {
-        // Format: <mode> <filename>\0<sha1_hash_20_bytes>
-        // Find space after mode
-        if let Some(space_idx) = tree_data[cursor..].iter().position(|&b| b == b' ') {
-            let name_start_idx = cursor + space_idx + 1;
-            // Find null after filename
-            if let Some(null_idx_rel) = tree_data[name_start_idx..].iter().position(|&b| b == 0) {
-                let null_idx_abs = name_start_idx + null_idx_rel;
-                let filename_bytes = &tree_data[name_start_idx..null_idx_abs];
-                let filename = String::from_utf8(filename_bytes.to_vec())
-                    .map_err(|e| anyhow::anyhow!("Invalid UTF-8 in filename: {}", e))?;
-
-                let sha1_start = null_idx_abs + 1;
-                let sha1_end = sha1_start + 20; // SHA-1 hash is 20 bytes
-                if sha1_end <= tree_data.len() {
-                    let sha1_bytes = &tree_data[sha1_start..sha1_end];
-                    let sha1_hex = hex::encode(sha1_bytes);
-                    files.insert(filename, sha1_hex);
-                    cursor = sha1_end;
-                } else {
-                    // Not enough data for SHA1 hash, indicates malformed tree or end of data
-                    break; 
-                }
-            } else {
-                // No null terminator found for filename, malformed tree
-                break;
-            }
-        } else {
-            // No space found for mode, malformed tree or end of data
-            break;
+// The mode of each file in the trees of `commit_ids`, by path and object id, so a file written
+// from one of them is recreated as a symlink when it was one. Merged content is a regular file.
+fn get_file_modes(repo: &Repository, commit_ids: &[&str]) -> Result<HashMap<(PathBuf, String), u32>> {
+    let store = repo.objects.as_ref();
+    let mut modes = HashMap::new();
+    for commit_id in commit_ids {
+        let tree_id = objects::read_commit(store, commit_id)?.tree;
+        for entry in objects::flatten_tree(store, &tree_id)? {
+            modes.insert((entry.name, entry.oid), entry.mode);
         }
     }
-    Ok(files)
+    Ok(modes)
 }
 
-// Helper function to find the merge base (common ancestor) of two commits
-// This is a simplified implementation that finds the most recent common ancestor
-fn find_merge_base(repo: &Repository, commit1: &str, commit2: &str) -> Result<Option<String>> {
-    // For simplicity, we'll implement a basic algorithm
-    // In a real Git implementation, this would be more sophisticated
-    
-    // Get all ancestors of commit1
-    let mut ancestors1 = std::collections::HashSet::new();
-    let mut queue = vec![commit1.to_string()];
-    
-    while let Some(commit_id) = queue.pop() {
-        if ancestors1.contains(&commit_id) {
-            continue;
-        }
-        ancestors1.insert(commit_id.clone());
-        
-        // Get parents of this commit
-        if let Ok((commit_type, commit_data)) = objects::read_object(&repo.git_dir.join("objects"), &commit_id) {
-            if commit_type == "commit" {
-                let commit_content = String::from_utf8_lossy(&commit_data);
-                for line in commit_content.lines() {
-                    if line.starts_with("parent ") {
-                        let parent_id = line.strip_prefix("parent ").unwrap().trim();
-                        queue.push(parent_id.to_string());
-                    }
-                }
+// Make the working tree and index match `target_files`, given that they currently match
+// `current_files`; `modes` gives the mode of files taken from a commit as `get_file_modes` does
+fn update_working_tree(
+    repo: &mut Repository,
+    current_files: &HashMap<PathBuf, String>,
+    target_files: &HashMap<PathBuf, String>,
+    modes: &HashMap<(PathBuf, String), u32>,
+) -> Result<()> {
+    // Remove files that exist in current but not in the target
+    for filename in current_files.keys() {
+        if !target_files.contains_key(filename) {
+            let file_path = repo.path.join(filename);
+            if file_path.symlink_metadata().is_ok() {
+                std::fs::remove_file(&file_path)?;
             }
+            repo.index.remove_path(&repo.path, filename)?;
         }
     }
     
-    // Find first common ancestor in commit2's ancestry
-    let mut queue = vec![commit2.to_string()];
-    let mut visited = std::collections::HashSet::new();
-    
-    while let Some(commit_id) = queue.pop() {
-        if visited.contains(&commit_id) {
-            continue;
-        }
-        visited.insert(commit_id.clone());
-        
-        if ancestors1.contains(&commit_id) {
-            return Ok(Some(commit_id));
-        }
-        
-        // Get parents of this commit
-        if let Ok((commit_type, commit_data)) = objects::read_object(&repo.git_dir.join("objects"), &commit_id) {
-            if commit_type == "commit" {
-                let commit_content = String::from_utf8_lossy(&commit_data);
-                for line in commit_content.lines() {
-                    if line.starts_with("parent ") {
-                        let parent_id = line.strip_prefix("parent ").unwrap().trim();
-                        queue.push(parent_id.to_string());
-                    }
-                }
-            }
+    // Add/update files in working directory
+    for (filename, object_id) in target_files {
+        let object = repo.read_object(object_id)?;
+        if object.object_type == ObjectType::Blob {
+            let file_path = repo.path.join(filename);
+            let mode = modes.get(&(filename.clone(), object_id.clone())).copied().unwrap_or(index::MODE_FILE);
+            checkout::write_working_file(&file_path, &object.data, mode, &repo.config)?;
+            
+            // Update index
+            repo.index.add_file(&repo.path, &file_path, object_id)?;
         }
     }
     
-    Ok(None)
+    Ok(())
 }
 
-pub fn execute(branch_to_merge: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let mut repo = Repository::open(&current_dir)?;
-    let current_branch_name = repo.current_branch()?;
-
-    // Check if trying to merge onto itself
-    if current_branch_name == branch_to_merge {
-        #[cfg(not(feature = "online_judge"))]
-        println!("Already on '{}'", branch_to_merge);
-        return Ok(());
-    }
-
-    // Get commit IDs
-    let current_branch_commit_id = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", current_branch_name))?;
-    
-    // Try to resolve the branch_to_merge argument.
-    // It could be a local branch (e.g., "feature-branch") or a remote-tracking branch (e.g., "origin/master").
-    let merge_branch_commit_id = 
-        // First, check if it's a local branch
-        refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", branch_to_merge))
-        // If not, check if it's a remote-tracking branch
-        .or_else(|_| refs::read_ref(&repo.git_dir, &format!("refs/remotes/{}", branch_to_merge)))
-        // If it's neither, then the branch is not found
-        .map_err(|_| anyhow::anyhow!("Branch '{}' not found", branch_to_merge))?;
-
-    if current_branch_commit_id == merge_branch_commit_id {
-        #[cfg(not(feature = "online_judge"))]
-        println!("Already up-to-date.");
-        return Ok(());
-    }
-
-    // Find merge base (common ancestor)
-    let merge_base = find_merge_base(&repo, &current_branch_commit_id, &merge_branch_commit_id)?;
-    
-    // Get file lists for three versions
-    let current_files = get_files_from_commit(&repo, &current_branch_commit_id)?;
-    let merge_files = get_files_from_commit(&repo, &merge_branch_commit_id)?;
-    let base_files = if let Some(base_commit) = &merge_base {
-        get_files_from_commit(&repo, base_commit)?
-    } else {
-        HashMap::new() // No common ancestor, treat as empty
-    };
-
-    let mut conflict_found = false;
+// Merge two descendants of `base_files` path by path, merging both-modified text files line by line.
+// Returns the merged paths and the conflicted ones. Conflicted paths keep the current version
+// (or the merged-in one when the current side deleted them) among the merged paths, and map
+// to what the working tree should show: the text with conflict markers where there is one.
+fn merge_file_sets(
+    repo: &Repository,
+    base_files: &HashMap<PathBuf, String>,
+    current_files: &HashMap<PathBuf, String>,
+    merge_files: &HashMap<PathBuf, String>,
+) -> Result<(HashMap<PathBuf, String>, HashMap<PathBuf, String>)> {
+    let mut conflicts = HashMap::new();
     let mut merged_files = HashMap::new();
 
     // Combine all filenames from all three versions
@@ -205,53 +109,46 @@ pub fn execute(branch_to_merge: &str) -> Result<()> {
                     // Both branches made same change
                     merged_files.insert(filename.clone(), current.clone());
                 } else {
-                    // Both branches changed differently - conflict!
-                    conflict_found = true;
+                    // Both branches changed the file - merge the contents line by line
+                    let base_data = repo.read_object(base)?.data;
+                    let current_data = repo.read_object(current)?.data;
+                    let merge_data = repo.read_object(merge)?.data;
                     
-                    // For line-level conflict detection, compare file contents
-                    let (_, current_data) = objects::read_object(&repo.git_dir.join("objects"), current)?;
-                    let current_content = String::from_utf8_lossy(&current_data);
-                    let current_lines: Vec<&str> = current_content.lines().collect();
-
-                    let (_, merge_data) = objects::read_object(&repo.git_dir.join("objects"), merge)?;
-                    let merge_content = String::from_utf8_lossy(&merge_data);
-                    let merge_lines: Vec<&str> = merge_content.lines().collect();
-                    
-                    // Find conflicting line ranges
-                    let max_len = std::cmp::max(current_lines.len(), merge_lines.len());
-                    let mut i = 0;
-                    while i < max_len {
-                        let current_line = current_lines.get(i);
-                        let merge_line = merge_lines.get(i);
-
-                        if current_line != merge_line {
-                            let conflict_start = i + 1;
-                            let mut conflict_end = conflict_start;
-                            i += 1;
-                            
-                            while i < max_len {
-                                let cl = current_lines.get(i);
-                                let ml = merge_lines.get(i);
-                                if cl != ml {
-                                    conflict_end = i + 1;
-                                    i += 1;
-                                } else {
-                                    break;
+                    match (
+                        String::from_utf8(base_data),
+                        String::from_utf8(current_data),
+                        String::from_utf8(merge_data),
+                    ) {
+                        (Ok(base_text), Ok(current_text), Ok(merge_text)) => {
+                            match diff::three_way_merge(&base_text, &current_text, &merge_text) {
+                                MergeResult::Clean(merged_text) => {
+                                    let merged_id = objects::write_blob(repo.objects.as_ref(), merged_text.as_bytes())?;
+                                    merged_files.insert(filename.clone(), merged_id);
+                                }
+                                MergeResult::Conflicted { content, conflicts: ranges } => {
+                                    let marked_id = objects::write_blob(repo.objects.as_ref(), content.as_bytes())?;
+                                    conflicts.insert(filename.clone(), marked_id);
+                                    // Report conflicting line ranges (1-based) in the current branch's version
+                                    for conflict in ranges {
+                                        let conflict_start = conflict.ours.start + 1;
+                                        let conflict_end = conflict.ours.end.max(conflict_start);
+                                        if conflict_start == conflict_end {
+                                            println!("Merge conflict in {}: {}", filename.display(), conflict_start);
+                                        } else {
+                                            println!("Merge conflict in {}: [{}, {}]", filename.display(), conflict_start, conflict_end);
+                                        }
+                                    }
+                                    merged_files.insert(filename.clone(), current.clone());
                                 }
                             }
-                            
-                            if conflict_start == conflict_end {
-                                println!("Merge conflict in {}: {}", filename, conflict_start);
-                            } else {
-                                println!("Merge conflict in {}: [{}, {}]", filename, conflict_start, conflict_end);
-                            }
-                        } else {
-                            i += 1;
+                        }
+                        _ => {
+                            // Binary content cannot be merged line by line
+                            conflicts.insert(filename.clone(), current.clone());
+                            println!("Merge conflict in {}: binary files differ", filename.display());
+                            merged_files.insert(filename.clone(), current.clone());
                         }
                     }
-                    
-                    // For now, use current version in merged result (could be improved)
-                    merged_files.insert(filename.clone(), current.clone());
                 }
             }
             // File exists in base and current, but not in merge (deleted in merge)
@@ -261,8 +158,8 @@ pub fn execute(branch_to_merge: &str) -> Result<()> {
                     // Don't add to merged_files
                 } else {
                     // Modified in current, deleted in merge - conflict
-                    conflict_found = true;
-                    println!("Merge conflict in {}: modified in current branch but deleted in merge branch", filename);
+                    conflicts.insert(filename.clone(), current.clone());
+                    println!("Merge conflict in {}: modified in current branch but deleted in merge branch", filename.display());
                     // Keep current version
                     merged_files.insert(filename.clone(), current.clone());
                 }
@@ -274,8 +171,8 @@ pub fn execute(branch_to_merge: &str) -> Result<()> {
                     // Don't add to merged_files
                 } else {
                     // Modified in merge, deleted in current - conflict
-                    conflict_found = true;
-                    println!("Merge conflict in {}: modified in merge branch but deleted in current branch", filename);
+                    conflicts.insert(filename.clone(), merge.clone());
+                    println!("Merge conflict in {}: modified in merge branch but deleted in current branch", filename.display());
                     // Use merge version
                     merged_files.insert(filename.clone(), merge.clone());
                 }
@@ -287,8 +184,8 @@ pub fn execute(branch_to_merge: &str) -> Result<()> {
                     merged_files.insert(filename.clone(), current.clone());
                 } else {
                     // Different new files - conflict
-                    conflict_found = true;
-                    println!("Merge conflict in {}: different versions of new file", filename);
+                    conflicts.insert(filename.clone(), current.clone());
+                    println!("Merge conflict in {}: different versions of new file", filename.display());
                     merged_files.insert(filename.clone(), current.clone());
                 }
             }
@@ -309,50 +206,330 @@ pub fn execute(branch_to_merge: &str) -> Result<()> {
         }
     }
 
-    if conflict_found {
-        #[cfg(not(feature = "online_judge"))]
-        println!("Merge conflicts detected. Please resolve conflicts manually.");
+    Ok((merged_files, conflicts))
+}
+
+// Make the working tree and index, which match `current_files`, hold a merge result. The index
+// keeps the current version of conflicted files while the working tree shows them with
+// conflict markers, so they count as modified until resolved. Returns the conflicted paths,
+// sorted.
+fn write_merge_result(
+    repo: &mut Repository,
+    current_files: &HashMap<PathBuf, String>,
+    merged_files: &HashMap<PathBuf, String>,
+    conflicts: &HashMap<PathBuf, String>,
+    modes: &HashMap<(PathBuf, String), u32>,
+) -> Result<Vec<String>> {
+    update_working_tree(repo, current_files, merged_files, modes)?;
+    let mut conflicted: Vec<&PathBuf> = conflicts.keys().collect();
+    conflicted.sort();
+    for filename in &conflicted {
+        let object = repo.read_object(&conflicts[*filename])?;
+        fs::write(repo.path.join(filename), repo.config.autocrlf().to_worktree(&object.data))?;
+    }
+    Ok(conflicted.into_iter().map(|filename| filename.to_string_lossy().into_owned()).collect())
+}
+
+/// Apply the change `commit_id` made to its first parent onto the working tree and index,
+/// which match commit `onto`, as a cherry-pick does. Returns the paths that conflicted,
+/// sorted; see `run` for how they are left. The index is not saved.
+pub fn pick(repo: &mut Repository, onto: &str, commit_id: &str) -> Result<Vec<String>> {
+    let base_files = match objects::read_commit(repo.objects.as_ref(), commit_id)?.parents.first() {
+        Some(parent) => get_files_from_commit(repo, parent)?,
+        None => HashMap::new(),
+    };
+    let current_files = get_files_from_commit(repo, onto)?;
+    let picked_files = get_files_from_commit(repo, commit_id)?;
+    let (merged_files, conflicts) = merge_file_sets(repo, &base_files, &current_files, &picked_files)?;
+    let modes = get_file_modes(repo, &[onto, commit_id])?;
+    write_merge_result(repo, &current_files, &merged_files, &conflicts, &modes)
+}
+
+/// Make the working tree and index, which match commit `from`, match commit `to`. The index
+/// is not saved.
+pub fn switch_tree(repo: &mut Repository, from: &str, to: &str) -> Result<()> {
+    let from_files = get_files_from_commit(repo, from)?;
+    let to_files = get_files_from_commit(repo, to)?;
+    let modes = get_file_modes(repo, &[to])?;
+    update_working_tree(repo, &from_files, &to_files, &modes)
+}
+
+/// The commit being merged in when a merge stopped on conflicts, from `.git/MERGE_HEAD`.
+/// The next commit records it as a second parent.
+pub fn merge_head(repo: &Repository) -> Result<Option<String>> {
+    match fs::read_to_string(repo.git_dir.join("MERGE_HEAD")) {
+        Ok(content) => Ok(Some(content.trim().to_string())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The message prepared for the merge commit in `.git/MERGE_MSG`, comments included
+pub fn merge_message(repo: &Repository) -> Result<Option<String>> {
+    match fs::read_to_string(repo.git_dir.join("MERGE_MSG")) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Forget the merge in progress, once its commit is made
+pub fn clear_merge_state(repo: &Repository) -> Result<()> {
+    for name in ["MERGE_HEAD", "MERGE_MSG"] {
+        match fs::remove_file(repo.git_dir.join(name)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Refuse to start a merge while another one waits for its conflicts to be resolved
+fn ensure_no_merge_in_progress(repo: &Repository) -> Result<()> {
+    if merge_head(repo)?.is_some() {
+        anyhow::bail!("You have not concluded your merge (MERGE_HEAD exists). Please, commit your changes before you merge.");
+    }
+    Ok(())
+}
+
+pub fn execute(location: &Location, branches: &[String], no_ff: bool) -> Result<()> {
+    let mut repo = location.open()?;
+    match branches {
+        [branch_to_merge] => run(&mut repo, branch_to_merge, no_ff),
+        _ => {
+            let branches: Vec<&str> = branches.iter().map(String::as_str).collect();
+            run_octopus(&mut repo, &branches)
+        }
+    }
+}
+
+// Resolve what to merge to the commit it names: a local branch (e.g. "feature"), a
+// remote-tracking one (e.g. "origin/master"), a tag, a full ref name or a commit id.
+// Annotated tags are followed to the commit they tag.
+fn resolve_merge_target(repo: &Repository, name: &str) -> Result<String> {
+    let mut object_id = refs::resolve_revision(&repo.git_dir, name)
+        .or_else(|_| objects::expand_object_id(repo.common_dir.join("objects"), name))
+        .map_err(|_| anyhow::anyhow!("'{}' does not name a branch, tag or commit", name))?;
+    loop {
+        let object = repo.read_object(&object_id)?;
+        match object.object_type {
+            ObjectType::Commit => return Ok(object_id),
+            ObjectType::Tag => object_id = objects::parse_tag(&object.data)?.object,
+            other => anyhow::bail!("'{}' names a {}, which cannot be merged", name, other),
+        }
+    }
+}
+
+// How a merge commit message refers to `name`: "branch 'topic'", "tag 'v1.0'",
+// "remote-tracking branch 'origin/master'" or, for a commit id, "commit '<id>'"
+fn describe_merge_target(repo: &Repository, name: &str) -> String {
+    let ref_names = if name.starts_with("refs/") {
+        vec![name.to_string()]
+    } else {
+        ["refs/heads/", "refs/tags/", "refs/remotes/"].iter().map(|prefix| format!("{}{}", prefix, name)).collect()
+    };
+    let kind = match ref_names.iter().find(|ref_name| refs::read_ref(&repo.git_dir, ref_name).is_ok()) {
+        None => "commit",
+        Some(ref_name) if ref_name.starts_with("refs/tags/") => "tag",
+        Some(ref_name) if ref_name.starts_with("refs/remotes/") => "remote-tracking branch",
+        Some(_) => "branch",
+    };
+    format!("{} '{}'", kind, name)
+}
+
+/// Merge several branches into the current branch at once, recording a single commit whose
+/// parents are the current tip followed by every merged tip.
+///
+/// Each branch is merged in turn into the running result, against its merge base with the
+/// current branch. On the first conflict the merge is abandoned before the working tree,
+/// index or branch are touched. Branches already contained in the current branch are skipped.
+pub fn run_octopus(repo: &mut Repository, branches: &[&str]) -> Result<()> {
+    ensure_no_merge_in_progress(repo)?;
+    let current_branch_name = repo.current_branch()?;
+    let current_branch_commit_id = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", current_branch_name))?;
+
+    let mut tips: Vec<(&str, String)> = Vec::new();
+    for &branch in branches {
+        let commit_id = resolve_merge_target(repo, branch)?;
+        if objects::is_ancestor(repo, &commit_id, &current_branch_commit_id)? {
+            info!("Already up-to-date with '{}'", branch);
+            continue;
+        }
+        if !tips.iter().any(|(_, tip)| *tip == commit_id) {
+            tips.push((branch, commit_id));
+        }
+    }
+    match tips.as_slice() {
+        [] => {
+            info!("Already up-to-date.");
+            return Ok(());
+        }
+        [(branch, _)] => return run(repo, branch, false),
+        _ => {}
+    }
+
+    let current_files = get_files_from_commit(repo, &current_branch_commit_id)?;
+    let mut merged_files = current_files.clone();
+    for (branch, tip) in &tips {
+        let base_files = match objects::merge_base(repo, &current_branch_commit_id, tip)? {
+            Some(base_commit) => get_files_from_commit(repo, &base_commit)?,
+            None => HashMap::new(),
+        };
+        let tip_files = get_files_from_commit(repo, tip)?;
+        let (merged, conflicts) = merge_file_sets(repo, &base_files, &merged_files, &tip_files)?;
+        if !conflicts.is_empty() {
+            anyhow::bail!("Merge with '{}' failed. Merge aborted; nothing was changed.", branch);
+        }
+        merged_files = merged;
+    }
+
+    let mut commit_ids = vec![current_branch_commit_id.as_str()];
+    commit_ids.extend(tips.iter().map(|(_, tip)| tip.as_str()));
+    let modes = get_file_modes(repo, &commit_ids)?;
+    update_working_tree(repo, &current_files, &merged_files, &modes)?;
+
+    let names: Vec<String> = tips.iter().map(|(branch, _)| format!("'{}'", branch)).collect();
+    let (last, rest) = names.split_last().expect("at least two branches are merged");
+    let message = format!("Merge branches {} and {} into {}", rest.join(", "), last, current_branch_name);
+
+    let mut parents = vec![current_branch_commit_id.as_str()];
+    parents.extend(tips.iter().map(|(_, tip)| tip.as_str()));
+    let signature = objects::Signature::now(&repo.config.user_identity());
+    let tree_id = objects::write_tree(repo)?;
+    let merge_commit_id = objects::write_commit(
+        repo.objects.as_ref(),
+        &tree_id,
+        &parents,
+        &message,
+        &signature,
+        &signature,
+    )?;
+
+    refs::update_ref(&repo.git_dir, &format!("refs/heads/{}", current_branch_name), &merge_commit_id)?;
+    repo.index.save(repo.git_dir.join("index"))?;
+
+    info!("Merge made by the 'octopus' strategy.");
+    Ok(())
+}
+
+/// Merge `branch_to_merge` into the current branch. Besides branch names, tags,
+/// remote-tracking branches and commit ids are accepted.
+///
+/// When the current branch is an ancestor of the other branch it is fast-forwarded,
+/// unless `no_ff` asks for an explicit merge commit.
+///
+/// Otherwise the merge commit's message is prepared in `.git/MERGE_MSG`. On conflicts the
+/// working tree gets the merged files, with conflict markers in conflicted text, and the
+/// other commit is recorded in `.git/MERGE_HEAD`; once the conflicts are resolved and
+/// staged, `commit` makes the merge commit, taking its message from `MERGE_MSG`.
+pub fn run(repo: &mut Repository, branch_to_merge: &str, no_ff: bool) -> Result<()> {
+    ensure_no_merge_in_progress(repo)?;
+    let current_branch_name = repo.current_branch()?;
+
+    // Check if trying to merge onto itself
+    if current_branch_name == branch_to_merge {
+        info!("Already on '{}'", branch_to_merge);
         return Ok(());
     }
 
-    // If no conflicts, perform the actual merge
-    #[cfg(not(feature = "online_judge"))]
-    println!("Merge successful. No conflicts found.");
+    // Get commit IDs
+    let current_branch_commit_id = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", current_branch_name))?;
     
-    // Update working directory with merged files
-    // Remove files that exist in current but not in merged result
-    for (filename, _) in &current_files {
-        if !merged_files.contains_key(filename) {
-            let file_path = repo.path.join(filename);
-            if file_path.is_file() {
-                std::fs::remove_file(&file_path)?;
-            }
+    let merge_branch_commit_id = resolve_merge_target(repo, branch_to_merge)?;
+
+    if objects::is_ancestor(repo, &merge_branch_commit_id, &current_branch_commit_id)? {
+        info!("Already up-to-date.");
+        return Ok(());
+    }
+    
+    // The current branch has no commits of its own: move it forward to the other branch
+    if !no_ff && objects::is_ancestor(repo, &current_branch_commit_id, &merge_branch_commit_id)? {
+        let overwritten = checkout::local_changes_overwritten(repo, Some(&current_branch_commit_id), &merge_branch_commit_id)?;
+        if !overwritten.is_empty() {
+            anyhow::bail!(
+                "Your local changes to the following files would be overwritten by merge:\n\t{}\nPlease commit your changes or stash them before you merge.",
+                overwritten.join("\n\t")
+            );
+        }
+
+        // Only files the fast-forward changes are written, so local changes to the rest carry over
+        let mut current_files = get_files_from_commit(repo, &current_branch_commit_id)?;
+        let mut merge_files = get_files_from_commit(repo, &merge_branch_commit_id)?;
+        let current_modes = get_file_modes(repo, &[&current_branch_commit_id])?;
+        let modes = get_file_modes(repo, &[&merge_branch_commit_id])?;
+        let unchanged: Vec<PathBuf> = merge_files
+            .iter()
+            .filter(|&(path, object_id)| {
+                let key = (path.clone(), object_id.clone());
+                current_files.get(path) == Some(object_id) && current_modes.get(&key) == modes.get(&key)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &unchanged {
+            current_files.remove(path);
+            merge_files.remove(path);
         }
+        update_working_tree(repo, &current_files, &merge_files, &modes)?;
+        refs::update_ref(
+            &repo.git_dir,
+            &format!("refs/heads/{}", current_branch_name),
+            &merge_branch_commit_id,
+        )?;
+        repo.index.save(repo.git_dir.join("index"))?;
+        
+        info!("Fast-forward");
+        return Ok(());
     }
+
+    let message = format!("Merge {} into {}", describe_merge_target(repo, branch_to_merge), current_branch_name);
+    fs::write(repo.git_dir.join("MERGE_MSG"), format!("{}\n", message))?;
+
+    // Find merge base (common ancestor)
+    let merge_base = objects::merge_base(repo, &current_branch_commit_id, &merge_branch_commit_id)?;
     
-    // Add/update files in working directory
-    for (filename, object_id) in &merged_files {
-        let (obj_type, blob_data) = objects::read_object(&repo.git_dir.join("objects"), object_id)?;
-        if obj_type == "blob" {
-            let file_path = repo.path.join(filename);
-            if let Some(parent) = file_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
-            std::fs::write(&file_path, &blob_data)?;
-            
-            // Update index
-            repo.index.add_file(&repo.path, &file_path, object_id)?;
+    // Get file lists for three versions
+    let current_files = get_files_from_commit(repo, &current_branch_commit_id)?;
+    let merge_files = get_files_from_commit(repo, &merge_branch_commit_id)?;
+    let base_files = if let Some(base_commit) = &merge_base {
+        get_files_from_commit(repo, base_commit)?
+    } else {
+        HashMap::new() // No common ancestor, treat as empty
+    };
+
+    let (merged_files, conflicts) = merge_file_sets(repo, &base_files, &current_files, &merge_files)?;
+    let modes = get_file_modes(repo, &[&current_branch_commit_id, &merge_branch_commit_id])?;
+
+    if !conflicts.is_empty() {
+        let conflicted = write_merge_result(repo, &current_files, &merged_files, &conflicts, &modes)?;
+        let mut merge_message = format!("{}\n\n# Conflicts:\n", message);
+        for filename in conflicted {
+            merge_message.push_str(&format!("#\t{}\n", filename));
         }
+        repo.index.save(repo.git_dir.join("index"))?;
+        fs::write(repo.git_dir.join("MERGE_HEAD"), format!("{}\n", merge_branch_commit_id))?;
+        fs::write(repo.git_dir.join("MERGE_MSG"), merge_message)?;
+
+        info!("Merge conflicts detected. Please resolve conflicts manually.");
+        info!("Automatic merge failed; fix conflicts and then commit the result.");
+        return Ok(());
     }
+
+    // If no conflicts, perform the actual merge
+    info!("Merge successful. No conflicts found.");
+    
+    // Update working directory with merged files
+    update_working_tree(repo, &current_files, &merged_files, &modes)?;
     
     // Create merge commit
-    let current_tree_id = objects::write_tree(&repo)?;
+    let signature = objects::Signature::now(&repo.config.user_identity());
+    let current_tree_id = objects::write_tree(repo)?;
     let merge_commit_id = objects::write_commit(
-        &repo.git_dir.join("objects"),
+        repo.objects.as_ref(),
         &current_tree_id,
         &[&current_branch_commit_id, &merge_branch_commit_id], // Two parents for merge commit
-        &format!("Merge branch '{}' into {}", branch_to_merge, current_branch_name),
-        "Rust-git <user@example.com>",
+        &message,
+        &signature,
+        &signature,
     )?;
     
     // Update current branch ref
@@ -364,6 +541,260 @@ pub fn execute(branch_to_merge: &str) -> Result<()> {
     
     // Save updated index
     repo.index.save(repo.git_dir.join("index"))?;
+    clear_merge_state(repo)?;
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::repository::index;
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, name: &str, content: &str, message: &str) -> Result<String> {
+        commit_file_with_message(repo, name, content, Some(message))
+    }
+
+    fn commit_file_with_message(repo: &mut Repository, name: &str, content: &str, message: Option<&str>) -> Result<String> {
+        let file_path = repo.path.join(name);
+        std::fs::write(&file_path, content)?;
+        let object_id = objects::write_blob(repo.objects.as_ref(), content.as_bytes())?;
+        repo.index.add_file(&repo.path, &file_path, &object_id)?;
+        let options = CommitOptions { message, ..Default::default() };
+        Ok(commit::run(repo, &options)?.unwrap())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_merge_writes_symlinks_as_links() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "a.txt", "a\n", "Add a")?;
+        let link_path = repo.path.join("link");
+        std::os::unix::fs::symlink("a.txt", &link_path)?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let topic = commit::run(&mut repo, &CommitOptions { message: Some("Add link"), ..Default::default() })?.unwrap();
+        refs::create_branch(&repo.git_dir, "topic", &topic)?;
+
+        for no_ff in [false, true] {
+            switch_tree(&mut repo, &topic, &base)?;
+            refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+            assert!(link_path.symlink_metadata().is_err());
+
+            run(&mut repo, "topic", no_ff)?;
+            assert!(link_path.symlink_metadata()?.file_type().is_symlink(), "no_ff: {}", no_ff);
+            assert_eq!(std::fs::read_link(&link_path)?, PathBuf::from("a.txt"));
+            assert_eq!(repo.index.get_entries()[&PathBuf::from("link")].mode, index::MODE_SYMLINK);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_ff_creates_merge_commit() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "a.txt", "a\n", "Add a")?;
+        let topic = commit_file(&mut repo, "b.txt", "b\n", "Add b")?;
+        refs::create_branch(&repo.git_dir, "topic", &topic)?;
+
+        // Fast-forwardable by default
+        switch_tree(&mut repo, &topic, &base)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        run(&mut repo, "topic", false)?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        assert_eq!(head, topic);
+
+        // --no-ff records both histories in a merge commit
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        run(&mut repo, "topic", true)?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        assert_ne!(head, topic);
+        assert_eq!(objects::read_commit_parents(&repo, &head), vec![base, topic]);
+        assert!(repo.path.join("b.txt").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_files_in_subdirectories() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        std::fs::create_dir_all(repo.path.join("dir/sub"))?;
+        let base = commit_file(&mut repo, "dir/a.txt", "a\n", "Add a")?;
+        let topic = commit_file(&mut repo, "dir/sub/b.txt", "b\n", "Add b")?;
+        refs::create_branch(&repo.git_dir, "topic", &topic)?;
+
+        switch_tree(&mut repo, &topic, &base)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        commit_file(&mut repo, "dir/a.txt", "a changed\n", "Change a")?;
+        run(&mut repo, "topic", false)?;
+
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let tree_id = objects::read_commit(repo.objects.as_ref(), &head)?.tree;
+        let files: HashMap<PathBuf, String> = objects::flatten_tree(repo.objects.as_ref(), &tree_id)?
+            .into_iter()
+            .map(|entry| (entry.name, entry.oid))
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[&PathBuf::from("dir/a.txt")], objects::hash_object(b"a changed\n", ObjectType::Blob));
+        assert_eq!(std::fs::read_to_string(repo.path.join("dir/sub/b.txt"))?, "b\n");
+        assert!(repo.index.get_entries().contains_key(&PathBuf::from("dir/sub/b.txt")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_forward_keeps_local_changes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        commit_file(&mut repo, "kept.txt", "kept\n", "Add kept")?;
+        let base = commit_file(&mut repo, "a.txt", "a\n", "Add a")?;
+        let topic = commit_file(&mut repo, "a.txt", "a changed\n", "Change a")?;
+        refs::create_branch(&repo.git_dir, "topic", &topic)?;
+        switch_tree(&mut repo, &topic, &base)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+
+        // A local change to a file the fast-forward writes stops it
+        std::fs::write(repo.path.join("a.txt"), "local\n")?;
+        let error = run(&mut repo, "topic", false).unwrap_err();
+        assert!(error.to_string().contains("would be overwritten by merge:\n\ta.txt\n"), "{}", error);
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, base);
+        assert_eq!(std::fs::read_to_string(repo.path.join("a.txt"))?, "local\n");
+
+        // One to a file it leaves alone is carried over
+        std::fs::write(repo.path.join("a.txt"), "a\n")?;
+        std::fs::write(repo.path.join("kept.txt"), "local\n")?;
+        run(&mut repo, "topic", false)?;
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, topic);
+        assert_eq!(std::fs::read_to_string(repo.path.join("a.txt"))?, "a changed\n");
+        assert_eq!(std::fs::read_to_string(repo.path.join("kept.txt"))?, "local\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_octopus_merge_of_two_branches() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "base.txt", "base\n", "Add base")?;
+        let first = commit_file(&mut repo, "first.txt", "first\n", "Add first")?;
+        refs::create_branch(&repo.git_dir, "first", &first)?;
+
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        repo.index.remove_path(&repo.path, "first.txt")?;
+        std::fs::remove_file(repo.path.join("first.txt"))?;
+        let second = commit_file(&mut repo, "second.txt", "second\n", "Add second")?;
+        refs::create_branch(&repo.git_dir, "second", &second)?;
+
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        repo.index.remove_path(&repo.path, "second.txt")?;
+        std::fs::remove_file(repo.path.join("second.txt"))?;
+        let main = commit_file(&mut repo, "main.txt", "main\n", "Add main")?;
+
+        run_octopus(&mut repo, &["first", "second"])?;
+
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        assert_eq!(objects::read_commit_parents(&repo, &head), vec![main, first, second]);
+        for name in ["base.txt", "main.txt", "first.txt", "second.txt"] {
+            assert!(repo.path.join(name).is_file(), "{} missing", name);
+        }
+        let mut tree_files: Vec<PathBuf> = get_files_from_commit(&repo, &head)?.into_keys().collect();
+        tree_files.sort();
+        assert_eq!(tree_files, ["base.txt", "first.txt", "main.txt", "second.txt"].map(PathBuf::from));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_octopus_merge_aborts_on_conflict() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "file.txt", "base\n", "Base")?;
+        let first = commit_file(&mut repo, "file.txt", "first\n", "First")?;
+        refs::create_branch(&repo.git_dir, "first", &first)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        let second = commit_file(&mut repo, "other.txt", "other\n", "Other")?;
+        refs::create_branch(&repo.git_dir, "second", &second)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        let main = commit_file(&mut repo, "file.txt", "main\n", "Main")?;
+
+        let error = run_octopus(&mut repo, &["second", "first"]).unwrap_err();
+        assert!(error.to_string().contains("Merge aborted; nothing was changed"), "{}", error);
+
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, main);
+        assert_eq!(std::fs::read_to_string(repo.path.join("file.txt"))?, "main\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicted_merge_is_concluded_by_commit() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "file.txt", "base\n", "Base")?;
+        let topic = commit_file(&mut repo, "file.txt", "topic\n", "Topic")?;
+        commit_file(&mut repo, "topic.txt", "topic only\n", "Topic only")?;
+        let topic_tip = refs::get_head_commit(&repo.git_dir)?;
+        refs::create_branch(&repo.git_dir, "topic", &topic_tip)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        // Back to the base's files before diverging
+        repo.index = index::Index::new();
+        let main = commit_file(&mut repo, "file.txt", "main\n", "Main")?;
+        assert_ne!(topic, main);
+
+        run(&mut repo, "topic", false)?;
+
+        // The merge stops with the conflict marked and the clean change in place
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, main);
+        assert_eq!(merge_head(&repo)?, Some(topic_tip.clone()));
+        assert_eq!(
+            merge_message(&repo)?.unwrap(),
+            "Merge branch 'topic' into master\n\n# Conflicts:\n#\tfile.txt\n"
+        );
+        assert!(fs::read_to_string(repo.path.join("file.txt"))?.contains("<<<<<<<"));
+        assert!(repo.path.join("topic.txt").is_file());
+        assert!(run(&mut repo, "topic", false).is_err());
+
+        // Committing the resolution takes the prepared message and both parents
+        let merge = commit_file_with_message(&mut repo, "file.txt", "resolved\n", None)?;
+        let commit = objects::read_commit(repo.objects.as_ref(), &merge)?;
+        assert_eq!(commit.parents, vec![main, topic_tip]);
+        assert_eq!(commit.message.trim_end(), "Merge branch 'topic' into master");
+        assert_eq!(merge_head(&repo)?, None);
+        assert_eq!(merge_message(&repo)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_commit_id_and_tag() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "base.txt", "base\n", "Add base")?;
+        let side = commit_file(&mut repo, "side.txt", "side\n", "Add side")?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        let main = commit_file(&mut repo, "main.txt", "main\n", "Add main")?;
+
+        // A commit that no branch points at
+        run(&mut repo, &side, false)?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let merge = objects::read_commit(repo.objects.as_ref(), &head)?;
+        assert_eq!(merge.parents, vec![main, side.clone()]);
+        assert_eq!(merge.message.trim_end(), format!("Merge commit '{}' into master", side));
+        assert!(repo.path.join("side.txt").is_file());
+
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &base)?;
+        commit_file(&mut repo, "other.txt", "other\n", "Add other")?;
+        refs::update_ref(&repo.git_dir, "refs/tags/v1.0", &side)?;
+        run(&mut repo, "v1.0", false)?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        assert_eq!(objects::read_commit(repo.objects.as_ref(), &head)?.message.trim_end(), "Merge tag 'v1.0' into master");
+
+        assert!(run(&mut repo, "no-such-thing", false).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use anyhow::Result;
+use crate::repository::{objects, refs};
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location, commit1: &str, commit2: &str) -> Result<()> {
+    let repo = location.open()?;
+    
+    let commit1_id = refs::resolve_revision(&repo.git_dir, commit1)?;
+    let commit2_id = refs::resolve_revision(&repo.git_dir, commit2)?;
+    
+    match objects::merge_base(&repo, &commit1_id, &commit2_id)? {
+        Some(base) => println!("{}", base),
+        None => anyhow::bail!("no merge base found between '{}' and '{}'", commit1, commit2),
+    }
+    
+    Ok(())
+}
@@ -1,15 +1,44 @@
 pub mod add;
+pub mod blame;
 pub mod branch;
 pub mod cat_file;
 pub mod checkout;
+pub mod clean;
+pub mod clone;
 pub mod commit;
+pub mod config;
+pub mod count_objects;
+pub mod describe;
+pub mod diff_tree;
 pub mod fetch;
+pub mod fsck;
 pub mod gc;
+pub mod hash_object;
 pub mod init;
+pub mod log;
+pub mod ls_tree;
 pub mod merge;
+pub mod merge_base;
+pub mod pack_objects;
+pub mod pathspec;
+pub mod prune;
 pub mod pull;
 pub mod push;
+pub mod read_tree;
+pub mod rebase;
+pub mod reflog;
 pub mod repack;
+pub mod reset;
+pub mod restore;
+pub mod rev_list;
 pub mod rm;
 pub mod remote;
-pub mod status;
\ No newline at end of file
+pub mod show_ref;
+pub mod status;
+pub mod switch;
+pub mod tag;
+pub mod unpack_objects;
+pub mod update_ref;
+pub mod verify_pack;
+pub mod worktree;
+pub mod write_tree;
\ No newline at end of file
@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use crate::repository::{Repository, pack};
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location) -> Result<()> {
+    let repo = location.open()?;
+
+    // Each line starts with an object id; anything after it, such as a path, is ignored
+    let mut object_ids = Vec::new();
+    for line in io::stdin().lock().lines() {
+        if let Some(object_id) = line?.split_whitespace().next() {
+            object_ids.push(object_id.to_string());
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    run(&repo, &object_ids, &mut stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Write the objects `object_ids` names to `writer` as a pack stream in git's format, each
+/// object once, in the order first listed. Returns the number of objects packed.
+pub fn run(repo: &Repository, object_ids: &[String], writer: impl Write) -> Result<usize> {
+    let mut seen = HashSet::new();
+    let mut objects = Vec::new();
+    for object_id in object_ids {
+        if !seen.insert(object_id.as_str()) {
+            continue;
+        }
+        let object = repo.read_object(object_id).with_context(|| format!("cannot pack object {}", object_id))?;
+        objects.push((object.object_type, object.data));
+    }
+
+    pack::write_pack_stream(writer, &objects)?;
+    Ok(objects.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::commands::unpack_objects;
+    use crate::repository::{objects, refs};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pack_and_unpack_round_trip() -> Result<()> {
+        let source_dir = tempdir()?;
+        let mut source = Repository::init(source_dir.path())?;
+        fs::create_dir(source.path.join("src"))?;
+        fs::write(source.path.join("src/main.rs"), "fn main() {}\n")?;
+        fs::write(source.path.join("README"), "readme\n")?;
+        source.index.add_directory(&source.path, &source.path, source.objects.as_ref(), &source.config)?;
+        commit::run(&mut source, &CommitOptions { message: Some("Add files"), ..Default::default() })?;
+
+        let head = refs::get_head_commit(&source.git_dir)?;
+        let tree_id = objects::read_commit_tree(source.objects.as_ref(), &head)?;
+        let mut object_ids = vec![head.clone(), tree_id.clone(), head];
+        object_ids.extend(objects::flatten_tree(source.objects.as_ref(), &tree_id)?.into_iter().map(|entry| entry.oid));
+
+        let mut stream = Vec::new();
+        assert_eq!(run(&source, &object_ids, &mut stream)?, object_ids.len() - 1);
+
+        let target_dir = tempdir()?;
+        let target = Repository::init_bare(target_dir.path())?;
+        assert!(object_ids.iter().all(|object_id| !target.objects.exists(object_id)));
+
+        let mut unpacked = unpack_objects::run(&target, &stream)?;
+        let mut expected = object_ids.clone();
+        expected.sort();
+        expected.dedup();
+        unpacked.sort();
+        assert_eq!(unpacked, expected);
+        for object_id in &object_ids {
+            let original = source.read_object(object_id)?;
+            let copy = target.read_object(object_id)?;
+            assert_eq!((copy.object_type, copy.data), (original.object_type, original.data));
+        }
+
+        assert!(run(&source, &["0".repeat(40)], &mut Vec::new()).is_err());
+
+        Ok(())
+    }
+}
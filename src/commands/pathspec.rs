@@ -0,0 +1,114 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::repository::{Repository, is_git_dir_name, normalize_path};
+use crate::repository::ignore::wildmatch;
+
+/// The files a list of pathspecs selected
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Expansion {
+    /// Matching paths relative to the repository root, sorted and without duplicates
+    pub paths: Vec<PathBuf>,
+    /// The pathspecs that matched nothing, as given
+    pub unmatched: Vec<String>,
+}
+
+/// Paths recorded in the index
+pub fn tracked_files(repo: &Repository) -> Vec<PathBuf> {
+    repo.index.get_entries().keys().cloned().collect()
+}
+
+/// Files and symlinks in the working tree, outside `.git` and the repository's git dir
+/// wherever it is, relative to the repository root
+pub fn working_files(repo: &Repository) -> Vec<PathBuf> {
+    WalkDir::new(&repo.path)
+        .into_iter()
+        .filter_entry(|entry| {
+            !is_git_dir_name(entry.file_name()) && entry.path() != repo.git_dir && entry.path() != repo.common_dir
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() || entry.file_type().is_symlink())
+        .filter_map(|entry| entry.path().strip_prefix(&repo.path).ok().map(normalize_path))
+        .collect()
+}
+
+fn is_glob(spec: &str) -> bool {
+    spec.contains(['*', '?'])
+}
+
+/// Select the `candidates` (repository-relative paths) each of `specs` names.
+///
+/// Specs are relative to `current_dir`. A plain spec names a file or everything under a
+/// directory; a spec with `*` or `?` is a glob where `*` stays within one path component
+/// and `**` matches across directories.
+pub fn expand(repo: &Repository, current_dir: &Path, specs: &[String], candidates: &[PathBuf]) -> Result<Expansion> {
+    let prefix = current_dir.strip_prefix(&repo.path).unwrap_or(Path::new(""));
+
+    let mut paths = BTreeSet::new();
+    let mut unmatched = Vec::new();
+    for spec in specs {
+        let full_spec = if Path::new(spec).is_absolute() {
+            match Path::new(spec).strip_prefix(&repo.path) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => anyhow::bail!("'{}' is outside repository at '{}'", spec, repo.path.display()),
+            }
+        } else {
+            prefix.join(spec)
+        };
+        let pattern = normalize_path(&full_spec);
+        if pattern.starts_with("..") {
+            anyhow::bail!("'{}' is outside repository at '{}'", spec, repo.path.display());
+        }
+
+        let pattern_str = pattern.to_string_lossy();
+        let matched: Vec<&PathBuf> = if is_glob(spec) {
+            candidates
+                .iter()
+                .filter(|path| wildmatch(&pattern_str, &path.to_string_lossy()))
+                .collect()
+        } else {
+            candidates.iter().filter(|path| path.starts_with(&pattern)).collect()
+        };
+
+        if matched.is_empty() {
+            unmatched.push(spec.clone());
+        }
+        paths.extend(matched.into_iter().cloned());
+    }
+
+    Ok(Expansion { paths: paths.into_iter().collect(), unmatched })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_expand_globs_and_directories() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        for path in ["main.rs", "lib.rs", "README.md", "src/util.rs", "docs/a.md", "docs/deep/b.md"] {
+            let full_path = repo.path.join(path);
+            fs::create_dir_all(full_path.parent().unwrap())?;
+            fs::write(full_path, path)?;
+        }
+        let candidates = working_files(&repo);
+
+        let specs = ["*.rs".to_string(), "docs/**".to_string(), "nothing*".to_string()];
+        let expansion = expand(&repo, &repo.path, &specs, &candidates)?;
+        let paths: Vec<&str> = expansion.paths.iter().map(|path| path.to_str().unwrap()).collect();
+        assert_eq!(paths, vec!["docs/a.md", "docs/deep/b.md", "lib.rs", "main.rs"]);
+        assert_eq!(expansion.unmatched, vec!["nothing*"]);
+
+        // Specs are relative to the current directory
+        let expansion = expand(&repo, &repo.path.join("src"), &["*.rs".to_string(), ".".to_string()], &candidates)?;
+        assert_eq!(expansion.paths, vec![PathBuf::from("src/util.rs")]);
+
+        assert!(expand(&repo, &repo.path, &["../outside".to_string()], &candidates).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use std::time::{Duration, SystemTime};
+use crate::repository::location::Location;
+use crate::info;
+
+/// Grace period used when `--expire` is not given
+pub const DEFAULT_EXPIRE: &str = "2.weeks.ago";
+
+/// Parse an expiry such as `now`, `never`, `2.weeks.ago` or `3.days.ago` into the cutoff time.
+///
+/// Loose objects last modified before the cutoff are old enough to be pruned.
+pub fn parse_expire(expire: &str, now: SystemTime) -> Result<SystemTime> {
+    match expire {
+        "now" | "all" => return Ok(now),
+        "never" => return Ok(SystemTime::UNIX_EPOCH),
+        _ => {}
+    }
+
+    let parts: Vec<&str> = expire.split('.').collect();
+    let [amount, unit, "ago"] = parts.as_slice() else {
+        anyhow::bail!("invalid expiry '{}': expected now, never or <n>.<unit>.ago", expire);
+    };
+    let amount: u64 = amount.parse()
+        .map_err(|_| anyhow::anyhow!("invalid expiry '{}': '{}' is not a number", expire, amount))?;
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        _ => anyhow::bail!("invalid expiry '{}': unknown unit '{}'", expire, unit),
+    };
+
+    Ok(now.checked_sub(Duration::from_secs(amount * seconds)).unwrap_or(SystemTime::UNIX_EPOCH))
+}
+
+pub fn execute(location: &Location, expire: Option<&str>) -> Result<()> {
+    let repo = location.open()?;
+
+    let expire_before = parse_expire(expire.unwrap_or(DEFAULT_EXPIRE), SystemTime::now())?;
+    let pruned = repo.prune(expire_before)?;
+
+    for object_id in &pruned {
+        info!("{}", object_id);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{Repository, objects, refs};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn loose_path(repo: &Repository, object_id: &str) -> std::path::PathBuf {
+        repo.git_dir.join("objects").join(&object_id[..2]).join(&object_id[2..])
+    }
+
+    #[test]
+    fn test_prune_removes_only_old_unreachable_loose_objects() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+
+        let reachable = objects::write_blob(repo.objects.as_ref(), b"reachable")?;
+        refs::update_ref(&repo.git_dir, "refs/tags/keep", &reachable)?;
+        let unreachable = objects::write_blob(repo.objects.as_ref(), b"unreachable")?;
+        let recent = objects::write_blob(repo.objects.as_ref(), b"recent but unreachable")?;
+
+        let month_ago = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+        for object_id in [&reachable, &unreachable] {
+            fs::File::options().write(true).open(loose_path(&repo, object_id))?.set_modified(month_ago)?;
+        }
+
+        let pruned = repo.prune(parse_expire(DEFAULT_EXPIRE, SystemTime::now())?)?;
+
+        assert_eq!(pruned, vec![unreachable.clone()]);
+        assert!(loose_path(&repo, &reachable).exists());
+        assert!(!loose_path(&repo, &unreachable).exists());
+        assert!(loose_path(&repo, &recent).exists());
+        // History reachable from HEAD is kept too
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        assert!(repo.read_object(&head).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_expire() -> Result<()> {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(10_000_000);
+        assert_eq!(parse_expire("now", now)?, now);
+        assert_eq!(parse_expire("2.weeks.ago", now)?, now - Duration::from_secs(14 * 24 * 60 * 60));
+        assert_eq!(parse_expire("1.hour.ago", now)?, now - Duration::from_secs(60 * 60));
+        assert!(parse_expire("soon", now).is_err());
+        Ok(())
+    }
+}
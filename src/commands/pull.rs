@@ -1,32 +1,85 @@
 use anyhow::Result;
-use std::env;
+use crate::info;
 use crate::repository::Repository;
 use super::{fetch, merge};
+use crate::repository::location::Location;
 
-pub fn execute(remote_or_url: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
+pub fn execute(location: &Location, remote_or_url: &str) -> Result<()> {
+    let mut repo = location.open()?;
     
     // The `pull` command is a combination of `fetch` followed by `merge`.
     // We can reuse the fetch logic entirely. The `fetch` command will
     // handle resolving the name/URL and printing appropriate messages.
     
     // 1. Fetch from the remote or URL
-    fetch::execute(remote_or_url)?;
+    fetch::run(&repo, remote_or_url, None)?;
     
     // 2. Merge the fetched branch
-    // We need to determine what branch to merge. By convention, it's `remote_name/current_branch`.
-    // If a URL was passed, the `fetch` command uses the URL as the remote name, which is
-    // not ideal for merging. A more robust solution would be needed for complex cases,
-    // but for the common case (pulling into the current branch from a remote of the same name),
-    // this works. The remote name for merging is simply the argument we were passed.
-    println!("Merging...");
-    let current_branch = repo.current_branch()?;
-    let remote_branch_to_merge = format!("{}/{}", remote_or_url, current_branch);
-    
-    merge::execute(&remote_branch_to_merge)?;
+    info!("Merging...");
+    run(&mut repo, remote_or_url)?;
     
-    println!("Successfully pulled and merged from remote '{}'.", remote_or_url);
+    info!("Successfully pulled and merged from remote '{}'.", remote_or_url);
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Merge what was fetched from `remote` for the current branch, i.e. the remote-tracking
+/// branch `refs/remotes/<remote>/<branch>`, into the current branch.
+///
+/// If a URL was fetched from, `fetch` uses the URL itself as the remote name, so the same
+/// argument names the remote-tracking branch here.
+pub fn run(repo: &mut Repository, remote: &str) -> Result<()> {
+    let current_branch = repo.current_branch()?;
+    merge::run(repo, &format!("{}/{}", remote, current_branch), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::repository::{bundle, objects, refs};
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, name: &str, content: &str, message: &str) -> Result<String> {
+        fs::write(repo.path.join(name), content)?;
+        crate::commands::add::run(repo, &[PathBuf::from(name)])?;
+        Ok(commit::run(repo, &CommitOptions { message: Some(message), ..Default::default() })?.unwrap())
+    }
+
+    #[test]
+    fn test_pull_merges_remote_tracking_branch() -> Result<()> {
+        let upstream_dir = tempdir()?;
+        let mut upstream = Repository::init(upstream_dir.path())?;
+        let base = commit_file(&mut upstream, "a.txt", "a\n", "Add a")?;
+
+        // The local repository starts from the same history
+        let local_dir = tempdir()?;
+        let mut local = Repository::init(local_dir.path())?;
+        let mut bundle_bytes = Vec::new();
+        bundle::create_bundle(&upstream, &mut bundle_bytes)?;
+        bundle::unbundle(&local, bundle_bytes.as_slice(), bundle::RefMapping::Remote("origin"))?;
+        refs::update_ref(&local.git_dir, "refs/heads/master", &base)?;
+
+        // Both sides move on, then the upstream work is fetched and pulled in
+        let theirs = commit_file(&mut upstream, "b.txt", "b\n", "Add b")?;
+        let ours = commit_file(&mut local, "c.txt", "c\n", "Add c")?;
+        let mut bundle_bytes = Vec::new();
+        bundle::create_bundle(&upstream, &mut bundle_bytes)?;
+        bundle::unbundle(&local, bundle_bytes.as_slice(), bundle::RefMapping::Remote("origin"))?;
+        assert_eq!(refs::read_ref(&local.git_dir, "origin/master")?, theirs);
+
+        run(&mut local, "origin")?;
+        let head = refs::get_head_commit(&local.git_dir)?;
+        assert_eq!(local.parents(&head), vec![ours, theirs.clone()]);
+        assert!(local.path.join("b.txt").is_file());
+        assert!(objects::is_ancestor(&local, &theirs, &head)?);
+
+        // Pulling again finds nothing new
+        run(&mut local, "origin")?;
+        assert_eq!(refs::get_head_commit(&local.git_dir)?, head);
+
+        Ok(())
+    }
+}
@@ -1,10 +1,15 @@
 use anyhow::{anyhow, Result};
 use std::env;
-use crate::repository::{bundle, Repository};
+use crate::info;
+use crate::repository::{bundle, http, refs, Repository};
+use crate::repository::config::Config;
+use crate::repository::location::Location;
 
-pub fn execute(remote_arg: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
+/// Push the branches `refspecs` name to the remote, or every branch when there are none.
+/// Unless `force` is set, the server refuses to move a branch to a commit that does not
+/// descend from its current tip.
+pub fn execute(location: &Location, remote_arg: &str, refspecs: &[String], force: bool) -> Result<()> {
+    let repo = location.open()?;
 
     // 1. Determine the URL. The argument could be a remote name or a direct URL.
     let (remote_name, remote_url) = 
@@ -24,19 +29,90 @@ pub fn execute(remote_arg: &str) -> Result<()> {
             );
         };
 
-    println!("Pushing to remote '{}' at '{}'", remote_name, remote_url);
+    info!("Pushing to remote '{}' at '{}'", remote_name, remote_url);
 
     // 2. Create the bundle in an in-memory buffer.
-    let mut buffer: Vec<u8> = Vec::new();
-    bundle::create_bundle(&repo, &mut buffer)?;
+    let buffer = create_push_bundle(&repo, refspecs)?;
     
-    // 3. Make an HTTP POST request with the bundle as the body.
-    let client = reqwest::blocking::Client::new();
-    let response = client.post(&remote_url)
+    // 3. Send the bundle to the remote.
+    send_bundle(&repo.config, &remote_url, buffer, force)?;
+    
+    if refspecs.is_empty() {
+        let current_branch = repo.current_branch()?;
+        info!("Successfully pushed branch '{}' to remote '{}'.", current_branch, remote_name);
+    } else {
+        info!("Successfully pushed {} to remote '{}'.", refspecs.join(", "), remote_name);
+    }
+    
+    Ok(())
+}
+
+/// Resolve a push refspec, `<src>` or `<src>:<dst>`, to the remote ref to update and the
+/// commit to point it at. `<src>` is any revision; `<dst>` defaults to `<src>` and, like it,
+/// is taken as a branch name unless it is a full `refs/...` name.
+pub fn parse_refspec(repo: &Repository, refspec: &str) -> Result<(String, String)> {
+    let (src, dst) = refspec.split_once(':').unwrap_or((refspec, refspec));
+    if src.is_empty() || dst.is_empty() {
+        anyhow::bail!("invalid refspec '{}'", refspec);
+    }
+    let commit_id = refs::resolve_revision(&repo.git_dir, src)
+        .map_err(|_| anyhow!("src refspec '{}' does not match any", src))?;
+    let dst = match dst.starts_with("refs/") {
+        true => dst.to_string(),
+        false => format!("refs/heads/{}", dst),
+    };
+    Ok((dst, commit_id))
+}
+
+/// The bundle a push sends: every branch when `refspecs` is empty, otherwise just the refs
+/// they name and the objects reachable from them
+pub fn create_push_bundle(repo: &Repository, refspecs: &[String]) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    if refspecs.is_empty() {
+        bundle::create_bundle(repo, &mut buffer)?;
+    } else {
+        let pushed = refspecs
+            .iter()
+            .map(|refspec| parse_refspec(repo, refspec))
+            .collect::<Result<Vec<_>>>()?;
+        bundle::create_bundle_of_refs(repo, &mut buffer, &pushed)?;
+    }
+    Ok(buffer)
+}
+
+/// Hint shown when the server rejects a push as non-fast-forward.
+pub const NON_FAST_FORWARD_HINT: &str =
+    "Updates were rejected because the remote contains work that you do not have locally. Pull first.";
+
+/// POST a bundle to the remote's bundle endpoint.
+///
+/// With `force`, the URL carries a `force=1` query parameter asking the server to overwrite
+/// branches even when the update is not a fast-forward. Otherwise a `409 Conflict` response
+/// means the server refused a non-fast-forward update, which is reported with a git-style
+/// hint instead of the raw server body. The POST is sent once, without retries, and gives up
+/// after `http.timeout`.
+pub fn send_bundle(config: &Config, remote_url: &str, buffer: Vec<u8>, force: bool) -> Result<()> {
+    let client = http::client(config)?;
+    let mut request = client.post(remote_url)
         .header("Content-Type", "application/octet-stream")
-        .body(buffer)
-        .send()
-        .map_err(|e| anyhow!("Failed to connect to remote url '{}': {}", remote_url, e))?;
+        .body(buffer);
+    if force {
+        request = request.query(&[("force", "1")]);
+    }
+    // Servers started with RUST_GIT_PUSH_TOKEN expect the same token as a bearer credential.
+    if let Ok(token) = env::var("RUST_GIT_PUSH_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    let response = http::send(request, remote_url, config.http_timeout())?;
+
+    if response.status() == reqwest::StatusCode::CONFLICT {
+        anyhow::bail!(
+            "failed to push some refs to '{}'\n{}\n(server said: {})",
+            remote_url,
+            NON_FAST_FORWARD_HINT,
+            response.text().unwrap_or_else(|_| "No body".into())
+        );
+    }
 
     if !response.status().is_success() {
         anyhow::bail!(
@@ -45,10 +121,6 @@ pub fn execute(remote_arg: &str) -> Result<()> {
             response.text().unwrap_or_else(|_| "No body".into())
         );
     }
-    
-    let current_branch = repo.current_branch()?;
-    
-    println!("Successfully pushed branch '{}' to remote '{}'.", current_branch, remote_name);
-    
+
     Ok(())
 } 
\ No newline at end of file
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use crate::repository::{Repository, normalize_path, objects};
+use crate::repository::index::IndexEntry;
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location, tree_id: &str) -> Result<()> {
+    let mut repo = location.open()?;
+
+    run(&mut repo, tree_id)?;
+    repo.index.save(repo.git_dir.join("index"))?;
+
+    Ok(())
+}
+
+/// Replace the index with the entries of `tree_id`, recursing into subtrees.
+///
+/// The working tree is left untouched.
+pub fn run(repo: &mut Repository, tree_id: &str) -> Result<()> {
+    let objects_dir = repo.common_dir.join("objects");
+    let tree_id = objects::expand_object_id(&objects_dir, tree_id)?;
+    let entries = objects::flatten_tree(repo.objects.as_ref(), &tree_id)?;
+
+    repo.index.clear();
+    for entry in entries {
+        repo.index.insert_entry(normalize_path(&entry.name), IndexEntry {
+            mtime: 0,
+            object_id: entry.oid,
+            mode: entry.mode,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::objects::ObjectType;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn index_snapshot(repo: &Repository) -> BTreeMap<PathBuf, (u32, String)> {
+        repo.index
+            .get_entries()
+            .iter()
+            .map(|(path, entry)| (path.clone(), (entry.mode, entry.object_id.clone())))
+            .collect()
+    }
+
+    #[test]
+    fn test_write_tree_then_read_tree_round_trips_index() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::create_dir_all(repo.path.join("src"))?;
+        fs::write(repo.path.join("README"), "readme\n")?;
+        fs::write(repo.path.join("src/lib.rs"), "fn main() {}\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let staged = index_snapshot(&repo);
+
+        let tree_id = objects::write_tree(&repo)?;
+        assert_eq!(objects::write_tree(&repo)?, tree_id);
+
+        repo.index.clear();
+        fs::write(repo.path.join("README"), "changed on disk\n")?;
+        run(&mut repo, &tree_id[..8])?;
+
+        assert_eq!(index_snapshot(&repo), staged);
+        assert_eq!(objects::write_tree(&repo)?, tree_id);
+        // The working tree is not touched
+        assert_eq!(fs::read_to_string(repo.path.join("README"))?, "changed on disk\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_tree_recurses_into_subtrees() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"nested\n")?;
+        let mut subtree = b"100644 file.txt\0".to_vec();
+        subtree.extend(hex::decode(&blob_id)?);
+        let subtree_id = repo.objects.write(ObjectType::Tree, &subtree)?;
+        let mut root = b"40000 dir\0".to_vec();
+        root.extend(hex::decode(&subtree_id)?);
+        let root_id = repo.objects.write(ObjectType::Tree, &root)?;
+
+        run(&mut repo, &root_id)?;
+
+        let entry = repo.index.get_entries().get(&PathBuf::from("dir/file.txt")).unwrap();
+        assert_eq!(entry.object_id, blob_id);
+        assert_eq!(entry.mode, 0o100644);
+        assert_eq!(repo.index.get_entries().len(), 1);
+
+        Ok(())
+    }
+}
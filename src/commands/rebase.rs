@@ -0,0 +1,347 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use crate::commands::{merge, rev_list, status};
+use crate::repository::{Head, Repository, objects, refs};
+use crate::repository::objects::Signature;
+use crate::repository::location::Location;
+use crate::info;
+
+/// Directory in the git dir holding the plan of a rebase stopped on a conflict
+const STATE_DIR: &str = "rebase-merge";
+
+pub fn execute(location: &Location, upstream: Option<&str>, resume: bool, abort: bool) -> Result<()> {
+    let mut repo = location.open()?;
+    match upstream {
+        _ if abort => self::abort(&mut repo),
+        _ if resume => resume_rebase(&mut repo).map(|_| ()),
+        Some(upstream) => run(&mut repo, upstream).map(|_| ()),
+        None => anyhow::bail!("An upstream is required to start a rebase"),
+    }
+}
+
+/// Where a rebase stands, kept in `.git/rebase-merge` while it is stopped, in git's layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RebaseState {
+    /// The ref being rebased, `refs/heads/<branch>`, or `HEAD` when it was detached
+    head_name: String,
+    /// The commit the branch is replayed onto
+    onto: String,
+    /// Where the branch pointed before the rebase
+    orig_head: String,
+    /// Commits picked so far, the one that stopped the rebase included
+    done: Vec<String>,
+    /// Commits still to pick, in order
+    todo: Vec<String>,
+}
+
+impl RebaseState {
+    // The state of the rebase in progress, if any
+    fn load(repo: &Repository) -> Result<Option<Self>> {
+        let state_dir = repo.git_dir.join(STATE_DIR);
+        if !state_dir.is_dir() {
+            return Ok(None);
+        }
+        let read = |name: &str| -> Result<String> { Ok(fs::read_to_string(state_dir.join(name))?.trim().to_string()) };
+        let read_plan = |name: &str| -> Result<Vec<String>> {
+            Ok(read(name)?
+                .lines()
+                .filter_map(|line| line.strip_prefix("pick "))
+                .map(|rest| rest.split_whitespace().next().unwrap_or_default().to_string())
+                .collect())
+        };
+        Ok(Some(Self {
+            head_name: read("head-name")?,
+            onto: read("onto")?,
+            orig_head: read("orig-head")?,
+            done: read_plan("done")?,
+            todo: read_plan("git-rebase-todo")?,
+        }))
+    }
+
+    fn save(&self, repo: &Repository) -> Result<()> {
+        let state_dir = repo.git_dir.join(STATE_DIR);
+        fs::create_dir_all(&state_dir)?;
+        fs::write(state_dir.join("head-name"), format!("{}\n", self.head_name))?;
+        fs::write(state_dir.join("onto"), format!("{}\n", self.onto))?;
+        fs::write(state_dir.join("orig-head"), format!("{}\n", self.orig_head))?;
+        let plan = |commits: &[String]| -> String { commits.iter().map(|commit_id| format!("pick {}\n", commit_id)).collect() };
+        fs::write(state_dir.join("done"), plan(&self.done))?;
+        fs::write(state_dir.join("git-rebase-todo"), plan(&self.todo))?;
+        Ok(())
+    }
+}
+
+/// Replay the commits of the current branch that `upstream` lacks (`upstream..HEAD`) on top
+/// of `upstream`, oldest first, then move the branch to the last one. Each commit is
+/// cherry-picked, keeping its message and author. Merge commits are left out, and so are
+/// commits whose changes `upstream` already has.
+///
+/// On a conflict the rebase stops with HEAD detached at the last commit replayed and the
+/// conflicted files left as `merge` leaves them. The commit that failed to apply is recorded
+/// in `.git/REBASE_HEAD` and the plan in `.git/rebase-merge`, for `resume_rebase` or `abort`.
+///
+/// Returns whether the rebase finished.
+pub fn run(repo: &mut Repository, upstream: &str) -> Result<bool> {
+    if RebaseState::load(repo)?.is_some() {
+        anyhow::bail!("A rebase is already in progress; use --continue or --abort");
+    }
+    let head_name = match repo.head()? {
+        Head::Branch(branch) => format!("refs/heads/{}", branch),
+        Head::Detached(_) => "HEAD".to_string(),
+        Head::Unborn(branch) => anyhow::bail!("Branch '{}' has no commits to rebase", branch),
+    };
+    let report = status::run(repo)?;
+    if !report.staged.is_empty() || !report.unstaged.is_empty() {
+        anyhow::bail!("cannot rebase: You have uncommitted changes. Please commit them first.");
+    }
+
+    let orig_head = refs::get_head_commit(&repo.git_dir)?;
+    let onto = refs::resolve_revision(&repo.git_dir, upstream)
+        .or_else(|_| objects::expand_object_id(repo.common_dir.join("objects"), upstream))
+        .map_err(|_| anyhow!("invalid upstream '{}'", upstream))?;
+    if objects::is_ancestor(repo, &onto, &orig_head)? {
+        info!("Current branch is up to date.");
+        return Ok(true);
+    }
+
+    let mut todo = rev_list::run(repo, &format!("{}..{}", onto, orig_head))?;
+    todo.reverse();
+    let shallow = objects::shallow_commits(&repo.git_dir);
+    todo.retain(|commit_id| objects::commit_parents(repo, commit_id, &shallow).len() <= 1);
+
+    // Start from the upstream with HEAD detached, as git does
+    fs::write(repo.git_dir.join("ORIG_HEAD"), format!("{}\n", orig_head))?;
+    merge::switch_tree(repo, &orig_head, &onto)?;
+    refs::update_ref(&repo.git_dir, "HEAD", &onto)?;
+
+    let state = RebaseState { head_name, onto: onto.clone(), orig_head, done: Vec::new(), todo };
+    replay(repo, state, onto)
+}
+
+/// Resume a rebase stopped on a conflict once the conflicts are resolved and staged: commit
+/// the staged result for the commit that stopped it, then pick the rest of the plan. Staging
+/// the current commit's version, so nothing is left to commit, drops the commit that stopped
+/// the rebase.
+///
+/// Returns whether the rebase finished; it stops again on the next conflict.
+pub fn resume_rebase(repo: &mut Repository) -> Result<bool> {
+    let Some(state) = RebaseState::load(repo)? else {
+        anyhow::bail!("No rebase in progress?");
+    };
+    if !status::run(repo)?.unstaged.is_empty() {
+        anyhow::bail!("You must edit all merge conflicts and then mark them as resolved using \"rust-git add\"");
+    }
+
+    let mut current = refs::get_head_commit(&repo.git_dir)?;
+    let rebase_head = repo.git_dir.join("REBASE_HEAD");
+    if let Ok(stopped) = fs::read_to_string(&rebase_head) {
+        let commit = objects::read_commit(repo.objects.as_ref(), stopped.trim())?;
+        let tree_id = objects::write_tree(repo)?;
+        if tree_id != objects::read_commit_tree(repo.objects.as_ref(), &current)? {
+            let committer = Signature::now(&repo.config.user_identity());
+            current = objects::write_commit(repo.objects.as_ref(), &tree_id, &[&current], &commit.message, &commit.author, &committer)?;
+            refs::update_ref(&repo.git_dir, "HEAD", &current)?;
+        }
+        fs::remove_file(&rebase_head)?;
+    }
+
+    replay(repo, state, current)
+}
+
+/// Give up the rebase in progress, putting the branch, HEAD, index and working tree back as
+/// they were before it started
+pub fn abort(repo: &mut Repository) -> Result<()> {
+    let Some(state) = RebaseState::load(repo)? else {
+        anyhow::bail!("No rebase in progress?");
+    };
+
+    // Rebuild the index from scratch, dropping whatever the stopped pick staged
+    let current = refs::get_head_commit(&repo.git_dir)?;
+    repo.index.clear();
+    merge::switch_tree(repo, &current, &state.orig_head)?;
+    repo.index.save(repo.git_dir.join("index"))?;
+
+    refs::update_ref(&repo.git_dir, &state.head_name, &state.orig_head)?;
+    if state.head_name != "HEAD" {
+        fs::write(repo.git_dir.join("HEAD"), format!("ref: {}\n", state.head_name))?;
+    }
+    finish(repo)
+}
+
+// Cherry-pick each commit left in `state` in turn on top of `current`, then point the rebased
+// ref at the result and check it out again. Stops at the first conflict, saving the state.
+fn replay(repo: &mut Repository, mut state: RebaseState, mut current: String) -> Result<bool> {
+    while !state.todo.is_empty() {
+        let commit_id = state.todo.remove(0);
+        state.done.push(commit_id.clone());
+        let commit = objects::read_commit(repo.objects.as_ref(), &commit_id)?;
+        let conflicts = merge::pick(repo, &current, &commit_id)?;
+        repo.index.save(repo.git_dir.join("index"))?;
+        if !conflicts.is_empty() {
+            state.save(repo)?;
+            fs::write(repo.git_dir.join("REBASE_HEAD"), format!("{}\n", commit_id))?;
+            info!("Could not apply {}... {}", &commit_id[..7], commit.message.lines().next().unwrap_or_default());
+            info!("Resolve all conflicts manually, mark them as resolved with \"rust-git add\", then run \"rust-git rebase --continue\".");
+            info!("To abort and get back to the state before the rebase, run \"rust-git rebase --abort\".");
+            return Ok(false);
+        }
+
+        // A change the upstream already has leaves nothing to commit
+        let tree_id = objects::write_tree(repo)?;
+        if tree_id == objects::read_commit_tree(repo.objects.as_ref(), &current)? {
+            continue;
+        }
+        let committer = Signature::now(&repo.config.user_identity());
+        current = objects::write_commit(repo.objects.as_ref(), &tree_id, &[&current], &commit.message, &commit.author, &committer)?;
+        refs::update_ref(&repo.git_dir, "HEAD", &current)?;
+    }
+
+    refs::update_ref(&repo.git_dir, &state.head_name, &current)?;
+    if state.head_name != "HEAD" {
+        fs::write(repo.git_dir.join("HEAD"), format!("ref: {}\n", state.head_name))?;
+    }
+    finish(repo)?;
+    info!("Successfully rebased and updated {}.", state.head_name);
+    Ok(true)
+}
+
+// Remove the state of a rebase that is over
+fn finish(repo: &Repository) -> Result<()> {
+    let state_dir = repo.git_dir.join(STATE_DIR);
+    if state_dir.exists() {
+        fs::remove_dir_all(state_dir)?;
+    }
+    let rebase_head = repo.git_dir.join("REBASE_HEAD");
+    if rebase_head.exists() {
+        fs::remove_file(rebase_head)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::checkout;
+    use crate::commands::commit::{self, CommitOptions};
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, name: &str, content: &str, message: &str) -> Result<String> {
+        commit_file_staged(repo, name, content)?;
+        Ok(commit::run(repo, &CommitOptions { message: Some(message), ..Default::default() })?.unwrap())
+    }
+
+    fn commit_file_staged(repo: &mut Repository, name: &str, content: &str) -> Result<()> {
+        fs::write(repo.path.join(name), content)?;
+        repo.index.add_directory(&repo.path, repo.path.join(name), repo.objects.as_ref(), &repo.config)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebase_onto_advanced_upstream() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "base.txt", "base\n", "Base")?;
+        refs::create_branch(&repo.git_dir, "topic", &base)?;
+        let main = commit_file(&mut repo, "main.txt", "main\n", "Main")?;
+
+        checkout::run(&mut repo, "topic", false, None, false)?;
+        let first = commit_file(&mut repo, "first.txt", "first\n", "First")?;
+        let second = commit_file(&mut repo, "second.txt", "second\n", "Second")?;
+
+        assert!(run(&mut repo, "master")?);
+
+        // The branch now continues the upstream in a straight line, with new commits
+        let history = rev_list::run(&repo, "HEAD")?;
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[2..4], [main.clone(), base]);
+        assert!(!history.contains(&first) && !history.contains(&second));
+        let messages: Vec<String> = history[..2]
+            .iter()
+            .map(|commit_id| objects::read_commit(repo.objects.as_ref(), commit_id).map(|commit| commit.message))
+            .collect::<Result<_>>()?;
+        assert_eq!(messages, vec!["Second", "First"]);
+        assert_eq!(objects::read_commit_parents(&repo, &history[1]), vec![main]);
+
+        assert_eq!(repo.head()?, Head::Branch("topic".to_string()));
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/topic")?, history[0]);
+        for name in ["base.txt", "main.txt", "first.txt", "second.txt"] {
+            assert!(repo.path.join(name).is_file(), "{} missing", name);
+        }
+        assert!(!repo.git_dir.join(STATE_DIR).exists());
+
+        // Nothing is left to replay a second time
+        assert!(run(&mut repo, "master")?);
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/topic")?, history[0]);
+
+        Ok(())
+    }
+
+    // A repository on branch `topic`, whose two commits change `file.txt` and add `other.txt`,
+    // forked from `master`, which changed `file.txt` differently. Returns the commits of
+    // master and topic.
+    fn conflicting_branches(repo: &mut Repository) -> Result<(String, Vec<String>)> {
+        let base = commit_file(repo, "file.txt", "base\n", "Base")?;
+        refs::create_branch(&repo.git_dir, "topic", &base)?;
+        let main = commit_file(repo, "file.txt", "main\n", "Main")?;
+        checkout::run(repo, "topic", false, None, false)?;
+        let topic = vec![
+            commit_file(repo, "file.txt", "topic\n", "Topic")?,
+            commit_file(repo, "other.txt", "other\n", "Other")?,
+        ];
+        Ok((main, topic))
+    }
+
+    #[test]
+    fn test_continue_after_resolving_conflict() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let (main, topic) = conflicting_branches(&mut repo)?;
+
+        assert!(!run(&mut repo, "master")?);
+        assert_eq!(repo.head()?, Head::Detached(main.clone()));
+        assert_eq!(fs::read_to_string(repo.git_dir.join("REBASE_HEAD"))?.trim(), topic[0]);
+        let state = RebaseState::load(&repo)?.unwrap();
+        assert_eq!((state.done, state.todo), (vec![topic[0].clone()], vec![topic[1].clone()]));
+        assert!(run(&mut repo, "master").is_err());
+
+        // Unresolved conflicts keep the rebase stopped
+        assert!(resume_rebase(&mut repo).is_err());
+        commit_file_staged(&mut repo, "file.txt", "resolved\n")?;
+        assert!(resume_rebase(&mut repo)?);
+
+        let history = rev_list::run(&repo, "HEAD")?;
+        assert_eq!(history[2], main);
+        let resolved = objects::read_commit(repo.objects.as_ref(), &history[1])?;
+        assert_eq!(resolved.message, "Topic");
+        let file = objects::find_tree_entry(repo.objects.as_ref(), &resolved.tree, std::path::Path::new("file.txt"))?.unwrap();
+        assert_eq!(repo.read_object(&file.oid)?.data, b"resolved\n");
+        assert_eq!(objects::read_commit(repo.objects.as_ref(), &history[0])?.message, "Other");
+        assert_eq!(repo.head()?, Head::Branch("topic".to_string()));
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/topic")?, history[0]);
+        assert!(RebaseState::load(&repo)?.is_none());
+        assert!(!repo.git_dir.join("REBASE_HEAD").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abort_restores_original_branch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let (_, topic) = conflicting_branches(&mut repo)?;
+
+        assert!(!run(&mut repo, "master")?);
+        abort(&mut repo)?;
+
+        assert_eq!(repo.head()?, Head::Branch("topic".to_string()));
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, topic[1]);
+        assert_eq!(fs::read_to_string(repo.path.join("file.txt"))?, "topic\n");
+        assert!(repo.path.join("other.txt").is_file());
+        let report = status::run(&Repository::open(&repo.path)?)?;
+        assert!(report.staged.is_empty() && report.unstaged.is_empty());
+        assert!(RebaseState::load(&repo)?.is_none());
+        assert!(abort(&mut repo).is_err());
+
+        Ok(())
+    }
+}
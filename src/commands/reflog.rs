@@ -0,0 +1,119 @@
+use anyhow::Result;
+use std::time::SystemTime;
+use crate::commands::prune;
+use crate::info;
+use crate::repository::{reflog, Repository};
+use crate::repository::location::Location;
+
+/// How old reflog entries get before they expire, when neither `--expire` nor
+/// `gc.reflogExpire` says otherwise
+pub const DEFAULT_EXPIRE: &str = "90.days.ago";
+
+pub fn execute_expire(location: &Location, expire: Option<&str>, all: bool, ref_names: &[String]) -> Result<()> {
+    let repo = location.open()?;
+    let expire = expire.or(repo.config.gc_reflog_expire()).unwrap_or(DEFAULT_EXPIRE);
+    let expire_before = prune::parse_expire(expire, SystemTime::now())?;
+
+    let expired = if all {
+        reflog::expire_all(&repo.git_dir, expire_before)?
+    } else if ref_names.is_empty() {
+        anyhow::bail!("no reflog specified to expire; name refs or use --all");
+    } else {
+        expire_refs(&repo, ref_names, expire_before)?
+    };
+    info!("Expired {} reflog entries", expired);
+
+    Ok(())
+}
+
+/// Drop the reflog entries of `ref_names` made before `expire_before`, returning how many were
+/// removed. Short names are looked up among branches, then tags, then remote-tracking refs.
+pub fn expire_refs(repo: &Repository, ref_names: &[String], expire_before: SystemTime) -> Result<usize> {
+    let logged = reflog::list_reflogs(&repo.git_dir)?;
+    let mut expired = 0;
+    for ref_name in ref_names {
+        let full_name = match ref_name.as_str() {
+            "HEAD" => ref_name.clone(),
+            name if name.starts_with("refs/") => ref_name.clone(),
+            name => ["heads", "tags", "remotes"]
+                .iter()
+                .map(|kind| format!("refs/{}/{}", kind, name))
+                .find(|candidate| logged.contains(candidate))
+                .unwrap_or_else(|| format!("refs/heads/{}", name)),
+        };
+        expired += reflog::expire(&repo.git_dir, &full_name, expire_before)?;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::gc;
+    use crate::repository::{objects, refs};
+    use crate::repository::objects::Signature;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn reflog_line(old_id: &str, new_id: &str, time: i64, message: &str) -> String {
+        format!("{} {} Test User <test@example.com> {} +0000\t{}\n", old_id, new_id, time, message)
+    }
+
+    #[test]
+    fn test_expire_drops_only_old_entries() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs() as i64;
+        let old = reflog_line(&"0".repeat(40), &head, now - 100 * DAY, "commit (initial): Old");
+        let recent = reflog_line(&head, &head, now - DAY, "commit: Recent");
+        let log_path = repo.git_dir.join("logs/refs/heads/master");
+        fs::create_dir_all(log_path.parent().unwrap())?;
+        fs::write(&log_path, format!("{}{}", old, recent))?;
+        fs::write(repo.git_dir.join("logs/HEAD"), &old)?;
+
+        assert_eq!(reflog::list_reflogs(&repo.git_dir)?, ["HEAD", "refs/heads/master"]);
+        let expire_before = SystemTime::now() - Duration::from_secs(30 * DAY as u64);
+        assert_eq!(expire_refs(&repo, &["master".to_string()], expire_before)?, 1);
+        assert_eq!(fs::read_to_string(&log_path)?, recent);
+        // Other reflogs are left alone
+        assert_eq!(fs::read_to_string(repo.git_dir.join("logs/HEAD"))?, old);
+
+        assert_eq!(reflog::expire_all(&repo.git_dir, expire_before)?, 1);
+        assert_eq!(fs::read_to_string(repo.git_dir.join("logs/HEAD"))?, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_keeps_commits_in_unexpired_reflog() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let tree_id = objects::read_commit_tree(repo.objects.as_ref(), &head)?;
+        let signature = Signature::now("Test User <test@example.com>");
+        let orphaned = objects::write_commit(repo.objects.as_ref(), &tree_id, &[&head], "Orphaned", &signature, &signature)?;
+        let forgotten = objects::write_commit(repo.objects.as_ref(), &tree_id, &[&head], "Forgotten", &signature, &signature)?;
+
+        // A reset moved master away from both; only the recent move is still in the reflog
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs() as i64;
+        let log_path = repo.git_dir.join("logs/refs/heads/master");
+        fs::create_dir_all(log_path.parent().unwrap())?;
+        fs::write(&log_path, format!(
+            "{}{}",
+            reflog_line(&head, &forgotten, now - 100 * DAY, "commit: Forgotten"),
+            reflog_line(&head, &orphaned, now - DAY, "commit: Orphaned"),
+        ))?;
+
+        gc::Command { prune: Some("now".to_string()), ..Default::default() }.run(&repo)?;
+
+        assert!(repo.read_object(&orphaned).is_ok());
+        assert!(repo.read_object(&forgotten).is_err());
+        assert_eq!(reflog::read_reflog(&repo.git_dir, "refs/heads/master")?.len(), 1);
+
+        Ok(())
+    }
+}
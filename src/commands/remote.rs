@@ -1,21 +1,20 @@
 use anyhow::{Context, Result};
-use std::env;
 use std::fs;
 use std::io::Write;
 
-use crate::repository::Repository;
+use crate::info;
+use crate::repository::location::Location;
 
-pub fn execute(subcommand: &str, name: &str, url: &str) -> Result<()> {
+pub fn execute(location: &Location, subcommand: &str, name: &str, url: &str) -> Result<()> {
     match subcommand {
-        "add" => add_remote(name, url),
+        "add" => add_remote(location, name, url),
         _ => anyhow::bail!("Unsupported remote subcommand: {}", subcommand),
     }
 }
 
-fn add_remote(name: &str, url: &str) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
-    let config_path = repo.git_dir.join("config");
+fn add_remote(location: &Location, name: &str, url: &str) -> Result<()> {
+    let repo = location.open()?;
+    let config_path = repo.common_dir.join("config");
 
     let new_remote_entry = format!("\n[remote \"{}\"]\n\turl = {}\n", name, url);
     
@@ -26,7 +25,7 @@ fn add_remote(name: &str, url: &str) -> Result<()> {
         .write_all(new_remote_entry.as_bytes())
         .with_context(|| "Failed to write to config file")?;
     
-    println!("Added remote '{}' with URL '{}'", name, url);
+    info!("Added remote '{}' with URL '{}'", name, url);
     
     Ok(())
 } 
\ No newline at end of file
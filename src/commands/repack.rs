@@ -1,7 +1,7 @@
 use anyhow::Result;
 use clap::Args;
-use std::env;
 use crate::repository::Repository;
+use crate::repository::location::Location;
 
 /// Pack all loose objects into a pack file
 #[derive(Args)]
@@ -14,9 +14,8 @@ impl Command {
     }
 }
 
-pub fn execute() -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let repo = Repository::open(&current_dir)?;
+pub fn execute(location: &Location) -> Result<()> {
+    let repo = location.open()?;
     Command{}.run(&repo)
 }
 
@@ -34,8 +33,8 @@ mod tests {
         let objects_dir = repo.git_dir.join("objects");
         fs::create_dir_all(&objects_dir)?;
         // Create loose objects
-        let id1 = objects::write_blob(&objects_dir, b"1")?;
-        let id2 = objects::write_blob(&objects_dir, b"2")?;
+        let id1 = objects::write_blob(repo.objects.as_ref(), b"1")?;
+        let id2 = objects::write_blob(repo.objects.as_ref(), b"2")?;
         let path1 = objects_dir.join(&id1[0..2]).join(&id1[2..]);
         let path2 = objects_dir.join(&id2[0..2]).join(&id2[2..]);
         assert!(path1.exists());
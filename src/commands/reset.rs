@@ -0,0 +1,125 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::repository::{Repository, normalize_path, objects, refs};
+use crate::repository::index::IndexEntry;
+use crate::repository::location::Location;
+use crate::info;
+
+pub fn execute(location: &Location, paths: &[String]) -> Result<()> {
+    let mut repo = location.open()?;
+    let current_dir = location.pathspec_dir(&repo);
+
+    // `reset HEAD <paths>` is the traditional spelling; HEAD is the only supported commit
+    let paths = match paths.split_first() {
+        Some((first, rest)) if first == "HEAD" && !rest.is_empty() => rest,
+        _ => paths,
+    };
+
+    let mut relative_paths = Vec::new();
+    for path in paths {
+        let full_path = current_dir.join(path);
+        let Ok(relative_path) = full_path.strip_prefix(&repo.path) else {
+            anyhow::bail!("'{}' is outside repository at '{}'", path, repo.path.display());
+        };
+        relative_paths.push(normalize_path(relative_path));
+    }
+
+    let unstaged = run(&mut repo, &relative_paths)?;
+    repo.index.save(repo.git_dir.join("index"))?;
+
+    if !unstaged.is_empty() {
+        info!("Unstaged changes after reset:");
+        for path in &unstaged {
+            info!("M\t{}", path);
+        }
+    }
+
+    Ok(())
+}
+
+// Whether `path` is `pathspec` itself or lies inside the directory `pathspec`
+fn matches_pathspec(path: &Path, pathspec: &Path) -> bool {
+    pathspec.as_os_str().is_empty() || path.starts_with(pathspec)
+}
+
+/// Unstage `paths` (relative to the repository root): make their index entries match HEAD,
+/// dropping entries HEAD does not have. The working tree is not touched.
+///
+/// Returns the paths whose index entry changed, sorted.
+pub fn run(repo: &mut Repository, paths: &[PathBuf]) -> Result<Vec<String>> {
+    let head_entries: HashMap<PathBuf, (u32, String)> = match refs::get_head_commit(&repo.git_dir) {
+        Ok(head) => {
+            let tree_id = objects::read_commit_tree(repo.objects.as_ref(), &head)?;
+            objects::flatten_tree(repo.objects.as_ref(), &tree_id)?
+                .into_iter()
+                .map(|entry| (normalize_path(&entry.name), (entry.mode, entry.oid)))
+                .collect()
+        }
+        Err(_) => HashMap::new(), // Nothing committed yet: unstaging empties the index
+    };
+
+    let mut changed = Vec::new();
+    for pathspec in paths {
+        let mut matched: Vec<PathBuf> = repo.index.get_entries().keys()
+            .chain(head_entries.keys())
+            .filter(|path| matches_pathspec(path, pathspec))
+            .cloned()
+            .collect();
+        matched.sort();
+        matched.dedup();
+
+        for path in matched {
+            let staged = repo.index.get_entries().get(&path).map(|entry| (entry.mode, entry.object_id.clone()));
+            let committed = head_entries.get(&path).cloned();
+            if staged == committed {
+                continue;
+            }
+            match committed {
+                Some((mode, object_id)) => repo.index.insert_entry(path.clone(), IndexEntry { mtime: 0, object_id, mode }),
+                None => {
+                    repo.index.remove_path(&repo.path, &path)?;
+                }
+            }
+            changed.push(path.to_string_lossy().to_string());
+        }
+    }
+
+    changed.sort();
+    changed.dedup();
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_reset_unstages_paths() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::write(repo.path.join("tracked.txt"), "v1\n")?;
+        repo.index.add_directory(&repo.path, repo.path.join("tracked.txt"), repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("Add tracked"), ..Default::default() })?;
+        let committed_id = repo.index.get_entries()[Path::new("tracked.txt")].object_id.clone();
+
+        // Stage a new file and a modification
+        fs::write(repo.path.join("new.txt"), "new\n")?;
+        fs::write(repo.path.join("tracked.txt"), "v2\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+
+        let changed = run(&mut repo, &[PathBuf::from("new.txt")])?;
+        assert_eq!(changed, vec!["new.txt"]);
+        assert!(!repo.index.get_entries().contains_key(Path::new("new.txt")));
+        assert!(repo.path.join("new.txt").is_file());
+
+        run(&mut repo, &[PathBuf::from("tracked.txt")])?;
+        assert_eq!(repo.index.get_entries()[Path::new("tracked.txt")].object_id, committed_id);
+        assert_eq!(fs::read_to_string(repo.path.join("tracked.txt"))?, "v2\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::commands::{checkout, ls_tree, pathspec};
+use crate::repository::{Repository, native_path, normalize_path, objects, refs};
+use crate::repository::index::IndexEntry;
+use crate::repository::location::Location;
+
+/// What `restore` restores and where from
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RestoreOptions<'a> {
+    /// Restore the index instead of the working tree
+    pub staged: bool,
+    /// Commit (or tree) to restore from; by default the working tree is restored from the
+    /// index and the index from HEAD
+    pub source: Option<&'a str>,
+}
+
+pub fn execute(location: &Location, paths: &[String], options: &RestoreOptions) -> Result<()> {
+    let mut repo = location.open()?;
+    let current_dir = location.pathspec_dir(&repo);
+    run(&mut repo, &current_dir, paths, options)?;
+    Ok(())
+}
+
+// The files to restore from, by path: the index, or the tree of `source`. An unborn HEAD
+// has no files.
+fn source_files(repo: &Repository, options: &RestoreOptions) -> Result<HashMap<PathBuf, (u32, String)>> {
+    let tree_id = match (options.source, options.staged) {
+        (Some(source), _) => ls_tree::resolve_tree(repo, source)?,
+        (None, true) => match refs::get_head_commit(&repo.git_dir) {
+            Ok(head) => objects::read_commit_tree(repo.objects.as_ref(), &head)?,
+            Err(_) => return Ok(HashMap::new()),
+        },
+        (None, false) => {
+            return Ok(repo.index.get_entries()
+                .iter()
+                .map(|(path, entry)| (path.clone(), (entry.mode, entry.object_id.clone())))
+                .collect());
+        }
+    };
+    Ok(objects::flatten_tree(repo.objects.as_ref(), &tree_id)?
+        .into_iter()
+        .map(|entry| (normalize_path(&entry.name), (entry.mode, entry.oid)))
+        .collect())
+}
+
+/// Restore the files `specs` (pathspecs relative to `current_dir`) select from their source:
+/// the working tree from the index, or with `staged` the index from HEAD. `source` restores
+/// from that commit instead.
+///
+/// Tracked paths the source does not have are removed from the working tree, or from the
+/// index with `staged`. Returns the restored paths, sorted.
+pub fn run(repo: &mut Repository, current_dir: &Path, specs: &[String], options: &RestoreOptions) -> Result<Vec<PathBuf>> {
+    let source = source_files(repo, options)?;
+    let mut candidates: Vec<PathBuf> = source.keys().cloned().collect();
+    candidates.extend(pathspec::tracked_files(repo));
+
+    let expansion = pathspec::expand(repo, current_dir, specs, &candidates)?;
+    if let Some(spec) = expansion.unmatched.first() {
+        anyhow::bail!("pathspec '{}' did not match any file(s) known to git", spec);
+    }
+
+    for path in &expansion.paths {
+        let wanted = source.get(path);
+        if options.staged {
+            match wanted {
+                Some((mode, object_id)) => {
+                    repo.index.insert_entry(path.clone(), IndexEntry { mtime: 0, object_id: object_id.clone(), mode: *mode });
+                }
+                None => {
+                    repo.index.remove_path(&repo.path, path)?;
+                }
+            }
+            continue;
+        }
+
+        let full_path = repo.path.join(native_path(path));
+        match wanted {
+            Some((mode, object_id)) => {
+                let blob = repo.read_object(object_id)?;
+                checkout::write_working_file(&full_path, &blob.data, *mode, &repo.config)?;
+            }
+            None if full_path.symlink_metadata().is_ok() => fs::remove_file(&full_path)?,
+            None => {}
+        }
+    }
+
+    if options.staged {
+        repo.index.save(repo.git_dir.join("index"))?;
+    }
+    Ok(expansion.paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use tempfile::tempdir;
+
+    // A repository with `file.txt` committed as "v1" and then "v2", returning the first commit
+    fn repo_with_history(temp_dir: &Path) -> Result<(Repository, String)> {
+        let mut repo = Repository::init(temp_dir)?;
+        let mut first = String::new();
+        for content in ["v1\n", "v2\n"] {
+            fs::write(repo.path.join("file.txt"), content)?;
+            repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+            let commit = commit::run(&mut repo, &CommitOptions { message: Some(content.trim()), ..Default::default() })?.unwrap();
+            if first.is_empty() {
+                first = commit;
+            }
+        }
+        Ok((repo, first))
+    }
+
+    fn staged_id(repo: &Repository, path: &str) -> Option<String> {
+        repo.index.get_entries().get(Path::new(path)).map(|entry| entry.object_id.clone())
+    }
+
+    #[test]
+    fn test_restore_working_tree_from_index() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let (mut repo, _) = repo_with_history(temp_dir.path())?;
+        let current_dir = repo.path.clone();
+
+        fs::write(repo.path.join("file.txt"), "v3\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        fs::write(repo.path.join("file.txt"), "scratch\n")?;
+
+        let restored = run(&mut repo, &current_dir, &["file.txt".to_string()], &RestoreOptions::default())?;
+        assert_eq!(restored, vec![PathBuf::from("file.txt")]);
+        // The staged content comes back, not the committed one
+        assert_eq!(fs::read_to_string(repo.path.join("file.txt"))?, "v3\n");
+
+        assert!(run(&mut repo, &current_dir, &["missing.txt".to_string()], &RestoreOptions::default()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_staged_from_head() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let (mut repo, _) = repo_with_history(temp_dir.path())?;
+        let current_dir = repo.path.clone();
+        let committed = staged_id(&repo, "file.txt");
+
+        fs::write(repo.path.join("file.txt"), "v3\n")?;
+        fs::write(repo.path.join("new.txt"), "new\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+
+        let options = RestoreOptions { staged: true, ..Default::default() };
+        run(&mut repo, &current_dir, &["file.txt".to_string(), "new.txt".to_string()], &options)?;
+
+        assert_eq!(staged_id(&repo, "file.txt"), committed);
+        assert_eq!(staged_id(&repo, "new.txt"), None);
+        // Unstaging leaves the working tree alone
+        assert_eq!(fs::read_to_string(repo.path.join("file.txt"))?, "v3\n");
+        assert!(repo.path.join("new.txt").is_file());
+        assert_eq!(staged_id(&Repository::open(&repo.path)?, "file.txt"), committed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_from_source_commit() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let (mut repo, first) = repo_with_history(temp_dir.path())?;
+        let current_dir = repo.path.clone();
+        let staged = staged_id(&repo, "file.txt");
+
+        let options = RestoreOptions { source: Some(&first), ..Default::default() };
+        run(&mut repo, &current_dir, &["file.txt".to_string()], &options)?;
+        assert_eq!(fs::read_to_string(repo.path.join("file.txt"))?, "v1\n");
+        assert_eq!(staged_id(&repo, "file.txt"), staged);
+
+        let options = RestoreOptions { staged: true, source: Some(&first) };
+        run(&mut repo, &current_dir, &["file.txt".to_string()], &options)?;
+        assert_ne!(staged_id(&repo, "file.txt"), staged);
+        assert_eq!(repo.read_object(&staged_id(&repo, "file.txt").unwrap())?.data, b"v1\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use crate::repository::{Repository, objects, refs};
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location, revision: &str, count: bool) -> Result<()> {
+    let repo = location.open()?;
+
+    let commits = run(&repo, revision)?;
+    if count {
+        println!("{}", commits.len());
+    } else {
+        for commit_id in &commits {
+            println!("{}", commit_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// The ids of the commits `revision` reaches, most recently committed first.
+///
+/// `<a>..<b>` lists the commits reachable from `b` but not from `a`, such as the commits a
+/// branch has that its upstream lacks. Either side may be left out to mean HEAD.
+pub fn run(repo: &Repository, revision: &str) -> Result<Vec<String>> {
+    let (exclude, include) = match revision.split_once("..") {
+        Some((exclude, include)) => (Some(exclude), include),
+        None => (None, revision),
+    };
+    let or_head = |side: &str| if side.is_empty() { "HEAD".to_string() } else { side.to_string() };
+
+    let excluded = match exclude {
+        Some(exclude) => objects::ancestors(repo, &refs::resolve_revision(&repo.git_dir, &or_head(exclude))?),
+        None => HashSet::new(),
+    };
+    let mut commits = Vec::new();
+    for commit in repo.log(&or_head(include)) {
+        let commit = commit?;
+        if !excluded.contains(&commit.oid) {
+            commits.push(commit.oid);
+        }
+    }
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, content: &str) -> Result<String> {
+        fs::write(repo.path.join("file.txt"), content)?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        Ok(commit::run(repo, &CommitOptions { message: Some(content.trim()), ..Default::default() })?.unwrap())
+    }
+
+    #[test]
+    fn test_linear_history() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let root = refs::get_head_commit(&repo.git_dir)?;
+        let first = commit_file(&mut repo, "one\n")?;
+        let second = commit_file(&mut repo, "two\n")?;
+
+        assert_eq!(run(&repo, "HEAD")?, vec![second.clone(), first.clone(), root.clone()]);
+        assert_eq!(run(&repo, &first)?, vec![first, root]);
+        assert!(run(&repo, "no-such-branch").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_excludes_reachable_commits() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let base = commit_file(&mut repo, "base\n")?;
+        refs::create_branch(&repo.git_dir, "upstream", &base)?;
+        let ahead_one = commit_file(&mut repo, "ahead one\n")?;
+        let ahead_two = commit_file(&mut repo, "ahead two\n")?;
+
+        assert_eq!(run(&repo, "upstream..master")?, vec![ahead_two.clone(), ahead_one]);
+        assert_eq!(run(&repo, "upstream..")?.len(), 2);
+        assert!(run(&repo, "master..upstream")?.is_empty());
+        assert_eq!(run(&repo, &format!("{}..{}", ahead_two, ahead_two))?, Vec::<String>::new());
+
+        Ok(())
+    }
+}
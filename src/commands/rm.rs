@@ -1,64 +1,92 @@
 use anyhow::Result;
-use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::PathBuf;
+use crate::commands::pathspec;
 use crate::repository::Repository;
+use crate::repository::location::Location;
+use crate::info;
 
-pub fn execute(paths: &[String]) -> Result<()> {
-    let current_dir = env::current_dir()?;
-    
+pub fn execute(location: &Location, paths: &[String]) -> Result<()> {
     // Open the repository
-    let mut repo = Repository::open(&current_dir)?;
+    let mut repo = location.open()?;
+    let current_dir = location.pathspec_dir(&repo);
+    
+    let candidates = pathspec::tracked_files(&repo);
+    let expansion = pathspec::expand(&repo, &current_dir, paths, &candidates)?;
+    
+    for spec in &expansion.unmatched {
+        info!("pathspec '{}' did not match any files in the index", spec);
+    }
+    
+    let removed_files = run(&mut repo, &expansion.paths)?;
     
+    // Save the index
+    repo.index.save(repo.git_dir.join("index"))?;
+    
+    for path in &removed_files {
+        info!("rm '{}'", path);
+    }
+    
+    if !removed_files.is_empty() {
+        info!("Removed {} file(s) from the index and working directory", removed_files.len());
+    }
+    
+    Ok(())
+}
+
+/// Remove the tracked `paths` (relative to the repository root) from the index and the
+/// working tree, along with directories left empty. Returns the removed paths.
+pub fn run(repo: &mut Repository, paths: &[PathBuf]) -> Result<Vec<String>> {
     let mut removed_files = Vec::new();
     
-    // Remove each path
-    for path_str in paths {
-        let path = Path::new(path_str);
-        
-        // Check if file exists in working directory
-        let full_path = if path.is_absolute() {
-            path.to_path_buf()
-        } else {
-            current_dir.join(path)
-        };
-        
-        if !full_path.exists() {
-            #[cfg(not(feature = "online_judge"))]
-            println!("pathspec '{}' did not match any files (file does not exist)", path_str);
-            continue;
-        }
-        
-        // Try to remove from index
+    for path in paths {
         let removed = repo.index.remove_path(&repo.path, path)?;
-        
         if removed.is_empty() {
-            #[cfg(not(feature = "online_judge"))]
-            println!("pathspec '{}' did not match any files in the index", path_str);
             continue;
         }
         
-        // Remove from working directory
-        if full_path.is_file() {
+        let full_path = repo.path.join(path);
+        if full_path.symlink_metadata().is_ok() {
             fs::remove_file(&full_path)?;
-            #[cfg(not(feature = "online_judge"))]
-            println!("rm '{}'", path_str);
-        } else if full_path.is_dir() {
-            fs::remove_dir_all(&full_path)?;
-            #[cfg(not(feature = "online_judge"))]
-            println!("rm -r '{}'", path_str);
+        }
+        // Clear out directories the removal emptied, stopping at the first one still in use
+        for dir in full_path.ancestors().skip(1).take_while(|dir| *dir != repo.path) {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
         }
         
         removed_files.extend(removed);
     }
     
-    // Save the index
-    repo.index.save(repo.git_dir.join("index"))?;
-    
-    if !removed_files.is_empty() {
-        #[cfg(not(feature = "online_judge"))]
-        println!("Removed {} file(s) from the index and working directory", removed_files.len());
+    Ok(removed_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rm_glob() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        for path in ["docs/a.md", "docs/deep/b.md", "README.md"] {
+            let full_path = repo.path.join(path);
+            fs::create_dir_all(full_path.parent().unwrap())?;
+            fs::write(full_path, path)?;
+        }
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        
+        let expansion = pathspec::expand(&repo, &repo.path, &["docs/**".to_string()], &pathspec::tracked_files(&repo))?;
+        let mut removed = run(&mut repo, &expansion.paths)?;
+        removed.sort();
+        
+        assert_eq!(removed, vec!["docs/a.md", "docs/deep/b.md"]);
+        assert!(!repo.path.join("docs").exists());
+        assert!(repo.path.join("README.md").exists());
+        assert_eq!(repo.index.get_entries().keys().collect::<Vec<_>>(), vec![&PathBuf::from("README.md")]);
+        
+        Ok(())
     }
-    
-    Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use crate::repository::{Repository, objects, refs};
+use crate::repository::objects::ObjectType;
+use crate::repository::location::Location;
+
+/// Which refs `show-ref` lists and how
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShowRefOptions {
+    /// Only branches under refs/heads; together with `tags`, both kinds
+    pub heads: bool,
+    /// Only tags under refs/tags; together with `heads`, both kinds
+    pub tags: bool,
+    /// Follow each annotated tag to the object it tags, listed as `<ref>^{}`
+    pub dereference: bool,
+}
+
+pub fn execute(location: &Location, options: &ShowRefOptions) -> Result<()> {
+    let repo = location.open()?;
+
+    for (object_id, ref_name) in run(&repo, options)? {
+        println!("{} {}", object_id, ref_name);
+    }
+
+    Ok(())
+}
+
+/// The refs of `repo`, loose and packed, as `(object_id, ref_name)` pairs sorted by name.
+///
+/// With `dereference`, each annotated tag is followed by a `<ref>^{}` entry naming the object
+/// it finally tags.
+pub fn run(repo: &Repository, options: &ShowRefOptions) -> Result<Vec<(String, String)>> {
+    let wanted = |ref_name: &str| {
+        (!options.heads && !options.tags)
+            || (options.heads && ref_name.starts_with("refs/heads/"))
+            || (options.tags && ref_name.starts_with("refs/tags/"))
+    };
+
+    let mut shown = Vec::new();
+    for (ref_name, object_id) in refs::list_refs(&repo.git_dir)? {
+        if !wanted(&ref_name) {
+            continue;
+        }
+        let peeled = if options.dereference { peel_tag(repo, &object_id)? } else { None };
+        shown.push((object_id, ref_name.clone()));
+        if let Some(peeled) = peeled {
+            shown.push((peeled, format!("{}^{{}}", ref_name)));
+        }
+    }
+
+    Ok(shown)
+}
+
+// The object an annotated tag finally points at, through tags of tags; `None` when `object_id`
+// is not a tag
+fn peel_tag(repo: &Repository, object_id: &str) -> Result<Option<String>> {
+    let mut peeled = None;
+    let mut object = repo.read_object(object_id)?;
+    while object.object_type == ObjectType::Tag {
+        let target = objects::parse_tag(&object.data)?.object;
+        object = repo.read_object(&target)?;
+        peeled = Some(target);
+    }
+    Ok(peeled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::tag;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_show_ref_dereferences_annotated_tags() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        refs::create_branch(&repo.git_dir, "topic", &head)?;
+        tag::create(&repo, "light", None, None)?;
+        let annotated = tag::create(&repo, "v1.0", None, Some("Release 1.0"))?;
+
+        // A ref that only packed-refs knows about is listed too
+        fs::write(
+            repo.git_dir.join("packed-refs"),
+            format!("# pack-refs with: peeled fully-peeled sorted\n{} refs/remotes/origin/master\n", head),
+        )?;
+
+        let entry = |object_id: &str, ref_name: &str| (object_id.to_string(), ref_name.to_string());
+        let options = ShowRefOptions { dereference: true, ..Default::default() };
+        assert_eq!(run(&repo, &options)?, vec![
+            entry(&head, "refs/heads/master"),
+            entry(&head, "refs/heads/topic"),
+            entry(&head, "refs/remotes/origin/master"),
+            entry(&head, "refs/tags/light"),
+            entry(&annotated, "refs/tags/v1.0"),
+            entry(&head, "refs/tags/v1.0^{}"),
+        ]);
+
+        let options = ShowRefOptions { tags: true, ..Default::default() };
+        assert_eq!(run(&repo, &options)?, vec![entry(&head, "refs/tags/light"), entry(&annotated, "refs/tags/v1.0")]);
+        let options = ShowRefOptions { heads: true, ..Default::default() };
+        assert_eq!(run(&repo, &options)?.len(), 2);
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/remotes/origin/master")?, head);
+
+        Ok(())
+    }
+}
@@ -1,144 +1,251 @@
 use anyhow::Result;
-use std::collections::{HashMap, HashSet};
-use std::env;
-use std::fs;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
 use walkdir::WalkDir;
-use crate::repository::{Repository, objects, refs};
+use crate::repository::{Head, Repository, index, is_git_dir_name, objects, refs};
+use crate::repository::objects::{Object, ObjectType};
+use crate::repository::location::Location;
+use crate::info;
 
-#[derive(Debug)]
-struct FileStatus {
-    staged: Option<String>,    // Object ID in index, None if not staged
-    head: Option<String>,      // Object ID in HEAD, None if not in HEAD
-    working: Option<String>,   // Object ID in working dir, None if deleted
+/// How the index differs from HEAD and the working tree differs from the index
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StatusReport {
+    /// Changes to be committed as (path, status); renames use `old -> new` as the path
+    pub staged: Vec<(String, &'static str)>,
+    /// Changes not staged for commit as (path, status); renames use `old -> new` as the path
+    pub unstaged: Vec<(String, &'static str)>,
+    /// Files neither in HEAD nor in the index
+    pub untracked: Vec<String>,
 }
 
-pub fn execute() -> Result<()> {
-    let current_dir = env::current_dir()?;
-    let _repo = Repository::open(&current_dir)?;
+// Pair each deleted path with an added path holding the same content and report them as a rename.
+// `old_ids` gives the content of deleted paths, `new_ids` the content of added paths.
+fn detect_renames(
+    changes: &mut Vec<(String, &'static str)>,
+    added: &mut Vec<String>,
+    old_ids: &HashMap<PathBuf, String>,
+    new_ids: &HashMap<PathBuf, String>,
+) {
+    let deleted: Vec<String> = changes
+        .iter()
+        .filter(|(_, status)| *status == "deleted")
+        .map(|(path, _)| path.clone())
+        .collect();
     
-    #[cfg(not(feature = "online_judge"))] {
-        println!("On branch {}", _repo.current_branch()?);
+    for old_path in deleted {
+        let Some(old_id) = old_ids.get(&PathBuf::from(&old_path)) else {
+            continue;
+        };
+        let Some(position) = added.iter().position(|new_path| new_ids.get(&PathBuf::from(new_path)) == Some(old_id)) else {
+            continue;
+        };
+        let new_path = added.remove(position);
+        changes.retain(|(path, _)| *path != old_path && *path != new_path);
+        changes.push((format!("{} -> {}", old_path, new_path), "renamed"));
+    }
+    changes.sort();
+}
+
+/// Compare HEAD, the index and the working tree.
+///
+/// A deleted file whose exact content reappears under a new path is reported as a rename.
+pub fn run(repo: &Repository) -> Result<StatusReport> {
+    // Get files from HEAD commit
+    let head_files = get_head_files(repo)?;
     
-        // Get files from HEAD commit
-        let head_files = get_head_files(&_repo)?;
-        
-        // Get files from index
-        let index_files = get_index_files(&_repo);
-        
-        // Get files from working directory
-        let working_files = get_working_files(&_repo)?;
+    // Get files from index
+    let index_files = get_index_files(repo);
+    
+    // Get files from working directory
+    let working_files = get_working_files(repo)?;
+    
+    // Combine all file paths
+    let mut all_files: Vec<PathBuf> = head_files.keys()
+        .chain(index_files.keys())
+        .chain(working_files.keys())
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    all_files.sort();
+    
+    // Categorize files
+    let mut report = StatusReport::default();
+    let mut new_in_index = Vec::new();
+    
+    for file_path in all_files {
+        let head_id = head_files.get(&file_path).cloned();
+        let index_id = index_files.get(&file_path).cloned();
+        let working_id = working_files.get(&file_path).cloned();
+        let path_str = file_path.to_string_lossy().to_string();
         
-        // Debug: show what's actually in the index
-        println!("DEBUG: Current index contents:");
-        for (path, object_id) in &index_files {
-            println!("  '{}' -> {}", path.display(), &object_id[..8]);
+        // Check if file is untracked (not in HEAD or index)
+        if head_id.is_none() && index_id.is_none() && working_id.is_some() {
+            report.untracked.push(path_str);
+            continue;
         }
         
-        // Combine all file paths
-        let mut all_files: HashSet<PathBuf> = HashSet::new();
-        all_files.extend(head_files.keys().cloned());
-        all_files.extend(index_files.keys().cloned());
-        all_files.extend(working_files.keys().cloned());
-        
-        // Categorize files
-        let mut staged_changes = Vec::new();
-        let mut unstaged_changes = Vec::new();
-        let mut untracked_files = Vec::new();
-        
-        for file_path in all_files {
-            let head_id = head_files.get(&file_path).cloned();
-            let index_id = index_files.get(&file_path).cloned();
-            let working_id = working_files.get(&file_path).cloned();
-            
-            // Check if file is untracked (not in HEAD or index)
-            if head_id.is_none() && index_id.is_none() && working_id.is_some() {
-                untracked_files.push(file_path.to_string_lossy().to_string());
-                continue;
-            }
-            
-            // Check staged changes (index vs HEAD)
-            if index_id != head_id {
-                let status = match (&head_id, &index_id) {
-                    (None, Some(_)) => "new file",
-                    (Some(_), None) => "deleted",
-                    (Some(_), Some(_)) => "modified",
-                    (None, None) => continue, // shouldn't happen
-                };
-                staged_changes.push((file_path.to_string_lossy().to_string(), status));
-            }
-            
-            // Check unstaged changes (working vs index)
-            if working_id != index_id {
-                let status = match (&index_id, &working_id) {
-                    (Some(_), None) => "deleted",
-                    (Some(_), Some(_)) => "modified",
-                    (None, Some(_)) => continue, // untracked, already handled
-                    (None, None) => continue, // shouldn't happen
-                };
-                unstaged_changes.push((file_path.to_string_lossy().to_string(), status));
-            }
+        // Check staged changes (index vs HEAD)
+        if index_id != head_id {
+            let status = match (&head_id, &index_id) {
+                (None, Some(_)) => {
+                    new_in_index.push(path_str.clone());
+                    "new file"
+                }
+                (Some(_), None) => "deleted",
+                (Some(_), Some(_)) => "modified",
+                (None, None) => continue, // shouldn't happen
+            };
+            report.staged.push((path_str.clone(), status));
         }
         
-        // Print results
-        let has_staged = !staged_changes.is_empty();
-        let has_unstaged = !unstaged_changes.is_empty();
-        let has_untracked = !untracked_files.is_empty();
-        
-        if has_staged {
-            println!("\nChanges to be committed:");
-            println!("  (use \"rust-git rm <file>...\" to unstage)");
-            println!();
-            for (file, status) in staged_changes {
-                println!("\t{}: {}", status, file);
-            }
+        // Check unstaged changes (working vs index)
+        if working_id != index_id {
+            let status = match (&index_id, &working_id) {
+                (Some(_), None) => "deleted",
+                (Some(_), Some(_)) => "modified",
+                (None, Some(_)) => continue, // untracked, already handled
+                (None, None) => continue, // shouldn't happen
+            };
+            report.unstaged.push((path_str, status));
         }
-        
-        if has_unstaged {
-            println!("\nChanges not staged for commit:");
-            println!("  (use \"rust-git add <file>...\" to update what will be committed)");
-            println!("  (use \"rust-git checkout -- <file>...\" to discard changes in working directory)");
-            println!();
-            for (file, status) in unstaged_changes {
-                println!("\t{}: {}", status, file);
-            }
+    }
+    
+    detect_renames(&mut report.staged, &mut new_in_index, &head_files, &index_files);
+    detect_renames(&mut report.unstaged, &mut report.untracked, &index_files, &working_files);
+    
+    Ok(report)
+}
+
+/// The `## <branch>...<upstream> [ahead N, behind M]` header of short status
+pub fn branch_header(repo: &Repository) -> Result<String> {
+    let branch = match repo.head()? {
+        Head::Branch(branch) => branch,
+        Head::Detached(_) => return Ok("## HEAD (no branch)".to_string()),
+        Head::Unborn(branch) => return Ok(format!("## No commits yet on {}", branch)),
+    };
+    let Some(upstream) = repo.config.branch_upstream(&branch) else {
+        return Ok(format!("## {}", branch));
+    };
+    let upstream_name = upstream
+        .strip_prefix("refs/remotes/")
+        .or_else(|| upstream.strip_prefix("refs/heads/"))
+        .unwrap_or(&upstream);
+    let header = format!("## {}...{}", branch, upstream_name);
+    
+    let (Ok(head), Ok(upstream_id)) = (refs::get_head_commit(&repo.git_dir), refs::read_ref(&repo.git_dir, &upstream)) else {
+        return Ok(format!("{} [gone]", header));
+    };
+    let counts = match objects::ahead_behind(repo, &head, &upstream_id)? {
+        (0, 0) => return Ok(header),
+        (ahead, 0) => format!("ahead {}", ahead),
+        (0, behind) => format!("behind {}", behind),
+        (ahead, behind) => format!("ahead {}, behind {}", ahead, behind),
+    };
+    Ok(format!("{} [{}]", header, counts))
+}
+
+// The one-letter code short status uses for a change
+fn short_code(status: &str) -> char {
+    match status {
+        "new file" => 'A',
+        "deleted" => 'D',
+        "renamed" => 'R',
+        _ => 'M',
+    }
+}
+
+/// Render a report in short format: `XY <path>` per changed path, where X is the staged and
+/// Y the unstaged change, followed by `?? <path>` for each untracked file
+pub fn short_format(report: &StatusReport) -> Vec<String> {
+    let mut codes: BTreeMap<&str, (char, char)> = BTreeMap::new();
+    for (path, status) in &report.staged {
+        codes.entry(path).or_insert((' ', ' ')).0 = short_code(status);
+    }
+    for (path, status) in &report.unstaged {
+        codes.entry(path).or_insert((' ', ' ')).1 = short_code(status);
+    }
+    
+    let mut lines: Vec<String> = codes
+        .into_iter()
+        .map(|(path, (staged, unstaged))| format!("{}{} {}", staged, unstaged, path))
+        .collect();
+    lines.extend(report.untracked.iter().map(|path| format!("?? {}", path)));
+    lines
+}
+
+pub fn execute(location: &Location, short: bool, branch: bool) -> Result<()> {
+    let repo = location.open()?;
+    
+    if short {
+        if branch {
+            println!("{}", branch_header(&repo)?);
         }
-        
-        if has_untracked {
-            println!("\nUntracked files:");
-            println!("  (use \"rust-git add <file>...\" to include in what will be committed)");
-            println!();
-            for file in untracked_files {
-                println!("\t{}", file);
-            }
-            if !has_staged && !has_unstaged {
-                println!("\nnothing added to commit but untracked files present (use \"rust-git add\" to track)");
-            }
+        for line in short_format(&run(&repo)?) {
+            println!("{}", line);
         }
-        
-        if !has_staged && !has_unstaged && !has_untracked {
-            println!("\nnothing to commit, working tree clean");
+        return Ok(());
+    }
+    
+    match repo.head()? {
+        Head::Branch(branch) => info!("On branch {}", branch),
+        Head::Detached(commit_id) => info!("HEAD detached at {}", &commit_id[..7.min(commit_id.len())]),
+        Head::Unborn(branch) => info!("On branch {}\n\nNo commits yet", branch),
+    }
+    
+    let report = run(&repo)?;
+    
+    // Print results
+    let has_staged = !report.staged.is_empty();
+    let has_unstaged = !report.unstaged.is_empty();
+    let has_untracked = !report.untracked.is_empty();
+    
+    if has_staged {
+        info!("\nChanges to be committed:");
+        info!("  (use \"rust-git reset HEAD <file>...\" to unstage)");
+        info!("");
+        for (file, status) in report.staged {
+            info!("\t{}: {}", status, file);
+        }
+    }
+    
+    if has_unstaged {
+        info!("\nChanges not staged for commit:");
+        info!("  (use \"rust-git add <file>...\" to update what will be committed)");
+        info!("  (use \"rust-git checkout -- <file>...\" to discard changes in working directory)");
+        info!("");
+        for (file, status) in report.unstaged {
+            info!("\t{}: {}", status, file);
+        }
+    }
+    
+    if has_untracked {
+        info!("\nUntracked files:");
+        info!("  (use \"rust-git add <file>...\" to include in what will be committed)");
+        info!("");
+        for file in report.untracked {
+            info!("\t{}", file);
+        }
+        if !has_staged && !has_unstaged {
+            info!("\nnothing added to commit but untracked files present (use \"rust-git add\" to track)");
         }
     }
+    
+    if !has_staged && !has_unstaged && !has_untracked {
+        info!("\nnothing to commit, working tree clean");
+    }
     Ok(())
 }
 
 fn get_head_files(repo: &Repository) -> Result<HashMap<PathBuf, String>> {
     let mut files = HashMap::new();
     
-    if let Ok(head_commit_id) = refs::get_head_commit(&repo.git_dir) {
-        if let Ok((commit_type, commit_data)) = objects::read_object(&repo.git_dir.join("objects"), &head_commit_id) {
-            if commit_type == "commit" {
-                let commit_content = String::from_utf8_lossy(&commit_data);
-                let lines: Vec<&str> = commit_content.lines().collect();
-                if !lines.is_empty() && lines[0].starts_with("tree ") {
-                    let tree_id = lines[0].strip_prefix("tree ").unwrap().trim();
-                    
-                    if let Ok((tree_type, tree_data)) = objects::read_object(&repo.git_dir.join("objects"), tree_id) {
-                        if tree_type == "tree" {
-                            parse_tree_entries(&tree_data, &mut files)?;
-                        }
-                    }
+    let store = repo.objects.as_ref();
+    if let Some(head_commit_id) = refs::head_commit(&repo.git_dir)? {
+        if let Ok(Object::Commit(commit)) = objects::parse_object(store, &head_commit_id) {
+            if let Ok(entries) = objects::flatten_tree(store, &commit.tree) {
+                for entry in entries {
+                    files.insert(crate::repository::normalize_path(&entry.name), entry.oid);
                 }
             }
         }
@@ -163,16 +270,13 @@ fn get_working_files(repo: &Repository) -> Result<HashMap<PathBuf, String>> {
     
     for entry in WalkDir::new(&repo.path)
         .into_iter()
+        // Skip the .git directory, or a linked working tree's .git file
+        .filter_entry(|e| !is_git_dir_name(e.file_name()))
         .filter_map(Result::ok)
-        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.file_type().is_file() || e.file_type().is_symlink())
     {
         let path = entry.path();
         
-        // Skip .git directory
-        if path.to_string_lossy().contains("/.git/") {
-            continue;
-        }
-        
         let relative_path = if path.starts_with(&repo.path) {
             path.strip_prefix(&repo.path)?
         } else {
@@ -181,8 +285,9 @@ fn get_working_files(repo: &Repository) -> Result<HashMap<PathBuf, String>> {
         
         // Use the unified normalize_path function
         let normalized_path = crate::repository::normalize_path(relative_path);
-        let content = fs::read(path)?;
-        let object_id = objects::hash_object(&content, "blob");
+        // Hashed the way `add` stores it, so a symlink is compared by its target
+        let content = index::read_worktree_content(path, &repo.config)?;
+        let object_id = objects::hash_object(&content, ObjectType::Blob);
         
         files.insert(normalized_path, object_id);
     }
@@ -190,40 +295,87 @@ fn get_working_files(repo: &Repository) -> Result<HashMap<PathBuf, String>> {
     Ok(files)
 }
 
-fn parse_tree_entries(tree_data: &[u8], files: &mut HashMap<PathBuf, String>) -> Result<()> {
-    let mut cursor = 0;
-    
-    while cursor < tree_data.len() {
-        // Find space after mode
-        if let Some(space_idx) = tree_data[cursor..].iter().position(|&b| b == b' ') {
-            let space_idx = space_idx + cursor;
-            
-            // Find null after filename
-            if let Some(null_idx) = tree_data[space_idx + 1..].iter().position(|&b| b == 0) {
-                let null_idx = null_idx + space_idx + 1;
-                let filename = std::str::from_utf8(&tree_data[space_idx + 1..null_idx])?;
-                
-                // Get SHA1 hash (next 20 bytes)
-                let sha1_start = null_idx + 1;
-                let sha1_end = sha1_start + 20;
-                if sha1_end <= tree_data.len() {
-                    let sha1_bytes = &tree_data[sha1_start..sha1_end];
-                    let sha1_hex = hex::encode(sha1_bytes);
-                    
-                    // Normalize the path before inserting
-                    let normalized_path = crate::repository::normalize_path(&PathBuf::from(filename));
-                    files.insert(normalized_path, sha1_hex);
-                    cursor = sha1_end;
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-        } else {
-            break;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_status_compares_symlinks_by_target() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let link_path = repo.path.join("link");
+        fs::write(repo.path.join("target.txt"), "target\n")?;
+        std::os::unix::fs::symlink("target.txt", &link_path)?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("Add link"), ..Default::default() })?;
+
+        assert_eq!(run(&repo)?, StatusReport::default());
+
+        // Pointing the link elsewhere is a modification, even though the new target is missing
+        fs::remove_file(&link_path)?;
+        std::os::unix::fs::symlink("missing.txt", &link_path)?;
+        assert_eq!(run(&repo)?.unstaged, vec![("link".to_string(), "modified")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_reports_renames() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        fs::write(repo.path.join("old.txt"), "same content\n")?;
+        fs::write(repo.path.join("other.txt"), "other\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("Add files"), ..Default::default() })?;
+
+        // Renamed on disk only
+        fs::rename(repo.path.join("old.txt"), repo.path.join("new.txt"))?;
+        let report = run(&repo)?;
+        assert_eq!(report.unstaged, vec![("old.txt -> new.txt".to_string(), "renamed")]);
+        assert!(report.untracked.is_empty());
+        assert!(report.staged.is_empty());
+
+        // Staged rename
+        repo.index.remove_path(&repo.path, "old.txt")?;
+        repo.index.add_directory(&repo.path, repo.path.join("new.txt"), repo.objects.as_ref(), &repo.config)?;
+        let report = run(&repo)?;
+        assert_eq!(report.staged, vec![("old.txt -> new.txt".to_string(), "renamed")]);
+        assert!(report.unstaged.is_empty());
+
+        fs::write(repo.path.join("other.txt"), "changed\n")?;
+        fs::write(repo.path.join("untracked.txt"), "new\n")?;
+        assert_eq!(short_format(&run(&repo)?), vec!["R  old.txt -> new.txt", " M other.txt", "?? untracked.txt"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_header_counts_ahead_of_upstream() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        assert_eq!(branch_header(&repo)?, "## master");
+
+        let config_path = repo.git_dir.join("config");
+        let config = fs::read_to_string(&config_path)?;
+        fs::write(&config_path, format!("{}[branch \"master\"]\n\tremote = origin\n\tmerge = refs/heads/master\n", config))?;
+        repo = Repository::open(&repo.path)?;
+        assert_eq!(branch_header(&repo)?, "## master...origin/master [gone]");
+
+        let tracked = refs::get_head_commit(&repo.git_dir)?;
+        refs::update_ref(&repo.git_dir, "refs/remotes/origin/master", &tracked)?;
+        assert_eq!(branch_header(&repo)?, "## master...origin/master");
+
+        for (name, message) in [("one.txt", "One"), ("two.txt", "Two")] {
+            fs::write(repo.path.join(name), message)?;
+            repo.index.add_directory(&repo.path, repo.path.join(name), repo.objects.as_ref(), &repo.config)?;
+            commit::run(&mut repo, &CommitOptions { message: Some(message), ..Default::default() })?;
         }
+        assert_eq!(branch_header(&repo)?, "## master...origin/master [ahead 2]");
+
+        Ok(())
     }
-    
-    Ok(())
-} 
\ No newline at end of file
+}
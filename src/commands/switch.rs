@@ -0,0 +1,99 @@
+use anyhow::Result;
+use std::path::Path;
+use crate::commands::checkout;
+use crate::repository::{refs, Repository, normalize_path};
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location, branch_name: &str, create: bool, start_point: Option<&str>, force: bool) -> Result<()> {
+    let mut repo = location.open()?;
+    let current_dir = location.pathspec_dir(&repo);
+    run(&mut repo, &current_dir, branch_name, create, start_point, force)
+}
+
+/// Switch to `branch_name`, or with `create` create it at `start_point` (HEAD by default)
+/// and switch to it. Unlike `checkout`, this never restores files: a name that is not a
+/// branch but names a file (relative to `current_dir`) is refused. Uncommitted changes the
+/// switch would overwrite stop it unless `force` is given.
+pub fn run(repo: &mut Repository, current_dir: &Path, branch_name: &str, create: bool, start_point: Option<&str>, force: bool) -> Result<()> {
+    let is_branch = refs::read_ref(&repo.git_dir, &format!("refs/heads/{}", branch_name)).is_ok();
+    if !create && !is_branch {
+        let relative_path = current_dir.join(branch_name).strip_prefix(&repo.path).map(normalize_path);
+        let is_file = relative_path.is_ok_and(|path| {
+            repo.index.get_entries().contains_key(&path) || repo.path.join(&path).symlink_metadata().is_ok()
+        });
+        if is_file {
+            anyhow::bail!("'{}' is a file, not a branch; use 'rust-git restore {}' to restore it", branch_name, branch_name);
+        }
+        anyhow::bail!("invalid reference: {}", branch_name);
+    }
+    checkout::run(repo, branch_name, create, start_point, force)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn commit_file(repo: &mut Repository, content: &str) -> Result<String> {
+        fs::write(repo.path.join("file.txt"), content)?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        Ok(commit::run(repo, &CommitOptions { message: Some(content.trim()), ..Default::default() })?.unwrap())
+    }
+
+    #[test]
+    fn test_switch_and_create() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let current_dir = repo.path.clone();
+        let first = commit_file(&mut repo, "v1\n")?;
+
+        run(&mut repo, &current_dir, "feature", true, None, false)?;
+        assert_eq!(repo.current_branch()?, "feature");
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/feature")?, first);
+        commit_file(&mut repo, "v2\n")?;
+
+        run(&mut repo, &current_dir, "master", false, None, false)?;
+        assert_eq!(repo.current_branch()?, "master");
+        assert_eq!(fs::read_to_string(repo.path.join("file.txt"))?, "v1\n");
+
+        // -c refuses a branch that already exists
+        assert!(run(&mut repo, &current_dir, "feature", true, None, false).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_refuses_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let current_dir = repo.path.clone();
+        commit_file(&mut repo, "v1\n")?;
+
+        let error = run(&mut repo, &current_dir, "file.txt", false, None, false).unwrap_err();
+        assert!(error.to_string().contains("is a file, not a branch"), "{}", error);
+        let error = run(&mut repo, &current_dir, "no-such-branch", false, None, false).unwrap_err();
+        assert_eq!(error.to_string(), "invalid reference: no-such-branch");
+        assert_eq!(repo.current_branch()?, "master");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_to_packed_branch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let current_dir = repo.path.clone();
+        let first = commit_file(&mut repo, "v1\n")?;
+        commit_file(&mut repo, "v2\n")?;
+
+        // A branch that exists only in packed-refs
+        fs::write(repo.common_dir.join("packed-refs"), format!("{} refs/heads/old\n", first))?;
+        run(&mut repo, &current_dir, "old", false, None, false)?;
+        assert_eq!(repo.current_branch()?, "old");
+        assert_eq!(fs::read_to_string(repo.path.join("file.txt"))?, "v1\n");
+
+        Ok(())
+    }
+}
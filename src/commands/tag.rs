@@ -0,0 +1,143 @@
+use anyhow::Result;
+use crate::repository::{Repository, objects, refs};
+use crate::repository::ignore::wildmatch;
+use crate::repository::objects::{Object, ObjectType, Signature};
+use crate::repository::location::Location;
+use crate::info;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TagOptions<'a> {
+    /// Tag to create or delete, or the pattern to list with `list`
+    pub name: Option<&'a str>,
+    /// Object to tag (defaults to HEAD)
+    pub object: Option<&'a str>,
+    /// Make an annotated tag carrying this message
+    pub message: Option<&'a str>,
+    pub delete: bool,
+    pub list: bool,
+    /// When listing, show the first line of each annotated tag's message
+    pub show_messages: bool,
+}
+
+pub fn execute(location: &Location, options: &TagOptions) -> Result<()> {
+    let repo = location.open()?;
+
+    match options.name {
+        Some(name) if options.delete => {
+            let object_id = delete(&repo, name)?;
+            info!("Deleted tag '{}' (was {})", name, &object_id[..7]);
+        }
+        Some(name) if !options.list => {
+            create(&repo, name, options.object, options.message)?;
+        }
+        pattern => {
+            let tags = list(&repo, pattern)?;
+            for (name, tag) in tags {
+                match tag.filter(|_| options.show_messages) {
+                    Some(tag) => println!("{:<15} {}", name, tag.message.lines().next().unwrap_or("")),
+                    None => println!("{}", name),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Point `refs/tags/<name>` at `object` (a revision, defaulting to HEAD).
+///
+/// With a `message`, a tag object naming the current user as tagger is written and the ref
+/// points at it instead. Returns the id the new ref holds.
+pub fn create(repo: &Repository, name: &str, object: Option<&str>, message: Option<&str>) -> Result<String> {
+    let ref_name = format!("refs/tags/{}", name);
+    if refs::resolve_ref_path(&repo.git_dir, &ref_name).exists() {
+        anyhow::bail!("tag '{}' already exists", name);
+    }
+    let target = refs::resolve_revision(&repo.git_dir, object.unwrap_or("HEAD"))?;
+
+    let object_id = match message {
+        Some(message) => {
+            let tagger = Signature::now(&repo.config.user_identity());
+            let data = format!(
+                "object {}\ntype {}\ntag {}\ntagger {}\n\n{}\n",
+                target,
+                repo.read_object(&target)?.object_type,
+                name,
+                tagger,
+                message.trim_end_matches('\n'),
+            );
+            repo.objects.write(ObjectType::Tag, data.as_bytes())?
+        }
+        None => target,
+    };
+    refs::update_ref(&repo.git_dir, &ref_name, &object_id)?;
+
+    Ok(object_id)
+}
+
+/// Remove `refs/tags/<name>`, returning the id it held. An annotated tag's object is left in
+/// the database, unreachable, for `gc`/`prune` to collect.
+pub fn delete(repo: &Repository, name: &str) -> Result<String> {
+    let ref_name = format!("refs/tags/{}", name);
+    let Ok(object_id) = refs::read_ref(&repo.git_dir, &ref_name) else {
+        anyhow::bail!("tag '{}' not found", name);
+    };
+    refs::delete_ref(&repo.git_dir, &ref_name, Some(&object_id))?;
+    Ok(object_id)
+}
+
+/// The tags whose names match the glob `pattern` (all tags without one), sorted by name,
+/// each with its parsed tag object when it is annotated.
+pub fn list(repo: &Repository, pattern: Option<&str>) -> Result<Vec<(String, Option<objects::Tag>)>> {
+    let mut tags = Vec::new();
+    for (name, object_id) in refs::list_tags(&repo.git_dir)? {
+        if pattern.is_some_and(|pattern| !wildmatch(pattern, &name)) {
+            continue;
+        }
+        let tag = match repo.read_object(&object_id)?.parse()? {
+            Object::Tag(tag) => Some(tag),
+            _ => None,
+        };
+        tags.push((name, tag));
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_create_delete_and_list_tags() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let head = refs::get_head_commit(&repo.git_dir)?;
+
+        create(&repo, "v1.0", None, None)?;
+        let annotated = create(&repo, "v1.1", None, Some("Release 1.1\n\nDetails"))?;
+        create(&repo, "v1.2", Some(&head), None)?;
+        create(&repo, "v2.0", None, None)?;
+        assert!(create(&repo, "v1.0", None, None).is_err());
+
+        assert_eq!(delete(&repo, "v1.2")?, head);
+        assert!(delete(&repo, "v1.2").is_err());
+
+        let listed = list(&repo, Some("v1.*"))?;
+        let names: Vec<&str> = listed.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["v1.0", "v1.1"]);
+        assert!(listed[0].1.is_none());
+        let tag = listed[1].1.as_ref().unwrap();
+        assert_eq!(tag.oid, annotated);
+        assert_eq!(tag.object, head);
+        assert_eq!(tag.object_type, ObjectType::Commit);
+        assert_eq!(tag.message, "Release 1.1\n\nDetails");
+        assert!(tag.tagger.is_some());
+
+        // The deleted tag's name is gone while the annotated tag object stays readable
+        assert_eq!(list(&repo, None)?.len(), 3);
+        assert!(repo.read_object(&annotated).is_ok());
+
+        Ok(())
+    }
+}
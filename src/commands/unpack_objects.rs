@@ -0,0 +1,33 @@
+use anyhow::Result;
+use std::fs;
+use std::io::{self, Read};
+use crate::repository::{Repository, pack};
+use crate::repository::location::Location;
+use crate::info;
+
+pub fn execute(location: &Location, file: Option<&str>) -> Result<()> {
+    let repo = location.open()?;
+
+    let stream = match file {
+        Some(file) => fs::read(file)?,
+        None => {
+            let mut stream = Vec::new();
+            io::stdin().read_to_end(&mut stream)?;
+            stream
+        }
+    };
+    let object_ids = run(&repo, &stream)?;
+
+    info!("Unpacked {} object(s)", object_ids.len());
+
+    Ok(())
+}
+
+/// Store every object in the pack stream `stream` (git's format, deltas included) as a loose
+/// object of `repo`, returning their ids in stream order
+pub fn run(repo: &Repository, stream: &[u8]) -> Result<Vec<String>> {
+    pack::read_pack_stream(&repo.common_dir.join("objects"), stream)?
+        .into_iter()
+        .map(|(object_type, data)| repo.objects.write(object_type, &data))
+        .collect()
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use crate::repository::{Repository, refs};
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location, ref_name: &str, new_value: Option<&str>, old_value: Option<&str>, delete: bool) -> Result<()> {
+    let repo = location.open()?;
+
+    if delete {
+        // `-d <ref> [<oldvalue>]`: the second value is the expected old one
+        return delete_ref(&repo, ref_name, new_value.or(old_value));
+    }
+    let Some(new_value) = new_value else {
+        anyhow::bail!("a new value is required unless -d is given");
+    };
+    update(&repo, ref_name, new_value, old_value)
+}
+
+/// Point `ref_name` at the object `new_value` names. With `old_value`, the ref must currently
+/// point at the object it names, or not exist when it is all zeros.
+pub fn update(repo: &Repository, ref_name: &str, new_value: &str, old_value: Option<&str>) -> Result<()> {
+    let new_id = refs::resolve_revision(&repo.git_dir, new_value)?;
+    let old_id = old_value.map(|old_value| resolve_old_value(repo, old_value)).transpose()?;
+    refs::update_ref_checked(&repo.git_dir, ref_name, &new_id, old_id.as_deref())
+}
+
+/// Delete `ref_name`, checking it still points at `old_value` when given
+pub fn delete_ref(repo: &Repository, ref_name: &str, old_value: Option<&str>) -> Result<()> {
+    let old_id = old_value.map(|old_value| resolve_old_value(repo, old_value)).transpose()?;
+    refs::delete_ref(&repo.git_dir, ref_name, old_id.as_deref())
+}
+
+// An expected old value may name an object that is gone, so a full id is taken as is
+fn resolve_old_value(repo: &Repository, old_value: &str) -> Result<String> {
+    if old_value.is_empty() {
+        return Ok(refs::NULL_ID.to_string());
+    }
+    if old_value.len() == 40 && old_value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(old_value.to_lowercase());
+    }
+    refs::resolve_revision(&repo.git_dir, old_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::objects::{self, Signature};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn second_commit(repo: &Repository) -> Result<(String, String)> {
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let tree_id = objects::read_commit_tree(repo.objects.as_ref(), &head)?;
+        let signature = Signature::now("Test User <test@example.com>");
+        let next = objects::write_commit(repo.objects.as_ref(), &tree_id, &[&head], "Next", &signature, &signature)?;
+        Ok((head, next))
+    }
+
+    #[test]
+    fn test_update_ref_with_and_without_old_value() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let (head, next) = second_commit(&repo)?;
+
+        update(&repo, "refs/heads/topic", &head, Some(refs::NULL_ID))?;
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/topic")?, head);
+        update(&repo, "refs/heads/topic", &next, Some(&head))?;
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/topic")?, next);
+
+        // HEAD is updated through the branch it names
+        update(&repo, "HEAD", &next, None)?;
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/master")?, next);
+        assert!(fs::read_to_string(repo.git_dir.join("HEAD"))?.starts_with("ref: "));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_ref_refuses_wrong_old_value() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let (head, next) = second_commit(&repo)?;
+
+        let err = update(&repo, "refs/heads/master", &next, Some(&next)).unwrap_err();
+        assert!(err.to_string().contains("expected"), "{}", err);
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/master")?, head);
+        assert!(update(&repo, "refs/heads/master", &next, Some(refs::NULL_ID)).is_err());
+        assert!(!repo.git_dir.join("refs/heads/master.lock").exists());
+
+        // A held lock makes the update fail rather than race
+        fs::write(repo.git_dir.join("refs/heads/master.lock"), "")?;
+        assert!(update(&repo, "refs/heads/master", &next, Some(&head)).is_err());
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/master")?, head);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_ref_loose_and_packed() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        let (head, next) = second_commit(&repo)?;
+        update(&repo, "refs/heads/topic", &next, None)?;
+
+        assert!(delete_ref(&repo, "topic", Some(&head)).is_err());
+        delete_ref(&repo, "topic", Some(&next))?;
+        assert!(refs::read_ref(&repo.git_dir, "refs/heads/topic").is_err());
+
+        fs::write(
+            repo.git_dir.join("packed-refs"),
+            format!("# pack-refs with: peeled fully-peeled sorted\n{} refs/tags/old\n^{}\n{} refs/tags/kept\n", next, head, head),
+        )?;
+        delete_ref(&repo, "refs/tags/old", None)?;
+        assert_eq!(refs::read_packed_refs(&repo.git_dir)?, vec![("refs/tags/kept".to_string(), head)]);
+        assert!(delete_ref(&repo, "refs/tags/old", None).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::path::Path;
+use crate::repository::pack;
+
+pub fn execute(idx_path: &str) -> Result<()> {
+    let objects = pack::verify_pack(Path::new(idx_path))?;
+
+    for object in &objects {
+        match &object.delta {
+            Some((base_oid, depth)) => println!(
+                "{} {} {} {} {} {}",
+                object.oid, object.object_type, object.size, object.offset, depth, base_oid
+            ),
+            None => println!("{} {} {} {}", object.oid, object.object_type, object.size, object.offset),
+        }
+    }
+    println!("{}: ok", Path::new(idx_path).with_extension("pack").display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::repository::Repository;
+    use crate::repository::objects::ObjectType;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn find_idx(repo: &Repository) -> Result<PathBuf> {
+        let pack_dir = repo.git_dir.join("objects/pack");
+        fs::read_dir(pack_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| path.extension() == Some("idx".as_ref()))
+            .ok_or_else(|| anyhow::anyhow!("no pack index written"))
+    }
+
+    #[test]
+    fn test_verify_pack_lists_committed_objects() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(temp_dir.path())?;
+        let objects_dir = repo.git_dir.join("objects");
+        let content = "line\n".repeat(200);
+        fs::write(repo.path.join("a.txt"), &content)?;
+        fs::write(repo.path.join("b.txt"), format!("{}extra\n", content))?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        commit::run(&mut repo, &CommitOptions { message: Some("Add files"), ..Default::default() })?;
+
+        // Record every object's type before packing removes the loose copies
+        let expected: HashMap<String, ObjectType> = pack::reachable_objects(&objects_dir, &repo.ref_tips()?)?
+            .into_iter()
+            .map(|oid| {
+                let object_type = repo.read_object(&oid)?.object_type;
+                Ok((oid, object_type))
+            })
+            .collect::<Result<_>>()?;
+
+        repo.repack()?;
+        let listed = pack::verify_pack(&find_idx(&repo)?)?;
+
+        let listed_types: HashMap<String, ObjectType> = listed
+            .iter()
+            .map(|object| (object.oid.clone(), object.object_type))
+            .collect();
+        for (oid, object_type) in &expected {
+            assert_eq!(listed_types.get(oid), Some(object_type), "object {} missing or mistyped", oid);
+        }
+        // The two similar blobs are stored as a delta
+        assert!(listed.iter().any(|object| object.delta.is_some()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_pack_rejects_checksum_mismatch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(temp_dir.path())?;
+        repo.repack()?;
+        let idx_path = find_idx(&repo)?;
+        assert!(pack::verify_pack(&idx_path).is_ok());
+
+        let pack_path = idx_path.with_extension("pack");
+        let mut data = fs::read(&pack_path)?;
+        data[12] ^= 0xff;
+        fs::write(&pack_path, data)?;
+        let err = pack::verify_pack(&idx_path).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{}", err);
+
+        Ok(())
+    }
+}
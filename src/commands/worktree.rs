@@ -0,0 +1,166 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::commands::checkout;
+use crate::repository::{Repository, refs};
+use crate::repository::location::Location;
+use crate::info;
+
+pub fn execute(location: &Location, path: &str, branch: &str) -> Result<()> {
+    let repo = location.open()?;
+    let worktree = add(&repo, &location.current_dir.join(path), branch)?;
+
+    info!("Preparing worktree (checking out '{}') at '{}'", branch, worktree.path.display());
+
+    Ok(())
+}
+
+/// Add a linked working tree at `path`, which must be missing or empty, with `branch` checked
+/// out, and return it opened.
+///
+/// Its git dir is `worktrees/<name>` in the common dir, named after the last part of `path`,
+/// and holds its own HEAD and index; a `commondir` file there points back at the common dir,
+/// so objects, refs and config are shared. `path/.git` is a file naming that git dir. A branch
+/// already checked out in another working tree is refused.
+pub fn add(repo: &Repository, path: &Path, branch: &str) -> Result<Repository> {
+    let ref_name = format!("refs/heads/{}", branch);
+    if refs::read_ref(&repo.git_dir, &ref_name).is_err() {
+        anyhow::bail!("invalid reference: {}", branch);
+    }
+    if let Some(checked_out) = checked_out_at(repo, &ref_name)? {
+        anyhow::bail!("'{}' is already checked out at '{}'", branch, checked_out.display());
+    }
+    if path.read_dir().is_ok_and(|mut entries| entries.next().is_some()) {
+        anyhow::bail!("'{}' already exists", path.display());
+    }
+    fs::create_dir_all(path)?;
+    let path = fs::canonicalize(path)?;
+
+    let git_dir = new_git_dir(repo, &path)?;
+    fs::write(git_dir.join("HEAD"), format!("ref: {}\n", ref_name))?;
+    fs::write(git_dir.join("commondir"), "../..\n")?;
+    fs::write(git_dir.join("gitdir"), format!("{}\n", path.join(".git").display()))?;
+    fs::write(path.join(".git"), format!("gitdir: {}\n", git_dir.display()))?;
+
+    let mut worktree = Repository::open(&path)?;
+    checkout::check_out_branch(&mut worktree, branch)?;
+    Ok(worktree)
+}
+
+// Create the git dir of a working tree at `path` under `worktrees/`, named after the last part
+// of `path` with a number added when that name is taken, as git does
+fn new_git_dir(repo: &Repository, path: &Path) -> Result<PathBuf> {
+    let worktrees_dir = repo.common_dir.join("worktrees");
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut git_dir = worktrees_dir.join(&name);
+    let mut suffix = 1;
+    while git_dir.exists() {
+        git_dir = worktrees_dir.join(format!("{}{}", name, suffix));
+        suffix += 1;
+    }
+    fs::create_dir_all(&git_dir)?;
+    Ok(git_dir)
+}
+
+// Where `ref_name` is checked out: the path of the working tree, main or linked, whose HEAD
+// names it. A bare repository's HEAD has no working tree and does not count.
+fn checked_out_at(repo: &Repository, ref_name: &str) -> Result<Option<PathBuf>> {
+    let head = format!("ref: {}", ref_name);
+    for git_dir in repo.worktree_git_dirs() {
+        if !fs::read_to_string(git_dir.join("HEAD")).is_ok_and(|content| content.trim() == head) {
+            continue;
+        }
+        let worktree = Repository::open_git_dir(&git_dir, None)?;
+        if !worktree.is_bare() {
+            return Ok(Some(worktree.path));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::repository::objects::{self, ObjectType, Signature};
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_commit_in_second_worktree() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let main_path = temp_dir.path().join("main");
+        fs::create_dir(&main_path)?;
+        let mut repo = Repository::init(&main_path)?;
+        fs::write(repo.path.join("file.txt"), "base\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let base = commit::run(&mut repo, &CommitOptions { message: Some("base"), ..Default::default() })?.unwrap();
+        refs::create_branch(&repo.git_dir, "feature", &base)?;
+
+        let linked_path = temp_dir.path().join("feature-tree");
+        let mut linked = add(&repo, &linked_path, "feature")?;
+        assert!(linked_path.join(".git").is_file());
+        assert_eq!(linked.git_dir, repo.git_dir.join("worktrees/feature-tree"));
+        assert_eq!(linked.common_dir, repo.git_dir);
+        assert_eq!(linked.current_branch()?, "feature");
+        assert_eq!(fs::read_to_string(linked_path.join("file.txt"))?, "base\n");
+
+        // A commit in the linked working tree moves its branch in the shared refs
+        fs::write(linked_path.join("file.txt"), "feature\n")?;
+        linked.index.add_directory(&linked.path, &linked.path, linked.objects.as_ref(), &linked.config)?;
+        let feature = commit::run(&mut linked, &CommitOptions { message: Some("feature"), ..Default::default() })?.unwrap();
+
+        let repo = Repository::open(&main_path)?;
+        assert_eq!(refs::read_ref(&repo.git_dir, "refs/heads/feature")?, feature);
+        assert_eq!(refs::get_head_commit(&repo.git_dir)?, base);
+        assert_eq!(repo.current_branch()?, "master");
+        assert_eq!(fs::read_to_string(main_path.join("file.txt"))?, "base\n");
+        assert!(repo.read_object(&feature).is_ok());
+
+        // Opening from inside the linked working tree follows its `.git` file
+        fs::create_dir(linked_path.join("sub"))?;
+        let reopened = Repository::open(linked_path.join("sub"))?;
+        assert_eq!(reopened.path, fs::canonicalize(&linked_path)?);
+        assert_eq!(refs::get_head_commit(&reopened.git_dir)?, feature);
+        assert_eq!(reopened.index.get_entries().len(), 1);
+
+        // Each branch can be checked out in one working tree only
+        assert!(add(&repo, &temp_dir.path().join("again"), "feature").is_err());
+        assert!(add(&repo, &temp_dir.path().join("again"), "master").is_err());
+        assert!(add(&repo, &temp_dir.path().join("again"), "missing").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_keeps_what_linked_worktrees_use() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let main_path = temp_dir.path().join("main");
+        fs::create_dir(&main_path)?;
+        let mut repo = Repository::init(&main_path)?;
+        fs::write(repo.path.join("file.txt"), "base\n")?;
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        let base = commit::run(&mut repo, &CommitOptions { message: Some("base"), ..Default::default() })?.unwrap();
+        refs::create_branch(&repo.git_dir, "feature", &base)?;
+        let linked_path = temp_dir.path().join("feature-tree");
+        let mut linked = add(&repo, &linked_path, "feature")?;
+
+        // A commit only the linked working tree's detached HEAD names, and a blob only its
+        // index stages
+        let signature = Signature::now("Test <test@example.com>");
+        let tree = objects::read_commit_tree(linked.objects.as_ref(), &base)?;
+        let detached = objects::write_commit(linked.objects.as_ref(), &tree, &[&base], "detached", &signature, &signature)?;
+        fs::write(linked.git_dir.join("HEAD"), format!("{}\n", detached))?;
+        fs::write(linked_path.join("staged.txt"), "staged\n")?;
+        linked.index.add_directory(&linked.path, &linked.path, linked.objects.as_ref(), &linked.config)?;
+        linked.index.save(linked.git_dir.join("index"))?;
+        let staged = objects::hash_object(b"staged\n", ObjectType::Blob);
+
+        let repo = Repository::open(&main_path)?;
+        repo.gc(SystemTime::now() + Duration::from_secs(60))?;
+        assert!(repo.read_object(&detached).is_ok());
+        assert!(repo.read_object(&staged).is_ok());
+
+        Ok(())
+    }
+}
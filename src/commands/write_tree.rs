@@ -0,0 +1,11 @@
+use anyhow::Result;
+use crate::repository::objects;
+use crate::repository::location::Location;
+
+pub fn execute(location: &Location) -> Result<()> {
+    let repo = location.open()?;
+
+    println!("{}", objects::write_tree(&repo)?);
+
+    Ok(())
+}
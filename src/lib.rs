@@ -1,2 +1,3 @@
 pub mod commands;
-pub mod repository; 
\ No newline at end of file
+pub mod output;
+pub mod repository;
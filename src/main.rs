@@ -1,10 +1,30 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use rust_git::commands;
+use rust_git::output::{self, Verbosity};
+use rust_git::repository::location::Location;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Path to the repository's git dir, instead of searching from the current directory
+    /// (also GIT_DIR)
+    #[arg(long, global = true, value_name = "PATH")]
+    git_dir: Option<PathBuf>,
+
+    /// Path to the working tree, instead of the directory holding the git dir (also GIT_WORK_TREE)
+    #[arg(long, global = true, value_name = "PATH")]
+    work_tree: Option<PathBuf>,
+
+    /// Print only what was asked for, without informational messages
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print details of the work done, such as each object packed
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -12,13 +32,24 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize a new Git repository
-    Init,
+    Init {
+        /// Directory to create the repository in (defaults to the current directory)
+        directory: Option<String>,
+
+        /// Create a bare repository without a working tree
+        #[arg(long)]
+        bare: bool,
+    },
     
     /// Add file contents to the index
     Add {
         /// Files to add
-        #[arg(required = true)]
+        #[arg(required_unless_present = "patch")]
         paths: Vec<String>,
+        
+        /// Choose which hunks of the changes to tracked files to stage
+        #[arg(short = 'p', long)]
+        patch: bool,
     },
     
     /// Remove files from the working tree and index
@@ -28,11 +59,77 @@ enum Commands {
         paths: Vec<String>,
     },
     
+    /// Unstage paths, restoring their index entries from HEAD
+    Reset {
+        /// Paths to unstage, optionally preceded by HEAD
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+    
+    /// Restore working tree files, or with --staged the index
+    Restore {
+        /// Paths to restore
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Restore the index from HEAD instead of the working tree from the index
+        #[arg(long)]
+        staged: bool,
+
+        /// Commit to restore from
+        #[arg(long)]
+        source: Option<String>,
+    },
+    
+    /// Remove untracked files from the working tree
+    Clean {
+        /// Actually remove the files instead of listing what would be removed
+        #[arg(short = 'f', long)]
+        force: bool,
+
+        /// Also remove untracked directories
+        #[arg(short = 'd')]
+        directories: bool,
+
+        /// Don't use the ignore rules from .gitignore
+        #[arg(short = 'x')]
+        ignored: bool,
+    },
+    
     /// Record changes to the repository
     Commit {
-        /// Commit message
-        #[arg(short = 'm', long, required = true)]
-        message: String,
+        /// Commit message; without it, the message is written in $EDITOR
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+        
+        /// Replace the tip of the current branch with a new commit
+        #[arg(long)]
+        amend: bool,
+        
+        /// When amending, make the current user the author of the new commit
+        #[arg(long, requires = "amend")]
+        reset_author: bool,
+        
+        /// Stage modified and deleted tracked files before committing
+        #[arg(short = 'a', long)]
+        all: bool,
+        
+        /// Add a Signed-off-by trailer for the configured user at the end of the message
+        #[arg(short = 's', long)]
+        signoff: bool,
+        
+        /// Do not run the pre-commit and commit-msg hooks
+        #[arg(short = 'n', long)]
+        no_verify: bool,
+        
+        /// Conclude a merge with its prepared message instead of opening the editor
+        #[arg(long, conflicts_with = "message")]
+        no_edit: bool,
+        
+        /// Make a commit to be folded into the given one by a later rebase, with the message
+        /// "fixup! <its subject>"
+        #[arg(long, value_name = "COMMIT", conflicts_with_all = ["message", "amend"])]
+        fixup: Option<String>,
     },
     
     /// List, create, or delete branches
@@ -40,9 +137,47 @@ enum Commands {
         /// Branch name
         name: Option<String>,
         
-        /// Delete the branch
+        /// Delete the branch, which must be merged into HEAD
         #[arg(short, long)]
         delete: bool,
+        
+        /// Delete the branch even if it is not merged
+        #[arg(short = 'D', long = "force")]
+        force_delete: bool,
+        
+        /// Print the name of the current branch (nothing when HEAD is detached)
+        #[arg(long, conflicts_with_all = ["name", "delete", "force_delete"])]
+        show_current: bool,
+        
+        /// Make the branch (default: the current one) track <remote>/<branch> or a local branch
+        #[arg(short = 'u', long, value_name = "UPSTREAM", conflicts_with_all = ["delete", "force_delete"])]
+        set_upstream_to: Option<String>,
+    },
+    
+    /// Create, list or delete tags
+    Tag {
+        /// Tag to create or delete, or with -l a pattern of tags to list
+        name: Option<String>,
+
+        /// Object the new tag points at (defaults to HEAD)
+        #[arg(requires = "name")]
+        object: Option<String>,
+
+        /// Create an annotated tag with this message
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+
+        /// Delete the tag
+        #[arg(short = 'd', long, requires = "name", conflicts_with_all = ["list", "message"])]
+        delete: bool,
+
+        /// List tags, optionally only those matching the pattern
+        #[arg(short = 'l', long)]
+        list: bool,
+
+        /// Show the first line of each annotated tag's message when listing
+        #[arg(short = 'n')]
+        show_messages: bool,
     },
     
     /// Switch branches or restore working tree files
@@ -53,18 +188,190 @@ enum Commands {
         /// Create a new branch and switch to it
         #[arg(short = 'b', long = "branch", required = false)]
         create_branch: bool,
+
+        /// Commit the new branch starts at (defaults to HEAD)
+        #[arg(requires = "create_branch")]
+        start_point: Option<String>,
+
+        /// Switch even if uncommitted changes would be overwritten, discarding them
+        #[arg(short, long)]
+        force: bool,
+    },
+    
+    /// Switch branches
+    Switch {
+        /// Branch to switch to or create
+        branch: String,
+
+        /// Create a new branch and switch to it
+        #[arg(short = 'c', long = "create")]
+        create: bool,
+
+        /// Commit the new branch starts at (defaults to HEAD)
+        #[arg(requires = "create")]
+        start_point: Option<String>,
+
+        /// Switch even if uncommitted changes would be overwritten, discarding them
+        #[arg(short, long)]
+        force: bool,
+    },
+    
+    /// List commits reachable from a revision, most recent first
+    RevList {
+        /// Revision to list from, or <a>..<b> for the commits in b but not in a
+        revision: String,
+
+        /// Print only the number of commits
+        #[arg(long)]
+        count: bool,
     },
     
     /// Join two or more development histories together
     Merge {
-        /// Branch to merge
-        branch: String,
+        /// Branches, tags or commits to merge; more than one makes an octopus merge
+        #[arg(required = true)]
+        branches: Vec<String>,
+        
+        /// Create a merge commit even when the merge could be resolved as a fast-forward
+        #[arg(long)]
+        no_ff: bool,
+    },
+    
+    /// Replay the current branch's own commits on top of another branch
+    Rebase {
+        /// Branch, tag or commit to replay the commits onto
+        #[arg(required_unless_present_any = ["resume", "abort"])]
+        upstream: Option<String>,
+        
+        /// Resume a rebase stopped on a conflict, once the resolution is staged
+        #[arg(long = "continue", conflicts_with_all = ["upstream", "abort"])]
+        resume: bool,
+        
+        /// Stop the rebase in progress and restore the branch as it was
+        #[arg(long, conflicts_with = "upstream")]
+        abort: bool,
+    },
+    
+    /// Find the best common ancestor of two commits
+    MergeBase {
+        /// First commit (branch name, ref or SHA)
+        commit1: String,
+        /// Second commit (branch name, ref or SHA)
+        commit2: String,
+    },
+    
+    /// List refs, loose and packed, with the objects they point at
+    ShowRef {
+        /// Only branches (combine with --tags for both)
+        #[arg(long)]
+        heads: bool,
+
+        /// Only tags (combine with --heads for both)
+        #[arg(long)]
+        tags: bool,
+
+        /// Also show the object each annotated tag points at, as <tag>^{}
+        #[arg(short = 'd', long)]
+        dereference: bool,
+    },
+
+    /// Point a ref at an object, optionally only if it still points at an expected old value
+    UpdateRef {
+        /// Delete the ref; a second value is then the expected old value
+        #[arg(short = 'd')]
+        delete: bool,
+
+        /// The ref to update, e.g. refs/heads/master
+        #[arg(name = "ref")]
+        ref_name: String,
+
+        /// The object to point the ref at
+        new_value: Option<String>,
+
+        /// Only update if the ref points here (all zeros: only if it does not exist)
+        #[arg(conflicts_with = "delete")]
+        old_value: Option<String>,
+    },
+    
+    /// Give an object a human readable name based on the nearest reachable tag
+    Describe {
+        /// Show the abbreviated commit name when no tag is reachable
+        #[arg(long)]
+        always: bool,
+    },
+    
+    /// Show which commit last changed each line of a file
+    Blame {
+        /// The file to annotate
+        file: String,
+    },
+    
+    /// Show commit logs
+    Log {
+        /// Print each commit with a template such as "%h %s" (placeholders: %H %h %P %an %ae %ad %ar %s %b %n %%)
+        #[arg(long)]
+        format: Option<String>,
+        
+        /// Only show commits authored at or after a date, such as "2024-01-31" or "2 weeks ago"
+        #[arg(long, visible_alias = "after", value_name = "DATE")]
+        since: Option<String>,
+        
+        /// Only show commits authored at or before a date
+        #[arg(long, visible_alias = "before", value_name = "DATE")]
+        until: Option<String>,
+        
+        /// Show at most this many commits
+        #[arg(short = 'n', long, value_name = "NUMBER")]
+        max_count: Option<usize>,
+        
+        /// Show dates relative to now, such as "2 hours ago"
+        #[arg(long)]
+        relative_date: bool,
+        
+        /// Only show commits that changed these paths (given after `--`)
+        #[arg(last = true)]
+        paths: Vec<String>,
+    },
+    
+    /// Show the files that differ between two commits or trees
+    DiffTree {
+        /// The commit or tree to compare from
+        commit_a: String,
+        
+        /// The commit or tree to compare to
+        commit_b: String,
+        
+        /// Also show each change as a patch
+        #[arg(short = 'p', long = "patch")]
+        patch: bool,
     },
     
     /// Download objects and refs from another repository
     Fetch {
         /// The remote to fetch from (e.g., "origin")
         remote_name: String,
+        
+        /// Limit fetching to the last <depth> commits of each branch
+        #[arg(long)]
+        depth: Option<usize>,
+    },
+    
+    /// Clone a served repository into a new directory and check out its default branch
+    Clone {
+        /// URL of the repository's bundle endpoint (e.g., "http://127.0.0.1:3000/repo.bundle")
+        url: String,
+        
+        /// Directory to clone into (defaults to the name at the end of the URL, plus `.git`
+        /// for a bare clone)
+        directory: Option<String>,
+
+        /// Make a bare repository whose branches are the remote's, ready to be served
+        #[arg(long)]
+        bare: bool,
+
+        /// Like --bare, and record the remote as a mirror
+        #[arg(long)]
+        mirror: bool,
     },
     
     /// Fetch from and integrate with another repository (currently only works for the current branch)
@@ -77,28 +384,184 @@ enum Commands {
     Push {
         /// The remote to push to (e.g., "origin") or a raw URL
         remote: String,
+
+        /// Branches to push, as <src> or <src>:<dst> (default: every branch)
+        refspecs: Vec<String>,
+
+        /// Overwrite remote branches even when the update is not a fast-forward
+        #[arg(short, long)]
+        force: bool,
     },
 
+    /// Show repository configuration
+    Config {
+        /// List every setting as name=value
+        #[arg(short = 'l', long, required_unless_present = "get_regexp")]
+        list: bool,
+        
+        /// Show the settings whose name matches a regular expression
+        #[arg(long, value_name = "PATTERN", conflicts_with = "list")]
+        get_regexp: Option<String>,
+    },
+    
     /// Manage set of tracked repositories
     Remote {
         #[command(subcommand)]
         command: RemoteCommands,
     },
 
+    /// Manage reflogs, the records of where refs used to point
+    Reflog {
+        #[command(subcommand)]
+        command: ReflogCommands,
+    },
+
+    /// Manage multiple working trees
+    Worktree {
+        #[command(subcommand)]
+        command: WorktreeCommands,
+    },
+
     /// Pretty-print Git objects
     CatFile {
         /// The object to display
-        #[arg(name = "object")]
-        object_hash: String,
+        #[arg(name = "object", required_unless_present_any = ["batch_check", "batch"])]
+        object_hash: Option<String>,
+
+        /// Pretty-print the object's content (the default)
+        #[arg(short = 'p', group = "mode")]
+        pretty: bool,
+
+        /// Show the object's type
+        #[arg(short = 't', group = "mode")]
+        show_type: bool,
+
+        /// Show the object's size
+        #[arg(short = 's', group = "mode")]
+        size: bool,
+
+        /// With -t or -s, report the type and size the object's header states, even for a type that is not known
+        #[arg(long)]
+        allow_unknown_type: bool,
+
+        /// Print the id, type and size of each object named on standard input
+        #[arg(long, conflicts_with_all = ["object", "mode"])]
+        batch_check: bool,
+
+        /// Like --batch-check, but follow each line with the object's raw content
+        #[arg(long, conflicts_with_all = ["object", "mode", "batch_check"])]
+        batch: bool,
+    },
+
+    /// Compute object ID and optionally create an object from a file
+    HashObject {
+        /// File to hash
+        #[arg(required_unless_present = "stdin")]
+        file: Option<String>,
+
+        /// Type of object to create
+        #[arg(short = 't', default_value = "blob")]
+        object_type: String,
+
+        /// Actually write the object into the object database
+        #[arg(short = 'w')]
+        write: bool,
+
+        /// Read the object from standard input instead of a file
+        #[arg(long, conflicts_with = "file")]
+        stdin: bool,
+    },
+
+    /// Create a tree object from the current index
+    WriteTree,
+
+    /// List the contents of a tree object
+    LsTree {
+        /// The tree, or a commit or tag leading to one
+        tree_ish: String,
+        
+        /// Recurse into subtrees
+        #[arg(short = 'r')]
+        recursive: bool,
+        
+        /// Show only subtrees
+        #[arg(short = 'd')]
+        trees_only: bool,
+    },
+
+    /// Read tree information into the index
+    ReadTree {
+        /// The tree to read (full or abbreviated id)
+        tree: String,
     },
 
     /// Show the working tree status
     /// Garbage collect unnecessary files and optimize the repository
-    Gc,
+    Gc {
+        /// Compress harder and search more delta bases, trading time for a smaller pack
+        #[arg(long)]
+        aggressive: bool,
+
+        /// Only delete unreachable objects older than this (e.g. "2.weeks.ago", "now")
+        #[arg(long)]
+        prune: Option<String>,
+
+        /// Only collect when there are more loose objects than gc.auto or more packs than
+        /// gc.autoPackLimit
+        #[arg(long)]
+        auto: bool,
+    },
     /// Repack loose objects into a pack file
     Repack,
+    /// Count loose objects and their disk usage; with -v, also packs, packed objects and garbage
+    CountObjects,
+    /// Check that every reachable object exists, parses and has the type it is referred to as
+    Fsck,
+    /// Validate a packed archive and list the objects it holds
+    VerifyPack {
+        /// Path to the pack's .idx file
+        idx: String,
+    },
+    /// Write a pack stream of the objects listed on standard input to standard output
+    PackObjects,
+    /// Store the objects of a pack stream as loose objects
+    UnpackObjects {
+        /// Pack file to read instead of standard input
+        file: Option<String>,
+    },
+    /// Prune unreachable loose objects from the object database
+    Prune {
+        /// Only prune objects older than this (e.g. "2.weeks.ago", "now")
+        #[arg(long)]
+        expire: Option<String>,
+    },
     /// Show the working tree status
-    Status,
+    Status {
+        /// Give the output in the short format
+        #[arg(short, long)]
+        short: bool,
+
+        /// Show the branch and its upstream in the short format
+        #[arg(short, long)]
+        branch: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReflogCommands {
+    /// Drop reflog entries older than the expiry time
+    Expire {
+        /// Drop entries older than this (default: gc.reflogExpire, or 90.days.ago)
+        #[arg(long)]
+        expire: Option<String>,
+
+        /// Expire the reflogs of every ref
+        #[arg(long, conflicts_with = "refs")]
+        all: bool,
+
+        /// Refs whose reflogs to expire
+        refs: Vec<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -112,27 +575,127 @@ enum RemoteCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum WorktreeCommands {
+    /// Create a working tree at <path> with <branch> checked out
+    Add {
+        /// Directory of the new working tree
+        path: String,
+        /// Branch to check out in it
+        branch: String,
+    },
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    if cli.quiet {
+        output::set_verbosity(Verbosity::Quiet);
+    } else if cli.verbose {
+        output::set_verbosity(Verbosity::Verbose);
+    }
+    let location = Location::from_env(cli.git_dir.as_deref(), cli.work_tree.as_deref())?;
 
     match &cli.command {
-        Commands::Init => commands::init::execute()?,
-        Commands::Add { paths } => commands::add::execute(paths)?,
-        Commands::Rm { paths } => commands::rm::execute(paths)?,
-        Commands::Commit { message } => commands::commit::execute(message)?,
-        Commands::Branch { name, delete } => commands::branch::execute(name.as_deref(), *delete)?,
-        Commands::Checkout { branch, create_branch } => commands::checkout::execute(branch, *create_branch)?,
-        Commands::Merge { branch } => commands::merge::execute(branch)?,
-        Commands::Fetch { remote_name } => commands::fetch::execute(remote_name)?,
-        Commands::Pull { remote } => commands::pull::execute(remote)?,
-        Commands::Push { remote } => commands::push::execute(remote)?,
+        Commands::Init { directory, bare } => commands::init::execute(&location, directory.as_deref(), *bare)?,
+        Commands::Add { paths, patch } => commands::add::execute(&location, paths, *patch)?,
+        Commands::Rm { paths } => commands::rm::execute(&location, paths)?,
+        Commands::Reset { paths } => commands::reset::execute(&location, paths)?,
+        Commands::Restore { paths, staged, source } => commands::restore::execute(&location, paths, &commands::restore::RestoreOptions {
+            staged: *staged,
+            source: source.as_deref(),
+        })?,
+        Commands::Clean { force, directories, ignored } => commands::clean::execute(&location, &commands::clean::CleanOptions {
+            force: *force,
+            directories: *directories,
+            ignored: *ignored,
+        })?,
+        Commands::Commit { message, amend, reset_author, all, signoff, no_verify, no_edit, fixup } => commands::commit::execute(&location, &commands::commit::CommitOptions {
+            message: message.as_deref(),
+            amend: *amend,
+            reset_author: *reset_author,
+            all: *all,
+            signoff: *signoff,
+            no_verify: *no_verify,
+            no_edit: *no_edit,
+            fixup: fixup.as_deref(),
+        })?,
+        Commands::Branch { name, delete, force_delete, show_current, set_upstream_to } => {
+            commands::branch::execute(&location, name.as_deref(), *delete, *force_delete, *show_current, set_upstream_to.as_deref())?
+        }
+        Commands::Tag { name, object, message, delete, list, show_messages } => commands::tag::execute(&location, &commands::tag::TagOptions {
+            name: name.as_deref(),
+            object: object.as_deref(),
+            message: message.as_deref(),
+            delete: *delete,
+            list: *list,
+            show_messages: *show_messages,
+        })?,
+        Commands::Checkout { branch, create_branch, start_point, force } => commands::checkout::execute(&location, branch, *create_branch, start_point.as_deref(), *force)?,
+        Commands::Switch { branch, create, start_point, force } => commands::switch::execute(&location, branch, *create, start_point.as_deref(), *force)?,
+        Commands::RevList { revision, count } => commands::rev_list::execute(&location, revision, *count)?,
+        Commands::Merge { branches, no_ff } => commands::merge::execute(&location, branches, *no_ff)?,
+        Commands::Rebase { upstream, resume, abort } => commands::rebase::execute(&location, upstream.as_deref(), *resume, *abort)?,
+        Commands::MergeBase { commit1, commit2 } => commands::merge_base::execute(&location, commit1, commit2)?,
+        Commands::ShowRef { heads, tags, dereference } => commands::show_ref::execute(&location, &commands::show_ref::ShowRefOptions {
+            heads: *heads,
+            tags: *tags,
+            dereference: *dereference,
+        })?,
+        Commands::UpdateRef { delete, ref_name, new_value, old_value } => {
+            commands::update_ref::execute(&location, ref_name, new_value.as_deref(), old_value.as_deref(), *delete)?
+        }
+        Commands::Describe { always } => commands::describe::execute(&location, *always)?,
+        Commands::Blame { file } => commands::blame::execute(&location, file)?,
+        Commands::Log { paths, format, since, until, max_count, relative_date } => {
+            commands::log::execute(&location, paths, format.as_deref(), since.as_deref(), until.as_deref(), *max_count, *relative_date)?
+        }
+        Commands::DiffTree { commit_a, commit_b, patch } => commands::diff_tree::execute(&location, commit_a, commit_b, *patch)?,
+        Commands::Fetch { remote_name, depth } => commands::fetch::execute(&location, remote_name, *depth)?,
+        Commands::Clone { url, directory, bare, mirror } => commands::clone::execute(&location, url, directory.as_deref(), &commands::clone::CloneOptions {
+            bare: *bare,
+            mirror: *mirror,
+        })?,
+        Commands::Pull { remote } => commands::pull::execute(&location, remote)?,
+        Commands::Push { remote, refspecs, force } => commands::push::execute(&location, remote, refspecs, *force)?,
+        Commands::Config { list, get_regexp } => commands::config::execute(&location, *list, get_regexp.as_deref())?,
         Commands::Remote { command } => match command {
-            RemoteCommands::Add { name, url } => commands::remote::execute("add", name, url)?,
+            RemoteCommands::Add { name, url } => commands::remote::execute(&location, "add", name, url)?,
+        },
+        Commands::Reflog { command } => match command {
+            ReflogCommands::Expire { expire, all, refs } => commands::reflog::execute_expire(&location, expire.as_deref(), *all, refs)?,
+        },
+        Commands::Worktree { command } => match command {
+            WorktreeCommands::Add { path, branch } => commands::worktree::execute(&location, path, branch)?,
         },
-        Commands::CatFile { object_hash } => commands::cat_file::execute(object_hash)?,
-        Commands::Gc => commands::gc::execute()?,
-        Commands::Repack => commands::repack::execute()?,
-        Commands::Status => commands::status::execute()?,
+        Commands::CatFile { object_hash, pretty: _, show_type, size, allow_unknown_type, batch_check, batch } => {
+            let mode = match (show_type, size) {
+                (true, _) => commands::cat_file::CatFileMode::Type,
+                (_, true) => commands::cat_file::CatFileMode::Size,
+                _ => commands::cat_file::CatFileMode::Pretty,
+            };
+            commands::cat_file::execute(&location, object_hash.as_deref(), mode, *allow_unknown_type, *batch_check, *batch)?
+        }
+        Commands::HashObject { file, object_type, write, stdin } => commands::hash_object::execute(
+            &location,
+            file.as_deref(),
+            *stdin,
+            &commands::hash_object::HashObjectOptions { object_type, write: *write },
+        )?,
+        Commands::WriteTree => commands::write_tree::execute(&location)?,
+        Commands::LsTree { tree_ish, recursive, trees_only } => commands::ls_tree::execute(&location, tree_ish, &commands::ls_tree::LsTreeOptions {
+            recursive: *recursive,
+            trees_only: *trees_only,
+        })?,
+        Commands::ReadTree { tree } => commands::read_tree::execute(&location, tree)?,
+        Commands::Gc { aggressive, prune, auto } => commands::gc::execute(&location, *aggressive, prune.as_deref(), *auto)?,
+        Commands::Repack => commands::repack::execute(&location)?,
+        Commands::CountObjects => commands::count_objects::execute(&location)?,
+        Commands::Fsck => commands::fsck::execute(&location)?,
+        Commands::VerifyPack { idx } => commands::verify_pack::execute(idx)?,
+        Commands::PackObjects => commands::pack_objects::execute(&location)?,
+        Commands::UnpackObjects { file } => commands::unpack_objects::execute(&location, file.as_deref())?,
+        Commands::Prune { expire } => commands::prune::execute(&location, expire.as_deref())?,
+        Commands::Status { short, branch } => commands::status::execute(&location, *short, *branch)?,
     }
     
     Ok(())
@@ -0,0 +1,77 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+/// How much the commands print
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only what was asked for, such as the output of `log` or `cat-file`, and errors
+    Quiet,
+    /// Also informational messages, such as "Added 2 file(s) to the index"
+    Normal,
+    /// Also details of the work done, such as each object packed
+    Verbose,
+}
+
+/// Quiet for the online judge, which checks output exactly; normal otherwise
+const DEFAULT_VERBOSITY: Verbosity = if cfg!(feature = "online_judge") { Verbosity::Quiet } else { Verbosity::Normal };
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        DEFAULT_VERBOSITY
+    }
+}
+
+thread_local! {
+    // Per thread, so commands running side by side, as tests do, keep their own settings
+    static VERBOSITY: Cell<Verbosity> = const { Cell::new(DEFAULT_VERBOSITY) };
+    // Where `print` collects lines instead of writing them to stdout while `capture` runs
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Set the verbosity of the commands run on this thread
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.set(verbosity);
+}
+
+/// The verbosity of the commands run on this thread
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get()
+}
+
+/// Print a line when the verbosity is at least `level`. Used through `info!` and `verbose!`.
+pub fn print(level: Verbosity, args: fmt::Arguments) {
+    if verbosity() < level {
+        return;
+    }
+    CAPTURED.with_borrow_mut(|captured| match captured {
+        Some(buffer) => {
+            buffer.push_str(&args.to_string());
+            buffer.push('\n');
+        }
+        None => println!("{}", args),
+    });
+}
+
+/// Run `f`, collecting the lines it prints through `print` instead of writing them to stdout
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let previous = CAPTURED.replace(Some(String::new()));
+    let result = f();
+    let captured = CAPTURED.replace(previous).unwrap_or_default();
+    (result, captured)
+}
+
+/// Print an informational message, like `println!`, unless the verbosity is quiet
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::output::print($crate::output::Verbosity::Normal, format_args!($($arg)*))
+    };
+}
+
+/// Print a detail of the work done, like `println!`, only when the verbosity is verbose
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        $crate::output::print($crate::output::Verbosity::Verbose, format_args!($($arg)*))
+    };
+}
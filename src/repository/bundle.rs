@@ -1,163 +1,554 @@
 use anyhow::{Context, Result};
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use std::collections::{BTreeSet, HashSet, VecDeque};
 use std::fs;
-use std::io::Write;
-use tar::Builder;
-use walkdir::WalkDir;
+use std::io::{Read, Write};
+use std::path::Path;
 
-use super::{objects, refs, Repository};
+use super::{objects, pack, refs, Repository};
+use super::objects::ObjectType;
 
-/// Creates a bundle file from the repository.
+/// First line of a bundle in git's v2 format
+const BUNDLE_SIGNATURE: &str = "# v2 git bundle\n";
+
+/// Returned by `unbundle` when a push would move a branch to a commit that does not
+/// descend from the branch's current tip.
+#[derive(Debug, thiserror::Error)]
+#[error("non-fast-forward push to branch '{ref_name}' is not allowed")]
+pub struct NonFastForwardError {
+    pub ref_name: String,
+}
+
+/// Lists the refs a server advertises to clients as `(ref_name, commit_id)` pairs.
 ///
-/// The bundle will be a .tar.gz file containing:
-/// - All objects from the .git/objects directory.
-/// - A 'packed-refs' file with a list of all branches and their commit SHAs.
-/// - The HEAD file.
-pub fn create_bundle(repo: &Repository, writer: impl Write) -> Result<()> {
-    let git_dir = &repo.git_dir;
-    let encoder = GzEncoder::new(writer, Compression::default());
-    let mut ar = Builder::new(encoder);
+/// Currently this is every branch head under `refs/heads`.
+pub fn advertised_refs(repo: &Repository) -> Result<Vec<(String, String)>> {
+    let mut advertised = Vec::new();
+    for branch_name in refs::list_branches(&repo.git_dir)? {
+        let ref_name = format!("refs/heads/{}", branch_name);
+        if let Ok(commit_id) = refs::read_ref(&repo.git_dir, &ref_name) {
+            advertised.push((ref_name, commit_id));
+        }
+    }
+    Ok(advertised)
+}
 
-    // 1. Add all objects
-    let objects_dir = git_dir.join("objects");
-    if objects_dir.exists() {
-        ar.append_dir_all("objects", &objects_dir)
-            .context("Failed to add objects directory to bundle")?;
+/// The text of the ref advertisement: a `ref: <branch>\tHEAD` line naming the branch HEAD
+/// points at, when it is on one, then `<commit_id>\t<ref_name>` for each advertised ref.
+pub fn format_advertisement(repo: &Repository) -> Result<String> {
+    let mut advertisement = String::new();
+    if let Ok(branch) = repo.current_branch() {
+        advertisement.push_str(&format!("ref: refs/heads/{}\tHEAD\n", branch));
+    }
+    for (ref_name, commit_id) in advertised_refs(repo)? {
+        advertisement.push_str(&format!("{}\t{}\n", commit_id, ref_name));
     }
+    Ok(advertisement)
+}
 
-    // 2. Create and add packed-refs file
-    let mut packed_refs_content = String::new();
-    let branches = super::refs::list_branches(git_dir)?;
-    for branch_name in branches {
-        let ref_name = format!("refs/heads/{}", branch_name);
-        if let Ok(commit_id) = super::refs::read_ref(git_dir, &ref_name) {
-            packed_refs_content.push_str(&format!("{} {}\n", commit_id, ref_name));
+/// The branch a ref advertisement says the remote's HEAD points at, e.g. `refs/heads/main`
+pub fn advertised_head(advertisement: &str) -> Option<&str> {
+    advertisement.lines().find_map(|line| {
+        line.strip_prefix("ref: ")?
+            .strip_suffix("\tHEAD")
+            .filter(|target| target.starts_with("refs/heads/"))
+    })
+}
+
+/// Point `refs/remotes/<remote_name>/HEAD` at the remote-tracking branch for `branch_name`
+pub fn write_remote_head(git_dir: &Path, remote_name: &str, branch_name: &str) -> Result<()> {
+    let remote_head_path = refs::common_dir(git_dir).join(format!("refs/remotes/{}/HEAD", remote_name));
+    if let Some(parent) = remote_head_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(remote_head_path, format!("ref: refs/remotes/{}/{}", remote_name, branch_name))?;
+    Ok(())
+}
+
+/// Creates a bundle of the repository's branches in git's bundle v2 format.
+///
+/// The bundle is the `# v2 git bundle` signature, one `<oid> <refname>` line per branch
+/// (plus `HEAD`), a blank line and a pack stream of every object reachable from them, so
+/// `git bundle verify`, `git clone` and `git fetch` can read it as well as `unbundle`.
+pub fn create_bundle(repo: &Repository, writer: impl Write) -> Result<()> {
+    create_partial_bundle(repo, writer, &[])
+}
+
+/// Creates a bundle that omits objects the receiver already has.
+///
+/// `haves` lists commits the receiver claims to have; every object reachable
+/// from them is left out of the bundle and the commits are listed as its
+/// prerequisites. Unknown `haves` are ignored. With no `haves` this produces the
+/// same full bundle as `create_bundle`.
+pub fn create_partial_bundle(repo: &Repository, writer: impl Write, haves: &[String]) -> Result<()> {
+    create_shallow_bundle(repo, writer, haves, None)
+}
+
+/// Walk at most `depth` commits down from each tip (the tips themselves being depth 1).
+///
+/// Returns the commits within the depth and the shallow boundary among them: those whose
+/// parents are left out, either because of the depth limit or because this repository is
+/// itself shallow there.
+fn commits_within_depth(repo: &Repository, tips: &[String], depth: usize) -> (HashSet<String>, BTreeSet<String>) {
+    let already_shallow = objects::shallow_commits(&repo.git_dir);
+    let mut commits = HashSet::new();
+    let mut boundary = BTreeSet::new();
+
+    // Breadth-first, so each commit is first reached at its smallest depth
+    let mut queue: VecDeque<(String, usize)> = tips.iter().map(|tip| (tip.clone(), 1)).collect();
+    while let Some((commit_id, commit_depth)) = queue.pop_front() {
+        if !commits.insert(commit_id.clone()) {
+            continue;
+        }
+        let parents = objects::commit_parents(repo, &commit_id, &already_shallow);
+        if commit_depth >= depth && !parents.is_empty() {
+            boundary.insert(commit_id);
+        } else {
+            if already_shallow.contains(&commit_id) {
+                boundary.insert(commit_id.clone());
+            }
+            queue.extend(parents.into_iter().map(|parent| (parent, commit_depth + 1)));
         }
     }
 
-    if !packed_refs_content.is_empty() {
-        let mut header = tar::Header::new_gnu();
-        header.set_size(packed_refs_content.len() as u64);
-        header.set_cksum();
-        ar.append_data(&mut header, "packed-refs", packed_refs_content.as_bytes())
-            .context("Failed to add packed-refs to bundle")?;
+    // A commit whose parents all arrived through other branches is not a boundary after all
+    boundary.retain(|commit_id| {
+        already_shallow.contains(commit_id)
+            || objects::commit_parents(repo, commit_id, &already_shallow).iter().any(|parent| !commits.contains(parent))
+    });
+
+    (commits, boundary)
+}
+
+/// Creates a bundle like `create_partial_bundle`, optionally limited to the last `depth`
+/// commits of each branch.
+///
+/// A depth-limited bundle carries the selected commits with their trees and blobs, and lists
+/// the parents it leaves out as prerequisites, as git does for a `<rev>..<branch>` bundle.
+/// The same goes for the parents of commits that are already shallow here.
+pub fn create_shallow_bundle(repo: &Repository, writer: impl Write, haves: &[String], depth: Option<usize>) -> Result<()> {
+    write_bundle(repo, writer, &advertised_refs(repo)?, haves, depth)
+}
+
+/// Creates a full bundle carrying only `refs`, `(ref_name, commit_id)` pairs listed under
+/// those names, and the objects reachable from them, e.g. for a push of some branches.
+pub fn create_bundle_of_refs(repo: &Repository, writer: impl Write, refs: &[(String, String)]) -> Result<()> {
+    write_bundle(repo, writer, refs, &[], None)
+}
+
+// Write a bundle listing `advertised` as its refs; see `create_shallow_bundle`
+fn write_bundle(repo: &Repository, mut writer: impl Write, advertised: &[(String, String)], haves: &[String], depth: Option<usize>) -> Result<()> {
+    if depth == Some(0) {
+        anyhow::bail!("depth must be a positive number of commits");
+    }
+    let objects_dir = repo.common_dir.join("objects");
+    let tips: Vec<String> = advertised.iter().map(|(_, oid)| oid.clone()).collect();
+
+    let (commits, boundary) = commits_within_depth(repo, &tips, depth.unwrap_or(usize::MAX));
+    let trees: Vec<String> = commits
+        .iter()
+        .filter_map(|commit_id| objects::read_commit_tree(repo.objects.as_ref(), commit_id).ok())
+        .collect();
+    let mut wanted = pack::reachable_objects(&objects_dir, &trees)?;
+    wanted.extend(commits.iter().cloned());
+
+    let known_haves: Vec<String> = haves
+        .iter()
+        .filter(|oid| repo.read_object(oid).is_ok_and(|object| object.object_type == ObjectType::Commit))
+        .cloned()
+        .collect();
+    let excluded = pack::reachable_objects(&objects_dir, &known_haves)?;
+
+    let mut prerequisites: BTreeSet<String> = known_haves.into_iter().collect();
+    for commit_id in &boundary {
+        let parents = objects::read_commit(repo.objects.as_ref(), commit_id)?.parents;
+        prerequisites.extend(parents.into_iter().filter(|parent| !commits.contains(parent)));
+    }
+
+    // Header: signature, prerequisites, refs
+    let mut header = String::from(BUNDLE_SIGNATURE);
+    for oid in &prerequisites {
+        match objects::read_commit(repo.objects.as_ref(), oid) {
+            Ok(commit) => header.push_str(&format!("-{} {}\n", oid, commit.message.lines().next().unwrap_or(""))),
+            Err(_) => header.push_str(&format!("-{}\n", oid)),
+        }
+    }
+    for (ref_name, commit_id) in advertised {
+        header.push_str(&format!("{} {}\n", commit_id, ref_name));
+    }
+    if let Ok(head) = refs::get_head_commit(&repo.git_dir) {
+        if tips.contains(&head) {
+            header.push_str(&format!("{} HEAD\n", head));
+        }
     }
+    header.push('\n');
+    writer.write_all(header.as_bytes())?;
 
-    // 3. Add HEAD
-    let head_path = git_dir.join("HEAD");
-    if head_path.exists() {
-        ar.append_path_with_name(&head_path, "HEAD")
-            .context("Failed to add HEAD to bundle")?;
+    let mut missing: Vec<&String> = wanted.difference(&excluded).collect();
+    missing.sort();
+    let mut objects_to_pack = Vec::with_capacity(missing.len());
+    for oid in missing {
+        let object = repo.read_object(oid)
+            .with_context(|| format!("Failed to read object {} for bundle", oid))?;
+        objects_to_pack.push((object.object_type, object.data));
     }
+    pack::write_pack_stream(writer, &objects_to_pack)
+}
 
-    ar.finish()?;
+/// The parts of a bundle's header
+struct BundleHeader {
+    prerequisites: Vec<String>,
+    /// `(oid, refname)` in the order listed
+    refs: Vec<(String, String)>,
+}
 
-    Ok(())
+// Split a bundle into its parsed header and the pack stream after it
+fn parse_bundle(data: &[u8]) -> Result<(BundleHeader, &[u8])> {
+    let mut header = BundleHeader { prerequisites: Vec::new(), refs: Vec::new() };
+    let mut rest = data;
+    let mut first = true;
+    loop {
+        let line_end = rest.iter().position(|&b| b == b'\n').context("Truncated bundle header")?;
+        let line = std::str::from_utf8(&rest[..line_end]).context("Bundle header is not UTF-8")?;
+        rest = &rest[line_end + 1..];
+
+        if first {
+            if line != BUNDLE_SIGNATURE.trim_end() && line != "# v3 git bundle" {
+                anyhow::bail!("Not a v2 or v3 git bundle");
+            }
+            first = false;
+        } else if line.is_empty() {
+            return Ok((header, rest));
+        } else if let Some(capability) = line.strip_prefix('@') {
+            if capability != "object-format=sha1" {
+                anyhow::bail!("Unsupported bundle capability '{}'", capability);
+            }
+        } else if let Some(prerequisite) = line.strip_prefix('-') {
+            let oid = prerequisite.split(' ').next().unwrap_or_default();
+            header.prerequisites.push(oid.to_string());
+        } else {
+            let (oid, ref_name) = line.split_once(' ').context("Malformed bundle ref line")?;
+            header.refs.push((oid.to_string(), ref_name.to_string()));
+        }
+    }
+}
+
+/// Where `unbundle` records the refs a bundle carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefMapping<'a> {
+    /// Branches become remote-tracking branches `refs/remotes/<remote>/<branch>`, for a fetch
+    Remote(&'a str),
+    /// Branches update the local branches of the same name, only ever forward, for a push
+    Local,
+    /// Like `Local`, but branches may be moved anywhere, for a forced push
+    LocalForced,
+    /// Every ref is stored under its own name, replacing what is there, for a bare or
+    /// mirror clone
+    Mirror,
 }
 
-/// Extracts a bundle file into the repository.
+/// Reads a git bundle (v2, or v3 with SHA-1 objects) into the repository.
 ///
 /// This will:
-/// - Unpack all objects into the .git/objects directory.
-/// - Update refs from the 'packed-refs' file.
-/// - If `remote_name` is Some, it creates remote-tracking branches.
-/// - If `remote_name` is None, it updates local branches (e.g. for a push).
-pub fn unbundle(repo: &Repository, reader: impl std::io::Read, remote_name: Option<&str>) -> Result<()> {
+/// - Store the objects of the bundle's pack that are new here, as one new pack with its
+///   index under `objects/pack` rather than as loose objects.
+/// - Record commits whose parents are prerequisites missing here as shallow, so history
+///   walks stop at them.
+/// - Update refs as `mapping` says. For `Remote`, the remote's HEAD is pointed at the branch
+///   the bundle's HEAD matches; for `Mirror`, HEAD itself is.
+pub fn unbundle(repo: &Repository, mut reader: impl Read, mapping: RefMapping) -> Result<()> {
     let git_dir = &repo.git_dir;
-    let gz_decoder = flate2::read::GzDecoder::new(reader);
-    let mut ar = tar::Archive::new(gz_decoder);
-
-    let temp_dir = tempfile::tempdir_in(git_dir.parent().unwrap())?;
-    
-    ar.unpack(&temp_dir)?;
-
-    // 1. Copy all objects
-    let bundle_objects_path = temp_dir.path().join("objects");
-    let local_objects_path = git_dir.join("objects");
-    if bundle_objects_path.exists() {
-        for entry in WalkDir::new(bundle_objects_path.clone()) {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                let relative_path = path.strip_prefix(&bundle_objects_path)?;
-                let dest_path = local_objects_path.join(relative_path);
-                
-                if let Some(parent) = dest_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                fs::copy(path, dest_path)?;
+    let objects_dir = repo.common_dir.join("objects");
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let (header, pack_data) = parse_bundle(&data)?;
+
+    // 1. Store the objects
+    let missing_prerequisites: HashSet<&String> = header
+        .prerequisites
+        .iter()
+        .filter(|oid| !repo.objects.exists(oid))
+        .collect();
+    let mut new_boundary = Vec::new();
+    let mut received = pack::read_pack_stream(&objects_dir, pack_data)?;
+    // The pack indexes are read once for all the received objects, not once per object
+    let packed = pack::PackIndexes::load(&objects_dir);
+    received.retain(|(object_type, object_data)| {
+        let object_id = objects::hash_object(object_data, *object_type);
+        !objects::loose_object_exists(&objects_dir, &object_id) && !packed.contains(&object_id)
+    });
+    for (object_type, object_data) in &received {
+        if *object_type == ObjectType::Commit && !missing_prerequisites.is_empty() {
+            let commit = objects::parse_commit(object_data)?;
+            if commit.parents.iter().any(|parent| missing_prerequisites.contains(parent)) {
+                new_boundary.push(objects::hash_object(object_data, *object_type));
             }
         }
     }
+    pack::write_pack_of_objects(&objects_dir, received, &pack::PackOptions::default())?;
 
-    // 2. Update refs from packed-refs
-    let packed_refs_path = temp_dir.path().join("packed-refs");
-    if packed_refs_path.exists() {
-        let packed_refs_content = fs::read_to_string(packed_refs_path)?;
-        for line in packed_refs_content.lines() {
-            let parts: Vec<&str> = line.split(' ').collect();
-            if parts.len() == 2 {
-                let commit_id = parts[0];
-                let orig_ref_name = parts[1]; // e.g., "refs/heads/main"
+    // Record the boundary of a depth-limited bundle, so history walks stop there
+    if !new_boundary.is_empty() {
+        let mut shallow: BTreeSet<String> = objects::shallow_commits(git_dir).into_iter().collect();
+        shallow.extend(new_boundary);
+        let shallow_content: String = shallow.iter().map(|commit_id| format!("{}\n", commit_id)).collect();
+        fs::write(repo.common_dir.join("shallow"), shallow_content)?;
+    }
 
-                if let Some(r_name) = remote_name {
-                    // This is a FETCH operation. Create remote-tracking refs.
-                    if let Some(branch_name) = orig_ref_name.strip_prefix("refs/heads/") {
-                        let remote_ref_name = format!("refs/remotes/{}/{}", r_name, branch_name);
-                        refs::update_ref(git_dir, &remote_ref_name, commit_id)?;
-                    }
-                } else {
-                    // This is a PUSH operation. Check for fast-forward and update the ref.
-                    if orig_ref_name.starts_with("refs/heads/") {
-                        // Get the server's current commit for this branch.
-                        let server_commit_id_result = refs::read_ref(git_dir, orig_ref_name);
-                        
-                        if let Ok(server_commit_id) = server_commit_id_result {
-                            // The branch exists on the server. Check for fast-forward.
-                            if server_commit_id == commit_id {
-                                // The commits are the same, nothing to do.
-                            } else {
-                            let is_fast_forward = objects::is_ancestor(repo, &server_commit_id, commit_id)?;
-                            
-                                if is_fast_forward {
-                                    refs::update_ref(git_dir, orig_ref_name, commit_id)?;
-                                } else {
-                                anyhow::bail!(
-                                    "non-fast-forward push to branch '{}' is not allowed",
-                                    orig_ref_name
-                                );
+    // 2. Update refs
+    for (commit_id, orig_ref_name) in &header.refs {
+        let commit_id = commit_id.as_str();
+        let orig_ref_name = orig_ref_name.as_str(); // e.g., "refs/heads/main"
+        match mapping {
+            RefMapping::Remote(r_name) => {
+                // This is a FETCH operation. Create remote-tracking refs.
+                if let Some(branch_name) = orig_ref_name.strip_prefix("refs/heads/") {
+                    let remote_ref_name = format!("refs/remotes/{}/{}", r_name, branch_name);
+                    refs::update_ref(git_dir, &remote_ref_name, commit_id)?;
+                }
+            }
+            // A clone copies refs as they are; there is nothing of its own to protect
+            RefMapping::Mirror if orig_ref_name.starts_with("refs/") => refs::update_ref(git_dir, orig_ref_name, commit_id)?,
+            RefMapping::Mirror => {}
+            // A forced push overwrites the branch, whatever it held
+            RefMapping::LocalForced if orig_ref_name.starts_with("refs/heads/") => refs::update_ref(git_dir, orig_ref_name, commit_id)?,
+            RefMapping::Local if orig_ref_name.starts_with("refs/heads/") => {
+                // This is a PUSH operation. Check for fast-forward and update the ref.
+                match refs::read_ref(git_dir, orig_ref_name) {
+                    // The commits are the same, nothing to do.
+                    Ok(server_commit_id) if server_commit_id == commit_id => {}
+                    Ok(server_commit_id) => {
+                        // The branch exists on the server. Check for fast-forward.
+                        if !objects::is_ancestor(repo, &server_commit_id, commit_id)? {
+                            return Err(NonFastForwardError {
+                                ref_name: orig_ref_name.to_string(),
                             }
+                            .into());
                         }
-                        } else {
-                        // If the branch doesn't exist on the server (server_commit_id_result is Err),
-                            // it's a new branch, which is always a fast-forward. So we can update.
                         refs::update_ref(git_dir, orig_ref_name, commit_id)?;
-                        }
                     }
+                    // A new branch is always a fast-forward.
+                    Err(_) => refs::update_ref(git_dir, orig_ref_name, commit_id)?,
                 }
             }
+            RefMapping::Local | RefMapping::LocalForced => {}
         }
     }
 
-    // 3. Update the remote-tracking HEAD file during a FETCH.
-    //    We do not touch the remote's actual HEAD during a PUSH.
-    if let Some(r_name) = remote_name {
-        let head_path = temp_dir.path().join("HEAD");
-        if head_path.exists() {
-            let head_content = fs::read_to_string(head_path)?;
-            if let Some(orig_ref_name) = head_content.trim().strip_prefix("ref: ") {
-                if let Some(branch_name) = orig_ref_name.strip_prefix("refs/heads/") {
-                    let remote_head_content = format!("ref: refs/remotes/{}/{}", r_name, branch_name);
-                    let remote_head_path = git_dir.join(format!("refs/remotes/{}/HEAD", r_name));
-                    if let Some(parent) = remote_head_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    fs::write(remote_head_path, remote_head_content)?;
-                }
-            }
+    // 3. Point the remote's HEAD at the branch the bundle's HEAD matches during a FETCH, and
+    //    HEAD itself during a clone. We do not touch the remote's actual HEAD during a PUSH.
+    let head = header.refs.iter().find(|(_, ref_name)| ref_name == "HEAD");
+    let head_branch = head.and_then(|(head_id, _)| {
+        header
+            .refs
+            .iter()
+            .find(|(oid, ref_name)| oid == head_id && ref_name.starts_with("refs/heads/"))
+    });
+    if let Some(branch_name) = head_branch.and_then(|(_, ref_name)| ref_name.strip_prefix("refs/heads/")) {
+        match mapping {
+            RefMapping::Remote(r_name) => write_remote_head(git_dir, r_name, branch_name)?,
+            RefMapping::Mirror => fs::write(git_dir.join("HEAD"), format!("ref: refs/heads/{}\n", branch_name))?,
+            RefMapping::Local | RefMapping::LocalForced => {}
         }
     }
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::objects::Signature;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_shallow_bundle_keeps_last_commits() -> Result<()> {
+        let source_dir = tempdir()?;
+        let source = Repository::init(source_dir.path())?;
+        let signature = Signature::now("Test User <test@example.com>");
+
+        // Five commits, each with its own tree and blob
+        let mut parent = refs::get_head_commit(&source.git_dir)?;
+        let mut history = Vec::new();
+        for i in 0..5 {
+            let blob_id = objects::write_blob(source.objects.as_ref(), format!("version {}\n", i).as_bytes())?;
+            let mut tree = b"100644 file.txt\0".to_vec();
+            tree.extend(hex::decode(&blob_id)?);
+            let tree_id = source.objects.write(ObjectType::Tree, &tree)?;
+            parent = objects::write_commit(source.objects.as_ref(), &tree_id, &[&parent], &format!("Commit {}", i), &signature, &signature)?;
+            history.push((parent.clone(), tree_id, blob_id));
+        }
+        refs::update_ref(&source.git_dir, "refs/heads/master", &parent)?;
+
+        let mut buffer = Vec::new();
+        create_shallow_bundle(&source, &mut buffer, &[], Some(2))?;
+
+        let target_dir = tempdir()?;
+        let target = Repository::init(target_dir.path())?;
+        unbundle(&target, buffer.as_slice(), RefMapping::Remote("origin"))?;
+
+        let has = |oid: &str| target.read_object(oid).is_ok();
+        for (commit_id, tree_id, blob_id) in &history[..3] {
+            assert!(!has(commit_id) && !has(tree_id) && !has(blob_id));
+        }
+        for (commit_id, tree_id, blob_id) in &history[3..] {
+            assert!(has(commit_id) && has(tree_id) && has(blob_id));
+        }
+
+        let boundary = &history[3].0;
+        assert_eq!(fs::read_to_string(target.git_dir.join("shallow"))?, format!("{}\n", boundary));
+        assert_eq!(refs::read_ref(&target.git_dir, "refs/remotes/origin/master")?, history[4].0);
+
+        // History walks end at the boundary
+        assert!(objects::read_commit_parents(&target, boundary).is_empty());
+        assert_eq!(objects::ancestors(&target, &history[4].0).len(), 2);
+        assert!(!objects::is_ancestor(&target, &history[2].0, &history[4].0)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbundle_writes_one_pack() -> Result<()> {
+        let source_dir = tempdir()?;
+        let source = Repository::init(source_dir.path())?;
+        let signature = Signature::now("Test User <test@example.com>");
+        let mut tip = refs::get_head_commit(&source.git_dir)?;
+        for i in 0..100 {
+            let blob_id = objects::write_blob(source.objects.as_ref(), format!("version {}\n", i).as_bytes())?;
+            let mut tree = b"100644 file.txt\0".to_vec();
+            tree.extend(hex::decode(&blob_id)?);
+            let tree_id = source.objects.write(ObjectType::Tree, &tree)?;
+            tip = objects::write_commit(source.objects.as_ref(), &tree_id, &[&tip], &format!("Commit {}", i), &signature, &signature)?;
+        }
+        refs::update_ref(&source.git_dir, "refs/heads/master", &tip)?;
+        let mut buffer = Vec::new();
+        create_bundle(&source, &mut buffer)?;
+
+        let target_dir = tempdir()?;
+        let target = Repository::init(target_dir.path())?;
+        let objects_dir = target.common_dir.join("objects");
+        let loose_before = loose_object_dirs(&objects_dir)?;
+        unbundle(&target, buffer.as_slice(), RefMapping::Remote("origin"))?;
+
+        // No new loose objects, just one pack and its index
+        assert_eq!(loose_object_dirs(&objects_dir)?, loose_before);
+        let mut pack_files: Vec<String> = fs::read_dir(objects_dir.join("pack"))?
+            .map(|entry| Ok(entry?.path().extension().unwrap_or_default().to_string_lossy().to_string()))
+            .collect::<Result<_>>()?;
+        pack_files.sort();
+        assert_eq!(pack_files, ["idx", "pack"]);
+
+        assert_eq!(refs::read_ref(&target.git_dir, "refs/remotes/origin/master")?, tip);
+        assert_eq!(objects::ancestors(&target, &tip).len(), 101);
+        let tree_id = objects::read_commit_tree(target.objects.as_ref(), &tip)?;
+        let entry = objects::find_tree_entry(target.objects.as_ref(), &tree_id, std::path::Path::new("file.txt"))?.unwrap();
+        assert_eq!(target.read_object(&entry.oid)?.data, b"version 99\n");
+
+        Ok(())
+    }
+
+    // The fan-out directories holding loose objects
+    fn loose_object_dirs(objects_dir: &std::path::Path) -> Result<Vec<String>> {
+        let mut dirs = Vec::new();
+        for entry in fs::read_dir(objects_dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.len() == 2 {
+                dirs.push(name);
+            }
+        }
+        dirs.sort();
+        Ok(dirs)
+    }
+
+    fn git_available() -> bool {
+        std::process::Command::new("git").arg("--version").output().is_ok_and(|output| output.status.success())
+    }
+
+    fn git(dir: &std::path::Path, args: &[&str]) -> Result<String> {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=Test User", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!("git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    #[test]
+    fn test_bundle_is_git_v2_format() -> Result<()> {
+        let source_dir = tempdir()?;
+        let source = Repository::init(source_dir.path())?;
+        let objects_dir = source.git_dir.join("objects");
+        let signature = Signature::now("Test User <test@example.com>");
+        let blob_id = objects::write_blob(source.objects.as_ref(), b"hello\n")?;
+        let mut tree = b"100644 hello.txt\0".to_vec();
+        tree.extend(hex::decode(&blob_id)?);
+        let tree_id = source.objects.write(ObjectType::Tree, &tree)?;
+        let root = refs::get_head_commit(&source.git_dir)?;
+        let tip = objects::write_commit(source.objects.as_ref(), &tree_id, &[&root], "Add hello", &signature, &signature)?;
+        refs::update_ref(&source.git_dir, "refs/heads/master", &tip)?;
+        refs::update_ref(&source.git_dir, "refs/heads/topic", &root)?;
+
+        let mut buffer = Vec::new();
+        create_bundle(&source, &mut buffer)?;
+        let expected_header = format!("# v2 git bundle\n{tip} refs/heads/master\n{root} refs/heads/topic\n{tip} HEAD\n\nPACK");
+        assert!(buffer.starts_with(expected_header.as_bytes()));
+
+        if git_available() {
+            let check_dir = tempdir()?;
+            let bundle_path = check_dir.path().join("repo.bundle");
+            fs::write(&bundle_path, &buffer)?;
+            git(check_dir.path(), &["init", "-q", "checker"])?;
+            git(&check_dir.path().join("checker"), &["bundle", "verify", bundle_path.to_str().unwrap()])?;
+        }
+
+        let target_dir = tempdir()?;
+        let target = Repository::init(target_dir.path())?;
+        unbundle(&target, buffer.as_slice(), RefMapping::Remote("origin"))?;
+        assert_eq!(refs::read_ref(&target.git_dir, "refs/remotes/origin/master")?, tip);
+        assert_eq!(refs::read_ref(&target.git_dir, "refs/remotes/origin/topic")?, root);
+        assert_eq!(fs::read_to_string(target.git_dir.join("refs/remotes/origin/HEAD"))?, "ref: refs/remotes/origin/master");
+        assert_eq!(target.read_object(&blob_id)?.data, b"hello\n");
+        assert!(!target.git_dir.join("shallow").exists());
+
+        // A receiver that has the tip already is sent only the prerequisite line and no objects
+        let mut partial = Vec::new();
+        create_partial_bundle(&source, &mut partial, std::slice::from_ref(&tip))?;
+        assert!(String::from_utf8_lossy(&partial).contains(&format!("-{} Add hello\n", tip)));
+        let (_, pack_data) = parse_bundle(&partial)?;
+        assert!(pack::read_pack_stream(&objects_dir, pack_data)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unbundle_reads_bundle_made_by_git() -> Result<()> {
+        if !git_available() {
+            return Ok(());
+        }
+        let git_dir = tempdir()?;
+        let work = git_dir.path();
+        git(work, &["init", "-q", "-b", "main"])?;
+        let lines: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+        fs::write(work.join("file.txt"), &lines)?;
+        git(work, &["add", "file.txt"])?;
+        git(work, &["commit", "-q", "-m", "First"])?;
+        fs::write(work.join("file.txt"), format!("{}one more\n", lines))?;
+        git(work, &["commit", "-q", "-am", "Second"])?;
+        git(work, &["bundle", "create", "-q", "repo.bundle", "--all"])?;
+        let head = git(work, &["rev-parse", "HEAD"])?.trim().to_string();
+
+        let target_dir = tempdir()?;
+        let target = Repository::init(target_dir.path())?;
+        unbundle(&target, fs::File::open(work.join("repo.bundle"))?, RefMapping::Remote("origin"))?;
+
+        assert_eq!(refs::read_ref(&target.git_dir, "refs/remotes/origin/main")?, head);
+        let tree_id = objects::read_commit_tree(target.objects.as_ref(), &head)?;
+        let entry = objects::find_tree_entry(target.objects.as_ref(), &tree_id, std::path::Path::new("file.txt"))?.unwrap();
+        assert_eq!(target.read_object(&entry.oid)?.data, format!("{}one more\n", lines).into_bytes());
+        assert_eq!(objects::ancestors(&target, &head).len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use super::{objects, Repository};
+
+/// Parents and generation numbers of the commits a `Repository` has looked at, so history
+/// walks read each commit at most once.
+///
+/// A commit's generation is one more than the largest generation among its parents, and 1
+/// for a root or a shallow boundary, so an ancestor always has a smaller generation than its
+/// descendants. Commits never change, so entries stay valid; commits that cannot be read
+/// are not remembered, in case they arrive later.
+#[derive(Debug, Default)]
+pub struct CommitGraph {
+    parents: Mutex<HashMap<String, Vec<String>>>,
+    generations: Mutex<HashMap<String, u64>>,
+}
+
+impl CommitGraph {
+    /// The parents of `commit_id`, reading the commit only the first time. Empty for a root,
+    /// a shallow boundary or a commit that cannot be read.
+    pub fn parents(&self, repo: &Repository, commit_id: &str) -> Vec<String> {
+        self.lookup(repo, commit_id, &mut None).unwrap_or_default()
+    }
+
+    // The cached parents of `commit_id`, or none when the commit cannot be read. `shallow`
+    // holds the shallow boundary once a lookup has needed it, so a walk reads it only once.
+    fn lookup(&self, repo: &Repository, commit_id: &str, shallow: &mut Option<HashSet<String>>) -> Option<Vec<String>> {
+        if let Some(parents) = self.parents.lock().unwrap().get(commit_id) {
+            return Some(parents.clone());
+        }
+        // The history of a shallow clone ends at its boundary commits, whose parents were never fetched
+        let shallow = shallow.get_or_insert_with(|| objects::shallow_commits(&repo.git_dir));
+        let parents = if shallow.contains(commit_id) {
+            Vec::new()
+        } else {
+            objects::read_commit(repo.objects.as_ref(), commit_id).ok()?.parents
+        };
+        self.parents.lock().unwrap().insert(commit_id.to_string(), parents.clone());
+        Some(parents)
+    }
+
+    /// The generation of `commit_id`; 0 when the commit cannot be read
+    pub fn generation(&self, repo: &Repository, commit_id: &str) -> u64 {
+        // Depth-first without recursion, so long histories cannot overflow the stack
+        let mut missing = HashSet::new();
+        let mut shallow = None;
+        let mut stack = vec![commit_id.to_string()];
+        while let Some(current) = stack.last().cloned() {
+            if self.generations.lock().unwrap().contains_key(&current) || missing.contains(&current) {
+                stack.pop();
+                continue;
+            }
+            let Some(parents) = self.lookup(repo, &current, &mut shallow) else {
+                missing.insert(current);
+                stack.pop();
+                continue;
+            };
+            let mut generations = self.generations.lock().unwrap();
+            let pending: Vec<String> = parents
+                .iter()
+                .filter(|parent| !generations.contains_key(*parent) && !missing.contains(*parent))
+                .cloned()
+                .collect();
+            if pending.is_empty() {
+                let generation = 1 + parents.iter().filter_map(|parent| generations.get(parent)).max().unwrap_or(&0);
+                generations.insert(current, generation);
+                stack.pop();
+            } else {
+                drop(generations);
+                stack.extend(pending);
+            }
+        }
+        self.generations.lock().unwrap().get(commit_id).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::objects::Signature;
+    use crate::repository::store::{CountingStore, MemObjectStore};
+    use anyhow::Result;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    // The walk `is_ancestor` did before the cache: every ancestor read on every call
+    fn naive_is_ancestor(repo: &Repository, ancestor: &str, commit: &str) -> bool {
+        let mut queue = vec![commit.to_string()];
+        let mut visited = HashSet::new();
+        while let Some(current) = queue.pop() {
+            if current == ancestor {
+                return true;
+            }
+            if visited.insert(current.clone()) {
+                queue.extend(objects::read_commit_parents(repo, &current));
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_is_ancestor_over_deep_history() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let store = CountingStore::new(MemObjectStore::new());
+        let reads = Arc::clone(&store.reads);
+        let repo = Repository::init_with_object_store(temp_dir.path(), Box::new(store))?;
+
+        let tree_id = "4b825dc642cb6eb9a060e54bf8d69288fbc88904";
+        let signature = Signature::now("Test User <test@example.com>");
+        let mut history: Vec<String> = Vec::new();
+        for i in 0..300 {
+            let parents: Vec<&str> = history.last().map(String::as_str).into_iter().collect();
+            history.push(objects::write_commit(repo.objects.as_ref(), tree_id, &parents, &format!("Commit {}", i), &signature, &signature)?);
+        }
+        let (root, head) = (&history[0], &history[299]);
+
+        assert_eq!(repo.generation(root), 1);
+        assert_eq!(repo.generation(head), 300);
+        assert!(objects::is_ancestor(&repo, root, head)?);
+
+        // With the graph filled in, further checks read nothing, in either direction
+        let queries = [(10, 299), (150, 200), (299, 10), (200, 150), (42, 42)];
+        reads.store(0, Ordering::SeqCst);
+        for (ancestor, descendant) in queries {
+            assert_eq!(
+                objects::is_ancestor(&repo, &history[ancestor], &history[descendant])?,
+                ancestor <= descendant
+            );
+        }
+        let cached_reads = reads.load(Ordering::SeqCst);
+
+        reads.store(0, Ordering::SeqCst);
+        for (ancestor, descendant) in queries {
+            assert_eq!(naive_is_ancestor(&repo, &history[ancestor], &history[descendant]), ancestor <= descendant);
+        }
+        let naive_reads = reads.load(Ordering::SeqCst);
+
+        assert_eq!(cached_reads, 0);
+        assert!(naive_reads > 500, "naive walk read {} objects", naive_reads);
+
+        Ok(())
+    }
+}
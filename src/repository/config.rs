@@ -1,17 +1,91 @@
 use anyhow::Result;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub type ConfigSection = HashMap<String, String>;
 pub type ConfigData = HashMap<String, ConfigSection>;
 
+/// Environment variable naming the initial branch of new repositories, ahead of `init.defaultBranch`
+pub const DEFAULT_BRANCH_ENV: &str = "RUST_GIT_DEFAULT_BRANCH";
+/// Environment variable overriding where the global config file is read from
+pub const GLOBAL_CONFIG_ENV: &str = "RUST_GIT_CONFIG_GLOBAL";
+
+/// Line-ending conversion requested by `core.autocrlf`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoCrlf {
+    /// Store and check out content unchanged
+    #[default]
+    False,
+    /// Store LF, check out CRLF
+    True,
+    /// Store LF, check out what is stored
+    Input,
+}
+
+impl AutoCrlf {
+    // Content with NUL bytes is treated as binary and never converted
+    fn is_text(data: &[u8]) -> bool {
+        !data.contains(&0)
+    }
+
+    /// Convert working tree content to the form stored in a blob
+    pub fn to_blob<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if *self == AutoCrlf::False || !Self::is_text(data) || !data.windows(2).any(|pair| pair == b"\r\n") {
+            return Cow::Borrowed(data);
+        }
+        let mut converted = Vec::with_capacity(data.len());
+        for (i, &byte) in data.iter().enumerate() {
+            if byte == b'\r' && data.get(i + 1) == Some(&b'\n') {
+                continue;
+            }
+            converted.push(byte);
+        }
+        Cow::Owned(converted)
+    }
+
+    /// Convert blob content to the form written to the working tree
+    pub fn to_worktree<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if *self != AutoCrlf::True || !Self::is_text(data) || !data.contains(&b'\n') {
+            return Cow::Borrowed(data);
+        }
+        let mut converted = Vec::with_capacity(data.len() + data.len() / 16);
+        for (i, &byte) in data.iter().enumerate() {
+            if byte == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+                converted.push(b'\r');
+            }
+            converted.push(byte);
+        }
+        Cow::Owned(converted)
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Config {
     pub data: ConfigData,
 }
 
 impl Config {
+    /// Where the global config lives: `$RUST_GIT_CONFIG_GLOBAL`, or `~/.rust-gitconfig`.
+    /// Git's own `~/.gitconfig` is left alone.
+    pub fn global_path() -> Option<PathBuf> {
+        match env::var_os(GLOBAL_CONFIG_ENV) {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".rust-gitconfig")),
+        }
+    }
+
+    /// The global config, empty when there is none
+    pub fn global() -> Result<Self> {
+        match Self::global_path() {
+            Some(path) => Self::open(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
     pub fn open(path: &Path) -> Result<Self> {
         if !path.exists() {
             return Ok(Self::default());
@@ -30,7 +104,7 @@ impl Config {
             if line.starts_with('[') && line.ends_with(']') {
                 let section_name = line.trim_matches(|c| c == '[' || c == ']').to_string();
                 current_section_name = section_name;
-                data.entry(current_section_name.clone()).or_insert_with(HashMap::new);
+                data.entry(current_section_name.clone()).or_default();
             } else if let Some((key, value)) = line.split_once('=') {
                 if !current_section_name.is_empty() {
                     if let Some(section) = data.get_mut(&current_section_name) {
@@ -42,8 +116,161 @@ impl Config {
         data
     }
 
+    /// Set `key` in `[section]` to `value`, both here and in the config file at `path`.
+    ///
+    /// An existing value is replaced in place; otherwise the key is added at the end of its
+    /// section, or in a new section at the end of the file.
+    pub fn set(&mut self, path: &Path, section: &str, key: &str, value: &str) -> Result<()> {
+        let content = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+        let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let entry = format!("\t{} = {}", key, value);
+
+        let is_header = |line: &str| line.trim().starts_with('[') && line.trim().ends_with(']');
+        let header = lines
+            .iter()
+            .position(|line| is_header(line) && line.trim().trim_matches(|c| c == '[' || c == ']') == section);
+        match header {
+            Some(header) => {
+                let mut end = lines[header + 1..]
+                    .iter()
+                    .position(|line| is_header(line))
+                    .map_or(lines.len(), |offset| header + 1 + offset);
+                while end > header + 1 && lines[end - 1].trim().is_empty() {
+                    end -= 1;
+                }
+                let existing = (header + 1..end).find(|&i| {
+                    lines[i].split_once('=').is_some_and(|(existing_key, _)| existing_key.trim() == key)
+                });
+                match existing {
+                    Some(i) => lines[i] = entry,
+                    None => lines.insert(end, entry),
+                }
+            }
+            None => {
+                lines.push(format!("[{}]", section));
+                lines.push(entry);
+            }
+        }
+
+        fs::write(path, lines.join("\n") + "\n")?;
+        self.data.entry(section.to_string()).or_default().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// The `Name <email>` identity from the `[user]` section, with a default for unconfigured repos
+    pub fn user_identity(&self) -> String {
+        let user = self.data.get("user");
+        let name = user.and_then(|section| section.get("name")).map_or("Rust-git", |name| name.as_str());
+        let email = user.and_then(|section| section.get("email")).map_or("user@example.com", |email| email.as_str());
+        format!("{} <{}>", name, email)
+    }
+
+    /// The `core.autocrlf` setting
+    pub fn autocrlf(&self) -> AutoCrlf {
+        match self.data.get("core").and_then(|core| core.get("autocrlf")).map(String::as_str) {
+            Some("true") => AutoCrlf::True,
+            Some("input") => AutoCrlf::Input,
+            _ => AutoCrlf::False,
+        }
+    }
+
+    /// The `http.timeout` setting in seconds, bounding both connecting to a remote and waiting
+    /// for its response; 30 seconds when unset or not a positive number
+    pub fn http_timeout(&self) -> Duration {
+        let seconds = self.data
+            .get("http")
+            .and_then(|http| http.get("timeout"))
+            .and_then(|timeout| timeout.parse::<u64>().ok())
+            .filter(|&seconds| seconds > 0)
+            .unwrap_or(30);
+        Duration::from_secs(seconds)
+    }
+
+    /// The `gc.auto` setting: how many loose objects make `gc --auto` run, 6700 when unset.
+    /// Zero turns automatic collection off.
+    pub fn gc_auto(&self) -> usize {
+        self.gc_setting("auto").and_then(|auto| auto.parse().ok()).unwrap_or(6700)
+    }
+
+    /// The `gc.autoPackLimit` setting: how many packs make `gc --auto` run, 50 when unset.
+    /// Zero turns this trigger off.
+    pub fn gc_auto_pack_limit(&self) -> usize {
+        self.gc_setting("autoPackLimit").and_then(|limit| limit.parse().ok()).unwrap_or(50)
+    }
+
+    /// The `gc.reflogExpire` setting: how old reflog entries get before gc drops them, such
+    /// as `90.days.ago` or `never`
+    pub fn gc_reflog_expire(&self) -> Option<&str> {
+        self.gc_setting("reflogExpire")
+    }
+
+    // A setting of the `[gc]` section, whose keys are matched case-insensitively
+    fn gc_setting(&self, key: &str) -> Option<&str> {
+        self.data
+            .get("gc")?
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.trim())
+    }
+
+    /// The ref `branch` tracks, from `branch.<name>.remote` and `branch.<name>.merge`:
+    /// `refs/remotes/<remote>/<branch>` for a remote, or the merge ref itself when the
+    /// remote is `.` (the local repository)
+    pub fn branch_upstream(&self, branch: &str) -> Option<String> {
+        let section = self.data.get(&format!("branch \"{}\"", branch))?;
+        let remote = section.get("remote")?;
+        let merge = section.get("merge")?;
+        if remote == "." {
+            return Some(merge.clone());
+        }
+        let merge_branch = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+        Some(format!("refs/remotes/{}/{}", remote, merge_branch))
+    }
+
+    /// The branch a new repository starts on: `$RUST_GIT_DEFAULT_BRANCH`, then this config's
+    /// `init.defaultBranch`, then `master`
+    pub fn default_branch(&self) -> Result<String> {
+        let configured = self.data
+            .get("init")
+            .and_then(|init| init.iter().find(|(key, _)| key.eq_ignore_ascii_case("defaultBranch")))
+            .map(|(_, branch)| branch.clone());
+        let branch = env::var(DEFAULT_BRANCH_ENV)
+            .ok()
+            .filter(|branch| !branch.is_empty())
+            .or(configured)
+            .unwrap_or_else(|| "master".to_string());
+
+        let invalid = branch.is_empty()
+            || branch.starts_with(['-', '/'])
+            || branch.ends_with(['/', '.'])
+            || branch.contains("..")
+            || branch.contains(|c: char| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+        if invalid {
+            anyhow::bail!("invalid default branch name '{}'", branch);
+        }
+        Ok(branch)
+    }
+
     pub fn get_remote_url(&self, remote_name: &str) -> Option<&String> {
         let section_name = format!("remote \"{}\"", remote_name);
         self.data.get(&section_name)?.get("url")
     }
+
+    /// Every setting as `(name, value)`, named `section.key`, or `section.subsection.key` for
+    /// sections such as `[remote "origin"]`, sorted by name
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = self
+            .data
+            .iter()
+            .flat_map(|(section, values)| {
+                let prefix = match section.split_once(' ') {
+                    Some((name, subsection)) => format!("{}.{}", name, subsection.trim().trim_matches('"')),
+                    None => section.clone(),
+                };
+                values.iter().map(move |(key, value)| (format!("{}.{}", prefix, key), value.clone()))
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
 } 
\ No newline at end of file
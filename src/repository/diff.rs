@@ -0,0 +1,419 @@
+use std::ops::Range;
+
+/// A single step of an edit script turning `old` into `new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    /// `old[old_index]` and `new[new_index]` are the same line
+    Equal { old_index: usize, new_index: usize },
+    /// `old[old_index]` was removed
+    Delete { old_index: usize },
+    /// `new[new_index]` was added
+    Insert { new_index: usize },
+}
+
+/// A contiguous run of changes: `old[old_start..old_end]` was replaced by `new[new_start..new_end]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_end: usize,
+    pub new_start: usize,
+    pub new_end: usize,
+}
+
+/// A region where both sides of a three-way merge changed the base differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Lines of "ours" (0-based, half-open) involved in the conflict
+    pub ours: Range<usize>,
+    /// Lines of "theirs" (0-based, half-open) involved in the conflict
+    pub theirs: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeResult {
+    /// Both sides merged without overlapping changes
+    Clean(String),
+    /// The merged text contains conflict markers for each entry in `conflicts`
+    Conflicted { content: String, conflicts: Vec<Conflict> },
+}
+
+/// Split text into lines, keeping each line's `\n` terminator so the text can be rebuilt exactly.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Compute a shortest edit script from `old` to `new` using Myers' O(ND) algorithm.
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffOp> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    // Forward pass: record the furthest reaching x on every diagonal k for each edit distance d
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // Backtrack from the end to recover the edit script
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) { k + 1 } else { k - 1 };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(DiffOp::Equal { old_index: (x - 1) as usize, new_index: (y - 1) as usize });
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(DiffOp::Insert { new_index: (y - 1) as usize });
+            } else {
+                ops.push(DiffOp::Delete { old_index: (x - 1) as usize });
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    ops
+}
+
+/// Group the edit script from `old` to `new` into hunks of consecutive changes.
+pub fn diff_hunks<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let mut current: Option<Hunk> = None;
+    let (mut old_index, mut new_index) = (0, 0);
+
+    for op in diff(old, new) {
+        match op {
+            DiffOp::Equal { .. } => {
+                if let Some(hunk) = current.take() {
+                    hunks.push(hunk);
+                }
+                old_index += 1;
+                new_index += 1;
+            }
+            DiffOp::Delete { .. } => {
+                let hunk = current.get_or_insert(Hunk { old_start: old_index, old_end: old_index, new_start: new_index, new_end: new_index });
+                old_index += 1;
+                hunk.old_end = old_index;
+            }
+            DiffOp::Insert { .. } => {
+                let hunk = current.get_or_insert(Hunk { old_start: old_index, old_end: old_index, new_start: new_index, new_end: new_index });
+                new_index += 1;
+                hunk.new_end = new_index;
+            }
+        }
+    }
+    if let Some(hunk) = current {
+        hunks.push(hunk);
+    }
+
+    hunks
+}
+
+// The `start,count` of one side of a unified hunk header, 1-based; an empty range names the
+// line before it
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+// Add `line` to a unified diff after `prefix`, marking a missing final newline as diff does
+fn push_diff_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// The `@@` hunks of a unified diff from `old` to `new`, with `context` unchanged lines
+/// around each change. Changes closer than twice the context share a hunk. Empty when the
+/// texts are equal.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let hunks = diff_hunks(&old_lines, &new_lines);
+
+    group_hunks(&hunks, context)
+        .into_iter()
+        .map(|group| format_hunk(&old_lines, &new_lines, group, context))
+        .collect()
+}
+
+/// Split `hunks`, in order, into the groups that share one `@@` hunk of a unified diff with
+/// `context` lines: those whose changes are closer than twice the context.
+pub fn group_hunks(hunks: &[Hunk], context: usize) -> Vec<&[Hunk]> {
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < hunks.len() {
+        let start = i;
+        while i + 1 < hunks.len() && hunks[i + 1].old_start - hunks[i].old_end <= 2 * context {
+            i += 1;
+        }
+        groups.push(&hunks[start..=i]);
+        i += 1;
+    }
+    groups
+}
+
+/// One `@@` hunk of a unified diff from `old_lines` to `new_lines`, holding the changes of
+/// `group` (see `group_hunks`) with `context` unchanged lines around them.
+pub fn format_hunk(old_lines: &[&str], new_lines: &[&str], group: &[Hunk], context: usize) -> String {
+    let (first, last) = (group[0], group[group.len() - 1]);
+
+    // Context lines are equal on both sides, so both ranges widen by the same amount
+    let leading = first.old_start.min(context);
+    let trailing = (old_lines.len() - last.old_end).min(context);
+    let (old_from, old_to) = (first.old_start - leading, last.old_end + trailing);
+    let (new_from, new_to) = (first.new_start - leading, last.new_end + trailing);
+    let mut out = format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(old_from, old_to - old_from),
+        hunk_range(new_from, new_to - new_from)
+    );
+
+    let mut pos = old_from;
+    for hunk in group {
+        old_lines[pos..hunk.old_start].iter().for_each(|line| push_diff_line(&mut out, ' ', line));
+        old_lines[hunk.old_start..hunk.old_end].iter().for_each(|line| push_diff_line(&mut out, '-', line));
+        new_lines[hunk.new_start..hunk.new_end].iter().for_each(|line| push_diff_line(&mut out, '+', line));
+        pos = hunk.old_end;
+    }
+    old_lines[pos..old_to].iter().for_each(|line| push_diff_line(&mut out, ' ', line));
+    out
+}
+
+/// `old` with only the changes of `hunks`, a subset of the diff from `old` to `new` in
+/// order, applied
+pub fn apply_hunks(old: &str, new: &str, hunks: &[Hunk]) -> String {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    side_region(&old_lines, &new_lines, hunks, 0, old_lines.len()).concat()
+}
+
+// Rebuild one side's version of `base[start..end]` from that side's hunks lying inside the range
+fn side_region<'a>(base: &[&'a str], side: &[&'a str], hunks: &[Hunk], start: usize, end: usize) -> Vec<&'a str> {
+    let mut region = Vec::new();
+    let mut pos = start;
+    for hunk in hunks {
+        region.extend_from_slice(&base[pos..hunk.old_start]);
+        region.extend_from_slice(&side[hunk.new_start..hunk.new_end]);
+        pos = hunk.old_end;
+    }
+    region.extend_from_slice(&base[pos..end]);
+    region
+}
+
+// Append lines to the output, making sure the last one is newline-terminated before a marker follows
+fn push_region(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Merge `ours` and `theirs`, which both descend from `base`, line by line.
+///
+/// Changes on one side only are applied; identical changes on both sides are applied once.
+/// Changes that overlap in the base (or insert at the same place) with different results
+/// are conflicts and are written between `<<<<<<<`, `=======` and `>>>>>>>` markers.
+pub fn three_way_merge(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_lines = split_lines(base);
+    let ours_lines = split_lines(ours);
+    let theirs_lines = split_lines(theirs);
+    let ours_hunks = diff_hunks(&base_lines, &ours_lines);
+    let theirs_hunks = diff_hunks(&base_lines, &theirs_lines);
+
+    let mut out = String::new();
+    let mut conflicts = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut pos = 0;
+    let (mut ours_line, mut theirs_line) = (0, 0);
+
+    loop {
+        // Start a group at whichever side's next hunk comes first in the base
+        let group_start = match (ours_hunks.get(i), theirs_hunks.get(j)) {
+            (Some(o), Some(t)) => o.old_start.min(t.old_start),
+            (Some(o), None) => o.old_start,
+            (None, Some(t)) => t.old_start,
+            (None, None) => break,
+        };
+        let mut group_end = group_start;
+        let (ours_first, theirs_first) = (i, j);
+
+        // Grow the group while hunks from either side overlap it
+        loop {
+            let overlaps = |hunk: &Hunk| hunk.old_start < group_end || hunk.old_start == group_start;
+            if let Some(hunk) = ours_hunks.get(i).filter(|h| overlaps(h)) {
+                group_end = group_end.max(hunk.old_end);
+                i += 1;
+            } else if let Some(hunk) = theirs_hunks.get(j).filter(|h| overlaps(h)) {
+                group_end = group_end.max(hunk.old_end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        // Unchanged lines before the group
+        for line in &base_lines[pos..group_start] {
+            out.push_str(line);
+        }
+        ours_line += group_start - pos;
+        theirs_line += group_start - pos;
+        pos = group_end;
+
+        let ours_region = side_region(&base_lines, &ours_lines, &ours_hunks[ours_first..i], group_start, group_end);
+        let theirs_region = side_region(&base_lines, &theirs_lines, &theirs_hunks[theirs_first..j], group_start, group_end);
+
+        if theirs_first == j || ours_region == theirs_region {
+            ours_region.iter().for_each(|line| out.push_str(line));
+        } else if ours_first == i {
+            theirs_region.iter().for_each(|line| out.push_str(line));
+        } else {
+            conflicts.push(Conflict {
+                ours: ours_line..ours_line + ours_region.len(),
+                theirs: theirs_line..theirs_line + theirs_region.len(),
+            });
+            out.push_str("<<<<<<< ours\n");
+            push_region(&mut out, &ours_region);
+            out.push_str("=======\n");
+            push_region(&mut out, &theirs_region);
+            out.push_str(">>>>>>> theirs\n");
+        }
+        ours_line += ours_region.len();
+        theirs_line += theirs_region.len();
+    }
+
+    for line in &base_lines[pos..] {
+        out.push_str(line);
+    }
+
+    if conflicts.is_empty() {
+        MergeResult::Clean(out)
+    } else {
+        MergeResult::Conflicted { content: out, conflicts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_edit_script() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "c", "d", "e"];
+        let ops = diff(&old, &new);
+
+        let deleted: Vec<usize> = ops.iter().filter_map(|op| match op {
+            DiffOp::Delete { old_index } => Some(*old_index),
+            _ => None,
+        }).collect();
+        let inserted: Vec<usize> = ops.iter().filter_map(|op| match op {
+            DiffOp::Insert { new_index } => Some(*new_index),
+            _ => None,
+        }).collect();
+        assert_eq!(deleted, vec![1]);
+        assert_eq!(inserted, vec![3]);
+        assert_eq!(ops.iter().filter(|op| matches!(op, DiffOp::Equal { .. })).count(), 3);
+
+        assert_eq!(
+            diff_hunks(&old, &new),
+            vec![
+                Hunk { old_start: 1, old_end: 2, new_start: 1, new_end: 1 },
+                Hunk { old_start: 4, old_end: 4, new_start: 3, new_end: 4 },
+            ]
+        );
+        assert!(diff::<&str>(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_three_way_merge_insert_top_and_edit_bottom() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let ours = "zero\none\ntwo\nthree\nfour\n";
+        let theirs = "one\ntwo\nthree\nFOUR\n";
+
+        assert_eq!(
+            three_way_merge(base, ours, theirs),
+            MergeResult::Clean("zero\none\ntwo\nthree\nFOUR\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_three_way_merge_identical_changes_are_clean() {
+        let base = "a\nb\nc\n";
+        let both = "a\nB\nc\n";
+        assert_eq!(three_way_merge(base, both, both), MergeResult::Clean(both.to_string()));
+    }
+
+    #[test]
+    fn test_three_way_merge_overlapping_changes_conflict() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+
+        match three_way_merge(base, ours, theirs) {
+            MergeResult::Conflicted { content, conflicts } => {
+                assert_eq!(content, "a\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nc\n");
+                assert_eq!(conflicts, vec![Conflict { ours: 1..2, theirs: 1..2 }]);
+            }
+            clean => panic!("expected a conflict, got {:?}", clean),
+        }
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old: String = (1..=12).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=12)
+            .map(|n| match n {
+                2 => "two\n".to_string(),
+                11 => "eleven\n".to_string(),
+                n => format!("{}\n", n),
+            })
+            .collect::<String>() + "13";
+
+        // The changes are too far apart to share a hunk
+        assert_eq!(
+            unified_diff(&old, &new, 3),
+            "@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n@@ -8,5 +8,6 @@\n 8\n 9\n 10\n-11\n+eleven\n 12\n+13\n\\ No newline at end of file\n"
+        );
+        assert_eq!(unified_diff(&old, &old, 3), "");
+        assert_eq!(unified_diff("", "a\n", 3), "@@ -0,0 +1 @@\n+a\n");
+    }
+}
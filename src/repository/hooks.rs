@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::Repository;
+
+// The hook `name` in `.git/hooks`, if present and executable
+fn find_hook(git_dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = git_dir.join("hooks").join(name);
+    let metadata = path.metadata().ok().filter(|metadata| metadata.is_file())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return None; // Like git, a hook that is not executable is ignored
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    Some(path)
+}
+
+/// Run the hook `name` (e.g. `pre-commit`) from `.git/hooks` with `args`, in the working tree.
+///
+/// A missing or non-executable hook counts as passing. A hook that exits non-zero fails
+/// with an error naming it; its own output goes straight to the terminal.
+pub fn run(repo: &Repository, name: &str, args: &[&Path]) -> Result<()> {
+    let Some(hook) = find_hook(&repo.common_dir, name) else {
+        return Ok(());
+    };
+    let status = Command::new(&hook)
+        .args(args)
+        .current_dir(&repo.path)
+        .status()
+        .with_context(|| format!("Failed to run the {} hook", name))?;
+    if !status.success() {
+        anyhow::bail!("The {} hook failed ({})", name, status);
+    }
+    Ok(())
+}
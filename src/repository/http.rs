@@ -0,0 +1,110 @@
+use anyhow::{anyhow, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use std::thread;
+use std::time::Duration;
+
+use super::config::Config;
+
+/// How many times an idempotent request is sent before giving up
+const MAX_ATTEMPTS: u32 = 3;
+/// Pause before the first retry; doubled before each further one
+const INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Returned when a remote does not answer within `http.timeout`.
+#[derive(Debug, thiserror::Error)]
+#[error("timed out after {}s waiting for remote '{url}' (raise http.timeout to wait longer)", .timeout.as_secs())]
+pub struct TimeoutError {
+    pub url: String,
+    pub timeout: Duration,
+}
+
+/// A client whose connects and requests give up after the configured `http.timeout`
+pub fn client(config: &Config) -> Result<Client> {
+    let timeout = config.http_timeout();
+    Ok(Client::builder().connect_timeout(timeout).timeout(timeout).build()?)
+}
+
+/// Send `request`, turning a timeout into a `TimeoutError` and other transport failures into
+/// an error naming the remote.
+pub fn send(request: RequestBuilder, url: &str, timeout: Duration) -> Result<Response> {
+    request.send().map_err(|e| {
+        if e.is_timeout() {
+            TimeoutError { url: url.to_string(), timeout }.into()
+        } else {
+            anyhow!("Failed to connect to remote url '{}': {}", url, e)
+        }
+    })
+}
+
+/// Send an idempotent `request` such as a fetch GET, retrying with backoff when the connection
+/// fails or the server answers 502, 503 or 504.
+///
+/// A timeout is not retried, so a silent remote costs at most one `timeout`.
+pub fn send_with_retry(request: RequestBuilder, url: &str, timeout: Duration) -> Result<Response> {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let attempt_request = request
+            .try_clone()
+            .ok_or_else(|| anyhow!("request to '{}' cannot be retried", url))?;
+        let retryable = match send(attempt_request, url, timeout) {
+            Ok(response) if is_transient(response.status()) && attempt < MAX_ATTEMPTS => true,
+            Ok(response) => return Ok(response),
+            Err(e) if e.is::<TimeoutError>() || attempt == MAX_ATTEMPTS => return Err(e),
+            Err(_) => true,
+        };
+        if retryable {
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+    unreachable!("the last attempt always returns")
+}
+
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 502..=504)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    fn config_with_timeout(seconds: u64) -> Config {
+        let mut config = Config::default();
+        config.data.entry("http".to_string()).or_default().insert("timeout".to_string(), seconds.to_string());
+        config
+    }
+
+    #[test]
+    fn test_unanswered_request_times_out() -> Result<()> {
+        // A bound listener that never accepts: the connection is queued but never answered
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        let config = config_with_timeout(1);
+
+        let started = Instant::now();
+        let err = send_with_retry(client(&config)?.get(&url), &url, config.http_timeout()).unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(err.is::<TimeoutError>());
+        assert!(err.to_string().contains("timed out after 1s"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_refused_connection_fails_after_bounded_retries() -> Result<()> {
+        // Bind and release a port so nothing listens on it
+        let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let url = format!("http://{}/", addr);
+        let config = config_with_timeout(1);
+
+        let started = Instant::now();
+        let err = send_with_retry(client(&config)?.get(&url), &url, config.http_timeout()).unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!err.is::<TimeoutError>());
+        assert!(err.to_string().contains("Failed to connect"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+/// One line of a `.gitignore` file
+#[derive(Debug, Clone)]
+struct Pattern {
+    glob: String,
+    /// `!pattern` re-includes what an earlier pattern excluded
+    negated: bool,
+    /// `pattern/` only matches directories
+    dir_only: bool,
+    /// A pattern with a slash before its end matches from the root rather than any file name
+    anchored: bool,
+}
+
+/// The patterns of the `.gitignore` file at the root of a working tree
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreRules {
+    /// Load `<work_dir>/.gitignore`; a missing file ignores nothing.
+    pub fn load<P: AsRef<Path>>(work_dir: P) -> Result<Self> {
+        let path = work_dir.as_ref().join(".gitignore");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut patterns = Vec::new();
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            patterns.push(Pattern {
+                glob: line.trim_start_matches('/').to_string(),
+                negated,
+                dir_only,
+                anchored,
+            });
+        }
+        Self { patterns }
+    }
+
+    /// Whether `path` (relative to the working tree root) is ignored, either itself or
+    /// because one of its parent directories is.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let mut prefix_end = 0;
+        while let Some(slash) = path[prefix_end..].find('/') {
+            prefix_end += slash;
+            if self.matches(&path[..prefix_end], true) {
+                return true;
+            }
+            prefix_end += 1;
+        }
+        self.matches(&path, is_dir)
+    }
+
+    // The last pattern matching `path` decides whether it is ignored
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let mut ignored = false;
+        for pattern in &self.patterns {
+            if pattern.dir_only && !is_dir {
+                continue;
+            }
+            let subject = if pattern.anchored { path } else { name };
+            if glob_match(pattern.glob.as_bytes(), subject.as_bytes()) {
+                ignored = !pattern.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// Match `text` against a glob pattern where `*` and `?` stay within one path component and
+/// `**` spans any number of them
+pub fn wildmatch(pattern: &str, text: &str) -> bool {
+    glob_match(pattern.as_bytes(), text.as_bytes())
+}
+
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            glob_match(rest, text)
+                || text.iter().enumerate().any(|(i, &b)| b == b'/' && glob_match(rest, &text[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        [b'*', rest @ ..] => {
+            let limit = text.iter().position(|&b| b == b'/').unwrap_or(text.len());
+            (0..=limit).any(|i| glob_match(rest, &text[i..]))
+        }
+        [b'?', rest @ ..] => matches!(text, [b, tail @ ..] if *b != b'/' && glob_match(rest, tail)),
+        [c, rest @ ..] => matches!(text, [b, tail @ ..] if b == c && glob_match(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_patterns() {
+        let rules = IgnoreRules::parse("# build output\n*.log\n!keep.log\n/target\nbuild/\ndocs/**/*.tmp\n");
+
+        assert!(rules.is_ignored(Path::new("debug.log"), false));
+        assert!(rules.is_ignored(Path::new("sub/debug.log"), false));
+        assert!(!rules.is_ignored(Path::new("keep.log"), false));
+        assert!(rules.is_ignored(Path::new("target/release/app"), false));
+        assert!(!rules.is_ignored(Path::new("sub/target"), false));
+        assert!(rules.is_ignored(Path::new("sub/build/out.o"), false));
+        assert!(!rules.is_ignored(Path::new("build"), false));
+        assert!(rules.is_ignored(Path::new("docs/a/b/c.tmp"), false));
+        assert!(rules.is_ignored(Path::new("docs/c.tmp"), false));
+        assert!(!rules.is_ignored(Path::new("src/main.rs"), false));
+    }
+}
@@ -1,17 +1,101 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
+use std::str;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use walkdir::WalkDir;
 use serde::{Serialize, Deserialize};
+use sha1::{Digest, Sha1};
+use super::config::Config;
+use super::store::ObjectStore;
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+/// Mode of a regular, non-executable file
+pub const MODE_FILE: u32 = 0o100644;
+/// Mode of a symbolic link; its blob holds the link target
+pub const MODE_SYMLINK: u32 = 0o120000;
+
+/// Read what gets stored in the blob for a working tree path: the file content with
+/// line endings normalized per `core.autocrlf`, or the target of a symbolic link (which is not followed).
+pub fn read_worktree_content<P: AsRef<Path>>(path: P, config: &Config) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    if path.symlink_metadata()?.file_type().is_symlink() {
+        Ok(fs::read_link(path)?.to_string_lossy().into_owned().into_bytes())
+    } else {
+        let content = fs::read(path)?;
+        Ok(config.autocrlf().to_blob(&content).into_owned())
+    }
+}
+
+/// Read, hash and store the blob for each path on a bounded pool of worker threads.
+///
+/// Returns the object ids in the same order as `paths`.
+fn write_blobs_parallel(paths: &[PathBuf], store: &dyn ObjectStore, config: &Config) -> Result<Vec<String>> {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::with_capacity(paths.len()));
+    
+    thread::scope(|scope| -> Result<()> {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| -> Result<()> {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(i) else {
+                        return Ok(());
+                    };
+                    let content = read_worktree_content(path, config)
+                        .with_context(|| format!("Cannot read {}", path.display()))?;
+                    let object_id = super::objects::write_blob(store, &content)?;
+                    results.lock().unwrap().push((i, object_id));
+                }
+            }))
+            .collect();
+        for handle in handles {
+            handle.join().map_err(|_| anyhow::anyhow!("Hashing thread panicked"))??;
+        }
+        Ok(())
+    })?;
+    
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    Ok(results.into_iter().map(|(_, object_id)| object_id).collect())
+}
+
+/// Signature at the start of an index file in git's format
+const INDEX_SIGNATURE: &[u8; 4] = b"DIRC";
+/// Version of git's index format that is written; versions 2 and 3 are read
+const INDEX_VERSION: u32 = 2;
+/// Size of an entry's fixed part: stat data, mode, object id and flags
+const ENTRY_HEADER_LEN: usize = 62;
+/// Flag bits of an entry: the name length (saturating), the merge stage and whether
+/// version 3 extended flags follow
+const NAME_LENGTH_MASK: u16 = 0x0fff;
+const STAGE_MASK: u16 = 0x3000;
+const EXTENDED_FLAG: u16 = 0x4000;
+
+#[derive(Debug, Default)]
 pub struct Index {
     entries: HashMap<PathBuf, IndexEntry>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The index as versions before the switch to git's format stored it, serialized with bincode
+#[derive(Serialize, Deserialize)]
+struct LegacyIndex {
+    entries: HashMap<PathBuf, LegacyIndexEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LegacyIndexEntry {
+    mtime: u64,
+    object_id: String,
+    mode: u32,
+}
+
+#[derive(Debug)]
 pub struct IndexEntry {
     pub mtime: u64,
     pub object_id: String,
@@ -40,8 +124,94 @@ impl Index {
             return Ok(Self::new());
         }
         
-        let index: Index = bincode::deserialize(&data)?;
-        Ok(index)
+        if data.starts_with(INDEX_SIGNATURE) {
+            return Self::parse(&data).with_context(|| format!("Cannot read index file {}", path.display()));
+        }
+        
+        // An index written before the switch to git's format; the next save converts it
+        let legacy: LegacyIndex = bincode::deserialize(&data)
+            .with_context(|| format!("Cannot read index file {}", path.display()))?;
+        let entries = legacy
+            .entries
+            .into_iter()
+            .map(|(path, entry)| (path, IndexEntry { mtime: entry.mtime, object_id: entry.object_id, mode: entry.mode }))
+            .collect();
+        Ok(Self { entries })
+    }
+    
+    /// Parse an index file in git's format (versions 2 and 3). Entries in a merge stage other
+    /// than 0 cannot be represented and are skipped, as are extensions.
+    fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 12 + 20 {
+            anyhow::bail!("index file is truncated");
+        }
+        let (body, checksum) = data.split_at(data.len() - 20);
+        if Sha1::digest(body).as_slice() != checksum {
+            anyhow::bail!("index file is corrupt: checksum mismatch");
+        }
+        let version = read_u32(body, 4);
+        if !(2..=3).contains(&version) {
+            anyhow::bail!("unsupported index version {}", version);
+        }
+        
+        let mut entries = HashMap::new();
+        let mut position = 12;
+        for _ in 0..read_u32(body, 8) {
+            let header = body.get(position..position + ENTRY_HEADER_LEN).context("index file is truncated")?;
+            let flags = u16::from_be_bytes([header[60], header[61]]);
+            let name_start = position + ENTRY_HEADER_LEN + if flags & EXTENDED_FLAG != 0 { 2 } else { 0 };
+            let name_len = body
+                .get(name_start..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .context("index file is truncated")?;
+            let name = super::path_from_bytes(&body[name_start..name_start + name_len]);
+            
+            // Entries are padded with 1 to 8 null bytes to a multiple of 8 bytes
+            let entry_len = name_start + name_len - position;
+            position += (entry_len + 8) & !7;
+            
+            if flags & STAGE_MASK == 0 {
+                entries.insert(name, IndexEntry {
+                    mtime: read_u32(header, 8) as u64,
+                    object_id: hex::encode(&header[40..60]),
+                    mode: read_u32(header, 24),
+                });
+            }
+        }
+        Ok(Self { entries })
+    }
+    
+    /// Encode the index in git's format, version 2, entries sorted by path. Only the
+    /// modification time of the stat data is kept; the rest is written as zero.
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut entries: Vec<(Cow<[u8]>, &IndexEntry)> = self
+            .entries
+            .iter()
+            .map(|(path, entry)| (super::path_to_bytes(path), entry))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        
+        let mut data = Vec::new();
+        data.extend_from_slice(INDEX_SIGNATURE);
+        data.extend_from_slice(&INDEX_VERSION.to_be_bytes());
+        data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for (name, entry) in entries {
+            let start = data.len();
+            data.extend_from_slice(&[0; 8]); // ctime
+            data.extend_from_slice(&(entry.mtime as u32).to_be_bytes());
+            data.extend_from_slice(&[0; 4]); // mtime nanoseconds
+            data.extend_from_slice(&[0; 8]); // dev, ino
+            data.extend_from_slice(&entry.mode.to_be_bytes());
+            data.extend_from_slice(&[0; 12]); // uid, gid, size
+            data.extend_from_slice(&hex::decode(&entry.object_id)?);
+            data.extend_from_slice(&(name.len().min(NAME_LENGTH_MASK as usize) as u16).to_be_bytes());
+            data.extend_from_slice(&name);
+            let entry_len = data.len() - start;
+            data.resize(start + ((entry_len + 8) & !7), 0);
+        }
+        let checksum = Sha1::digest(&data);
+        data.extend_from_slice(&checksum);
+        Ok(data)
     }
     
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
@@ -52,8 +222,7 @@ impl Index {
             fs::create_dir_all(parent)?;
         }
         
-        let data = bincode::serialize(&self)?;
-        fs::write(path, data)?;
+        fs::write(path, self.encode()?)?;
         
         Ok(())
     }
@@ -71,44 +240,55 @@ impl Index {
         // Normalize the path to ensure consistency
         let normalized_path = super::normalize_path(relative_path);
         
-        let metadata = fs::metadata(file_path)?;
+        // Look at the link itself rather than what it points to
+        let metadata = fs::symlink_metadata(file_path)?;
+        let mode = if metadata.file_type().is_symlink() { MODE_SYMLINK } else { MODE_FILE };
         
         self.entries.insert(
             normalized_path,
             IndexEntry {
                 mtime: metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?.as_secs(),
                 object_id: object_id.to_string(),
-                mode: 0o100644, // regular file
+                mode,
             },
         );
         
         Ok(())
     }
     
-    pub fn add_directory<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(&mut self, repo_path: P1, dir_path: P2, objects_dir: P3) -> Result<Vec<String>> {
+    pub fn add_directory<P1: AsRef<Path>, P2: AsRef<Path>>(&mut self, repo_path: P1, dir_path: P2, store: &dyn ObjectStore, config: &Config) -> Result<Vec<String>> {
         let repo_path = repo_path.as_ref();
         let dir_path = dir_path.as_ref();
-        let objects_dir = objects_dir.as_ref();
-        
-        let mut added_files = Vec::new();
         
-        for entry in WalkDir::new(dir_path)
+        // Collect the files first so hashing can be spread across threads
+        let mut paths: Vec<PathBuf> = WalkDir::new(dir_path)
             .into_iter()
+            // Skip the .git directory, or a linked working tree's .git file
+            .filter_entry(|e| !super::is_git_dir_name(e.file_name()))
             .filter_map(Result::ok)
-            .filter(|e| e.file_type().is_file())
-        {
-            let path = entry.path();
-            
-            // Skip .git directory
-            if path.to_string_lossy().contains("/.git/") {
-                continue;
+            .filter(|e| e.file_type().is_file() || e.file_type().is_symlink())
+            .map(|e| e.into_path())
+            .collect();
+        paths.sort();
+        
+        self.add_files(repo_path, &paths, store, config)
+    }
+    
+    /// Hash and store each of `paths` (in the working tree under `repo_path`) and record them,
+    /// returning their normalized paths relative to the repository root.
+    pub fn add_files<P: AsRef<Path>>(&mut self, repo_path: P, paths: &[PathBuf], store: &dyn ObjectStore, config: &Config) -> Result<Vec<String>> {
+        let repo_path = repo_path.as_ref();
+        for path in paths {
+            let relative_path = path.strip_prefix(repo_path).unwrap_or(path);
+            if relative_path.components().any(|component| super::is_git_dir_name(component.as_os_str())) {
+                anyhow::bail!("'{}' is inside a git directory", relative_path.display());
             }
-            
-            // Create blob object
-            let content = fs::read(path)?;
-            let object_id = super::objects::write_blob(objects_dir, &content)?;
-            
-            // Add to index
+        }
+        let object_ids = write_blobs_parallel(paths, store, config)?;
+        
+        // Update the index in path order so the result does not depend on thread scheduling
+        let mut added_files = Vec::with_capacity(paths.len());
+        for (path, object_id) in paths.iter().zip(object_ids) {
             self.add_file(repo_path, path, &object_id)?;
             
             let relative_path = if path.starts_with(repo_path) {
@@ -125,6 +305,43 @@ impl Index {
         Ok(added_files)
     }
     
+    /// Bring the entries of already-tracked files up to date with the working tree, as
+    /// `commit -a` does: changed files are re-hashed and stored, deleted files are dropped.
+    /// Untracked files are left alone. Returns the updated paths, sorted.
+    pub fn update_tracked<P: AsRef<Path>>(&mut self, repo_path: P, store: &dyn ObjectStore, config: &Config) -> Result<Vec<String>> {
+        let repo_path = repo_path.as_ref();
+        
+        let mut present = Vec::new();
+        let mut updated = Vec::new();
+        for path in self.entries.keys() {
+            if fs::symlink_metadata(repo_path.join(path)).is_ok() {
+                present.push(path.clone());
+            } else {
+                updated.push(path.clone());
+            }
+        }
+        for path in &updated {
+            self.entries.remove(path);
+        }
+        present.sort();
+        
+        let full_paths: Vec<PathBuf> = present.iter().map(|path| repo_path.join(path)).collect();
+        let object_ids = write_blobs_parallel(&full_paths, store, config)?;
+        for ((path, full_path), object_id) in present.into_iter().zip(&full_paths).zip(object_ids) {
+            let entry = &self.entries[&path];
+            let is_symlink = fs::symlink_metadata(full_path)?.file_type().is_symlink();
+            let mode = if is_symlink { MODE_SYMLINK } else { MODE_FILE };
+            if entry.object_id != object_id || entry.mode != mode {
+                self.add_file(repo_path, full_path, &object_id)?;
+                updated.push(path);
+            }
+        }
+        
+        let mut updated: Vec<String> = updated.iter().map(|path| path.to_string_lossy().to_string()).collect();
+        updated.sort();
+        Ok(updated)
+    }
+    
     pub fn remove_path<P1: AsRef<Path>, P2: AsRef<Path>>(&mut self, repo_path: P1, path: P2) -> Result<Vec<String>> {
         let repo_path = repo_path.as_ref();
         let path = path.as_ref();
@@ -166,6 +383,16 @@ impl Index {
         Ok(removed_files)
     }
     
+    /// Record an entry for `path` (relative to the repository root) as is, without looking at the working tree
+    pub fn insert_entry(&mut self, path: PathBuf, entry: IndexEntry) {
+        self.entries.insert(path, entry);
+    }
+    
+    /// Remove every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    
     pub fn get_entries(&self) -> &HashMap<PathBuf, IndexEntry> {
         &self.entries
     }
@@ -175,11 +402,18 @@ impl Index {
     }
 }
 
+// The big-endian u32 at `position` in `data`
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    u32::from_be_bytes(data[position..position + 4].try_into().unwrap())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Write;
     use tempfile::{tempdir, NamedTempFile};
+    use crate::repository::objects::ObjectType;
+    use crate::repository::store::{CountingStore, FsObjectStore};
     
     #[test]
     fn test_index_new() {
@@ -214,6 +448,47 @@ mod tests {
         Ok(())
     }
     
+    #[test]
+    fn test_legacy_index_is_migrated() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("index");
+        let blob_id = "abcdef0123456789abcdef0123456789abcdef01";
+        let long_name = format!("dir/{}.txt", "n".repeat(5000));
+        let legacy = LegacyIndex {
+            entries: HashMap::from([
+                (PathBuf::from("a.txt"), LegacyIndexEntry { mtime: 1700000000, object_id: blob_id.to_string(), mode: MODE_FILE }),
+                (PathBuf::from("dir/link"), LegacyIndexEntry { mtime: 0, object_id: blob_id.to_string(), mode: MODE_SYMLINK }),
+                (PathBuf::from(&long_name), LegacyIndexEntry { mtime: 0, object_id: blob_id.to_string(), mode: MODE_FILE }),
+            ]),
+        };
+        fs::write(&path, bincode::serialize(&legacy)?)?;
+
+        let index = Index::load(&path)?;
+        let entry = &index.get_entries()[Path::new("a.txt")];
+        assert_eq!((entry.mtime, entry.object_id.as_str(), entry.mode), (1700000000, blob_id, MODE_FILE));
+        assert_eq!(index.get_entries()[Path::new("dir/link")].mode, MODE_SYMLINK);
+
+        // Saving writes git's format, which loads back to the same entries
+        index.save(&path)?;
+        let data = fs::read(&path)?;
+        assert_eq!(&data[..8], b"DIRC\0\0\0\x02");
+        assert_eq!(&data[8..12], &3u32.to_be_bytes());
+        let reloaded = Index::load(&path)?;
+        assert_eq!(reloaded.get_entries().len(), 3);
+        for (path, entry) in index.get_entries() {
+            let reloaded_entry = &reloaded.get_entries()[path];
+            assert_eq!((reloaded_entry.mtime, &reloaded_entry.object_id, reloaded_entry.mode), (entry.mtime, &entry.object_id, entry.mode));
+        }
+
+        // A damaged file is refused rather than read as an empty index
+        let mut damaged = data.clone();
+        damaged[20] ^= 1;
+        fs::write(&path, damaged)?;
+        assert!(Index::load(&path).is_err());
+
+        Ok(())
+    }
+    
     #[test]
     fn test_add_file() -> Result<()> {
         let temp_dir = tempdir()?;
@@ -296,7 +571,7 @@ mod tests {
         
         // Add the directory to the index
         let mut index = Index::new();
-        index.add_directory(repo_path, &subdir, &objects_dir)?;
+        index.add_directory(repo_path, &subdir, &FsObjectStore::new(&objects_dir), &Config::default())?;
         
         // Check that files were added
         assert!(!index.is_empty());
@@ -311,4 +586,82 @@ mod tests {
         
         Ok(())
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_add_directory_of_many_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo_path = temp_dir.path();
+        let objects_dir = repo_path.join("objects");
+        fs::create_dir_all(&objects_dir)?;
+        
+        let mut expected = HashMap::new();
+        for i in 0..300 {
+            let dir = repo_path.join("files").join(format!("dir{}", i % 7));
+            fs::create_dir_all(&dir)?;
+            // Some files share content so the same blob is written concurrently
+            let content = format!("file {}\n", i % 50);
+            fs::write(dir.join(format!("file{}.txt", i)), &content)?;
+            let path = PathBuf::from(format!("files/dir{}/file{}.txt", i % 7, i));
+            expected.insert(path, crate::repository::objects::hash_object(content.as_bytes(), ObjectType::Blob));
+        }
+        
+        let mut index = Index::new();
+        let added = index.add_directory(repo_path, repo_path.join("files"), &FsObjectStore::new(&objects_dir), &Config::default())?;
+        
+        assert_eq!(added.len(), 300);
+        let mut sorted = added.clone();
+        sorted.sort();
+        assert_eq!(added, sorted);
+        let actual: HashMap<PathBuf, String> = index
+            .get_entries()
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.object_id.clone()))
+            .collect();
+        assert_eq!(actual, expected);
+        for object_id in expected.values() {
+            assert!(crate::repository::objects::read_object(&objects_dir, object_id).is_ok());
+        }
+        
+        Ok(())
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_add_symlink() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo_path = temp_dir.path();
+        let objects_dir = repo_path.join("objects");
+        fs::create_dir_all(&objects_dir)?;
+        
+        std::os::unix::fs::symlink("missing-target.txt", repo_path.join("link"))?;
+        assert_eq!(read_worktree_content(repo_path.join("link"), &Config::default())?, b"missing-target.txt");
+        
+        let mut index = Index::new();
+        index.add_directory(repo_path, repo_path, &FsObjectStore::new(&objects_dir), &Config::default())?;
+        let entry = index.get_entries().get(Path::new("link")).unwrap();
+        assert_eq!(entry.mode, MODE_SYMLINK);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_readd_unchanged_file_skips_compression() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo_path = temp_dir.path();
+        let objects_dir = repo_path.join("objects");
+        fs::create_dir_all(&objects_dir)?;
+        fs::write(repo_path.join("large.bin"), vec![b'x'; 4 * 1024 * 1024])?;
+        // Counts the objects handed over to be compressed and stored
+        let store = CountingStore::new(FsObjectStore::new(&objects_dir));
+        
+        let mut index = Index::new();
+        index.add_directory(repo_path, repo_path.join("large.bin"), &store, &Config::default())?;
+        assert_eq!(store.writes.load(Ordering::SeqCst), 1);
+        
+        index.add_directory(repo_path, repo_path.join("large.bin"), &store, &Config::default())?;
+        assert_eq!(store.writes.load(Ordering::SeqCst), 1);
+        assert!(store.exists(&index.get_entries()[Path::new("large.bin")].object_id));
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use super::Repository;
+
+/// Environment variable naming the git dir, as `--git-dir` does
+pub const GIT_DIR_ENV: &str = "GIT_DIR";
+/// Environment variable naming the working tree, as `--work-tree` does
+pub const WORK_TREE_ENV: &str = "GIT_WORK_TREE";
+
+/// Where a command finds its repository: the directory it runs in, plus the `--git-dir` and
+/// `--work-tree` overrides. Relative overrides are taken from `current_dir`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    pub current_dir: PathBuf,
+    /// The git dir to use instead of searching up from `current_dir`
+    pub git_dir: Option<PathBuf>,
+    /// The working tree to use instead of the one belonging to the git dir
+    pub work_tree: Option<PathBuf>,
+}
+
+impl Location {
+    /// The repository containing `current_dir`, with no overrides
+    pub fn at<P: AsRef<Path>>(current_dir: P) -> Self {
+        Self { current_dir: current_dir.as_ref().to_path_buf(), ..Default::default() }
+    }
+
+    /// The location of a command run in the process's current directory. Overrides given on
+    /// the command line win over `GIT_DIR` and `GIT_WORK_TREE`.
+    pub fn from_env(git_dir: Option<&Path>, work_tree: Option<&Path>) -> Result<Self> {
+        let from_env = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        Ok(Self {
+            current_dir: env::current_dir()?,
+            git_dir: git_dir.map(Path::to_path_buf).or_else(|| from_env(GIT_DIR_ENV)),
+            work_tree: work_tree.map(Path::to_path_buf).or_else(|| from_env(WORK_TREE_ENV)),
+        })
+    }
+
+    /// Open the repository. A `--git-dir` is used as is; otherwise it is searched for upwards
+    /// from `current_dir`. Without `--work-tree` the working tree is the directory holding
+    /// the git dir, or none for a bare repository.
+    pub fn open(&self) -> Result<Repository> {
+        let work_tree = self.work_tree.as_ref().map(|work_tree| self.current_dir.join(work_tree));
+        match &self.git_dir {
+            Some(git_dir) => Repository::open_git_dir(self.current_dir.join(git_dir), work_tree.as_deref()),
+            None => {
+                let repo = Repository::open(&self.current_dir)?;
+                match work_tree {
+                    Some(work_tree) => Repository::open_git_dir(&repo.git_dir, Some(&work_tree)),
+                    None => Ok(repo),
+                }
+            }
+        }
+    }
+
+    /// The directory pathspecs given to a command are relative to: `current_dir` when it is
+    /// inside `repo`'s working tree, otherwise the top of the working tree
+    pub fn pathspec_dir(&self, repo: &Repository) -> PathBuf {
+        match self.current_dir.canonicalize() {
+            Ok(current_dir) if current_dir.starts_with(&repo.path) => current_dir,
+            _ => repo.path.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::CommitOptions;
+    use crate::commands::{add, commit};
+    use crate::repository::{objects, refs};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_add_and_commit_with_git_dir_outside_current_dir() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let project = temp_dir.path().join("project");
+        let elsewhere = temp_dir.path().join("elsewhere");
+        fs::create_dir_all(&project)?;
+        fs::create_dir_all(&elsewhere)?;
+        Repository::init(&project)?;
+        fs::write(project.join("hello.txt"), "hello\n")?;
+
+        // Run from a directory that is not inside any repository
+        let location = Location {
+            current_dir: elsewhere.clone(),
+            git_dir: Some(PathBuf::from("../project/.git")),
+            work_tree: None,
+        };
+        assert!(Repository::open(&elsewhere).is_err());
+        add::execute(&location, &["hello.txt".to_string()], false)?;
+        commit::execute(&location, &CommitOptions { message: Some("Add hello"), ..Default::default() })?;
+
+        let repo = Repository::open(&project)?;
+        let head = refs::head_commit(&repo.git_dir)?.unwrap();
+        let commit = objects::read_commit(repo.objects.as_ref(), &head)?;
+        assert_eq!(commit.message, "Add hello");
+        let entry = objects::find_tree_entry(repo.objects.as_ref(), &commit.tree, Path::new("hello.txt"))?.unwrap();
+        assert_eq!(repo.read_object(&entry.oid)?.data, b"hello\n");
+        assert!(fs::read_dir(&elsewhere)?.next().is_none());
+
+        // A working tree given on its own replaces the one the git dir belongs to
+        let location = Location { work_tree: Some(elsewhere.clone()), ..Location::at(&project) };
+        let repo = location.open()?;
+        assert_eq!(repo.path, elsewhere.canonicalize()?);
+        assert_eq!(repo.git_dir, project.join(".git").canonicalize()?);
+        assert_eq!(location.pathspec_dir(&repo), repo.path);
+
+        Ok(())
+    }
+}
@@ -1,29 +1,143 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 
 pub mod objects;
 pub mod index;
 pub mod refs;
 pub mod bundle;
+pub mod diff;
+pub mod ignore;
 pub mod config;
+pub mod http;
 pub mod pack;
+pub mod store;
+pub mod commit_graph;
+pub mod hooks;
+pub mod reflog;
+pub mod location;
 
-// Utility function for consistent path normalization across the entire system
+// Utility function for consistent path normalization across the entire system.
+// Index keys and tree paths are stored in this form: components joined by `/` on every
+// platform, with `.` dropped and `..` resolved logically (without touching the filesystem).
 pub fn normalize_path(path: &Path) -> PathBuf {
-    let path_str = path.to_string_lossy();
-    if path_str.starts_with("./") {
-        PathBuf::from(&path_str[2..])
-    } else {
-        path.to_path_buf()
+    let mut root = OsString::new();
+    let mut parts: Vec<&OsStr> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => root = prefix.as_os_str().to_os_string(),
+            Component::RootDir => root.push("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if parts.last().is_some_and(|last| *last != "..") {
+                    parts.pop();
+                } else if root.is_empty() {
+                    parts.push(OsStr::new(".."));
+                }
+            }
+            Component::Normal(part) => parts.push(part),
+        }
+    }
+    let mut normalized = root;
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            normalized.push("/");
+        }
+        normalized.push(part);
+    }
+    PathBuf::from(normalized)
+}
+
+// Turn a stored `/`-separated path back into one using the platform's separator
+pub fn native_path(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+// The bytes a path is stored as in tree objects and the index. Unix paths are bytes already,
+// so names that are not valid UTF-8 are kept as they are; elsewhere they are converted lossily.
+pub fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(path.as_os_str().as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        match path.to_string_lossy() {
+            Cow::Borrowed(path) => Cow::Borrowed(path.as_bytes()),
+            Cow::Owned(path) => Cow::Owned(path.into_bytes()),
+        }
+    }
+}
+
+// The path stored as `bytes` in a tree object or the index, the reverse of `path_to_bytes`
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+// Whether a file name is `.git`, which working trees never track. Case is ignored, as git
+// does, since `.GIT` is the same directory on case-insensitive filesystems.
+pub fn is_git_dir_name(name: &OsStr) -> bool {
+    name.eq_ignore_ascii_case(".git")
+}
+
+// `path` with its longest existing prefix resolved, so a path that does not exist yet still
+// comes out in the same form as canonicalized ones
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|ancestor| {
+            let resolved = fs::canonicalize(ancestor).ok()?;
+            Some(resolved.join(path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// What HEAD points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// A branch, by name, that has commits
+    Branch(String),
+    /// A commit, by id, checked out without a branch
+    Detached(String),
+    /// A branch, by name, that has no commits yet, such as the first branch of an empty clone
+    Unborn(String),
+}
+
+impl Head {
+    /// The name of the branch HEAD is on, born or not; none when detached
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            Head::Branch(name) | Head::Unborn(name) => Some(name),
+            Head::Detached(_) => None,
+        }
     }
 }
 
 pub struct Repository {
     pub path: PathBuf,
+    /// The git dir of this working tree, holding its HEAD and index
     pub git_dir: PathBuf,
+    /// The git dir shared by all working trees, holding objects, refs and config; the same
+    /// as `git_dir` except in a linked working tree made by `worktree add`
+    pub common_dir: PathBuf,
     pub index: index::Index,
     pub config: config::Config,
+    /// Where objects are read and written; `.git/objects` unless replaced
+    pub objects: Box<dyn store::ObjectStore>,
+    /// Parents and generation numbers of the commits read so far
+    commit_graph: commit_graph::CommitGraph,
 }
 
 impl Repository {
@@ -31,112 +145,390 @@ impl Repository {
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = fs::canonicalize(path)?;
         let git_dir = find_git_dir(&path)?;
+        Self::open_git_dir(git_dir, None)
+    }
+    
+    /// Open the repository whose git dir is exactly `git_dir`, as `--git-dir` names it, with
+    /// its working tree at `work_tree`
+    pub fn open_git_dir<P: AsRef<Path>>(git_dir: P, work_tree: Option<&Path>) -> Result<Self> {
+        let git_dir = fs::canonicalize(git_dir.as_ref())
+            .ok()
+            .filter(|git_dir| is_bare_git_dir(git_dir))
+            .with_context(|| format!("not a git repository: '{}'", git_dir.as_ref().display()))?;
         
-        let index = index::Index::load(&git_dir.join("index"))?;
-        let config = config::Config::open(&git_dir.join("config"))?;
+        // Without an explicit working tree it is the directory holding `.git`, even when opened
+        // from a subdirectory; a linked working tree's git dir records where its `.git` file
+        // is. A bare repository has no working tree, so the repository path is the git dir itself
+        let linked_work_tree = fs::read_to_string(git_dir.join("gitdir"))
+            .ok()
+            .and_then(|dot_git| Path::new(dot_git.trim()).parent().map(Path::to_path_buf));
+        let path = match (work_tree, linked_work_tree, git_dir.parent()) {
+            (Some(work_tree), _, _) => fs::canonicalize(work_tree)
+                .with_context(|| format!("invalid working tree: '{}'", work_tree.display()))?,
+            (None, Some(work_tree), _) => work_tree,
+            (None, None, Some(work_tree)) if git_dir.ends_with(".git") => work_tree.to_path_buf(),
+            _ => git_dir.clone(),
+        };
+        
+        let common_dir = refs::common_dir(&git_dir);
+        let index = index::Index::load(git_dir.join("index"))?;
+        let config = config::Config::open(&common_dir.join("config"))?;
         
         Ok(Self {
             path,
+            objects: Box::new(store::FsObjectStore::new(common_dir.join("objects"))),
+            common_dir,
+            git_dir,
+            index,
+            config,
+            commit_graph: Default::default(),
+        })
+    }
+    
+    /// Open the bare repository whose git dir is exactly `path`, without searching parent
+    /// directories or treating a `.git` subdirectory as the repository
+    pub fn open_bare<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let git_dir = fs::canonicalize(path)?;
+        if !is_bare_git_dir(&git_dir) {
+            anyhow::bail!("'{}' is not a bare git repository", git_dir.display());
+        }
+        
+        let index = index::Index::load(git_dir.join("index"))?;
+        let config = config::Config::open(&git_dir.join("config"))?;
+        
+        Ok(Self {
+            path: git_dir.clone(),
+            objects: Box::new(store::FsObjectStore::new(git_dir.join("objects"))),
+            common_dir: git_dir.clone(),
             git_dir,
             index,
             config,
+            commit_graph: Default::default(),
         })
     }
     
     /// Initialize a new Git repository
     pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let objects = Box::new(store::FsObjectStore::new(path.join(".git/objects")));
+        Self::init_with_object_store(path, objects)
+    }
+    
+    /// Initialize a new Git repository whose objects, including the initial commit, live in
+    /// `objects` rather than `.git/objects`. Refs, the index and config are still files, so
+    /// a `store::MemObjectStore` gives a repository that writes no objects to disk.
+    pub fn init_with_object_store<P: AsRef<Path>>(path: P, objects: Box<dyn store::ObjectStore>) -> Result<Self> {
         let path = fs::canonicalize(path)?;
         let git_dir = path.join(".git");
         
-        // Create directory structure
-        fs::create_dir_all(&git_dir)?;
-        fs::create_dir_all(git_dir.join("objects"))?;
-        fs::create_dir_all(git_dir.join("refs/heads"))?;
-        fs::create_dir_all(git_dir.join("refs/tags"))?;
-        
-        // Create initial HEAD file
-        fs::write(
-            git_dir.join("HEAD"),
-            "ref: refs/heads/master\n",
-        )?;
-        
-        // Create empty config
-        fs::write(
-            git_dir.join("config"),
-            "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = false\n",
-        )?;
-        
-        // Create description
-        fs::write(
-            git_dir.join("description"),
-            "Unnamed repository; edit this file 'description' to name the repository.\n",
-        )?;
-        
-        // Ensure the empty tree object exists in the object store
-        // The hash for an empty tree is "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
-        // Data for an empty tree is an empty byte array.
-        objects::write_object(
-            &git_dir.join("objects"),
-            &[], // Empty data for an empty tree
-            "tree"
-        )?;
-        
-        // Create initial master branch with a null commit
-        let null_commit = objects::write_commit(
-            &git_dir.join("objects"),
-            "4b825dc642cb6eb9a060e54bf8d69288fbee4904", // Empty tree
-            &[],
-            "Initial commit",
-            "Rust-Git <user@example.com>",
-        )?;
-        
-        // Create the master branch reference
-        fs::write(
-            git_dir.join("refs/heads/master"),
-            format!("{}\n", null_commit),
-        )?;
+        init_git_dir(&git_dir, false, objects.as_ref())?;
         
         let index = index::Index::new();
         let config = config::Config::open(&git_dir.join("config"))?;
         
         Ok(Self {
             path,
+            objects,
+            common_dir: git_dir.clone(),
             git_dir,
             index,
             config,
+            commit_graph: Default::default(),
         })
     }
     
-    /// Get the current branch name
-    pub fn current_branch(&self) -> Result<String> {
+    /// Initialize a new bare Git repository directly in `path`, without a working tree
+    pub fn init_bare<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let git_dir = fs::canonicalize(path)?;
+        let objects = store::FsObjectStore::new(git_dir.join("objects"));
+        
+        init_git_dir(&git_dir, true, &objects)?;
+        
+        let index = index::Index::new();
+        let config = config::Config::open(&git_dir.join("config"))?;
+        
+        Ok(Self {
+            path: git_dir.clone(),
+            objects: Box::new(objects),
+            common_dir: git_dir.clone(),
+            git_dir,
+            index,
+            config,
+            commit_graph: Default::default(),
+        })
+    }
+    
+    /// Whether this repository is bare (has no working tree)
+    pub fn is_bare(&self) -> bool {
+        self.path == self.git_dir
+    }
+    
+    /// Whether `path` (absolute, or relative to the working tree) is in a git dir rather than
+    /// the working tree: this repository's own git dir or common dir, wherever they are, or
+    /// any `.git` inside the working tree.
+    ///
+    /// The directories leading to `path` are resolved, so `sub/../.git` and symlinked
+    /// directories are caught, but `path` itself is not: a symlink to a file in the git dir
+    /// is an ordinary working tree file.
+    pub fn is_inside_git_dir(&self, path: &Path) -> bool {
+        let path = normalize_path(&self.path.join(path));
+        let resolved = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => canonicalize_existing(parent).join(name),
+            _ => path,
+        };
+        if resolved.starts_with(&self.git_dir) || resolved.starts_with(&self.common_dir) {
+            return true;
+        }
+        resolved.strip_prefix(&self.path).is_ok_and(|relative| {
+            relative.components().any(|component| is_git_dir_name(component.as_os_str()))
+        })
+    }
+    
+    /// Read HEAD: the branch it is on, and whether that branch has commits yet, or the commit
+    /// it is detached at
+    pub fn head(&self) -> Result<Head> {
         let head_content = fs::read_to_string(self.git_dir.join("HEAD"))?;
-        if head_content.starts_with("ref: refs/heads/") {
-            Ok(head_content
-                .trim_start_matches("ref: refs/heads/")
-                .trim_end()
-                .to_string())
+        let Some(ref_name) = head_content.strip_prefix("ref: ") else {
+            return Ok(Head::Detached(head_content.trim().to_string()));
+        };
+        let ref_name = ref_name.trim();
+        let branch = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name).to_string();
+        if refs::read_ref(&self.git_dir, ref_name).is_ok() {
+            Ok(Head::Branch(branch))
         } else {
-            anyhow::bail!("HEAD is detached")
+            Ok(Head::Unborn(branch))
+        }
+    }
+    
+    /// Get the current branch name, which may have no commits yet; fails when HEAD is detached
+    pub fn current_branch(&self) -> Result<String> {
+        match self.head()? {
+            Head::Branch(name) | Head::Unborn(name) => Ok(name),
+            Head::Detached(_) => anyhow::bail!("HEAD is detached"),
         }
     }
 
+    /// Read an object from this repository's object store
+    pub fn read_object(&self, oid: &str) -> Result<objects::RawObject> {
+        self.objects.read(oid)
+    }
+
+    /// The parents of commit `oid`; none for a root commit or a shallow clone's boundary
+    pub fn parents(&self, oid: &str) -> Vec<String> {
+        self.commit_graph.parents(self, oid)
+    }
+
+    /// The generation number of commit `oid`: 1 for a root commit, otherwise one more than
+    /// its highest parent. 0 when the commit cannot be read.
+    pub fn generation(&self, oid: &str) -> u64 {
+        self.commit_graph.generation(self, oid)
+    }
+
+    /// Walk the history reachable from `start` (any revision `refs::resolve_revision` accepts),
+    /// most recently committed first. Each commit is yielded once; a shallow clone's history
+    /// ends at its boundary commits.
+    pub fn log(&self, start: &str) -> impl Iterator<Item = Result<objects::Commit>> + '_ {
+        let mut log = Log {
+            repo: self,
+            queue: BinaryHeap::new(),
+            found: HashMap::new(),
+            seen: HashSet::new(),
+            shallow: objects::shallow_commits(&self.common_dir),
+            pending_error: None,
+            next_sequence: 0,
+        };
+        match refs::resolve_revision(&self.git_dir, start) {
+            Ok(commit_id) => log.push(commit_id),
+            Err(error) => log.pending_error = Some(error),
+        }
+        log
+    }
+
     /// Repack all loose objects into a pack file
     pub fn repack(&self) -> Result<()> {
-        let objects_dir = self.git_dir.join("objects");
+        let objects_dir = self.common_dir.join("objects");
         pack::create_pack(&objects_dir)
     }
 
-    /// Garbage collect loose objects and pack reachable ones
-    pub fn gc(&self) -> Result<()> {
-        // In a more complete implementation, gc would first determine which objects are
-        // truly unreachable by traversing the commit graph from all refs.
-        // For now, we treat all loose objects as reachable and pack them.
-        // The cleanup of loose objects is now handled inside create_pack.
-        self.repack()
+    /// The git dirs of every working tree sharing this repository: the common dir, holding
+    /// the main working tree's HEAD and index, then each linked one under `worktrees/`
+    pub fn worktree_git_dirs(&self) -> Vec<PathBuf> {
+        let mut git_dirs = vec![self.common_dir.clone()];
+        if let Ok(entries) = fs::read_dir(self.common_dir.join("worktrees")) {
+            git_dirs.extend(entries.filter_map(Result::ok).map(|entry| entry.path()));
+        }
+        git_dirs
+    }
+
+    /// The objects that refs and the HEAD of every working tree point at, from which
+    /// reachability is computed
+    pub fn ref_tips(&self) -> Result<Vec<String>> {
+        let mut tips: Vec<String> = refs::list_refs(&self.git_dir)?
+            .into_iter()
+            .map(|(_, object_id)| object_id)
+            .collect();
+        for git_dir in self.worktree_git_dirs() {
+            if let Ok(head) = refs::get_head_commit(&git_dir) {
+                tips.push(head);
+            }
+        }
+        Ok(tips)
+    }
+
+    // Objects reachable from refs, HEADs and the commits reflogs remember, plus the blobs staged
+    // in every working tree's index, which a commit in progress is about to reference
+    fn reachable_objects(&self) -> Result<HashSet<String>> {
+        let mut tips = self.ref_tips()?;
+        tips.extend(reflog::reflog_objects(&self.git_dir)?);
+        let mut reachable = pack::reachable_objects(&self.common_dir.join("objects"), &tips)?;
+        reachable.extend(self.index.get_entries().values().map(|entry| entry.object_id.clone()));
+        for git_dir in self.worktree_git_dirs() {
+            let index = index::Index::load(git_dir.join("index"))?;
+            reachable.extend(index.get_entries().values().map(|entry| entry.object_id.clone()));
+        }
+        Ok(reachable)
+    }
+
+    /// Delete unreachable loose objects last modified before `expire_before`, returning their ids
+    pub fn prune(&self, expire_before: SystemTime) -> Result<Vec<String>> {
+        let objects_dir = self.common_dir.join("objects");
+        pack::prune_loose_objects(&objects_dir, &self.reachable_objects()?, expire_before)
+    }
+
+    /// Garbage collect: delete unreachable loose objects last modified before `expire_before`
+    /// and pack the reachable ones. More recent unreachable objects stay loose, since an
+    /// operation in progress may still need them; a later gc expires them.
+    ///
+    /// Returns the ids of the deleted objects.
+    pub fn gc(&self, expire_before: SystemTime) -> Result<Vec<String>> {
+        self.gc_with_options(&pack::PackOptions::default(), expire_before)
+    }
+
+    /// Like `gc`, packing with the given compression and delta window
+    pub fn gc_with_options(&self, options: &pack::PackOptions, expire_before: SystemTime) -> Result<Vec<String>> {
+        let objects_dir = self.common_dir.join("objects");
+        let reachable = self.reachable_objects()?;
+        let pruned = pack::prune_loose_objects(&objects_dir, &reachable, expire_before)?;
+        pack::create_pack_of(&objects_dir, options, |object_id| reachable.contains(object_id))?;
+        Ok(pruned)
     }
 }
 
-/// Find the .git directory by looking up the directory tree
+/// Iterator behind `Repository::log`
+struct Log<'a> {
+    repo: &'a Repository,
+    /// Commits waiting to be yielded as (committer time, sequence number), newest first and,
+    /// among equal times, the most recently found first
+    queue: BinaryHeap<(i64, u64)>,
+    /// Waiting commits by sequence number
+    found: HashMap<u64, objects::Commit>,
+    seen: HashSet<String>,
+    shallow: HashSet<String>,
+    pending_error: Option<anyhow::Error>,
+    next_sequence: u64,
+}
+
+impl Log<'_> {
+    fn push(&mut self, commit_id: String) {
+        if !self.seen.insert(commit_id.clone()) {
+            return;
+        }
+        match objects::read_commit(self.repo.objects.as_ref(), &commit_id) {
+            Ok(commit) => {
+                self.next_sequence += 1;
+                self.queue.push((commit.committer.seconds(), self.next_sequence));
+                self.found.insert(self.next_sequence, commit);
+            }
+            Err(error) => {
+                self.pending_error.get_or_insert(error);
+            }
+        }
+    }
+}
+
+impl Iterator for Log<'_> {
+    type Item = Result<objects::Commit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.pending_error.take() {
+            return Some(Err(error));
+        }
+        let (_, sequence) = self.queue.pop()?;
+        let commit = self.found.remove(&sequence)?;
+        if !self.shallow.contains(&commit.oid) {
+            for parent in &commit.parents {
+                self.push(parent.clone());
+            }
+        }
+        Some(Ok(commit))
+    }
+}
+
+/// Create the on-disk layout of a git directory (objects, refs, HEAD, config), writing the
+/// initial objects to `objects`
+fn init_git_dir(git_dir: &Path, bare: bool, objects: &dyn store::ObjectStore) -> Result<()> {
+    // Create directory structure
+    fs::create_dir_all(git_dir)?;
+    fs::create_dir_all(git_dir.join("objects"))?;
+    fs::create_dir_all(git_dir.join("refs/heads"))?;
+    fs::create_dir_all(git_dir.join("refs/tags"))?;
+    
+    // Create initial HEAD file, on the configured default branch
+    let branch = config::Config::global()?.default_branch()?;
+    fs::write(
+        git_dir.join("HEAD"),
+        format!("ref: refs/heads/{}\n", branch),
+    )?;
+    
+    // Create empty config
+    fs::write(
+        git_dir.join("config"),
+        format!("[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = {}\n", bare),
+    )?;
+    
+    // Create description
+    fs::write(
+        git_dir.join("description"),
+        "Unnamed repository; edit this file 'description' to name the repository.\n",
+    )?;
+    
+    // Ensure the empty tree object exists in the object store
+    // The hash for an empty tree is "4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+    // Data for an empty tree is an empty byte array.
+    objects.write(
+        objects::ObjectType::Tree,
+        &[], // Empty data for an empty tree
+    )?;
+    
+    // Create the initial branch with a null commit
+    let signature = objects::Signature::now("Rust-Git <user@example.com>");
+    let null_commit = objects::write_commit(
+        objects,
+        "4b825dc642cb6eb9a060e54bf8d69288fbee4904", // Empty tree
+        &[],
+        "Initial commit",
+        &signature,
+        &signature,
+    )?;
+    
+    // Create the branch reference
+    let branch_path = git_dir.join("refs/heads").join(&branch);
+    if let Some(parent) = branch_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(branch_path, format!("{}\n", null_commit))?;
+    
+    Ok(())
+}
+
+/// Check whether a directory is itself a git dir (as in a bare repository, or the git dir of
+/// a linked working tree, whose objects are in the common dir)
+fn is_bare_git_dir(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && refs::common_dir(dir).join("objects").is_dir()
+}
+
+/// Find the .git directory by looking up the directory tree. A linked working tree has a
+/// `.git` file instead, whose `gitdir:` line names its git dir.
 fn find_git_dir(start_path: &Path) -> Result<PathBuf> {
     let mut current = start_path.to_path_buf();
     
@@ -145,6 +537,19 @@ fn find_git_dir(start_path: &Path) -> Result<PathBuf> {
         if git_dir.is_dir() {
             return Ok(git_dir);
         }
+        if git_dir.is_file() {
+            let content = fs::read_to_string(&git_dir)?;
+            let linked = content
+                .trim()
+                .strip_prefix("gitdir:")
+                .with_context(|| format!("invalid gitfile format: '{}'", git_dir.display()))?;
+            return Ok(current.join(linked.trim()));
+        }
+        
+        // Bare repositories keep HEAD and objects at the top level
+        if is_bare_git_dir(&current) {
+            return Ok(current);
+        }
         
         if !current.pop() {
             anyhow::bail!("Not a git repository (or any of the parent directories)")
@@ -155,9 +560,29 @@ fn find_git_dir(start_path: &Path) -> Result<PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env;
-    use std::fs::File;
-    use std::io::Write;
+    
+    #[test]
+    fn test_normalize_path() {
+        for (input, expected) in [
+            ("./a/../b", "b"),
+            ("a/./b/c", "a/b/c"),
+            ("a/b/../../c", "c"),
+            ("../a/./b", "../a/b"),
+            ("a/..", ""),
+            ("/abs/./x/../y", "/abs/y"),
+            ("dir/file.txt", "dir/file.txt"),
+        ] {
+            assert_eq!(normalize_path(Path::new(input)), PathBuf::from(expected), "{}", input);
+        }
+    }
+    
+    #[cfg(windows)]
+    #[test]
+    fn test_normalize_path_backslashes() {
+        assert_eq!(normalize_path(Path::new(r"a\b\..\c")), PathBuf::from("a/c"));
+        assert_eq!(normalize_path(Path::new(r".\dir\file.txt")), PathBuf::from("dir/file.txt"));
+        assert_eq!(native_path(Path::new("dir/file.txt")), PathBuf::from(r"dir\file.txt"));
+    }
     
     fn setup_test_repo() -> Result<(tempfile::TempDir, Repository)> {
         let temp_dir = tempfile::tempdir()?;
@@ -167,7 +592,7 @@ mod tests {
     
     #[test]
     fn test_init() -> Result<()> {
-        let (temp_dir, repo) = setup_test_repo()?;
+        let (_temp_dir, repo) = setup_test_repo()?;
         
         // Check if .git directory exists
         assert!(repo.git_dir.exists());
@@ -187,6 +612,45 @@ mod tests {
         Ok(())
     }
     
+    #[test]
+    fn test_init_on_default_branch_from_env() -> Result<()> {
+        // The variable would reach every test running in parallel, so the check runs in a
+        // child process of this test binary that runs only this test
+        if std::env::var_os(config::DEFAULT_BRANCH_ENV).is_none() {
+            let status = std::process::Command::new(std::env::current_exe()?)
+                .args(["--exact", "repository::tests::test_init_on_default_branch_from_env", "--quiet"])
+                .env(config::DEFAULT_BRANCH_ENV, "main")
+                .stdout(std::process::Stdio::null())
+                .status()?;
+            assert!(status.success());
+            return Ok(());
+        }
+        
+        let (_temp_dir, repo) = setup_test_repo()?;
+        assert_eq!(fs::read_to_string(repo.git_dir.join("HEAD"))?, "ref: refs/heads/main\n");
+        assert!(repo.git_dir.join("refs/heads/main").is_file());
+        assert!(!repo.git_dir.join("refs/heads/master").exists());
+        assert_eq!(repo.current_branch()?, "main");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_default_branch_from_config() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, "[init]\n\tdefaultBranch = trunk\n")?;
+        
+        if std::env::var_os(config::DEFAULT_BRANCH_ENV).is_none() {
+            assert_eq!(config::Config::open(&config_path)?.default_branch()?, "trunk");
+            assert_eq!(config::Config::default().default_branch()?, "master");
+        }
+        fs::write(&config_path, "[init]\n\tdefaultBranch = bad name\n")?;
+        assert!(config::Config::open(&config_path)?.default_branch().is_err());
+        
+        Ok(())
+    }
+    
     #[test]
     fn test_open() -> Result<()> {
         let (temp_dir, _) = setup_test_repo()?;
@@ -221,6 +685,113 @@ mod tests {
         // Check if they match after normalization
         assert_eq!(normalized_git_dir, normalized_repo_git_dir);
         
+        // Opening from the subdirectory finds the same working tree
+        assert_eq!(Repository::open(&subdir)?.path, repo.path);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_init_bare() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let repo = Repository::init_bare(&temp_dir)?;
+        
+        // Layout lives directly in the target directory, without a .git subdirectory
+        assert!(!temp_dir.path().join(".git").exists());
+        assert!(temp_dir.path().join("objects").is_dir());
+        assert!(temp_dir.path().join("refs/heads").is_dir());
+        assert!(temp_dir.path().join("HEAD").is_file());
+        assert!(repo.is_bare());
+        
+        let config = fs::read_to_string(temp_dir.path().join("config"))?;
+        assert!(config.contains("bare = true"));
+        
+        // Opening the bare repository finds it as its own git dir
+        let opened = Repository::open(&temp_dir)?;
+        assert!(opened.is_bare());
+        assert_eq!(opened.git_dir, fs::canonicalize(temp_dir.path())?);
+        assert_eq!(opened.current_branch()?, "master");
+        
+        // A regular repository is never mistaken for a bare one
+        let work_dir = tempfile::tempdir()?;
+        Repository::init(&work_dir)?;
+        assert!(!Repository::open(&work_dir)?.is_bare());
+        
+        // open_bare accepts only the git dir itself
+        let opened = Repository::open_bare(&temp_dir)?;
+        assert!(opened.is_bare());
+        assert_eq!(opened.git_dir, fs::canonicalize(temp_dir.path())?);
+        assert!(Repository::open_bare(&work_dir).is_err());
+        
         Ok(())
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_log_follows_parents() -> Result<()> {
+        let (_temp_dir, repo) = setup_test_repo()?;
+        let store = repo.objects.as_ref();
+        let signature = objects::Signature::parse("Test User <test@example.com> 1700000000 +0000").unwrap();
+        
+        let root = refs::get_head_commit(&repo.git_dir)?;
+        let tree = objects::read_commit_tree(store, &root)?;
+        let second = objects::write_commit(store, &tree, &[&root], "Second", &signature, &signature)?;
+        let third = objects::write_commit(store, &tree, &[&second], "Third\n\nWith a body", &signature, &signature)?;
+        refs::update_ref(&repo.git_dir, "refs/heads/master", &third)?;
+        
+        let history = repo.log("HEAD").collect::<Result<Vec<_>>>()?;
+        let oids: Vec<&str> = history.iter().map(|commit| commit.oid.as_str()).collect();
+        assert_eq!(oids, vec![third.as_str(), second.as_str(), root.as_str()]);
+        assert_eq!(history[0].parents, vec![second.clone()]);
+        assert_eq!(history[1].parents, vec![root]);
+        assert!(history[2].parents.is_empty());
+        assert_eq!(history[0].message, "Third\n\nWith a body");
+        assert_eq!(history[0].author, signature);
+        assert_eq!(history[0].tree, tree);
+        
+        assert!(repo.log("no-such-branch").next().unwrap().is_err());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_head() -> Result<()> {
+        let (_temp_dir, repo) = setup_test_repo()?;
+        assert_eq!(repo.head()?, Head::Branch("master".to_string()));
+        assert_eq!(repo.current_branch()?, "master");
+        
+        let commit = refs::get_head_commit(&repo.git_dir)?;
+        fs::write(repo.git_dir.join("HEAD"), format!("{}\n", commit))?;
+        assert_eq!(repo.head()?, Head::Detached(commit));
+        assert_eq!(repo.head()?.branch(), None);
+        assert!(repo.current_branch().is_err());
+        
+        fs::write(repo.git_dir.join("HEAD"), "ref: refs/heads/orphan\n")?;
+        assert_eq!(repo.head()?, Head::Unborn("orphan".to_string()));
+        assert_eq!(repo.head()?.branch(), Some("orphan"));
+        assert_eq!(repo.current_branch()?, "orphan");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_read_object_by_repository() -> Result<()> {
+        let (_temp_dir, repo) = setup_test_repo()?;
+        let blob_id = objects::write_blob(repo.objects.as_ref(), b"blob content\n")?;
+        let blob = repo.read_object(&blob_id)?;
+        assert_eq!(blob.object_type, objects::ObjectType::Blob);
+        assert_eq!(blob.data, b"blob content\n");
+        
+        let head = refs::get_head_commit(&repo.git_dir)?;
+        let commit = repo.read_object(&head)?;
+        assert_eq!(commit.object_type, objects::ObjectType::Commit);
+        let objects::Object::Commit(parsed) = commit.parse()? else {
+            panic!("expected a commit");
+        };
+        assert_eq!(parsed.oid, head);
+        assert_eq!(parsed.tree, "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+        
+        assert!(repo.read_object("0000000000000000000000000000000000000000").is_err());
+        
+        Ok(())
+    }
+}
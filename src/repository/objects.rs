@@ -1,18 +1,20 @@
 use anyhow::{Context, Result};
 use flate2::write::ZlibEncoder;
-use flate2::read::ZlibDecoder;
+use flate2::bufread::{DeflateDecoder, ZlibDecoder};
 use flate2::Compression;
 use sha1::{Sha1, Digest};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str;
 use chrono::Utc;
 use hex;
 use super::Repository;
+use super::store::ObjectStore;
 
 // Hash an object and return its ID
-pub fn hash_object(data: &[u8], object_type: &str) -> String {
+pub fn hash_object(data: &[u8], object_type: ObjectType) -> String {
     let header = format!("{} {}", object_type, data.len());
     let mut hasher = Sha1::new();
     hasher.update(header.as_bytes());
@@ -23,70 +25,271 @@ pub fn hash_object(data: &[u8], object_type: &str) -> String {
     hex::encode(result)
 }
 
-// Write a blob object to the object store
-pub fn write_blob<P: AsRef<Path>>(objects_dir: P, data: &[u8]) -> Result<String> {
-    write_object(objects_dir, data, "blob")
+// Write a blob object to the object store. Content the store already has is only hashed,
+// so re-adding an unchanged file does not compress it again.
+pub fn write_blob(store: &dyn ObjectStore, data: &[u8]) -> Result<String> {
+    let object_id = hash_object(data, ObjectType::Blob);
+    if store.exists(&object_id) {
+        return Ok(object_id);
+    }
+    store.write(ObjectType::Blob, data)
+}
+
+/// The type of an object, as named in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectType {
+    /// Every object type
+    pub const ALL: [ObjectType; 4] = [ObjectType::Blob, ObjectType::Tree, ObjectType::Commit, ObjectType::Tag];
+
+    /// The name used in object headers and tag objects
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectType::Blob => "blob",
+            ObjectType::Tree => "tree",
+            ObjectType::Commit => "commit",
+            ObjectType::Tag => "tag",
+        }
+    }
+
+    /// The type number of an undeltified pack entry holding this type
+    pub fn type_id(&self) -> u8 {
+        match self {
+            ObjectType::Commit => 1,
+            ObjectType::Tree => 2,
+            ObjectType::Blob => 3,
+            ObjectType::Tag => 4,
+        }
+    }
+
+    /// The type of an undeltified pack entry with type number `type_id`; none for the delta
+    /// types and unused numbers
+    pub fn from_type_id(type_id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|object_type| object_type.type_id() == type_id)
+    }
+}
+
+impl std::str::FromStr for ObjectType {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|object_type| object_type.as_str() == name)
+            .with_context(|| format!("invalid object type \"{}\"", name))
+    }
+}
+
+impl std::fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-// Write an object to the object store
-pub fn write_object<P: AsRef<Path>>(objects_dir: P, data: &[u8], object_type: &str) -> Result<String> {
+// Write an object as a loose file in `objects_dir`
+pub fn write_object<P: AsRef<Path>>(objects_dir: P, data: &[u8], object_type: ObjectType) -> Result<String> {
     let object_id = hash_object(data, object_type);
-    let dir_name = &object_id[0..2];
-    let file_name = &object_id[2..];
+    if !object_exists(&objects_dir, &object_id) {
+        write_loose_object(objects_dir.as_ref(), data, object_type)?;
+    }
     
-    let dir_path = objects_dir.as_ref().join(dir_name);
+    Ok(object_id)
+}
+
+/// Write an object as a loose file in `objects_dir` without first checking whether the
+/// store already has it, returning its id
+pub fn write_loose_object(objects_dir: &Path, data: &[u8], object_type: ObjectType) -> Result<String> {
+    let object_id = hash_object(data, object_type);
+    let dir_path = objects_dir.join(&object_id[0..2]);
     fs::create_dir_all(&dir_path)?;
+    let object_path = dir_path.join(&object_id[2..]);
+
+    // Write to a temporary file and rename it into place, so an interrupted write never
+    // leaves a truncated object under the real name
+    let mut temp_file = tempfile::Builder::new()
+        .prefix("tmp_obj_")
+        .tempfile_in(objects_dir)?;
+    temp_file.write_all(&encode_loose_object(data, object_type)?)?;
+    temp_file.as_file().sync_all()?;
+    temp_file.persist(&object_path)?;
+    Ok(object_id)
+}
+
+/// Encode an object the way it is stored as a loose file: zlib-compressed `"<type> <size>\0<data>"`
+pub fn encode_loose_object(data: &[u8], object_type: ObjectType) -> Result<Vec<u8>> {
+    let header = format!("{} {}", object_type, data.len());
+    let mut content = Vec::new();
+    content.extend_from_slice(header.as_bytes());
+    content.push(0);
+    content.extend_from_slice(data);
     
-    let object_path = dir_path.join(file_name);
-    if !object_path.exists() {
-        let header = format!("{} {}", object_type, data.len());
-        let mut content = Vec::new();
-        content.extend_from_slice(header.as_bytes());
-        content.push(0);
-        content.extend_from_slice(data);
-        
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&content)?;
-        let compressed = encoder.finish()?;
-        
-        fs::write(object_path, compressed)?;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&content)?;
+    Ok(encoder.finish()?)
+}
+
+/// Maximum depth of alternates chains (an alternate that itself has alternates)
+const MAX_ALTERNATES_DEPTH: usize = 5;
+
+/// List the object directories named in `objects/info/alternates`, one path per line.
+/// Relative paths are resolved against `objects_dir`; blank lines and `#` comments are skipped.
+pub fn alternate_object_dirs<P: AsRef<Path>>(objects_dir: P) -> Vec<PathBuf> {
+    let objects_dir = objects_dir.as_ref();
+    let Ok(content) = fs::read_to_string(objects_dir.join("info").join("alternates")) else {
+        return Vec::new();
+    };
+    
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| objects_dir.join(line))
+        .collect()
+}
+
+/// `objects_dir` followed by its alternates, their alternates in turn and so on, down to
+/// `MAX_ALTERNATES_DEPTH`
+pub fn object_dirs_with_alternates<P: AsRef<Path>>(objects_dir: P) -> Vec<PathBuf> {
+    let mut dirs = vec![objects_dir.as_ref().to_path_buf()];
+    let mut level = dirs.clone();
+    for _ in 0..MAX_ALTERNATES_DEPTH {
+        level = level.iter().flat_map(alternate_object_dirs).collect();
+        dirs.extend(level.iter().cloned());
+    }
+    dirs
+}
+
+/// Find the loose object file for `object_id`, looking in `objects_dir` and then its alternates.
+fn find_loose_object(objects_dir: &Path, object_id: &str, depth: usize) -> Option<PathBuf> {
+    let object_path = objects_dir.join(&object_id[0..2]).join(&object_id[2..]);
+    if object_path.is_file() {
+        return Some(object_path);
+    }
+    if depth >= MAX_ALTERNATES_DEPTH {
+        return None;
+    }
+    alternate_object_dirs(objects_dir)
+        .iter()
+        .find_map(|alternate| find_loose_object(alternate, object_id, depth + 1))
+}
+
+/// Whether the object `object_id` is stored in `objects_dir`, loose (here or in an alternate)
+/// or packed. Only file names and pack indexes are looked at; nothing is decompressed.
+pub fn object_exists<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> bool {
+    let objects_dir = objects_dir.as_ref();
+    loose_object_exists(objects_dir, object_id) || super::pack::has_packed_object(objects_dir, object_id)
+}
+
+/// Whether `object_id` is a loose object in `objects_dir` or one of its alternates, which
+/// takes no more than a look at file names
+pub fn loose_object_exists<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> bool {
+    object_id.len() == 40
+        && object_id.bytes().all(|b| b.is_ascii_hexdigit())
+        && find_loose_object(objects_dir.as_ref(), object_id, 0).is_some()
+}
+
+/// Minimum number of hex digits accepted as an abbreviated object id
+pub const MIN_ABBREV_LEN: usize = 4;
+
+// Collect loose object ids starting with `prefix` from `objects_dir` and its alternates
+fn collect_prefix_matches(objects_dir: &Path, prefix: &str, depth: usize, matches: &mut HashSet<String>) {
+    if let Ok(entries) = fs::read_dir(objects_dir.join(&prefix[..2])) {
+        for entry in entries.filter_map(Result::ok) {
+            let object_id = format!("{}{}", &prefix[..2], entry.file_name().to_string_lossy());
+            if object_id.len() == 40 && object_id.starts_with(prefix) {
+                matches.insert(object_id);
+            }
+        }
+    }
+    if depth < MAX_ALTERNATES_DEPTH {
+        for alternate in alternate_object_dirs(objects_dir) {
+            collect_prefix_matches(&alternate, prefix, depth + 1, matches);
+        }
+    }
+}
+
+/// Expand an abbreviated object id to the full id of the one object it names, loose or packed,
+/// here or in an alternate.
+///
+/// Full 40-digit ids are returned unchanged without checking that the object exists.
+pub fn expand_object_id<P: AsRef<Path>>(objects_dir: P, prefix: &str) -> Result<String> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.len() < MIN_ABBREV_LEN || prefix.len() > 40 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Not a valid object name {}", prefix);
+    }
+    if prefix.len() == 40 {
+        return Ok(prefix);
     }
     
-    Ok(object_id)
+    let mut matches = HashSet::new();
+    collect_prefix_matches(objects_dir.as_ref(), &prefix, 0, &mut matches);
+    matches.extend(super::pack::packed_object_ids_with_prefix(objects_dir.as_ref(), &prefix)?);
+    let mut matches: Vec<String> = matches.into_iter().collect();
+    match matches.len() {
+        0 => anyhow::bail!("Not a valid object name {}", prefix),
+        1 => Ok(matches.remove(0)),
+        _ => anyhow::bail!("short object ID {} is ambiguous", prefix),
+    }
 }
 
 /// Read a raw git object (header + data) from the object store.
 fn read_raw_git_object<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<Vec<u8>> {
-    let dir_name = &object_id[0..2];
-    let file_name = &object_id[2..];
+    if object_id.len() < 3 {
+        anyhow::bail!("Invalid object id '{}'", object_id);
+    }
     
-    let object_path = objects_dir.as_ref().join(dir_name).join(file_name);
+    let object_path = find_loose_object(objects_dir.as_ref(), object_id, 0)
+        .with_context(|| format!("Object {} not found", object_id))?;
     let compressed = fs::read(object_path)?;
     
-    let mut decoder = ZlibDecoder::new(&compressed[..]);
-    let mut decompressed = Vec::new();
-    decoder.read_to_end(&mut decompressed)?;
+    let (decompressed, _) = decompress(&compressed)
+        .with_context(|| format!("Failed to decompress object {}", object_id))?;
     Ok(decompressed)
 }
 
-// Read an object from the object store and parse its header
-pub fn read_object<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<(String, Vec<u8>)> {
-    let decompressed = read_raw_git_object(objects_dir, object_id)?;
-    
-    // Parse header
-    let null_pos = decompressed
-        .iter()
-        .position(|&b| b == 0)
-        .context("Invalid git object: no null byte")?;
-    
-    let header = str::from_utf8(&decompressed[0..null_pos])?;
-    let parts: Vec<&str> = header.split(' ').collect();
-    if parts.len() != 2 {
-        anyhow::bail!("Invalid git object header: '{}'", header);
+/// Decompress a single zlib stream from the start of `data`, stopping at the end of the stream.
+///
+/// Any bytes after the stream (such as the next entry in a pack) are left untouched.
+/// Returns the decompressed bytes and the number of input bytes the stream occupied.
+/// Streams without a zlib header are retried as raw deflate.
+pub fn decompress(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+    let mut decoder = ZlibDecoder::new(data);
+    let mut decompressed = Vec::new();
+    match decoder.read_to_end(&mut decompressed) {
+        Ok(_) => Ok((decompressed, decoder.total_in() as usize)),
+        Err(zlib_err) => {
+            let mut decoder = DeflateDecoder::new(data);
+            let mut decompressed = Vec::new();
+            decoder
+                .read_to_end(&mut decompressed)
+                .map_err(|_| anyhow::Error::new(zlib_err).context("Invalid zlib stream"))?;
+            Ok((decompressed, decoder.total_in() as usize))
+        }
     }
+}
+
+// Read an object from the object store and parse its header
+pub fn read_object<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<(ObjectType, Vec<u8>)> {
+    let objects_dir = objects_dir.as_ref();
+    let decompressed = match read_raw_git_object(objects_dir, object_id) {
+        Ok(decompressed) => decompressed,
+        Err(loose_err) => {
+            // Not loose: it may have been packed by repack or gc
+            return match super::pack::read_packed_object(objects_dir, object_id)? {
+                Some(object) => Ok(object),
+                None => Err(loose_err),
+            };
+        }
+    };
     
-    let object_type = parts[0].to_string();
-    let size: usize = parts[1].parse().context("Invalid object size in header")?;
+    let (type_name, size, null_pos) = split_header(&decompressed)?;
+    let object_type: ObjectType = type_name.parse()?;
     let data = decompressed[null_pos + 1..].to_vec();
 
     if data.len() != size {
@@ -101,102 +304,494 @@ pub fn read_object<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<(S
     Ok((object_type, data))
 }
 
+/// The type name and size an object's header states, neither checked against what the
+/// object holds. A loose object of a type this tool does not know, such as a corrupt one,
+/// is reported as it is stored instead of failing.
+pub fn read_object_header<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<(String, usize)> {
+    let objects_dir = objects_dir.as_ref();
+    match read_raw_git_object(objects_dir, object_id) {
+        Ok(decompressed) => {
+            let (type_name, size, _) = split_header(&decompressed)?;
+            Ok((type_name.to_string(), size))
+        }
+        // Packs store types as numbers, so a packed object's type is always a known one
+        Err(loose_err) => match super::pack::read_packed_object(objects_dir, object_id)? {
+            Some((object_type, data)) => Ok((object_type.to_string(), data.len())),
+            None => Err(loose_err),
+        },
+    }
+}
+
+// Split a decompressed loose object's `<type> <size>` header, returning the type name, the
+// size and the position of the null byte ending the header
+fn split_header(decompressed: &[u8]) -> Result<(&str, usize, usize)> {
+    let null_pos = decompressed
+        .iter()
+        .position(|&b| b == 0)
+        .context("Invalid git object: no null byte")?;
+    
+    let header = str::from_utf8(&decompressed[0..null_pos])?;
+    let parts: Vec<&str> = header.split(' ').collect();
+    if parts.len() != 2 {
+        anyhow::bail!("Invalid git object header: '{}'", header);
+    }
+    
+    let size: usize = parts[1].parse().context("Invalid object size in header")?;
+    Ok((parts[0], size, null_pos))
+}
+
 /// Read only the raw data of an object, without the git header.
 /// This is useful for operations like diffing.
-pub fn read_raw_object<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<(String, Vec<u8>)> {
+pub fn read_raw_object<P: AsRef<Path>>(objects_dir: P, object_id: &str) -> Result<(ObjectType, Vec<u8>)> {
     // For packing, we need to know the type to group similar objects, and the raw data for diffing.
     // The existing read_object function already separates type and data, which is what we need.
     // So this function can just be an alias that properly calls the parsing function.
     read_object(objects_dir.as_ref(), object_id)
 }
 
-// Create a tree object from the index
+// Create a tree object from the index, with a subtree for each directory
 pub fn write_tree(repo: &super::Repository) -> Result<String> {
-    let mut tree_entries = Vec::new();
-    
+    let mut root = TreeLevel::default();
     for (path, entry) in repo.index.get_entries() {
-        // In a real implementation, we would handle subdirectories by creating subtrees
-        // For simplicity, we'll just create a flat tree
-        
-        let mode_str = format!("{:o}", entry.mode);
-        let path_str = path.to_string_lossy();
-        
-        // Convert hex object_id to binary
-        let object_id_bytes = hex::decode(&entry.object_id)?;
-        if object_id_bytes.len() != 20 {
-            anyhow::bail!("Invalid SHA-1 hash length: expected 20 bytes, got {}", object_id_bytes.len());
+        let path_bytes = super::path_to_bytes(path);
+        let mut components: Vec<&[u8]> = path_bytes.split(|&b| b == b'/').collect();
+        let name = components.pop().unwrap_or_default().to_vec();
+        let mut level = &mut root;
+        for component in components {
+            level = level.subtrees.entry(component.to_vec()).or_default();
         }
-        
-        // Create tree entry: mode + space + filename + null + 20-byte sha1
-        let mut entry_data = Vec::new();
-        entry_data.extend_from_slice(mode_str.as_bytes());
-        entry_data.push(b' ');
-        entry_data.extend_from_slice(path_str.as_bytes());
-        entry_data.push(0);
-        entry_data.extend_from_slice(&object_id_bytes);
-        
-        tree_entries.push(entry_data);
-    }
-    
-    // Sort by filename (Git requirement)
-    tree_entries.sort_by(|a, b| {
-        // Find the filename part (after mode and space, before null byte)
-        let find_filename_string = |entry: &[u8]| -> String {
-            if let Some(space_pos) = entry.iter().position(|&b| b == b' ') {
-                if let Some(null_pos) = entry[space_pos + 1..].iter().position(|&b| b == 0) {
-                    let filename_bytes = &entry[space_pos + 1..space_pos + 1 + null_pos];
-                    return String::from_utf8_lossy(filename_bytes).to_string();
-                }
+        level.entries.push((name, entry.mode, entry.object_id.clone()));
+    }
+    root.write(repo.objects.as_ref())
+}
+
+/// One directory of the tree `write_tree` builds: the index entries directly in it and its
+/// subdirectories by name
+#[derive(Default)]
+struct TreeLevel {
+    entries: Vec<(Vec<u8>, u32, String)>,
+    subtrees: BTreeMap<Vec<u8>, TreeLevel>,
+}
+
+impl TreeLevel {
+    // Write the subtrees, then this tree, returning its id
+    fn write(&self, store: &dyn ObjectStore) -> Result<String> {
+        let mut entries = self.entries.clone();
+        for (name, subtree) in &self.subtrees {
+            entries.push((name.clone(), MODE_TREE, subtree.write(store)?));
+        }
+
+        // Git orders entries by name byte by byte, comparing a subtree's name as if it ended in '/'
+        let sort_key = |(name, mode, _): &(Vec<u8>, u32, String)| {
+            let mut key = name.clone();
+            if *mode == MODE_TREE {
+                key.push(b'/');
             }
-            String::new()
+            key
         };
+        entries.sort_by_key(sort_key);
+
+        let mut tree_content = Vec::new();
+        for (name, mode, object_id) in entries {
+            let object_id_bytes = hex::decode(&object_id)?;
+            if object_id_bytes.len() != 20 {
+                anyhow::bail!("Invalid SHA-1 hash length: expected 20 bytes, got {}", object_id_bytes.len());
+            }
+            // mode + space + filename + null + 20-byte sha1
+            tree_content.extend_from_slice(format!("{:o} ", mode).as_bytes());
+            tree_content.extend_from_slice(&name);
+            tree_content.push(0);
+            tree_content.extend_from_slice(&object_id_bytes);
+        }
+        store.write(ObjectType::Tree, &tree_content)
+    }
+}
+
+/// Mode of a subtree entry
+pub const MODE_TREE: u32 = 0o40000;
+/// Mode of a submodule entry, which names a commit in another repository
+pub const MODE_GITLINK: u32 = 0o160000;
+
+/// One entry of a tree object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    /// Entry name, or the path from the root tree after `flatten_tree`
+    pub name: PathBuf,
+    pub oid: String,
+}
+
+/// Parse the entries of a tree object's data: `<mode> <name>\0<20-byte sha1>` repeated.
+pub fn parse_tree(data: &[u8]) -> Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut cursor = 0;
+    
+    while cursor < data.len() {
+        let space_idx = data[cursor..].iter().position(|&b| b == b' ')
+            .context("Invalid tree object: missing space after mode")? + cursor;
+        let null_idx = data[space_idx + 1..].iter().position(|&b| b == 0)
+            .context("Invalid tree object: missing null terminator after name")? + space_idx + 1;
+        let sha1_end = null_idx + 1 + 20;
+        if sha1_end > data.len() {
+            anyhow::bail!("Invalid tree object: insufficient data for SHA-1 hash");
+        }
         
-        let filename_a = find_filename_string(a);
-        let filename_b = find_filename_string(b);
-        filename_a.cmp(&filename_b)
-    });
+        let mode = u32::from_str_radix(str::from_utf8(&data[cursor..space_idx])?, 8)
+            .context("Invalid tree object: bad mode")?;
+        entries.push(TreeEntry {
+            mode,
+            name: super::path_from_bytes(&data[space_idx + 1..null_idx]),
+            oid: hex::encode(&data[null_idx + 1..sha1_end]),
+        });
+        cursor = sha1_end;
+    }
+    
+    Ok(entries)
+}
+
+/// A parsed commit object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub oid: String,
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    /// The message without its trailing newlines
+    pub message: String,
+}
+
+/// Parse the data of a commit object (without the object header)
+pub fn parse_commit(data: &[u8]) -> Result<Commit> {
+    let oid = hash_object(data, ObjectType::Commit);
+    let content = String::from_utf8_lossy(data);
+    let (header, message) = content.split_once("\n\n").unwrap_or((&content, ""));
+    
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut author = None;
+    let mut committer = None;
+    for line in header.lines() {
+        // Continuation lines of multi-line headers (such as signatures) start with a space
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        match key {
+            "tree" => tree = Some(value.trim().to_string()),
+            "parent" => parents.push(value.trim().to_string()),
+            "author" => author = Signature::parse(value),
+            "committer" => committer = Signature::parse(value),
+            _ => {}
+        }
+    }
+    
+    Ok(Commit {
+        tree: tree.with_context(|| format!("Invalid commit object {}: no tree", oid))?,
+        parents,
+        author: author.with_context(|| format!("Invalid commit object {}: no valid author", oid))?,
+        committer: committer.with_context(|| format!("Invalid commit object {}: no valid committer", oid))?,
+        message: message.trim_end_matches('\n').to_string(),
+        oid,
+    })
+}
+
+/// Read and parse a commit object
+pub fn read_commit(store: &dyn ObjectStore, commit_id: &str) -> Result<Commit> {
+    let RawObject { object_type, data } = store.read(commit_id)?;
+    if object_type != ObjectType::Commit {
+        anyhow::bail!("Expected commit object for {}, got {}", commit_id, object_type);
+    }
+    parse_commit(&data)
+}
+
+/// A parsed annotated tag object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub oid: String,
+    /// The tagged object
+    pub object: String,
+    pub object_type: ObjectType,
+    pub tag: String,
+    pub tagger: Option<Signature>,
+    /// The message without its trailing newlines
+    pub message: String,
+}
+
+/// Parse the data of a tag object (without the object header)
+pub fn parse_tag(data: &[u8]) -> Result<Tag> {
+    let oid = hash_object(data, ObjectType::Tag);
+    let content = String::from_utf8_lossy(data);
+    let (header, message) = content.split_once("\n\n").unwrap_or((&content, ""));
+    
+    let mut object = None;
+    let mut object_type = None;
+    let mut tag = None;
+    let mut tagger = None;
+    for line in header.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        match key {
+            "object" => object = Some(value.trim().to_string()),
+            "type" => object_type = Some(value.trim().parse()?),
+            "tag" => tag = Some(value.trim().to_string()),
+            "tagger" => tagger = Signature::parse(value),
+            _ => {}
+        }
+    }
+    
+    Ok(Tag {
+        object: object.with_context(|| format!("Invalid tag object {}: no object", oid))?,
+        object_type: object_type.with_context(|| format!("Invalid tag object {}: no type", oid))?,
+        tag: tag.with_context(|| format!("Invalid tag object {}: no tag name", oid))?,
+        tagger,
+        message: message.trim_end_matches('\n').to_string(),
+        oid,
+    })
+}
+
+/// Read the tag object `tag_id` and check it the way git does: the `object`, `type`, `tag` and
+/// `tagger` headers come first and in that order, the tagger is a valid signature, and the
+/// tagged object exists with the declared type
+pub fn verify_tag(store: &dyn ObjectStore, tag_id: &str) -> Result<Tag> {
+    let RawObject { object_type, data } = store.read(tag_id)?;
+    if object_type != ObjectType::Tag {
+        anyhow::bail!("Expected tag object for {}, got {}", tag_id, object_type);
+    }
+
+    let content = String::from_utf8_lossy(&data);
+    let header = content.split_once("\n\n").map_or(&*content, |(header, _)| header);
+    let keys: Vec<&str> = header.lines().take(4).map(|line| line.split(' ').next().unwrap_or_default()).collect();
+    if keys != ["object", "type", "tag", "tagger"] {
+        anyhow::bail!("Invalid tag object {}: headers must start with object, type, tag and tagger", tag_id);
+    }
+    let tag = parse_tag(&data)?;
+    if tag.tagger.is_none() {
+        anyhow::bail!("Invalid tag object {}: no valid tagger", tag_id);
+    }
+    if tag.object.len() != 40 || !tag.object.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid tag object {}: bad object id '{}'", tag_id, tag.object);
+    }
+
+    let target = store
+        .read(&tag.object)
+        .with_context(|| format!("Invalid tag object {}: tagged object {} is missing", tag_id, tag.object))?;
+    if target.object_type != tag.object_type {
+        anyhow::bail!(
+            "Invalid tag object {}: tagged object {} is a {}, not a {}",
+            tag_id,
+            tag.object,
+            target.object_type,
+            tag.object_type
+        );
+    }
+    Ok(tag)
+}
+
+/// An object from the database, parsed according to its type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Object {
+    Blob(Vec<u8>),
+    Tree(Vec<TreeEntry>),
+    Commit(Commit),
+    Tag(Tag),
+}
+
+impl Object {
+    /// Parse object `data` of the given type
+    pub fn parse(object_type: ObjectType, data: Vec<u8>) -> Result<Self> {
+        Ok(match object_type {
+            ObjectType::Blob => Object::Blob(data),
+            ObjectType::Tree => Object::Tree(parse_tree(&data)?),
+            ObjectType::Commit => Object::Commit(parse_commit(&data)?),
+            ObjectType::Tag => Object::Tag(parse_tag(&data)?),
+        })
+    }
+
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            Object::Blob(_) => ObjectType::Blob,
+            Object::Tree(_) => ObjectType::Tree,
+            Object::Commit(_) => ObjectType::Commit,
+            Object::Tag(_) => ObjectType::Tag,
+        }
+    }
+}
+
+/// An object's type and content as stored, before parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawObject {
+    pub object_type: ObjectType,
+    pub data: Vec<u8>,
+}
+
+impl RawObject {
+    pub fn parse(self) -> Result<Object> {
+        Object::parse(self.object_type, self.data)
+    }
+}
+
+/// Read and parse the object `oid`
+pub fn parse_object(store: &dyn ObjectStore, oid: &str) -> Result<Object> {
+    store.read(oid)?.parse()
+}
+
+/// Read the id of the tree a commit records
+pub fn read_commit_tree(store: &dyn ObjectStore, commit_id: &str) -> Result<String> {
+    Ok(read_commit(store, commit_id)?.tree)
+}
+
+/// List every non-tree entry reachable from `tree_id`, recursing into subtrees,
+/// with paths relative to the root tree.
+pub fn flatten_tree(store: &dyn ObjectStore, tree_id: &str) -> Result<Vec<TreeEntry>> {
+    let RawObject { object_type, data } = store.read(tree_id)?;
+    if object_type != ObjectType::Tree {
+        anyhow::bail!("Expected tree object for {}, got {}", tree_id, object_type);
+    }
+    
+    let mut entries = Vec::new();
+    for entry in parse_tree(&data)? {
+        if entry.mode == MODE_TREE {
+            for mut child in flatten_tree(store, &entry.oid)? {
+                child.name = super::normalize_path(&entry.name.join(&child.name));
+                entries.push(child);
+            }
+        } else {
+            entries.push(entry);
+        }
+    }
+    
+    Ok(entries)
+}
+
+/// Find the non-tree entry at `path` in `tree_id`, looking inside subtrees
+pub fn find_tree_entry(store: &dyn ObjectStore, tree_id: &str, path: &Path) -> Result<Option<TreeEntry>> {
+    Ok(flatten_tree(store, tree_id)?
+        .into_iter()
+        .find(|entry| super::normalize_path(&entry.name) == super::normalize_path(path)))
+}
+
+/// An identity (`Name <email>`) and the moment it acted, as recorded on author/committer lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub identity: String,
+    /// Seconds since the epoch followed by the timezone offset, e.g. `1700000000 +0000`
+    pub timestamp: String,
+}
+
+impl Signature {
+    /// A signature for `identity` stamped with the current time
+    pub fn now(identity: &str) -> Self {
+        Self {
+            identity: identity.to_string(),
+            timestamp: Utc::now().format("%s %z").to_string(),
+        }
+    }
+    
+    /// Parse the value of an author/committer line: `Name <email> <epoch> <offset>`
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim_end().rsplitn(3, ' ');
+        let offset = parts.next()?;
+        let epoch = parts.next()?;
+        let identity = parts.next()?;
+        epoch.parse::<i64>().ok()?;
+        Some(Self {
+            identity: identity.to_string(),
+            timestamp: format!("{} {}", epoch, offset),
+        })
+    }
+    
+    /// The name part of the identity, before ` <email>`
+    pub fn name(&self) -> &str {
+        self.identity.split_once(" <").map_or(&self.identity, |(name, _)| name)
+    }
     
-    let mut tree_content = Vec::new();
-    for entry in tree_entries {
-        tree_content.extend_from_slice(&entry);
+    /// The email part of the identity, without the angle brackets
+    pub fn email(&self) -> &str {
+        self.identity
+            .split_once('<')
+            .and_then(|(_, rest)| rest.strip_suffix('>'))
+            .unwrap_or("")
     }
     
-    write_object(&repo.git_dir.join("objects"), &tree_content, "tree")
+    /// Seconds since the epoch
+    pub fn seconds(&self) -> i64 {
+        self.timestamp
+            .split(' ')
+            .next()
+            .and_then(|epoch| epoch.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.identity, self.timestamp)
+    }
 }
 
 // Create a commit object
-pub fn write_commit<P: AsRef<Path>>(
-    objects_dir: P,
+pub fn write_commit(
+    store: &dyn ObjectStore,
     tree_id: &str,
     parent_ids: &[&str],
     message: &str,
-    author: &str,
+    author: &Signature,
+    committer: &Signature,
 ) -> Result<String> {
-    let timestamp = Utc::now().format("%s %z").to_string();
-    
     let mut commit_content = format!("tree {}\n", tree_id);
     
     for parent_id in parent_ids {
         commit_content.push_str(&format!("parent {}\n", parent_id));
     }
     
-    commit_content.push_str(&format!("author {} {}\n", author, timestamp));
-    commit_content.push_str(&format!("committer {} {}\n", author, timestamp));
-    commit_content.push_str("\n");
+    commit_content.push_str(&format!("author {}\n", author));
+    commit_content.push_str(&format!("committer {}\n", committer));
+    commit_content.push('\n');
     commit_content.push_str(message);
-    commit_content.push_str("\n");
+    commit_content.push('\n');
     
-    write_object(objects_dir, commit_content.as_bytes(), "commit")
+    store.write(ObjectType::Commit, commit_content.as_bytes())
+}
+
+/// Read the parent ids of a commit. Objects that cannot be read or are not commits have no parents.
+///
+/// This reads `.git/shallow` each time; walks read it once and use `commit_parents`.
+pub fn read_commit_parents(repo: &Repository, commit_id: &str) -> Vec<String> {
+    commit_parents(repo, commit_id, &shallow_commits(&repo.git_dir))
+}
+
+/// The parent ids of a commit, none for a commit in `shallow`, the boundary commits
+/// `shallow_commits` read, or one that cannot be read.
+pub fn commit_parents(repo: &Repository, commit_id: &str, shallow: &HashSet<String>) -> Vec<String> {
+    // The history of a shallow clone ends at its boundary commits, whose parents were never fetched
+    if shallow.contains(commit_id) {
+        return Vec::new();
+    }
+    read_commit(repo.objects.as_ref(), commit_id).map_or_else(|_| Vec::new(), |commit| commit.parents)
+}
+
+/// Read the boundary commits of a shallow clone from `.git/shallow`, one id per line.
+/// Empty for a repository with complete history.
+pub fn shallow_commits<P: AsRef<Path>>(git_dir: P) -> HashSet<String> {
+    fs::read_to_string(super::refs::common_dir(git_dir).join("shallow"))
+        .map(|content| content.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
 }
 
 /// Check if `potential_ancestor_id` is an ancestor of `commit_id`.
+///
+/// An ancestor's generation is always below its descendants', so the walk does not go past
+/// commits whose generation is no higher than the candidate's. Parents and generations come
+/// from the repository's commit graph cache, so repeated checks barely read any objects.
 pub fn is_ancestor(repo: &Repository, potential_ancestor_id: &str, commit_id: &str) -> Result<bool> {
     if potential_ancestor_id == commit_id {
         return Ok(true);
     }
-    
+    let target_generation = repo.generation(potential_ancestor_id);
+
     let mut queue = vec![commit_id.to_string()];
-    let mut visited = std::collections::HashSet::new();
+    let mut visited = HashSet::new();
 
     while let Some(current_commit_id) = queue.pop() {
         if !visited.insert(current_commit_id.clone()) {
@@ -206,54 +801,131 @@ pub fn is_ancestor(repo: &Repository, potential_ancestor_id: &str, commit_id: &s
         if current_commit_id == potential_ancestor_id {
             return Ok(true);
         }
-
-        // Get parents of the current commit and add them to the queue
-        if let Ok((commit_type, commit_data)) = read_object(&repo.git_dir.join("objects"), &current_commit_id) {
-            if commit_type == "commit" {
-                let commit_content = String::from_utf8_lossy(&commit_data);
-                for line in commit_content.lines() {
-                    if line.starts_with("parent ") {
-                        if let Some(parent_id) = line.strip_prefix("parent ") {
-                            queue.push(parent_id.trim().to_string());
-                        }
-                    }
-                }
-            }
-        } else {
-            // Could not read object, might be a shallow clone or corrupted history.
-            // For this check, we assume it means the ancestor is not found down this path.
+        if repo.generation(&current_commit_id) <= target_generation {
+            continue; // Everything below here is older than the candidate
         }
+
+        // Get parents of the current commit and add them to the queue.
+        // A commit that cannot be read (shallow clone or corrupted history) ends this path.
+        queue.extend(repo.parents(&current_commit_id));
     }
 
     Ok(false)
 }
 
+/// Collect `commit_id` and all of its ancestors.
+pub fn ancestors(repo: &Repository, commit_id: &str) -> HashSet<String> {
+    let shallow = shallow_commits(&repo.git_dir);
+    let mut ancestors = HashSet::new();
+    let mut queue = vec![commit_id.to_string()];
+    
+    while let Some(commit_id) = queue.pop() {
+        if !ancestors.insert(commit_id.clone()) {
+            continue;
+        }
+        queue.extend(commit_parents(repo, &commit_id, &shallow));
+    }
+    
+    ancestors
+}
+
+/// Count the commits reachable from `commit_id` but not from `upstream_id`, and the other way
+/// round, as `(ahead, behind)`, like `git rev-list --left-right --count commit...upstream`.
+pub fn ahead_behind(repo: &Repository, commit_id: &str, upstream_id: &str) -> Result<(usize, usize)> {
+    let ours = ancestors(repo, commit_id);
+    let theirs = ancestors(repo, upstream_id);
+    Ok((ours.difference(&theirs).count(), theirs.difference(&ours).count()))
+}
+
+/// Find all best common ancestors of two commits.
+///
+/// A common ancestor is "best" when it is not an ancestor of another common ancestor.
+/// Simple forked histories have exactly one; criss-cross merges can have several.
+/// The result is sorted and empty when the commits share no history.
+pub fn merge_bases(repo: &Repository, commit1: &str, commit2: &str) -> Result<Vec<String>> {
+    let ancestors1 = ancestors(repo, commit1);
+    let ancestors2 = ancestors(repo, commit2);
+    let common: HashSet<&String> = ancestors1.intersection(&ancestors2).collect();
+    
+    // Every proper ancestor of a common ancestor is itself common, and redundant
+    let shallow = shallow_commits(&repo.git_dir);
+    let mut redundant = HashSet::new();
+    let mut queue: Vec<String> = common
+        .iter()
+        .flat_map(|commit_id| commit_parents(repo, commit_id, &shallow))
+        .collect();
+    while let Some(commit_id) = queue.pop() {
+        if !redundant.insert(commit_id.clone()) {
+            continue;
+        }
+        queue.extend(commit_parents(repo, &commit_id, &shallow));
+    }
+    
+    let mut bases: Vec<String> = common
+        .into_iter()
+        .filter(|commit_id| !redundant.contains(*commit_id))
+        .cloned()
+        .collect();
+    bases.sort();
+    Ok(bases)
+}
+
+/// Find the merge base (lowest common ancestor) of two commits.
+///
+/// When there are several equally good bases, the most recently committed one is chosen.
+/// Returns `None` when the commits share no history.
+pub fn merge_base(repo: &Repository, commit1: &str, commit2: &str) -> Result<Option<String>> {
+    let bases = merge_bases(repo, commit1, commit2)?;
+    Ok(bases.into_iter().max_by_key(|commit_id| commit_timestamp(repo, commit_id)))
+}
+
+/// Read the committer timestamp (seconds since the epoch) of a commit, or 0 if unavailable.
+pub fn commit_timestamp(repo: &Repository, commit_id: &str) -> i64 {
+    read_commit(repo.objects.as_ref(), commit_id).map_or(0, |commit| commit.committer.seconds())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::repository::store::MemObjectStore;
     use tempfile::tempdir;
     
     #[test]
     fn test_hash_object() {
         let data = b"test content";
-        let hash = hash_object(data, "blob");
+        let hash = hash_object(data, ObjectType::Blob);
         
         // The hash should be a 40-character hex string
         assert_eq!(hash.len(), 40);
         assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
         
         // Same content should produce the same hash
-        let hash2 = hash_object(data, "blob");
+        let hash2 = hash_object(data, ObjectType::Blob);
         assert_eq!(hash, hash2);
         
         // Different content should produce different hashes
-        let hash3 = hash_object(b"different content", "blob");
+        let hash3 = hash_object(b"different content", ObjectType::Blob);
         assert_ne!(hash, hash3);
         
         // Different types should produce different hashes
-        let hash4 = hash_object(data, "commit");
+        let hash4 = hash_object(data, ObjectType::Commit);
         assert_ne!(hash, hash4);
     }
+
+    #[test]
+    fn test_object_type_names() {
+        for object_type in ObjectType::ALL {
+            assert_eq!(object_type.as_str().parse::<ObjectType>().unwrap(), object_type);
+            assert_eq!(object_type.to_string(), object_type.as_str());
+        }
+        assert_eq!("commit".parse::<ObjectType>().unwrap(), ObjectType::Commit);
+        assert_eq!(ObjectType::Tag.as_str(), "tag");
+
+        for name in ["frob", "Blob", "blob ", ""] {
+            let err = name.parse::<ObjectType>().unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid object type \"{}\"", name));
+        }
+    }
     
     #[test]
     fn test_write_and_read_blob() -> Result<()> {
@@ -262,50 +934,408 @@ mod tests {
         fs::create_dir_all(&objects_dir)?;
         
         let data = b"test content";
-        let object_id = write_blob(&objects_dir, data)?;
+        let object_id = write_object(&objects_dir, data, ObjectType::Blob)?;
         
         // Read the object back
         let (object_type, content) = read_object(&objects_dir, &object_id)?;
         
         // Check that the content and type are correct
-        assert_eq!(object_type, "blob");
+        assert_eq!(object_type, ObjectType::Blob);
         assert_eq!(content, data);
         
         Ok(())
     }
     
     #[test]
-    fn test_write_commit() -> Result<()> {
+    fn test_partial_temp_file_does_not_shadow_object() -> Result<()> {
         let temp_dir = tempdir()?;
         let objects_dir = temp_dir.path().join("objects");
         fs::create_dir_all(&objects_dir)?;
         
+        // A write interrupted before its rename leaves only a truncated temporary file
+        let data = b"complete content";
+        let encoded = encode_loose_object(data, ObjectType::Blob)?;
+        let leftover = objects_dir.join("tmp_obj_interrupted");
+        fs::write(&leftover, &encoded[..encoded.len() / 2])?;
+        
+        let object_id = write_object(&objects_dir, data, ObjectType::Blob)?;
+        assert_eq!(read_object(&objects_dir, &object_id)?, (ObjectType::Blob, data.to_vec()));
+        assert_eq!(fs::read(objects_dir.join(&object_id[..2]).join(&object_id[2..]))?, encoded);
+        assert!(leftover.exists());
+        
+        // Nothing else is left next to the written object
+        let entries: Vec<_> = fs::read_dir(objects_dir.join(&object_id[..2]))?.collect();
+        assert_eq!(entries.len(), 1);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_write_commit() -> Result<()> {
+        let store = MemObjectStore::new();
+        
         let tree_id = "1234567890123456789012345678901234567890";
         let parent_id = "abcdef0123456789abcdef0123456789abcdef01";
         let message = "Test commit message";
         let author = "Test User <test@example.com>";
         
         let commit_id = write_commit(
-            &objects_dir,
+            &store,
             tree_id,
             &[parent_id],
             message,
-            author,
+            &Signature::now(author),
+            &Signature::now("Committer <committer@example.com>"),
         )?;
         
         // Read the commit back
-        let (object_type, content) = read_object(&objects_dir, &commit_id)?;
+        let RawObject { object_type, data: content } = store.read(&commit_id)?;
         
         // Check that the content and type are correct
-        assert_eq!(object_type, "commit");
+        assert_eq!(object_type, ObjectType::Commit);
         let content_str = str::from_utf8(&content)?;
         
         // Check that the commit contains the expected data
         assert!(content_str.contains(&format!("tree {}", tree_id)));
         assert!(content_str.contains(&format!("parent {}", parent_id)));
         assert!(content_str.contains(message));
-        assert!(content_str.contains(author));
+        assert!(content_str.contains(&format!("author {}", author)));
+        assert!(content_str.contains("committer Committer <committer@example.com>"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_parse_object_types() -> Result<()> {
+        let store = MemObjectStore::new();
+        
+        let blob_id = write_blob(&store, b"hello\n")?;
+        assert_eq!(parse_object(&store, &blob_id)?, Object::Blob(b"hello\n".to_vec()));
+        
+        let mut tree_data = Vec::new();
+        for (mode, name) in [("100644", "a.txt"), ("40000", "sub")] {
+            tree_data.extend(format!("{} {}\0", mode, name).as_bytes());
+            tree_data.extend(hex::decode(&blob_id)?);
+        }
+        let tree_id = store.write(ObjectType::Tree, &tree_data)?;
+        let Object::Tree(entries) = parse_object(&store, &tree_id)? else {
+            panic!("expected a tree");
+        };
+        assert_eq!(entries, vec![
+            TreeEntry { mode: 0o100644, name: PathBuf::from("a.txt"), oid: blob_id.clone() },
+            TreeEntry { mode: MODE_TREE, name: PathBuf::from("sub"), oid: blob_id.clone() },
+        ]);
+        
+        let author = Signature::parse("Jane Doe <jane@example.com> 1700000000 +0800").unwrap();
+        let commit_id = write_commit(&store, &tree_id, &[&blob_id], "Subject\n\nBody", &author, &author)?;
+        let Object::Commit(commit) = parse_object(&store, &commit_id)? else {
+            panic!("expected a commit");
+        };
+        assert_eq!(commit.oid, commit_id);
+        assert_eq!(commit.tree, tree_id);
+        assert_eq!(commit.parents, vec![blob_id.clone()]);
+        assert_eq!(commit.author, author);
+        assert_eq!(commit.message, "Subject\n\nBody");
+        
+        let tag_data = format!("object {}\ntype commit\ntag v1.0\ntagger {}\n\nRelease\n", commit_id, author);
+        let tag_id = store.write(ObjectType::Tag, tag_data.as_bytes())?;
+        let object = parse_object(&store, &tag_id)?;
+        assert_eq!(object.object_type(), ObjectType::Tag);
+        assert_eq!(object, Object::Tag(Tag {
+            oid: tag_id,
+            object: commit_id,
+            object_type: ObjectType::Commit,
+            tag: "v1.0".to_string(),
+            tagger: Some(author),
+            message: "Release".to_string(),
+        }));
+        
+        Ok(())
+    }
+    
+    // Write a commit with an empty tree on top of `parents`
+    fn commit(repo: &Repository, parents: &[&str], message: &str) -> Result<String> {
+        write_commit(
+            repo.objects.as_ref(),
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+            parents,
+            message,
+            &Signature::now("Test User <test@example.com>"),
+            &Signature::now("Test User <test@example.com>"),
+        )
+    }
+    
+    #[test]
+    fn test_merge_base_linear_history() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        
+        let a = commit(&repo, &[], "A")?;
+        let b = commit(&repo, &[&a], "B")?;
+        let c = commit(&repo, &[&b], "C")?;
+        
+        // The ancestor itself is the merge base
+        assert_eq!(merge_base(&repo, &b, &c)?, Some(b.clone()));
+        assert_eq!(merge_base(&repo, &c, &a)?, Some(a.clone()));
+        assert_eq!(merge_base(&repo, &c, &c)?, Some(c));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_merge_base_forked_history() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        
+        let a = commit(&repo, &[], "A")?;
+        let b = commit(&repo, &[&a], "B")?;
+        let left = commit(&repo, &[&b], "left")?;
+        let right = commit(&repo, &[&b], "right")?;
+        assert_eq!(merge_base(&repo, &left, &right)?, Some(b));
+        
+        // Unrelated histories have no merge base
+        let orphan = commit(&repo, &[], "orphan")?;
+        assert_eq!(merge_base(&repo, &left, &orphan)?, None);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_merge_base_criss_cross() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        
+        // A1 and B1 both fork from R, then each side merges the other (criss-cross).
+        // B3 additionally lists R as a parent.
+        let r = commit(&repo, &[], "R")?;
+        let a1 = commit(&repo, &[&r], "A1")?;
+        let b1 = commit(&repo, &[&r], "B1")?;
+        let a2 = commit(&repo, &[&a1, &b1], "A2")?;
+        let b2 = commit(&repo, &[&b1, &a1], "B2")?;
+        let a3 = commit(&repo, &[&a2], "A3")?;
+        let b3 = commit(&repo, &[&b2, &r], "B3")?;
+        
+        // A naive search from B3 reaches R first, although R is an ancestor of both real bases
+        let mut expected = vec![a1.clone(), b1.clone()];
+        expected.sort();
+        assert_eq!(merge_bases(&repo, &a3, &b3)?, expected);
+        
+        let base = merge_base(&repo, &a3, &b3)?.unwrap();
+        assert!(base == a1 || base == b1);
+        assert_ne!(base, r);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_ahead_behind_criss_cross() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        
+        // Either merge base alone leaves the other one counted on both sides
+        let r = commit(&repo, &[], "R")?;
+        let a1 = commit(&repo, &[&r], "A1")?;
+        let b1 = commit(&repo, &[&r], "B1")?;
+        let a2 = commit(&repo, &[&a1, &b1], "A2")?;
+        let b2 = commit(&repo, &[&b1, &a1], "B2")?;
+        let a3 = commit(&repo, &[&a2], "A3")?;
+        
+        assert_eq!(ahead_behind(&repo, &a3, &b2)?, (2, 1));
+        assert_eq!(ahead_behind(&repo, &b2, &a3)?, (1, 2));
+        assert_eq!(ahead_behind(&repo, &a3, &a1)?, (3, 0));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_read_object_through_alternates() -> Result<()> {
+        let shared_dir = tempdir()?;
+        let shared_objects = shared_dir.path().join("objects");
+        fs::create_dir_all(&shared_objects)?;
+        let object_id = write_object(&shared_objects, b"shared content", ObjectType::Blob)?;
+        
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        let objects_dir = repo.git_dir.join("objects");
+        assert!(read_object(&objects_dir, &object_id).is_err());
+        
+        // Borrow the shared store through the alternates file
+        fs::create_dir_all(objects_dir.join("info"))?;
+        fs::write(
+            objects_dir.join("info").join("alternates"),
+            format!("# shared store\n{}\n", shared_objects.display()),
+        )?;
+        
+        let (object_type, content) = read_object(&objects_dir, &object_id)?;
+        assert_eq!(object_type, ObjectType::Blob);
+        assert_eq!(content, b"shared content");
+        
+        // The object was not copied into the local store
+        assert!(!objects_dir.join(&object_id[0..2]).join(&object_id[2..]).exists());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_read_packed_object_through_alternates() -> Result<()> {
+        let shared_dir = tempdir()?;
+        let shared_objects = shared_dir.path().join("objects");
+        let object_id = write_object(&shared_objects, b"packed shared content", ObjectType::Blob)?;
+        crate::repository::pack::create_pack(&shared_objects)?;
+        assert!(!shared_objects.join(&object_id[0..2]).join(&object_id[2..]).exists());
+        
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        let objects_dir = repo.git_dir.join("objects");
+        fs::create_dir_all(objects_dir.join("info"))?;
+        fs::write(objects_dir.join("info").join("alternates"), format!("{}\n", shared_objects.display()))?;
+        
+        assert!(object_exists(&objects_dir, &object_id));
+        assert_eq!(read_object(&objects_dir, &object_id)?.1, b"packed shared content");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_object_exists_loose_and_packed() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        let objects_dir = repo.git_dir.join("objects");
+        let object_id = write_object(&objects_dir, b"exists\n", ObjectType::Blob)?;
+        
+        assert!(object_exists(&objects_dir, &object_id));
+        assert!(!object_exists(&objects_dir, "0123456789abcdef0123456789abcdef01234567"));
+        assert!(!object_exists(&objects_dir, &object_id[..8]));
+        
+        // Still found once it only lives in a pack
+        repo.repack()?;
+        assert!(!objects_dir.join(&object_id[0..2]).join(&object_id[2..]).exists());
+        assert!(object_exists(&objects_dir, &object_id));
+        assert!(repo.objects.exists(&object_id));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_decompress_stops_at_stream_end() -> Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(b"blob 5\0hello")?;
+        let compressed = encoder.finish()?;
+        
+        let mut with_junk = compressed.clone();
+        with_junk.extend_from_slice(b"trailing junk bytes");
+        
+        let (decompressed, consumed) = decompress(&with_junk)?;
+        assert_eq!(decompressed, b"blob 5\0hello");
+        assert_eq!(consumed, compressed.len());
+        assert_eq!(&with_junk[consumed..], b"trailing junk bytes");
+        
+        // Raw deflate streams (no zlib header) are accepted as well
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(b"raw data")?;
+        let raw = encoder.finish()?;
+        assert_eq!(decompress(&raw)?, (b"raw data".to_vec(), raw.len()));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_read_object_ignores_trailing_garbage() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+        let object_id = write_object(&objects_dir, b"payload", ObjectType::Blob)?;
+        
+        let object_path = objects_dir.join(&object_id[0..2]).join(&object_id[2..]);
+        let mut content = fs::read(&object_path)?;
+        content.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        fs::write(&object_path, content)?;
+        
+        assert_eq!(read_object(&objects_dir, &object_id)?, (ObjectType::Blob, b"payload".to_vec()));
         
         Ok(())
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_expand_packed_object_id() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let repo = Repository::init(&temp_dir)?;
+        let objects_dir = repo.git_dir.join("objects");
+        
+        // Two blobs whose ids share their first MIN_ABBREV_LEN digits
+        let mut seen: std::collections::HashMap<String, Vec<u8>> = std::collections::HashMap::new();
+        let (first, second) = (0..).find_map(|i: u32| {
+            let content = format!("blob {}\n", i).into_bytes();
+            let prefix = hash_object(&content, ObjectType::Blob)[..MIN_ABBREV_LEN].to_string();
+            match seen.get(&prefix) {
+                Some(other) => Some((other.clone(), content)),
+                None => {
+                    seen.insert(prefix, content);
+                    None
+                }
+            }
+        }).unwrap();
+        
+        // One packed, the other loose
+        let packed_id = write_object(&objects_dir, &first, ObjectType::Blob)?;
+        repo.repack()?;
+        assert_eq!(expand_object_id(&objects_dir, &packed_id[..7])?, packed_id);
+        let loose_id = write_object(&objects_dir, &second, ObjectType::Blob)?;
+        assert_eq!(expand_object_id(&objects_dir, &loose_id[..7])?, loose_id);
+        let error = expand_object_id(&objects_dir, &packed_id[..MIN_ABBREV_LEN]).unwrap_err();
+        assert!(error.to_string().contains("ambiguous"), "{}", error);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_signature_parse() {
+        let signature = Signature::parse("Jane Doe <jane@example.com> 1700000000 +0800").unwrap();
+        assert_eq!(signature.identity, "Jane Doe <jane@example.com>");
+        assert_eq!(signature.timestamp, "1700000000 +0800");
+        assert_eq!(signature.seconds(), 1700000000);
+        assert_eq!(signature.to_string(), "Jane Doe <jane@example.com> 1700000000 +0800");
+        
+        assert!(Signature::parse("no timestamp").is_none());
+    }
+    
+    #[test]
+    fn test_write_tree_nests_directories() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init(&temp_dir)?;
+        fs::create_dir_all(repo.path.join("dir/sub"))?;
+        for (name, content) in [("a.txt", "a\n"), ("dir.txt", "dir\n"), ("dir/b.txt", "b\n"), ("dir/sub/c.txt", "c\n")] {
+            fs::write(repo.path.join(name), content)?;
+        }
+        repo.index.add_directory(&repo.path, &repo.path, repo.objects.as_ref(), &repo.config)?;
+        
+        let tree_id = write_tree(&repo)?;
+        let store = repo.objects.as_ref();
+        let root = parse_tree(&store.read(&tree_id)?.data)?;
+        // "dir" sorts as "dir/", after "dir.txt"
+        let names: Vec<_> = root.iter().map(|entry| (entry.name.to_string_lossy().into_owned(), entry.mode)).collect();
+        assert_eq!(names, [("a.txt".to_string(), 0o100644), ("dir.txt".to_string(), 0o100644), ("dir".to_string(), MODE_TREE)]);
+        
+        let dir = parse_tree(&store.read(&root[2].oid)?.data)?;
+        assert_eq!(dir.iter().map(|entry| entry.name.clone()).collect::<Vec<_>>(), [PathBuf::from("b.txt"), PathBuf::from("sub")]);
+        let entry = find_tree_entry(store, &tree_id, Path::new("dir/sub/c.txt"))?.unwrap();
+        assert_eq!(entry.oid, hash_object(b"c\n", ObjectType::Blob));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_expand_object_id() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+        let blob_id = write_object(&objects_dir, b"expand me", ObjectType::Blob)?;
+        
+        assert_eq!(expand_object_id(&objects_dir, &blob_id[..7])?, blob_id);
+        assert_eq!(expand_object_id(&objects_dir, &blob_id.to_uppercase()[..10])?, blob_id);
+        assert_eq!(expand_object_id(&objects_dir, &blob_id)?, blob_id);
+        assert!(expand_object_id(&objects_dir, &blob_id[..3]).is_err());
+        assert!(expand_object_id(&objects_dir, "zzzzzzz").is_err());
+        assert!(expand_object_id(&objects_dir, "0000000").is_err());
+        
+        Ok(())
+    }
+}
@@ -1,113 +1,330 @@
-use anyhow::{Result};
-use std::collections::HashMap;
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{Write};
-use std::path::{Path};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use sha1::{Sha1, Digest};
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use hex;
 use fossil_delta;
 
-use super::objects;
+use super::objects::{self, ObjectType};
+use crate::verbose;
 
 struct PackedObject {
     oid: String,
-    object_type: String,
+    object_type: ObjectType,
     data: Vec<u8>, // Raw data without git object header
-    offset: u64,
 }
 
-pub fn create_pack(objects_dir: &Path) -> Result<()> {
-    // 1. Collect all loose objects
-    let mut loose_objects = Vec::new();
-    for entry in fs::read_dir(objects_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() && path.file_name().and_then(|s| s.to_str()).map_or(false, |s| s.len() == 2) {
-            for object_entry in fs::read_dir(path)? {
-                let object_entry = object_entry?;
-                let object_path = object_entry.path();
-                if object_path.is_file() {
-                    let dir_name = object_path.parent().unwrap().file_name().unwrap().to_str().unwrap();
-                    let file_name = object_path.file_name().unwrap().to_str().unwrap();
-                    let oid = format!("{}{}", dir_name, file_name);
-                    let (object_type, data) = objects::read_object(objects_dir, &oid)?;
-                    loose_objects.push(PackedObject { oid, object_type, data, offset: 0 });
+/// Collect the ids of every object (commits, trees, blobs, tags) reachable from `tips`.
+///
+/// Objects that cannot be read from the loose store are still reported, but are not
+/// traversed any further.
+pub fn reachable_objects(objects_dir: &Path, tips: &[String]) -> Result<HashSet<String>> {
+    let mut reachable = HashSet::new();
+    let mut stack: Vec<String> = tips.to_vec();
+
+    while let Some(oid) = stack.pop() {
+        if !reachable.insert(oid.clone()) {
+            continue;
+        }
+
+        let (object_type, data) = match objects::read_object(objects_dir, &oid) {
+            Ok(object) => object,
+            Err(_) => continue,
+        };
+
+        match object_type {
+            ObjectType::Commit | ObjectType::Tag => {
+                let content = String::from_utf8_lossy(&data);
+                for line in content.lines() {
+                    if line.is_empty() {
+                        break; // End of headers, the rest is the message
+                    }
+                    if let Some((key, value)) = line.split_once(' ') {
+                        if matches!(key, "tree" | "parent" | "object") {
+                            stack.push(value.trim().to_string());
+                        }
+                    }
+                }
+            }
+            ObjectType::Tree => {
+                let mut cursor = 0;
+                while cursor < data.len() {
+                    let Some(null_idx) = data[cursor..].iter().position(|&b| b == 0) else {
+                        break;
+                    };
+                    let sha1_start = cursor + null_idx + 1;
+                    let sha1_end = sha1_start + 20;
+                    if sha1_end > data.len() {
+                        anyhow::bail!("Malformed tree object {}: not enough data for SHA1 hash", oid);
+                    }
+                    stack.push(hex::encode(&data[sha1_start..sha1_end]));
+                    cursor = sha1_end;
                 }
             }
+            _ => {}
         }
     }
 
+    Ok(reachable)
+}
+
+/// Delete loose objects that are not in `reachable` and were last modified before `expire_before`.
+///
+/// Packed objects are never touched. Returns the ids of the deleted objects.
+pub fn prune_loose_objects(objects_dir: &Path, reachable: &HashSet<String>, expire_before: SystemTime) -> Result<Vec<String>> {
+    let mut pruned = Vec::new();
+    if !objects_dir.exists() {
+        return Ok(pruned);
+    }
+
+    for dir_entry in fs::read_dir(objects_dir)? {
+        let dir_entry = dir_entry?;
+        let dir_name = dir_entry.file_name().to_string_lossy().to_string();
+        if dir_name.len() != 2 || !dir_entry.path().is_dir() {
+            continue; // Skip pack/, info/ and anything else that is not a loose object fan-out dir
+        }
+
+        for file_entry in fs::read_dir(dir_entry.path())? {
+            let file_entry = file_entry?;
+            let object_id = format!("{}{}", dir_name, file_entry.file_name().to_string_lossy());
+            if object_id.len() != 40 || reachable.contains(&object_id) {
+                continue;
+            }
+            if file_entry.metadata()?.modified()? >= expire_before {
+                continue; // Too recent: may belong to an operation still in progress
+            }
+            fs::remove_file(file_entry.path())?;
+            pruned.push(object_id);
+        }
+
+        // Drop the fan-out directory once it is empty
+        if fs::read_dir(dir_entry.path())?.next().is_none() {
+            fs::remove_dir(dir_entry.path())?;
+        }
+    }
+
+    pruned.sort();
+    Ok(pruned)
+}
+
+/// Tuning for `create_pack_with_options`: how hard to compress and how far to look for delta bases
+#[derive(Debug, Clone, Copy)]
+pub struct PackOptions {
+    /// zlib compression applied to every pack entry
+    pub compression: Compression,
+    /// How many previously packed objects are tried as delta bases for each object
+    pub window: usize,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self { compression: Compression::default(), window: 10 }
+    }
+}
+
+impl PackOptions {
+    /// Smallest packs at the cost of time, as used by `gc --aggressive`
+    pub fn aggressive() -> Self {
+        Self { compression: Compression::best(), window: 250 }
+    }
+}
+
+/// Pack all loose objects with the default `PackOptions`
+pub fn create_pack(objects_dir: &Path) -> Result<()> {
+    create_pack_with_options(objects_dir, &PackOptions::default())
+}
+
+/// Pack all loose objects into a new pack file and remove them from the loose store
+pub fn create_pack_with_options(objects_dir: &Path, options: &PackOptions) -> Result<()> {
+    create_pack_of(objects_dir, options, |_| true)
+}
+
+/// Pack the loose objects whose id `include` accepts into a new pack file and remove them from
+/// the loose store. The other loose objects are left as they are.
+pub fn create_pack_of(objects_dir: &Path, options: &PackOptions, include: impl Fn(&str) -> bool) -> Result<()> {
+    // 1. Collect the loose objects to pack
+    let mut loose_objects = read_loose_objects(objects_dir)?;
+    loose_objects.retain(|obj| include(&obj.oid));
+
     if loose_objects.is_empty() { return Ok(()); }
 
-    // 2. Sort objects by type and size to improve delta potential
-    loose_objects.sort_by(|a, b| a.object_type.cmp(&b.object_type).then(a.data.len().cmp(&b.data.len())));
+    pack_objects(objects_dir, loose_objects, options)
+}
+
+/// Write `objects` into a new pack file under `objects_dir/pack`, deltified as `create_pack`
+/// would, without storing any of them as loose objects first. Objects given more than once
+/// are packed once.
+pub fn write_pack_of_objects(objects_dir: &Path, objects: Vec<(ObjectType, Vec<u8>)>, options: &PackOptions) -> Result<()> {
+    let mut seen = HashSet::new();
+    let objects: Vec<PackedObject> = objects
+        .into_iter()
+        .map(|(object_type, data)| PackedObject { oid: objects::hash_object(&data, object_type), object_type, data })
+        .filter(|obj| seen.insert(obj.oid.clone()))
+        .collect();
+    if objects.is_empty() { return Ok(()); }
 
-    // 3. Prepare pack data, finding deltas along the way
+    pack_objects(objects_dir, objects, options)
+}
+
+// Deltify `loose_objects` and write them as a new pack file, removing any loose copies
+fn pack_objects(objects_dir: &Path, mut loose_objects: Vec<PackedObject>, options: &PackOptions) -> Result<()> {
+    // 2. Sort objects by type, then file name, then size, so revisions of the same file sit together
+    let name_hints = name_hints(&loose_objects);
+    let name_of = |obj: &PackedObject| name_hints.get(&obj.oid).cloned().unwrap_or_default();
+    loose_objects.sort_by(|a, b| {
+        a.object_type.cmp(&b.object_type)
+            .then_with(|| name_of(a).cmp(&name_of(b)))
+            .then(a.data.len().cmp(&b.data.len()))
+    });
+
+    // 3. Prepare pack data, deltifying each object against the candidate base that gives the
+    //    smallest delta: the previous `window` objects, plus the previous `window` objects
+    //    stored under the same file name
     let mut packed_items = Vec::new();
-    let mut packed_objects_for_lookup: Vec<&PackedObject> = Vec::new();
+    let mut chain_depths: Vec<usize> = Vec::with_capacity(loose_objects.len());
+    let mut same_name: HashMap<String, Vec<usize>> = HashMap::new();
     
-    for obj in &loose_objects {
-        let mut best_base: Option<(&PackedObject, Vec<u8>)> = None;
-
-        let search_window = packed_objects_for_lookup.iter().rev().take(10);
-        for base in search_window {
-            if obj.object_type == base.object_type {
-                let delta = fossil_delta::delta(&base.data, &obj.data);
-                if !delta.is_empty() && delta.len() < obj.data.len() {
-                    best_base = Some((base, delta));
-                    break;
-                }
+    for (index, obj) in loose_objects.iter().enumerate() {
+        let name = name_of(obj);
+        let mut candidates: Vec<usize> = (index.saturating_sub(options.window)..index).collect();
+        if !name.is_empty() {
+            if let Some(previous) = same_name.get(&name) {
+                candidates.extend(previous.iter().rev().take(options.window));
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut best_base: Option<(usize, Vec<u8>)> = None;
+        for base_index in candidates {
+            let base = &loose_objects[base_index];
+            // Keep chains within what the reader is willing to follow
+            if base.object_type != obj.object_type || chain_depths[base_index] >= MAX_DELTA_DEPTH {
+                continue;
+            }
+            // fossil_delta::delta(target, source) rebuilds `target` when applied to `source`
+            let delta = fossil_delta::delta(&obj.data, &base.data);
+            let smallest_so_far = best_base.as_ref().is_none_or(|(_, best)| delta.len() < best.len());
+            if !delta.is_empty() && delta.len() < obj.data.len() && smallest_so_far {
+                best_base = Some((base_index, delta));
             }
         }
 
-        if let Some((base, delta)) = best_base {
-            packed_items.push(PackEntry::Delta { oid: obj.oid.clone(), base_oid: base.oid.clone(), delta });
+        if let Some((base_index, delta)) = best_base {
+            chain_depths.push(chain_depths[base_index] + 1);
+            packed_items.push(PackEntry::Delta { oid: obj.oid.clone(), base_oid: loose_objects[base_index].oid.clone(), delta });
         } else {
-            packed_items.push(PackEntry::Full { oid: obj.oid.clone(), object_type: obj.object_type.clone(), data: obj.data.clone() });
+            chain_depths.push(0);
+            packed_items.push(PackEntry::Full { oid: obj.oid.clone(), object_type: obj.object_type, data: obj.data.clone() });
+        }
+        if !name.is_empty() {
+            same_name.entry(name).or_default().push(index);
         }
-        packed_objects_for_lookup.push(obj);
     }
     
     // 4. Write pack file
-    write_pack_file(objects_dir, &mut packed_items)
+    write_pack_file(objects_dir, &mut packed_items, options.compression)
+}
+
+/// Read every loose object in `objects_dir`
+fn read_loose_objects(objects_dir: &Path) -> Result<Vec<PackedObject>> {
+    scan_loose_objects(objects_dir)?
+        .objects
+        .into_iter()
+        .map(|(oid, _)| {
+            let (object_type, data) = objects::read_object(objects_dir, &oid)?;
+            Ok(PackedObject { oid, object_type, data })
+        })
+        .collect()
+}
+
+/// The files in the fan-out directories of an objects dir, as `scan_loose_objects` finds them
+#[derive(Debug, Clone, Default)]
+pub struct LooseObjectFiles {
+    /// `(oid, path)` of each loose object
+    pub objects: Vec<(String, PathBuf)>,
+    /// Every other file, such as stray temporary files
+    pub others: Vec<PathBuf>,
+}
+
+/// List the files in the fan-out directories of `objects_dir`, telling loose objects apart
+/// from anything else found there
+pub fn scan_loose_objects(objects_dir: &Path) -> Result<LooseObjectFiles> {
+    let mut files = LooseObjectFiles::default();
+    if !objects_dir.exists() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(objects_dir)? {
+        let path = entry?.path();
+        let dir_name = path.file_name().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+        if !path.is_dir() || dir_name.len() != 2 || !dir_name.bytes().all(|b| b.is_ascii_hexdigit()) {
+            continue; // Not a fan-out dir, e.g. pack/ or info/
+        }
+        for object_entry in fs::read_dir(path)? {
+            let object_path = object_entry?.path();
+            if !object_path.is_file() {
+                continue;
+            }
+            let file_name = object_path.file_name().and_then(|s| s.to_str()).unwrap_or_default();
+            if file_name.len() == 38 && file_name.bytes().all(|b| b.is_ascii_hexdigit()) {
+                files.objects.push((format!("{}{}", dir_name, file_name), object_path));
+            } else {
+                files.others.push(object_path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Map blobs and trees among `objects` to the file name they are stored under in the trees
+/// among `objects`, so revisions of the same file can be deltified against each other.
+fn name_hints(objects: &[PackedObject]) -> HashMap<String, String> {
+    let mut hints = HashMap::new();
+    for obj in objects.iter().filter(|obj| obj.object_type == ObjectType::Tree) {
+        let Ok(entries) = objects::parse_tree(&obj.data) else {
+            continue;
+        };
+        for entry in entries {
+            let name = entry.name.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            hints.entry(entry.oid).or_insert(name);
+        }
+    }
+    hints
 }
 
 enum PackEntry {
-    Full { oid: String, object_type: String, data: Vec<u8> },
+    Full { oid: String, object_type: ObjectType, data: Vec<u8> },
     Delta { oid: String, base_oid: String, delta: Vec<u8> },
 }
 
-fn write_pack_file(objects_dir: &Path, items: &mut Vec<PackEntry>) -> Result<()> {
+fn write_pack_file(objects_dir: &Path, items: &mut [PackEntry], compression: Compression) -> Result<()> {
     let pack_dir = objects_dir.join("pack");
     fs::create_dir_all(&pack_dir)?;
     
-    let pack_name_sha = Sha1::new().chain_update(format!("{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos()).as_bytes()).finalize();
-    let pack_name = format!("pack-{}", hex::encode(pack_name_sha));
-    let pack_file_path = pack_dir.join(format!("{}.pack", &pack_name));
-    let idx_file_path = pack_dir.join(format!("{}.idx", &pack_name));
-
-    let mut pack_file = fs::File::create(&pack_file_path)?;
-    pack_file.write_all(b"PACK")?;
-    pack_file.write_all(&2u32.to_be_bytes())?;
-    pack_file.write_all(&(items.len() as u32).to_be_bytes())?;
+    // The pack is built in memory, so the trailing checksum covers exactly the bytes written
+    let mut pack_data = Vec::new();
+    pack_data.extend_from_slice(b"PACK");
+    pack_data.extend_from_slice(&2u32.to_be_bytes());
+    pack_data.extend_from_slice(&(items.len() as u32).to_be_bytes());
 
     let mut current_offset = 12;
     let mut oid_to_offset_map = HashMap::new();
     let mut final_offsets = HashMap::new();
 
     for item in items.iter_mut() {
-        let mut compressor = ZlibEncoder::new(Vec::new(), Compression::default());
-        let header;
+        let mut compressor = ZlibEncoder::new(Vec::new(), compression);
         
-        match item {
+        let header = match item {
             PackEntry::Full { oid: _, object_type, data } => {
                 let mut full_data_with_header = format!("{} {}\0", object_type, data.len()).into_bytes();
                 full_data_with_header.extend_from_slice(data);
                 compressor.write_all(&full_data_with_header)?;
-                header = get_pack_header(full_data_with_header.len(), object_type)?;
+                get_pack_header(full_data_with_header.len(), object_type.type_id())
             }
             PackEntry::Delta { oid: _, base_oid, delta } => {
                 let base_offset = oid_to_offset_map.get(base_oid).unwrap();
@@ -124,9 +341,9 @@ fn write_pack_file(objects_dir: &Path, items: &mut Vec<PackEntry>) -> Result<()>
                 }
                 delta_with_offset.extend_from_slice(delta);
                 compressor.write_all(&delta_with_offset)?;
-                header = get_pack_header(delta_with_offset.len(), "offset_delta")?;
+                get_pack_header(delta_with_offset.len(), OFS_DELTA)
             }
-        }
+        };
         
         let compressed_data = compressor.finish()?;
         let oid_str = match item {
@@ -135,17 +352,31 @@ fn write_pack_file(objects_dir: &Path, items: &mut Vec<PackEntry>) -> Result<()>
         };
         oid_to_offset_map.insert(oid_str.clone(), current_offset);
         final_offsets.insert(oid_str.clone(), current_offset);
+        match item {
+            PackEntry::Full { oid, object_type, .. } => verbose!("packed {} {}", oid, object_type),
+            PackEntry::Delta { oid, base_oid, .. } => verbose!("packed {} as a delta of {}", oid, base_oid),
+        }
 
-        pack_file.write_all(&header)?;
-        pack_file.write_all(&compressed_data)?;
+        pack_data.extend_from_slice(&header);
+        pack_data.extend_from_slice(&compressed_data);
         current_offset += (header.len() + compressed_data.len()) as u64;
     }
 
-    let pack_content = fs::read(&pack_file_path)?;
-    let pack_sha = Sha1::new().chain_update(&pack_content).finalize();
-    pack_file.write_all(&pack_sha[..])?;
+    let pack_sha = Sha1::digest(&pack_data);
+    pack_data.extend_from_slice(&pack_sha);
+
+    // Written under a temporary name until the index is ready too
+    let mut pack_file = tempfile::Builder::new().prefix("tmp_pack_").tempfile_in(&pack_dir)?;
+    pack_file.write_all(&pack_data)?;
+    pack_file.as_file().sync_all()?;
+    
+    let idx_file = tempfile::Builder::new().prefix("tmp_idx_").tempfile_in(&pack_dir)?;
+    write_idx_file(idx_file.path(), &final_offsets, &pack_sha)?;
     
-    write_idx_file(&idx_file_path, items, &final_offsets, &pack_sha)?;
+    // Move the pack into place before its index, so no index ever names a missing pack
+    let pack_name = pack_name(final_offsets.keys())?;
+    pack_file.persist(pack_dir.join(format!("{}.pack", pack_name)))?;
+    idx_file.persist(pack_dir.join(format!("{}.idx", pack_name)))?;
     
     // Cleanup: Precisely remove only the loose objects that were packed.
     for item in items.iter() {
@@ -171,32 +402,587 @@ fn write_pack_file(objects_dir: &Path, items: &mut Vec<PackEntry>) -> Result<()>
     Ok(())
 }
 
-fn get_pack_header(size: usize, object_type: &str) -> Result<Vec<u8>> {
-    let type_id = match object_type {
-        "commit" => 1,
-        "tree" => 2,
-        "blob" => 3,
-        "tag" => 4,
-        "offset_delta" => 6,
-        _ => anyhow::bail!("Unknown object type for packing: {}", object_type),
-    };
+/// Pack entry type of a delta naming its base by its offset back from the entry
+const OFS_DELTA: u8 = 6;
+/// Pack entry type of a delta naming its base by object id
+const REF_DELTA: u8 = 7;
+
+fn get_pack_header(size: usize, type_id: u8) -> Vec<u8> {
+    // Type and the low 4 bits of the size first, then the rest of the size 7 bits at a time,
+    // with the high bit of every byte but the last set
     let mut header = Vec::new();
-    let mut s = size;
-    let mut byte = ((type_id << 4) | (s & 0x0f)) as u8;
-    s >>= 4;
+    let mut s = size >> 4;
+    let mut byte = (type_id << 4) | (size & 0x0f) as u8;
     while s > 0 {
         header.push(byte | 0x80);
         byte = (s & 0x7f) as u8;
         s >>= 7;
     }
     header.push(byte);
-    header.reverse();
-    Ok(header)
+    header
+}
+
+/// Decode an entry header written by `get_pack_header`, returning the type id, the size
+/// and the header length in bytes.
+fn parse_pack_header(data: &[u8]) -> Result<(u8, usize, usize)> {
+    let first = *data.first().context("Truncated pack entry header")?;
+    let type_id = (first >> 4) & 0x07;
+    let mut size = (first & 0x0f) as usize;
+    let mut shift = 4;
+    let mut len = 1;
+    let mut byte = first;
+    while byte & 0x80 != 0 {
+        byte = *data.get(len).context("Truncated pack entry header")?;
+        if shift > 57 {
+            anyhow::bail!("Pack entry size is too large");
+        }
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        len += 1;
+    }
+    Ok((type_id, size, len))
+}
+
+/// Write `objects` as a pack stream in git's own format, as carried by bundles and the wire
+/// protocol: every entry undeltified, its data compressed without the loose object header,
+/// followed by the SHA-1 of everything before it.
+pub fn write_pack_stream(mut writer: impl Write, objects: &[(ObjectType, Vec<u8>)]) -> Result<()> {
+    let mut pack = Vec::new();
+    pack.extend_from_slice(b"PACK");
+    pack.extend_from_slice(&2u32.to_be_bytes());
+    pack.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+    for (object_type, data) in objects {
+        pack.extend(get_pack_header(data.len(), object_type.type_id()));
+        let mut compressor = ZlibEncoder::new(Vec::new(), Compression::default());
+        compressor.write_all(data)?;
+        pack.extend(compressor.finish()?);
+    }
+    let checksum = Sha1::new().chain_update(&pack).finalize();
+    writer.write_all(&pack)?;
+    writer.write_all(&checksum)?;
+    Ok(())
+}
+
+/// Apply a delta in git's format (as opposed to the fossil deltas of this crate's own packs)
+/// to `base`: the source and target sizes, then copy-from-base and insert-literal instructions.
+fn apply_git_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    fn read_size(delta: &[u8], pos: &mut usize) -> Result<usize> {
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(*pos).context("Truncated delta header")?;
+            *pos += 1;
+            if shift > 57 {
+                anyhow::bail!("Delta size is too large");
+            }
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(size);
+            }
+        }
+    }
+
+    let mut pos = 0;
+    if read_size(delta, &mut pos)? != base.len() {
+        anyhow::bail!("Delta base size does not match");
+    }
+    let target_size = read_size(delta, &mut pos)?;
+    // The sizes are untrusted, so reserve no more than the base and delta could plausibly yield
+    let mut target = Vec::with_capacity(target_size.min(base.len() + delta.len()));
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            let mut fields = [0usize; 7];
+            for (bit, field) in fields.iter_mut().enumerate() {
+                if op & (1 << bit) != 0 {
+                    *field = *delta.get(pos).context("Truncated delta copy")? as usize;
+                    pos += 1;
+                }
+            }
+            let offset = fields[0] | fields[1] << 8 | fields[2] << 16 | fields[3] << 24;
+            let size = match fields[4] | fields[5] << 8 | fields[6] << 16 {
+                0 => 0x10000,
+                size => size,
+            };
+            target.extend_from_slice(base.get(offset..offset + size).context("Delta copy is out of range")?);
+        } else if op != 0 {
+            let literal = delta.get(pos..pos + op as usize).context("Truncated delta insert")?;
+            target.extend_from_slice(literal);
+            pos += op as usize;
+        } else {
+            anyhow::bail!("Invalid delta instruction");
+        }
+        if target.len() > target_size {
+            anyhow::bail!("Delta produced more than {} bytes", target_size);
+        }
+    }
+    if target.len() != target_size {
+        anyhow::bail!("Delta produced {} bytes instead of {}", target.len(), target_size);
+    }
+    Ok(target)
+}
+
+/// Read a pack stream in git's format, as written by `write_pack_stream` or by git itself,
+/// returning each object's type and data in stream order.
+///
+/// Offset deltas are resolved against earlier entries; ref deltas against earlier entries
+/// or, failing that, objects already in `objects_dir`.
+pub fn read_pack_stream(objects_dir: &Path, pack: &[u8]) -> Result<Vec<(ObjectType, Vec<u8>)>> {
+    if pack.len() < 32 || &pack[..4] != b"PACK" {
+        anyhow::bail!("Not a pack stream");
+    }
+    let version = u32::from_be_bytes(pack[4..8].try_into()?);
+    if version != 2 && version != 3 {
+        anyhow::bail!("Unsupported pack version {}", version);
+    }
+    let (content, checksum) = pack.split_at(pack.len() - 20);
+    if Sha1::new().chain_update(content).finalize()[..] != *checksum {
+        anyhow::bail!("Pack checksum mismatch");
+    }
+
+    let count = u32::from_be_bytes(pack[8..12].try_into()?) as usize;
+    // Every entry takes at least two bytes, which bounds what an untrusted count can reserve
+    let mut entries: Vec<(ObjectType, Vec<u8>)> = Vec::with_capacity(count.min((content.len() - 12) / 2));
+    let mut index_by_offset: HashMap<usize, usize> = HashMap::new();
+    let mut index_by_oid: HashMap<String, usize> = HashMap::new();
+    let mut offset = 12;
+    for _ in 0..count {
+        let entry_offset = offset;
+        let (type_id, size, header_len) = parse_pack_header(content.get(offset..).context("Truncated pack stream")?)?;
+        offset += header_len;
+
+        let base: Option<(ObjectType, Vec<u8>)> = match type_id {
+            OFS_DELTA => {
+                let mut byte = *content.get(offset).context("Truncated offset delta")?;
+                offset += 1;
+                let mut distance = (byte & 0x7f) as usize;
+                while byte & 0x80 != 0 {
+                    byte = *content.get(offset).context("Truncated offset delta")?;
+                    offset += 1;
+                    if distance > content.len() {
+                        anyhow::bail!("Offset delta points outside the pack");
+                    }
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as usize;
+                }
+                let base_offset = entry_offset.checked_sub(distance).context("Offset delta points outside the pack")?;
+                let index = *index_by_offset.get(&base_offset).context("Offset delta base is not an entry")?;
+                Some(entries[index].clone())
+            }
+            REF_DELTA => {
+                let base_oid = hex::encode(content.get(offset..offset + 20).context("Truncated ref delta")?);
+                offset += 20;
+                match index_by_oid.get(&base_oid) {
+                    Some(&index) => Some(entries[index].clone()),
+                    None => Some(objects::read_object(objects_dir, &base_oid)
+                        .with_context(|| format!("Missing ref delta base {}", base_oid))?),
+                }
+            }
+            _ => None,
+        };
+
+        let (data, consumed) = objects::decompress(&content[offset..])?;
+        offset += consumed;
+        if data.len() != size {
+            anyhow::bail!("Pack entry at offset {} has size {} but header says {}", entry_offset, data.len(), size);
+        }
+
+        let (object_type, data) = match (ObjectType::from_type_id(type_id), base) {
+            (Some(object_type), _) => (object_type, data),
+            (None, Some((base_type, base_data))) => (base_type, apply_git_delta(&base_data, &data)?),
+            (None, None) => anyhow::bail!("Unsupported pack entry type {} at offset {}", type_id, entry_offset),
+        };
+        index_by_offset.insert(entry_offset, entries.len());
+        index_by_oid.insert(objects::hash_object(&data, object_type), entries.len());
+        entries.push((object_type, data));
+    }
+
+    Ok(entries)
 }
 
-fn write_idx_file(idx_path: &Path, items: &[PackEntry], offsets: &HashMap<String, u64>, pack_sha: &[u8]) -> Result<()> {
-    let mut idx_file = fs::File::create(idx_path)?;
-    idx_file.write_all(&[0xff, 0x74, 0x4f, 0x63, 0x00, 0x00, 0x00, 0x02])?;
+/// The number of objects a version 2 pack index lists, from the last entry of its fan-out table
+pub fn pack_index_count(idx_path: &Path) -> Result<usize> {
+    let mut header = [0u8; 8 + 256 * 4];
+    fs::File::open(idx_path)?
+        .read_exact(&mut header)
+        .with_context(|| format!("{} is not a version 2 pack index", idx_path.display()))?;
+    if header[..8] != [0xff, 0x74, 0x4f, 0x63, 0x00, 0x00, 0x00, 0x02] {
+        anyhow::bail!("{} is not a version 2 pack index", idx_path.display());
+    }
+    Ok(u32::from_be_bytes(header[8 + 255 * 4..].try_into()?) as usize)
+}
+
+/// Read the `(oid, offset)` pairs of a version 2 pack index, in oid order.
+pub fn read_pack_index(idx_path: &Path) -> Result<Vec<(String, u64)>> {
+    let idx = fs::read(idx_path)?;
+    if idx.len() < 8 + 256 * 4 + 40 || idx[..8] != [0xff, 0x74, 0x4f, 0x63, 0x00, 0x00, 0x00, 0x02] {
+        anyhow::bail!("{} is not a version 2 pack index", idx_path.display());
+    }
+
+    let count = u32::from_be_bytes(idx[8 + 255 * 4..8 + 256 * 4].try_into()?) as usize;
+    let oids_start = 8 + 256 * 4;
+    let crcs_start = oids_start + count * 20;
+    let offsets_start = crcs_start + count * 4;
+    if idx.len() < offsets_start + count * 4 + 40 {
+        anyhow::bail!("Truncated pack index {}", idx_path.display());
+    }
+
+    // Each listed object takes 28 bytes of the index, however many the header claims
+    let mut entries = Vec::with_capacity(count.min(idx.len() / 28));
+    for i in 0..count {
+        let oid = hex::encode(&idx[oids_start + i * 20..oids_start + (i + 1) * 20]);
+        let offset_bytes = &idx[offsets_start + i * 4..offsets_start + (i + 1) * 4];
+        let offset = u32::from_be_bytes(offset_bytes.try_into()?);
+        if offset & 0x8000_0000 != 0 {
+            anyhow::bail!("Large pack offsets are not supported");
+        }
+        entries.push((oid, offset as u64));
+    }
+    Ok(entries)
+}
+
+/// A pack entry as stored, before any delta is applied
+enum RawPackEntry {
+    Full { object_type: ObjectType, data: Vec<u8> },
+    OffsetDelta { base_offset: u64, delta: Vec<u8> },
+    RefDelta { base_oid: String, delta: Vec<u8> },
+}
+
+/// Decode the entry starting at `offset` in the pack.
+///
+/// Full entries compress `<type> <size>\0<data>`; offset deltas compress the little-endian
+/// base-7 distance back to their base entry followed by a fossil delta against the base.
+/// Ref deltas name their base by the 20-byte id stored before the compressed fossil delta,
+/// as git does, so the base may live outside the pack.
+fn read_raw_entry(pack: &[u8], offset: u64) -> Result<RawPackEntry> {
+    let start = offset as usize;
+    if start >= pack.len() {
+        anyhow::bail!("Pack offset {} is out of range", offset);
+    }
+    let (type_id, size, header_len) = parse_pack_header(&pack[start..])?;
+    let mut data_start = start + header_len;
+    let base_oid = if type_id == REF_DELTA {
+        let oid_bytes = pack.get(data_start..data_start + 20)
+            .with_context(|| format!("Truncated ref delta at offset {}", offset))?;
+        data_start += 20;
+        Some(hex::encode(oid_bytes))
+    } else {
+        None
+    };
+    let (content, _) = objects::decompress(&pack[data_start..])
+        .with_context(|| format!("Corrupt pack entry at offset {}", offset))?;
+    if content.len() != size {
+        anyhow::bail!("Pack entry at offset {} has size {} but header says {}", offset, content.len(), size);
+    }
+
+    match (type_id, base_oid) {
+        (1..=4, _) => {
+            // The stored header names the type; the entry's type id is not trusted over it
+            let null_pos = content.iter().position(|&b| b == 0)
+                .with_context(|| format!("Pack entry at offset {} has no object header", offset))?;
+            let header = std::str::from_utf8(&content[..null_pos])?;
+            let object_type = header.split(' ').next().unwrap_or_default().parse()?;
+            Ok(RawPackEntry::Full { object_type, data: content[null_pos + 1..].to_vec() })
+        }
+        (OFS_DELTA, _) => {
+            let mut distance = 0u64;
+            let mut shift = 0;
+            let mut len = 0;
+            loop {
+                let byte = *content.get(len).context("Truncated offset delta")?;
+                if shift > 57 {
+                    anyhow::bail!("Offset delta at {} points outside the pack", offset);
+                }
+                distance |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                len += 1;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            let base_offset = offset.checked_sub(distance)
+                .filter(|_| distance > 0)
+                .with_context(|| format!("Offset delta at {} points outside the pack", offset))?;
+            Ok(RawPackEntry::OffsetDelta { base_offset, delta: content[len..].to_vec() })
+        }
+        (REF_DELTA, Some(base_oid)) => Ok(RawPackEntry::RefDelta { base_oid, delta: content }),
+        (other, _) => anyhow::bail!("Unsupported pack entry type {} at offset {}", other, offset),
+    }
+}
+
+/// A pack file and its index, loaded for reading
+struct Pack<'a> {
+    objects_dir: &'a Path,
+    /// Pack contents without the trailing checksum
+    data: &'a [u8],
+    /// `(oid, offset)` pairs sorted by oid
+    index: &'a [(String, u64)],
+}
+
+/// Longest delta chain followed before a pack is considered corrupt
+const MAX_DELTA_DEPTH: usize = 50;
+
+impl Pack<'_> {
+    fn offset_of(&self, oid: &str) -> Option<u64> {
+        self.index
+            .binary_search_by(|(entry_oid, _)| entry_oid.as_str().cmp(oid))
+            .ok()
+            .map(|position| self.index[position].1)
+    }
+
+    /// Materialize the object stored at `offset`, applying deltas down to the full base entry.
+    ///
+    /// `depth` counts the deltas already followed; chains longer than `MAX_DELTA_DEPTH`
+    /// (including cycles) are rejected. Ref-delta bases missing from this pack are read
+    /// from the rest of the object store.
+    fn resolve(&self, offset: u64, depth: usize) -> Result<(ObjectType, Vec<u8>)> {
+        let (base, delta) = match read_raw_entry(self.data, offset)? {
+            RawPackEntry::Full { object_type, data } => return Ok((object_type, data)),
+            RawPackEntry::OffsetDelta { base_offset, delta } => (DeltaBase::Offset(base_offset), delta),
+            RawPackEntry::RefDelta { base_oid, delta } => (DeltaBase::Oid(base_oid), delta),
+        };
+        if depth >= MAX_DELTA_DEPTH {
+            anyhow::bail!("Delta chain at offset {} is too deep", offset);
+        }
+
+        let (object_type, base_data) = match base {
+            DeltaBase::Offset(base_offset) => self.resolve(base_offset, depth + 1)?,
+            DeltaBase::Oid(base_oid) => match self.offset_of(&base_oid) {
+                Some(base_offset) => self.resolve(base_offset, depth + 1)?,
+                None => objects::read_object(self.objects_dir, &base_oid)
+                    .with_context(|| format!("Cannot find delta base {}", base_oid))?,
+            },
+        };
+        let data = fossil_delta::apply(&base_data, &delta)
+            .map_err(|e| anyhow::anyhow!("Cannot apply delta at offset {}: {:?}", offset, e))?;
+        Ok((object_type, data))
+    }
+
+    /// Follow the delta chain starting at a delta entry's base to find the object's type and
+    /// how many deltas deep the entry is.
+    fn delta_chain(&self, first_base: DeltaBase) -> Result<(ObjectType, usize)> {
+        let mut depth = 1;
+        let mut base = first_base;
+        loop {
+            let offset = match base {
+                DeltaBase::Offset(offset) => offset,
+                DeltaBase::Oid(oid) => match self.offset_of(&oid) {
+                    Some(offset) => offset,
+                    None => return Ok((objects::read_object(self.objects_dir, &oid)?.0, depth)),
+                },
+            };
+            base = match read_raw_entry(self.data, offset)? {
+                RawPackEntry::Full { object_type, .. } => return Ok((object_type, depth)),
+                RawPackEntry::OffsetDelta { base_offset, .. } => DeltaBase::Offset(base_offset),
+                RawPackEntry::RefDelta { base_oid, .. } => DeltaBase::Oid(base_oid),
+            };
+            depth += 1;
+            if depth > MAX_DELTA_DEPTH {
+                anyhow::bail!("Delta chain at offset {} is too deep", offset);
+            }
+        }
+    }
+}
+
+/// Where a delta entry finds its base
+enum DeltaBase {
+    Offset(u64),
+    Oid(String),
+}
+
+// The pack indexes under `objects_dir/pack` and then under its alternates', each with the
+// objects dir holding it
+fn pack_index_paths(objects_dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let mut idx_paths = Vec::new();
+    for dir in objects::object_dirs_with_alternates(objects_dir) {
+        let Ok(pack_dir) = fs::read_dir(dir.join("pack")) else {
+            continue;
+        };
+        for path in pack_dir.filter_map(Result::ok).map(|entry| entry.path()) {
+            if path.extension().and_then(|ext| ext.to_str()) == Some("idx") {
+                idx_paths.push((dir.clone(), path));
+            }
+        }
+    }
+    idx_paths
+}
+
+/// Look up `oid` in the packs under `objects_dir/pack` and those of its alternates, returning
+/// its type and data.
+///
+/// Returns `None` when no pack holds the object.
+pub fn read_packed_object(objects_dir: &Path, oid: &str) -> Result<Option<(ObjectType, Vec<u8>)>> {
+    for (objects_dir, idx_path) in pack_index_paths(objects_dir) {
+        let objects_dir = objects_dir.as_path();
+        let index = read_pack_index(&idx_path)?;
+        let Ok(position) = index.binary_search_by(|(entry_oid, _)| entry_oid.as_str().cmp(oid)) else {
+            continue;
+        };
+
+        // Only a pack whose index lists the object is read
+        let data = fs::read(idx_path.with_extension("pack"))?;
+        let pack = Pack { objects_dir, data: &data, index: &index };
+        let object = pack.resolve(index[position].1, 0)
+            .with_context(|| format!("Cannot read {} from pack {}", oid, idx_path.display()))?;
+        return Ok(Some(object));
+    }
+
+    Ok(None)
+}
+
+/// The ids starting with `prefix` of the objects in the packs under `objects_dir/pack` and
+/// those of its alternates
+pub fn packed_object_ids_with_prefix(objects_dir: &Path, prefix: &str) -> Result<Vec<String>> {
+    let mut object_ids = Vec::new();
+    for (_, idx_path) in pack_index_paths(objects_dir) {
+        let index = read_pack_index(&idx_path)?;
+        let start = index.partition_point(|(oid, _)| oid.as_str() < prefix);
+        object_ids.extend(index[start..].iter().take_while(|(oid, _)| oid.starts_with(prefix)).map(|(oid, _)| oid.clone()));
+    }
+    Ok(object_ids)
+}
+
+/// Whether a pack under `objects_dir/pack` or an alternate's holds `oid`, consulting only the
+/// pack indexes. Each call reads every index; `PackIndexes` reads them once for many lookups.
+pub fn has_packed_object(objects_dir: &Path, oid: &str) -> bool {
+    PackIndexes::load(objects_dir).contains(oid)
+}
+
+/// The object ids listed by the pack indexes under an objects dir and its alternates, read
+/// once so that checking many objects does not parse every index for each.
+///
+/// Packs are named after their contents and never change once written, so the loaded ids
+/// stay right for as long as the same indexes are present.
+#[derive(Debug, Default)]
+pub struct PackIndexes {
+    idx_paths: Vec<PathBuf>,
+    /// Each index's ids, sorted
+    object_ids: Vec<Vec<String>>,
+}
+
+impl PackIndexes {
+    /// Read the indexes under `objects_dir` and its alternates; unreadable ones are skipped
+    pub fn load(objects_dir: &Path) -> Self {
+        let mut pack_indexes = Self::default();
+        for (_, idx_path) in pack_index_paths(objects_dir) {
+            if let Ok(index) = read_pack_index(&idx_path) {
+                pack_indexes.object_ids.push(index.into_iter().map(|(oid, _)| oid).collect());
+            }
+            pack_indexes.idx_paths.push(idx_path);
+        }
+        pack_indexes
+    }
+
+    /// Whether these are still the indexes under `objects_dir` and its alternates
+    pub fn is_current(&self, objects_dir: &Path) -> bool {
+        pack_index_paths(objects_dir).into_iter().map(|(_, idx_path)| idx_path).eq(self.idx_paths.iter().cloned())
+    }
+
+    /// Whether one of the indexes lists `oid`
+    pub fn contains(&self, oid: &str) -> bool {
+        self.object_ids.iter().any(|object_ids| object_ids.binary_search_by(|entry_oid| entry_oid.as_str().cmp(oid)).is_ok())
+    }
+}
+
+/// What `verify_pack` reports about one packed object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedObjectInfo {
+    pub oid: String,
+    /// Type of the object itself, also for deltas
+    pub object_type: ObjectType,
+    /// Size of the object's data
+    pub size: usize,
+    pub offset: u64,
+    /// For deltas: the base object's id and how many deltas deep the entry is
+    pub delta: Option<(String, usize)>,
+}
+
+/// Check a pack against its index and its trailing checksum, describing every object in offset order.
+pub fn verify_pack(idx_path: &Path) -> Result<Vec<PackedObjectInfo>> {
+    let pack_path = idx_path.with_extension("pack");
+    let pack_data = fs::read(&pack_path)
+        .with_context(|| format!("Cannot read pack {}", pack_path.display()))?;
+    if pack_data.len() < 12 + 20 || &pack_data[..4] != b"PACK" {
+        anyhow::bail!("{} is not a pack file", pack_path.display());
+    }
+
+    let (body, trailer) = pack_data.split_at(pack_data.len() - 20);
+    let computed = Sha1::new().chain_update(body).finalize();
+    if computed[..] != *trailer {
+        anyhow::bail!(
+            "Pack checksum mismatch for {}: trailer says {} but contents hash to {}",
+            pack_path.display(),
+            hex::encode(trailer),
+            hex::encode(computed)
+        );
+    }
+
+    let index = read_pack_index(idx_path)?;
+    let object_count = u32::from_be_bytes(pack_data[8..12].try_into()?) as usize;
+    if object_count != index.len() {
+        anyhow::bail!("Pack holds {} objects but its index lists {}", object_count, index.len());
+    }
+    // Packs live in <objects>/pack/
+    let objects_dir = idx_path.parent().and_then(Path::parent).unwrap_or(Path::new("."));
+    let pack = Pack { objects_dir, data: body, index: &index };
+    let oid_at: HashMap<u64, &String> = index.iter().map(|(oid, offset)| (*offset, oid)).collect();
+
+    let mut by_offset: Vec<&(String, u64)> = index.iter().collect();
+    by_offset.sort_by_key(|(_, offset)| *offset);
+
+    let mut infos = Vec::with_capacity(index.len());
+    for (oid, offset) in by_offset {
+        let (base, delta) = match read_raw_entry(body, *offset)? {
+            RawPackEntry::Full { object_type, data } => {
+                infos.push(PackedObjectInfo {
+                    oid: oid.clone(),
+                    object_type,
+                    size: data.len(),
+                    offset: *offset,
+                    delta: None,
+                });
+                continue;
+            }
+            RawPackEntry::OffsetDelta { base_offset, delta } => (DeltaBase::Offset(base_offset), delta),
+            RawPackEntry::RefDelta { base_oid, delta } => (DeltaBase::Oid(base_oid), delta),
+        };
+
+        let base_oid = match &base {
+            DeltaBase::Offset(base_offset) => oid_at.get(base_offset)
+                .map(|oid| (*oid).clone())
+                .with_context(|| format!("Delta base at offset {} is not in the index", base_offset))?,
+            DeltaBase::Oid(oid) => oid.clone(),
+        };
+        let (object_type, depth) = pack.delta_chain(base)?;
+        infos.push(PackedObjectInfo {
+            oid: oid.clone(),
+            object_type,
+            size: fossil_delta::delta_output_size(&delta),
+            offset: *offset,
+            delta: Some((base_oid, depth)),
+        });
+    }
+
+    Ok(infos)
+}
+
+// The name of a pack holding `object_ids`: `pack-` and the SHA-1 of their sorted binary ids,
+// as git names packs. The same objects always give the same name, whenever they are packed.
+fn pack_name<'a>(object_ids: impl Iterator<Item = &'a String>) -> Result<String> {
+    let mut sorted_oids: Vec<&String> = object_ids.collect();
+    sorted_oids.sort();
+    let mut hasher = Sha1::new();
+    for oid in sorted_oids {
+        hasher.update(hex::decode(oid)?);
+    }
+    Ok(format!("pack-{}", hex::encode(hasher.finalize())))
+}
+
+fn write_idx_file(idx_path: &Path, offsets: &HashMap<String, u64>, pack_sha: &[u8]) -> Result<()> {
+    // Built in memory, so the trailing checksum covers exactly the bytes written
+    let mut idx_data = Vec::new();
+    idx_data.write_all(&[0xff, 0x74, 0x4f, 0x63, 0x00, 0x00, 0x00, 0x02])?;
 
     let mut sorted_oids: Vec<&String> = offsets.keys().collect();
     sorted_oids.sort();
@@ -205,28 +991,316 @@ fn write_idx_file(idx_path: &Path, items: &[PackEntry], offsets: &HashMap<String
     let mut fanout = [0u32; 256];
     for (i, oid_str) in sorted_oids.iter().enumerate() {
         let first_byte = hex::decode(&oid_str[0..2])?[0] as usize;
-        for j in first_byte..256 {
-            fanout[j] = (i + 1) as u32;
+        for count in fanout.iter_mut().skip(first_byte) {
+            *count = (i + 1) as u32;
         }
     }
     for count in fanout.iter() {
-        idx_file.write_all(&count.to_be_bytes())?;
+        idx_data.write_all(&count.to_be_bytes())?;
     }
 
     // OIDs
     for oid in &sorted_oids {
-        idx_file.write_all(&hex::decode(oid)?)?;
+        idx_data.write_all(&hex::decode(oid)?)?;
     }
     // CRCs (dummy)
-    for _ in 0..items.len() { idx_file.write_all(&0u32.to_be_bytes())?; }
+    for _ in 0..sorted_oids.len() { idx_data.write_all(&0u32.to_be_bytes())?; }
     // Offsets
     for oid in &sorted_oids {
-        idx_file.write_all(&(offsets[oid.as_str()] as u32).to_be_bytes())?;
+        idx_data.write_all(&(offsets[oid.as_str()] as u32).to_be_bytes())?;
     }
     
-    idx_file.write_all(pack_sha)?;
-    let idx_content = fs::read(idx_path)?;
-    idx_file.write_all(&Sha1::new().chain_update(&idx_content).finalize()[..])?;
+    idx_data.write_all(pack_sha)?;
+    let idx_sha = Sha1::digest(&idx_data);
+    idx_data.extend_from_slice(&idx_sha);
+    fs::write(idx_path, idx_data)?;
 
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // Write the same varied set of blobs into a fresh object store and pack it with `options`,
+    // returning the size of the pack file
+    fn pack_size_with(options: &PackOptions) -> Result<u64> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+        for i in 0..40 {
+            let mut blob = format!("header {}\n", i % 3);
+            for line in 0..200 {
+                blob.push_str(&format!("line {} of revision {}\n", line, if line % 17 == 0 { i } else { 0 }));
+            }
+            objects::write_object(&objects_dir, blob.as_bytes(), ObjectType::Blob)?;
+        }
+
+        create_pack_with_options(&objects_dir, options)?;
+        pack_file_size(&objects_dir)
+    }
+
+    // Store eight revisions each of two files of similar size, with a tree per revision.
+    // The files share their first half, so either makes a passable base for the other, but
+    // each revision is far closer to the previous revision of the same file.
+    fn write_file_revisions(objects_dir: &Path) -> Result<()> {
+        for revision in 0..8 {
+            let mut notes = String::new();
+            let mut other = String::new();
+            for line in 0..150 + revision * 5 {
+                notes.push_str(&format!("note {} says the value is {}\n", line, line * 7 % 13));
+                if line < 75 {
+                    other.push_str(&format!("note {} says the value is {}\n", line, line * 7 % 13));
+                } else {
+                    other.push_str(&format!("{:x} entry {} {}\n", line * 2654435761usize % 65521, revision, line));
+                }
+            }
+            let mut tree = Vec::new();
+            for (name, content) in [("notes.txt", &notes), ("other.txt", &other)] {
+                let blob_id = objects::write_object(objects_dir, content.as_bytes(), ObjectType::Blob)?;
+                tree.extend(format!("100644 {}\0", name).into_bytes());
+                tree.extend(hex::decode(blob_id)?);
+            }
+            objects::write_object(objects_dir, &tree, ObjectType::Tree)?;
+        }
+        Ok(())
+    }
+
+    fn pack_file_size(objects_dir: &Path) -> Result<u64> {
+        let pack_path = fs::read_dir(objects_dir.join("pack"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| path.extension() == Some("pack".as_ref()))
+            .unwrap();
+        Ok(fs::metadata(pack_path)?.len())
+    }
+
+    #[test]
+    fn test_delta_selection_beats_first_fit() -> Result<()> {
+        let temp_dir = tempdir()?;
+
+        // The previous strategy: objects sorted by type and size, each deltified against the
+        // nearest of the last 10 objects that gives any delta smaller than the object itself
+        let first_fit_dir = temp_dir.path().join("first-fit");
+        write_file_revisions(&first_fit_dir)?;
+        let mut loose_objects = read_loose_objects(&first_fit_dir)?;
+        loose_objects.sort_by(|a, b| a.object_type.cmp(&b.object_type).then(a.data.len().cmp(&b.data.len())));
+        let mut items = Vec::new();
+        for (index, obj) in loose_objects.iter().enumerate() {
+            let base = loose_objects[..index]
+                .iter()
+                .rev()
+                .take(10)
+                .filter(|base| base.object_type == obj.object_type)
+                .map(|base| (base, fossil_delta::delta(&obj.data, &base.data)))
+                .find(|(_, delta)| !delta.is_empty() && delta.len() < obj.data.len());
+            items.push(match base {
+                Some((base, delta)) => PackEntry::Delta { oid: obj.oid.clone(), base_oid: base.oid.clone(), delta },
+                None => PackEntry::Full { oid: obj.oid.clone(), object_type: obj.object_type, data: obj.data.clone() },
+            });
+        }
+        write_pack_file(&first_fit_dir, &mut items, Compression::default())?;
+
+        let best_fit_dir = temp_dir.path().join("best-fit");
+        write_file_revisions(&best_fit_dir)?;
+        create_pack(&best_fit_dir)?;
+
+        let first_fit_size = pack_file_size(&first_fit_dir)?;
+        let best_fit_size = pack_file_size(&best_fit_dir)?;
+        assert!(best_fit_size < first_fit_size, "best fit {} >= first fit {}", best_fit_size, first_fit_size);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggressive_pack_is_not_larger() -> Result<()> {
+        let default_size = pack_size_with(&PackOptions::default())?;
+        let aggressive_size = pack_size_with(&PackOptions::aggressive())?;
+        assert!(aggressive_size <= default_size, "aggressive {} > default {}", aggressive_size, default_size);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_packed_objects_through_delta_chain() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+        let base = "shared line of text\n".repeat(100);
+        let blobs = [
+            base.clone(),
+            format!("{}second\n", base),
+            format!("{}second\nthird\n", base),
+        ];
+        let oids: Vec<String> = blobs
+            .iter()
+            .map(|blob| objects::write_object(&objects_dir, blob.as_bytes(), ObjectType::Blob))
+            .collect::<Result<_>>()?;
+
+        create_pack(&objects_dir)?;
+        for oid in &oids {
+            assert!(!objects_dir.join(&oid[..2]).join(&oid[2..]).exists());
+        }
+
+        // Each blob is a delta against the previous one
+        let idx_path = fs::read_dir(objects_dir.join("pack"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| path.extension() == Some("idx".as_ref()))
+            .unwrap();
+        let max_depth = verify_pack(&idx_path)?
+            .iter()
+            .filter_map(|info| info.delta.as_ref().map(|(_, depth)| *depth))
+            .max();
+        assert_eq!(max_depth, Some(2));
+
+        for (oid, blob) in oids.iter().zip(&blobs) {
+            let (object_type, data) = objects::read_object(&objects_dir, oid)?;
+            assert_eq!(object_type, ObjectType::Blob);
+            assert_eq!(data, blob.as_bytes());
+        }
+        assert!(read_packed_object(&objects_dir, &"0".repeat(40))?.is_none());
+
+        Ok(())
+    }
+
+    // The names of the files in `objects_dir/pack`, sorted
+    fn pack_dir_names(objects_dir: &Path) -> Result<Vec<String>> {
+        let mut names: Vec<String> = fs::read_dir(objects_dir.join("pack"))?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+        names.sort();
+        Ok(names)
+    }
+
+    #[test]
+    fn test_pack_trailer_is_checksum_of_contents() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+        write_file_revisions(&objects_dir)?;
+        create_pack(&objects_dir)?;
+
+        let pack_name = pack_dir_names(&objects_dir)?.into_iter().find(|name| name.ends_with(".pack")).unwrap();
+        let pack = fs::read(objects_dir.join("pack").join(pack_name))?;
+        let (body, trailer) = pack.split_at(pack.len() - 20);
+        assert_eq!(trailer, &Sha1::digest(body)[..]);
+
+        // The index ends with the same checksum, followed by its own
+        let idx_path = objects_dir.join("pack").join(pack_dir_names(&objects_dir)?.into_iter().find(|name| name.ends_with(".idx")).unwrap());
+        let idx = fs::read(idx_path)?;
+        assert_eq!(&idx[idx.len() - 40..idx.len() - 20], trailer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_names_come_from_contents() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let first_dir = temp_dir.path().join("first");
+        let second_dir = temp_dir.path().join("second");
+        for objects_dir in [&first_dir, &second_dir] {
+            write_file_revisions(objects_dir)?;
+            create_pack(objects_dir)?;
+        }
+        let names = pack_dir_names(&first_dir)?;
+        assert_eq!(names.len(), 2);
+        assert_eq!(names, pack_dir_names(&second_dir)?);
+
+        // Other objects packed into the same directory get a pack of their own, named after
+        // the SHA-1 of the binary ids it holds
+        let blob_id = objects::write_object(&first_dir, b"another\n", ObjectType::Blob)?;
+        create_pack(&first_dir)?;
+        let expected = format!("pack-{}", hex::encode(Sha1::digest(hex::decode(&blob_id)?)));
+        let names = pack_dir_names(&first_dir)?;
+        assert_eq!(names.len(), 4);
+        assert!(names.contains(&format!("{}.idx", expected)));
+        assert!(names.contains(&format!("{}.pack", expected)));
+        assert!(objects::read_object(&first_dir, &blob_id).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_type_ids() -> Result<()> {
+        let ids: Vec<(ObjectType, u8)> = ObjectType::ALL.iter().map(|&object_type| (object_type, object_type.type_id())).collect();
+        assert_eq!(ids, vec![(ObjectType::Blob, 3), (ObjectType::Tree, 2), (ObjectType::Commit, 1), (ObjectType::Tag, 4)]);
+        for object_type in ObjectType::ALL {
+            assert_eq!(ObjectType::from_type_id(object_type.type_id()), Some(object_type));
+        }
+        for type_id in [0, 5, OFS_DELTA, REF_DELTA] {
+            assert_eq!(ObjectType::from_type_id(type_id), None);
+        }
+
+        // The type id survives an entry header, whatever the size
+        for (type_id, size) in [(ObjectType::Commit.type_id(), 0), (ObjectType::Tag.type_id(), 15), (ObjectType::Blob.type_id(), 16), (OFS_DELTA, 1 << 20)] {
+            let header = get_pack_header(size, type_id);
+            assert_eq!(parse_pack_header(&header)?, (type_id, size, header.len()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ref_delta_with_loose_base() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+        let base = "base content\n".repeat(50);
+        let target = format!("{}changed\n", base);
+        let base_oid = objects::write_object(&objects_dir, base.as_bytes(), ObjectType::Blob)?;
+        let target_oid = objects::hash_object(target.as_bytes(), ObjectType::Blob);
+
+        // A single-entry pack: a ref delta whose base stays loose
+        let delta = fossil_delta::delta(target.as_bytes(), base.as_bytes());
+        let mut pack = b"PACK".to_vec();
+        pack.extend(2u32.to_be_bytes());
+        pack.extend(1u32.to_be_bytes());
+        pack.extend(get_pack_header(delta.len(), REF_DELTA));
+        pack.extend(hex::decode(&base_oid)?);
+        let mut compressor = ZlibEncoder::new(Vec::new(), Compression::default());
+        compressor.write_all(&delta)?;
+        pack.extend(compressor.finish()?);
+        let pack_sha = Sha1::new().chain_update(&pack).finalize();
+        pack.extend(&pack_sha[..]);
+
+        let pack_dir = objects_dir.join("pack");
+        fs::create_dir_all(&pack_dir)?;
+        fs::write(pack_dir.join("pack-test.pack"), &pack)?;
+        let offsets = HashMap::from([(target_oid.clone(), 12u64)]);
+        write_idx_file(&pack_dir.join("pack-test.idx"), &offsets, &pack_sha)?;
+
+        let (object_type, data) = objects::read_object(&objects_dir, &target_oid)?;
+        assert_eq!(object_type, ObjectType::Blob);
+        assert_eq!(data, target.as_bytes());
+
+        let listed = verify_pack(&pack_dir.join("pack-test.idx"))?;
+        assert_eq!(listed[0].object_type, ObjectType::Blob);
+        assert_eq!(listed[0].size, target.len());
+        assert_eq!(listed[0].delta, Some((base_oid, 1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crafted_sizes_fail_without_allocating() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let objects_dir = temp_dir.path().join("objects");
+
+        // A pack stream claiming four billion entries but holding one
+        let mut pack = Vec::new();
+        write_pack_stream(&mut pack, &[(ObjectType::Blob, b"hello\n".to_vec())])?;
+        pack[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        let content_len = pack.len() - 20;
+        let checksum = Sha1::new().chain_update(&pack[..content_len]).finalize();
+        pack[content_len..].copy_from_slice(&checksum);
+        assert!(read_pack_stream(&objects_dir, &pack).is_err());
+
+        // A delta whose target size is close to the address space
+        let mut delta = vec![0x05];
+        delta.extend([0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]);
+        delta.extend([0x05, b'h', b'e', b'l', b'l', b'o']);
+        assert!(apply_git_delta(b"hello", &delta).is_err());
+
+        // A size varint that never ends within 64 bits
+        let mut delta = vec![0x05];
+        delta.extend([0xff; 12]);
+        delta.push(0x01);
+        assert!(apply_git_delta(b"hello", &delta).is_err());
+
+        Ok(())
+    }
+}
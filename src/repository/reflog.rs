@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::refs;
+
+/// One line of a reflog: `<old> <new> <name> <<email>> <timestamp> <tz>\t<message>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old_id: String,
+    pub new_id: String,
+    /// When the ref was moved, in seconds since the epoch
+    pub time: i64,
+    /// The line as stored, without its newline
+    pub line: String,
+}
+
+impl ReflogEntry {
+    /// Parse a reflog line
+    pub fn parse(line: &str) -> Result<Self> {
+        let (header, _message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut fields = header.split(' ');
+        let old_id = fields.next().unwrap_or_default().to_string();
+        let new_id = fields.next().unwrap_or_default().to_string();
+        // The timestamp and time zone close the header, after the identity
+        let mut tail = header.rsplitn(3, ' ');
+        let _tz = tail.next();
+        let time = tail
+            .next()
+            .and_then(|time| time.parse().ok())
+            .with_context(|| format!("Malformed reflog line '{}'", line))?;
+        if old_id.len() != 40 || new_id.len() != 40 {
+            anyhow::bail!("Malformed reflog line '{}'", line);
+        }
+        Ok(Self { old_id, new_id, time, line: line.to_string() })
+    }
+}
+
+// Where the reflog of `ref_name` is kept: HEAD's belongs to the working tree, the others to
+// the common dir
+fn reflog_path(git_dir: &Path, ref_name: &str) -> PathBuf {
+    if ref_name == "HEAD" {
+        git_dir.join("logs/HEAD")
+    } else {
+        refs::common_dir(git_dir).join("logs").join(ref_name)
+    }
+}
+
+/// The entries of `ref_name`'s reflog, oldest first; none when it has no reflog
+pub fn read_reflog<P: AsRef<Path>>(git_dir: P, ref_name: &str) -> Result<Vec<ReflogEntry>> {
+    let Ok(content) = fs::read_to_string(reflog_path(git_dir.as_ref(), ref_name)) else {
+        return Ok(Vec::new());
+    };
+    content.lines().filter(|line| !line.is_empty()).map(ReflogEntry::parse).collect()
+}
+
+/// The refs that have a reflog: HEAD first, then the others under `logs/refs` by name
+pub fn list_reflogs<P: AsRef<Path>>(git_dir: P) -> Result<Vec<String>> {
+    let git_dir = git_dir.as_ref();
+    let mut ref_names = Vec::new();
+    if reflog_path(git_dir, "HEAD").is_file() {
+        ref_names.push("HEAD".to_string());
+    }
+
+    let logs_dir = refs::common_dir(git_dir).join("logs");
+    let mut logged: Vec<String> = walkdir::WalkDir::new(logs_dir.join("refs"))
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative_path = entry.path().strip_prefix(&logs_dir).ok()?;
+            let components: Vec<_> = relative_path.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+            Some(components.join("/"))
+        })
+        .collect();
+    logged.sort();
+    ref_names.extend(logged);
+    Ok(ref_names)
+}
+
+/// Drop the entries of `ref_name`'s reflog made before `expire_before`, returning how many were
+/// removed
+pub fn expire<P: AsRef<Path>>(git_dir: P, ref_name: &str, expire_before: SystemTime) -> Result<usize> {
+    let git_dir = git_dir.as_ref();
+    let entries = read_reflog(git_dir, ref_name)?;
+    let cutoff = expire_before
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs() as i64;
+    let (kept, expired): (Vec<ReflogEntry>, Vec<ReflogEntry>) = entries.into_iter().partition(|entry| entry.time >= cutoff);
+    if !expired.is_empty() {
+        let content: String = kept.iter().map(|entry| format!("{}\n", entry.line)).collect();
+        fs::write(reflog_path(git_dir, ref_name), content)?;
+    }
+    Ok(expired.len())
+}
+
+/// Expire every reflog as `expire` does, returning how many entries were removed in all
+pub fn expire_all<P: AsRef<Path>>(git_dir: P, expire_before: SystemTime) -> Result<usize> {
+    let git_dir = git_dir.as_ref();
+    let mut expired = 0;
+    for ref_name in list_reflogs(git_dir)? {
+        expired += expire(git_dir, &ref_name, expire_before)?;
+    }
+    Ok(expired)
+}
+
+/// Every commit the reflogs still remember, which gc and prune keep alive
+pub fn reflog_objects<P: AsRef<Path>>(git_dir: P) -> Result<Vec<String>> {
+    let git_dir = git_dir.as_ref();
+    let mut object_ids = Vec::new();
+    for ref_name in list_reflogs(git_dir)? {
+        for entry in read_reflog(git_dir, &ref_name)? {
+            object_ids.extend([entry.old_id, entry.new_id].into_iter().filter(|object_id| object_id != refs::NULL_ID));
+        }
+    }
+    object_ids.sort();
+    object_ids.dedup();
+    Ok(object_ids)
+}
@@ -9,24 +9,75 @@ pub fn read_ref<P: AsRef<Path>>(git_dir: P, ref_name: &str) -> Result<String> {
     
     if ref_path.exists() {
         let content = fs::read_to_string(&ref_path)?;
-        Ok(content.trim().to_string())
-    } else {
-        anyhow::bail!("Ref {} not found", ref_name)
+        return Ok(content.trim().to_string());
+    }
+    // A ref without a file of its own may still be in packed-refs, under its full name or
+    // one a short name abbreviates
+    if ref_name != "HEAD" {
+        let candidates = if ref_name.starts_with("refs/") {
+            vec![ref_name.to_string()]
+        } else {
+            vec![
+                format!("refs/heads/{}", ref_name),
+                format!("refs/tags/{}", ref_name),
+                format!("refs/remotes/{}", ref_name),
+            ]
+        };
+        let packed = read_packed_refs(git_dir)?;
+        for candidate in &candidates {
+            if let Some((_, object_id)) = packed.iter().find(|(name, _)| name == candidate) {
+                return Ok(object_id.clone());
+            }
+        }
     }
+    anyhow::bail!("Ref {} not found", ref_name)
 }
 
-// Convert a ref name to a file path
-pub fn resolve_ref_path<P: AsRef<Path>>(git_dir: P, ref_name: &str) -> PathBuf {
+/// The refs listed in the common dir's `packed-refs` file as `(ref_name, object_id)` pairs, in
+/// file order. Comment lines and the `^<peeled id>` lines following annotated tags are skipped.
+pub fn read_packed_refs<P: AsRef<Path>>(git_dir: P) -> Result<Vec<(String, String)>> {
+    let Ok(content) = fs::read_to_string(common_dir(git_dir).join("packed-refs")) else {
+        return Ok(Vec::new());
+    };
+    let mut refs = Vec::new();
+    for line in content.lines() {
+        if line.starts_with('#') || line.starts_with('^') || line.is_empty() {
+            continue;
+        }
+        let (object_id, ref_name) = line
+            .split_once(' ')
+            .ok_or_else(|| anyhow::anyhow!("Malformed packed-refs line '{}'", line))?;
+        refs.push((ref_name.to_string(), object_id.to_string()));
+    }
+    Ok(refs)
+}
+
+/// The git dir holding the refs, objects and config shared by every working tree: for a
+/// linked working tree's git dir, the one its `commondir` file names, otherwise `git_dir`
+pub fn common_dir<P: AsRef<Path>>(git_dir: P) -> PathBuf {
     let git_dir = git_dir.as_ref();
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(content) => {
+            let common_dir = git_dir.join(content.trim());
+            fs::canonicalize(&common_dir).unwrap_or(common_dir)
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+// Convert a ref name to a file path. HEAD belongs to the working tree; every other ref lives
+// in the common dir
+pub fn resolve_ref_path<P: AsRef<Path>>(git_dir: P, ref_name: &str) -> PathBuf {
+    if ref_name == "HEAD" {
+        return git_dir.as_ref().join("HEAD");
+    }
+    
+    let git_dir = common_dir(git_dir);
     
     if ref_name.starts_with("refs/") {
         return git_dir.join(ref_name);
     }
     
-    if ref_name == "HEAD" {
-        return git_dir.join("HEAD");
-    }
-    
     // Try to resolve common ref names
     let candidates = [
         format!("refs/heads/{}", ref_name),
@@ -60,22 +111,180 @@ pub fn update_ref<P: AsRef<Path>>(git_dir: P, ref_name: &str, commit_id: &str) -
     Ok(())
 }
 
-// Get the current HEAD commit
+/// Id naming a ref that does not exist, as the expected old value of `update_ref_checked`
+pub const NULL_ID: &str = "0000000000000000000000000000000000000000";
+
+/// Point `ref_name` at `new_id`, but only if it currently points at `old_id`; an `old_id` of
+/// `NULL_ID` requires the ref not to exist yet, and `None` skips the check. HEAD is updated
+/// through the branch it names, if any.
+///
+/// The check and the update happen under `<ref>.lock`, so of two concurrent updates expecting
+/// the same old value only one succeeds.
+pub fn update_ref_checked<P: AsRef<Path>>(git_dir: P, ref_name: &str, new_id: &str, old_id: Option<&str>) -> Result<()> {
+    let git_dir = git_dir.as_ref();
+    let ref_name = symref_target(git_dir, ref_name)?;
+    let ref_path = resolve_ref_path(git_dir, &ref_name);
+    with_ref_lock(&ref_path, |lock_path| {
+        check_old_value(git_dir, &ref_name, old_id)?;
+        fs::write(lock_path, format!("{}\n", new_id))?;
+        fs::rename(lock_path, &ref_path)?;
+        Ok(())
+    })
+}
+
+/// Delete `ref_name`, loose or packed, checking its current value against `old_id` as
+/// `update_ref_checked` does
+pub fn delete_ref<P: AsRef<Path>>(git_dir: P, ref_name: &str, old_id: Option<&str>) -> Result<()> {
+    let git_dir = git_dir.as_ref();
+    let ref_name = symref_target(git_dir, ref_name)?;
+    let ref_path = resolve_ref_path(git_dir, &ref_name);
+    with_ref_lock(&ref_path, |_| {
+        check_old_value(git_dir, &ref_name, old_id)?;
+        // packed-refs knows the ref by its full name, which a short name resolves to
+        let full_name = ref_path
+            .strip_prefix(common_dir(git_dir))
+            .map(|path| path.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+            .unwrap_or_else(|_| ref_name.clone());
+        let packed = remove_packed_ref(git_dir, &full_name)?;
+        match fs::remove_file(&ref_path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && packed => Ok(()),
+            Err(_) => anyhow::bail!("Ref {} not found", ref_name),
+        }
+    })
+}
+
+// The ref a HEAD update lands on: the branch HEAD names, or HEAD itself when detached
+fn symref_target(git_dir: &Path, ref_name: &str) -> Result<String> {
+    if ref_name == "HEAD" {
+        let head_content = fs::read_to_string(git_dir.join("HEAD"))?;
+        if let Some(target) = head_content.strip_prefix("ref: ") {
+            return Ok(target.trim().to_string());
+        }
+    }
+    Ok(ref_name.to_string())
+}
+
+// Run `update` while holding `<ref_path>.lock`, which it may rename into place; the lock is
+// removed afterwards if it is still there
+fn with_ref_lock<T>(ref_path: &Path, update: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    if let Some(parent) = ref_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut lock_name = ref_path.as_os_str().to_os_string();
+    lock_name.push(".lock");
+    let lock_path = PathBuf::from(lock_name);
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock_path)
+        .map_err(|err| anyhow::anyhow!("Unable to lock '{}': {}", lock_path.display(), err))?;
+
+    let result = update(&lock_path);
+    let _ = fs::remove_file(&lock_path);
+    result
+}
+
+fn check_old_value(git_dir: &Path, ref_name: &str, old_id: Option<&str>) -> Result<()> {
+    let Some(old_id) = old_id else {
+        return Ok(());
+    };
+    let current = read_ref(git_dir, ref_name).ok();
+    match current {
+        None if old_id == NULL_ID => Ok(()),
+        Some(current) if current == old_id => Ok(()),
+        Some(current) => anyhow::bail!("Ref {} is at {} but expected {}", ref_name, current, old_id),
+        None => anyhow::bail!("Ref {} does not exist but expected {}", ref_name, old_id),
+    }
+}
+
+// Drop `ref_name` and the peeled line after it from packed-refs, returning whether it was there
+fn remove_packed_ref(git_dir: &Path, ref_name: &str) -> Result<bool> {
+    let packed_path = common_dir(git_dir).join("packed-refs");
+    let Ok(content) = fs::read_to_string(&packed_path) else {
+        return Ok(false);
+    };
+    let mut kept = String::new();
+    let mut removed = false;
+    let mut skipping = false;
+    for line in content.lines() {
+        if skipping && line.starts_with('^') {
+            continue;
+        }
+        skipping = line.split_once(' ').is_some_and(|(_, name)| name == ref_name) && !line.starts_with('#');
+        if skipping {
+            removed = true;
+            continue;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    if removed {
+        fs::write(&packed_path, kept)?;
+    }
+    Ok(removed)
+}
+
+/// Returned by `get_head_commit` when HEAD names a branch that has no commits yet.
+#[derive(Debug, thiserror::Error)]
+#[error("branch '{branch}' has no commits yet")]
+pub struct UnbornBranchError {
+    pub branch: String,
+}
+
+// Get the current HEAD commit; fails with `UnbornBranchError` when HEAD's branch has no commits
 pub fn get_head_commit<P: AsRef<Path>>(git_dir: P) -> Result<String> {
     let git_dir = git_dir.as_ref();
     let head_content = fs::read_to_string(git_dir.join("HEAD"))?;
     
     if head_content.starts_with("ref: ") {
         let ref_name = head_content.trim_start_matches("ref: ").trim();
-        read_ref(git_dir, ref_name)
+        match read_ref(git_dir, ref_name) {
+            Ok(commit_id) => Ok(commit_id),
+            // Neither a loose nor a packed ref: the branch has no commits yet
+            Err(_) if !resolve_ref_path(git_dir, ref_name).exists() => {
+                let branch = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+                Err(UnbornBranchError { branch: branch.to_string() }.into())
+            }
+            Err(err) => Err(err),
+        }
     } else {
         Ok(head_content.trim().to_string())
     }
 }
 
+/// The current HEAD commit, or `None` when HEAD's branch is unborn
+pub fn head_commit<P: AsRef<Path>>(git_dir: P) -> Result<Option<String>> {
+    match get_head_commit(git_dir) {
+        Ok(commit_id) => Ok(Some(commit_id)),
+        Err(err) if err.is::<UnbornBranchError>() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// Resolve a revision (HEAD, a branch, tag or remote-tracking ref, or a full commit SHA) to an object ID
+pub fn resolve_revision<P: AsRef<Path>>(git_dir: P, revision: &str) -> Result<String> {
+    let git_dir = git_dir.as_ref();
+    
+    if revision == "HEAD" {
+        return get_head_commit(git_dir);
+    }
+    
+    if let Ok(object_id) = read_ref(git_dir, revision) {
+        return Ok(object_id);
+    }
+    
+    let is_full_sha = revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit());
+    if is_full_sha && super::objects::read_object(common_dir(git_dir).join("objects"), revision).is_ok() {
+        return Ok(revision.to_lowercase());
+    }
+    
+    anyhow::bail!("unknown revision '{}'", revision)
+}
+
 // List all branches
 pub fn list_branches<P: AsRef<Path>>(git_dir: P) -> Result<Vec<String>> {
-    let heads_dir = git_dir.as_ref().join("refs/heads");
+    let heads_dir = common_dir(git_dir).join("refs/heads");
     if !heads_dir.exists() {
         return Ok(Vec::new());
     }
@@ -101,6 +310,63 @@ pub fn list_branches<P: AsRef<Path>>(git_dir: P) -> Result<Vec<String>> {
     Ok(branches)
 }
 
+// List every ref under refs/ (branches, tags, remote-tracking refs), loose or packed, as (ref name, object id) pairs
+pub fn list_refs<P: AsRef<Path>>(git_dir: P) -> Result<Vec<(String, String)>> {
+    let git_dir = common_dir(git_dir);
+    let mut refs = Vec::new();
+    
+    for entry in walkdir::WalkDir::new(git_dir.join("refs"))
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+    {
+        let Ok(relative_path) = entry.path().strip_prefix(&git_dir) else {
+            continue;
+        };
+        let ref_name = relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let object_id = fs::read_to_string(entry.path())?.trim().to_string();
+        refs.push((ref_name, object_id));
+    }
+
+    // Loose refs take precedence over their packed copies
+    for (ref_name, object_id) in read_packed_refs(&git_dir)? {
+        if !refs.iter().any(|(loose_name, _)| *loose_name == ref_name) {
+            refs.push((ref_name, object_id));
+        }
+    }
+    
+    refs.sort();
+    Ok(refs)
+}
+
+// List all tags as (name, object id) pairs; annotated tags point at tag objects
+pub fn list_tags<P: AsRef<Path>>(git_dir: P) -> Result<Vec<(String, String)>> {
+    let git_dir = git_dir.as_ref();
+    let tags_dir = common_dir(git_dir).join("refs/tags");
+    if !tags_dir.exists() {
+        return Ok(Vec::new());
+    }
+    
+    let mut tags = Vec::new();
+    for entry in fs::read_dir(tags_dir)? {
+        let entry = entry?;
+        if !entry.path().is_file() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str() {
+            let object_id = read_ref(git_dir, &format!("refs/tags/{}", name))?;
+            tags.push((name.to_string(), object_id));
+        }
+    }
+    
+    tags.sort();
+    Ok(tags)
+}
+
 // Create a new branch
 pub fn create_branch<P: AsRef<Path>>(git_dir: P, branch_name: &str, commit_id: &str) -> Result<()> {
     update_ref(git_dir, &format!("refs/heads/{}", branch_name), commit_id)
@@ -108,15 +374,12 @@ pub fn create_branch<P: AsRef<Path>>(git_dir: P, branch_name: &str, commit_id: &
 
 // Delete a branch
 pub fn delete_branch<P: AsRef<Path>>(git_dir: P, branch_name: &str) -> Result<()> {
-    let ref_path = resolve_ref_path(git_dir, &format!("refs/heads/{}", branch_name));
-    
-    if !ref_path.exists() {
+    let ref_name = format!("refs/heads/{}", branch_name);
+    let Ok(commit_id) = read_ref(&git_dir, &ref_name) else {
         anyhow::bail!("Branch {} not found", branch_name);
-    }
-    
-    fs::remove_file(ref_path)?;
+    };
     
-    Ok(())
+    delete_ref(git_dir, &ref_name, Some(&commit_id))
 }
 
 #[cfg(test)]
@@ -244,4 +507,26 @@ mod tests {
         
         Ok(())
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_packed_refs_are_not_missing() -> Result<()> {
+        let temp_dir = setup_test_git_dir()?;
+        let git_dir = temp_dir.path();
+        
+        let commit_id = "abcdef0123456789abcdef0123456789abcdef01";
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!("{} refs/heads/master\n{} refs/heads/feature\n", commit_id, commit_id),
+        )?;
+        
+        // HEAD's branch is packed, so it is born
+        assert_eq!(get_head_commit(git_dir)?, commit_id);
+        assert_eq!(resolve_revision(git_dir, "feature")?, commit_id);
+        
+        delete_branch(git_dir, "feature")?;
+        assert!(resolve_revision(git_dir, "feature").is_err());
+        assert_eq!(read_packed_refs(git_dir)?, vec![("refs/heads/master".to_string(), commit_id.to_string())]);
+        
+        Ok(())
+    }
+}
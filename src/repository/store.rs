@@ -0,0 +1,235 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::objects::{self, ObjectType, RawObject};
+use super::pack::PackIndexes;
+
+/// Where a repository's objects live. `Repository` reads and writes objects only through
+/// this trait, so the same commands run against the filesystem or an in-memory map.
+pub trait ObjectStore: Send + Sync {
+    /// Read the object `oid`
+    fn read(&self, oid: &str) -> Result<RawObject>;
+    /// Store `data` as an object of `object_type`, returning its id
+    fn write(&self, object_type: ObjectType, data: &[u8]) -> Result<String>;
+    /// Whether the object `oid` is present
+    fn exists(&self, oid: &str) -> bool {
+        self.read(oid).is_ok()
+    }
+}
+
+/// Objects in `.git/objects`: loose files, packs and alternates
+#[derive(Debug, Clone)]
+pub struct FsObjectStore {
+    objects_dir: PathBuf,
+    /// The pack indexes as last read, reloaded when packs are added or removed
+    pack_indexes: Arc<RwLock<Option<PackIndexes>>>,
+}
+
+impl FsObjectStore {
+    pub fn new<P: AsRef<Path>>(objects_dir: P) -> Self {
+        Self { objects_dir: objects_dir.as_ref().to_path_buf(), pack_indexes: Arc::default() }
+    }
+
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    // Whether a pack holds `oid`, reading the pack indexes only when they have changed since
+    // the last lookup
+    fn has_packed(&self, oid: &str) -> bool {
+        if let Some(pack_indexes) = self.pack_indexes.read().unwrap().as_ref() {
+            if pack_indexes.is_current(&self.objects_dir) {
+                return pack_indexes.contains(oid);
+            }
+        }
+        let pack_indexes = PackIndexes::load(&self.objects_dir);
+        let found = pack_indexes.contains(oid);
+        *self.pack_indexes.write().unwrap() = Some(pack_indexes);
+        found
+    }
+}
+
+impl ObjectStore for FsObjectStore {
+    fn read(&self, oid: &str) -> Result<RawObject> {
+        let (object_type, data) = objects::read_object(&self.objects_dir, oid)?;
+        Ok(RawObject { object_type, data })
+    }
+
+    fn write(&self, object_type: ObjectType, data: &[u8]) -> Result<String> {
+        let oid = objects::hash_object(data, object_type);
+        if self.exists(&oid) {
+            return Ok(oid);
+        }
+        objects::write_loose_object(&self.objects_dir, data, object_type)
+    }
+
+    fn exists(&self, oid: &str) -> bool {
+        objects::loose_object_exists(&self.objects_dir, oid) || self.has_packed(oid)
+    }
+}
+
+/// Objects kept in a map and dropped with the store, for tests and throwaway repositories
+#[derive(Debug, Default)]
+pub struct MemObjectStore {
+    objects: RwLock<HashMap<String, RawObject>>,
+}
+
+impl MemObjectStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of objects stored
+    pub fn len(&self) -> usize {
+        self.objects.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ObjectStore for MemObjectStore {
+    fn read(&self, oid: &str) -> Result<RawObject> {
+        self.objects
+            .read()
+            .unwrap()
+            .get(oid)
+            .cloned()
+            .with_context(|| format!("Object {} not found", oid))
+    }
+
+    fn write(&self, object_type: ObjectType, data: &[u8]) -> Result<String> {
+        let oid = objects::hash_object(data, object_type);
+        self.objects.write().unwrap().entry(oid.clone()).or_insert_with(|| RawObject {
+            object_type,
+            data: data.to_vec(),
+        });
+        Ok(oid)
+    }
+
+    fn exists(&self, oid: &str) -> bool {
+        self.objects.read().unwrap().contains_key(oid)
+    }
+}
+
+/// Wraps another store and counts the objects read from and written to it, for tests that
+/// check how much work an operation does. The counters are shared so they can still be read
+/// once the store is boxed into a `Repository`.
+#[cfg(test)]
+pub struct CountingStore<S> {
+    pub inner: S,
+    pub reads: Arc<AtomicUsize>,
+    pub writes: Arc<AtomicUsize>,
+}
+
+#[cfg(test)]
+impl<S: ObjectStore> CountingStore<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, reads: Arc::default(), writes: Arc::default() }
+    }
+}
+
+#[cfg(test)]
+impl<S: ObjectStore> ObjectStore for CountingStore<S> {
+    fn read(&self, oid: &str) -> Result<RawObject> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        self.inner.read(oid)
+    }
+
+    fn write(&self, object_type: ObjectType, data: &[u8]) -> Result<String> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        self.inner.write(object_type, data)
+    }
+
+    fn exists(&self, oid: &str) -> bool {
+        self.inner.exists(oid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commit::{self, CommitOptions};
+    use crate::commands::{add, cat_file};
+    use crate::repository::objects::Object;
+    use crate::repository::{pack, Repository};
+    use std::fs;
+    use tempfile::tempdir;
+    use walkdir::WalkDir;
+
+    // Both stores agree on ids and content
+    fn round_trip(store: &dyn ObjectStore) -> Result<()> {
+        let oid = store.write(ObjectType::Blob, b"hello\n")?;
+        assert_eq!(oid, objects::hash_object(b"hello\n", ObjectType::Blob));
+        assert!(store.exists(&oid));
+        assert_eq!(store.read(&oid)?, RawObject { object_type: ObjectType::Blob, data: b"hello\n".to_vec() });
+        assert!(!store.exists("0000000000000000000000000000000000000000"));
+        assert!(store.read("0000000000000000000000000000000000000000").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fs_and_mem_stores() -> Result<()> {
+        let temp_dir = tempdir()?;
+        round_trip(&FsObjectStore::new(temp_dir.path()))?;
+
+        let store = MemObjectStore::new();
+        round_trip(&store)?;
+        store.write(ObjectType::Blob, b"hello\n")?;
+        assert_eq!(store.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fs_store_notices_new_packs() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let store = FsObjectStore::new(temp_dir.path());
+        let first = store.write(ObjectType::Blob, b"first\n")?;
+        pack::create_pack(temp_dir.path())?;
+        assert!(store.exists(&first));
+
+        // Packing again after the indexes were read makes them stale
+        let second = store.write(ObjectType::Blob, b"second\n")?;
+        pack::create_pack(temp_dir.path())?;
+        assert!(!objects::loose_object_exists(temp_dir.path(), &second));
+        assert!(store.exists(&second));
+        assert!(store.exists(&first));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_commit_cat_file_in_memory() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let mut repo = Repository::init_with_object_store(temp_dir.path(), Box::new(MemObjectStore::new()))?;
+        fs::write(repo.path.join("hello.txt"), "hello\n")?;
+
+        add::run(&mut repo, &[PathBuf::from("hello.txt")])?;
+        let commit_id = commit::run(&mut repo, &CommitOptions { message: Some("Add hello"), ..Default::default() })?.unwrap();
+
+        let (_, commit) = cat_file::run(&repo, &commit_id)?;
+        let Object::Commit(commit) = commit.parse()? else {
+            panic!("expected a commit");
+        };
+        assert_eq!(commit.message, "Add hello");
+        assert_eq!(commit.parents.len(), 1);
+        let entry = objects::find_tree_entry(repo.objects.as_ref(), &commit.tree, Path::new("hello.txt"))?.unwrap();
+        assert_eq!(cat_file::run(&repo, &entry.oid)?.1.data, b"hello\n");
+
+        // Every object, the initial commit included, stayed out of .git/objects
+        let object_files = WalkDir::new(repo.git_dir.join("objects"))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .count();
+        assert_eq!(object_files, 0);
+
+        Ok(())
+    }
+}
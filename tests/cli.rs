@@ -0,0 +1,38 @@
+use assert_cmd::Command;
+use tempfile::tempdir;
+
+fn rust_git(dir: &std::path::Path) -> Command {
+    let mut command = Command::cargo_bin("rust-git").unwrap();
+    command.current_dir(dir);
+    command
+}
+
+#[test]
+fn test_quiet_still_prints_what_was_asked_for() {
+    let temp_dir = tempdir().unwrap();
+    rust_git(temp_dir.path()).arg("init").assert().success();
+    std::fs::write(temp_dir.path().join("file.txt"), "content\n").unwrap();
+
+    rust_git(temp_dir.path())
+        .args(["-q", "branch", "--show-current"])
+        .assert()
+        .success()
+        .stdout("master\n");
+    rust_git(temp_dir.path())
+        .args(["-q", "status", "-s"])
+        .assert()
+        .success()
+        .stdout("?? file.txt\n");
+
+    // Quiet drops only the chatter around the answer
+    rust_git(temp_dir.path())
+        .args(["-q", "branch", "topic"])
+        .assert()
+        .success()
+        .stdout("");
+    rust_git(temp_dir.path())
+        .args(["-q", "branch"])
+        .assert()
+        .success()
+        .stdout("* master\n  topic\n");
+}